@@ -0,0 +1,590 @@
+//! # File attribution
+//!
+//! This file provides the per-process energy attribution, sharing the CPU package power by
+//! the CPU usage of each process, the GPU power by its streaming multiprocessor usage,
+//! and estimating the disk energy of its read and written bytes, and the per-cgroup one
+//! sharing the CPU package and memory powers by the CPU time and memory of each cgroup.
+
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    sync::{Arc, Mutex},
+};
+use userv_core::{
+    core::init_db,
+    query::{Filter, list_tables, select},
+};
+
+use cgroup::CgroupInfo;
+use storage::estimate_energy;
+use system::ProcessInfo;
+
+use crate::{component::Component, energy::readings, snapshot::Snapshot};
+
+/// Request creating the table of the process energies.
+pub const REQUEST: &str = "CREATE TABLE IF NOT EXISTS process_energy (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    interval_s REAL NOT NULL,
+    pid INTEGER NOT NULL,
+    name TEXT,
+    cpu_power_W REAL NOT NULL,
+    gpu_power_W REAL NOT NULL,
+    disk_energy_J REAL NOT NULL,
+    power_W REAL NOT NULL,
+    energy_J REAL NOT NULL
+);";
+
+/// Request creating the table of the cgroup energies.
+pub const CGROUP_REQUEST: &str = "CREATE TABLE IF NOT EXISTS cgroup_energy (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    interval_s REAL NOT NULL,
+    path TEXT NOT NULL,
+    kind TEXT,
+    container_id TEXT,
+    pod TEXT,
+    unit TEXT,
+    cpu_power_W REAL NOT NULL,
+    memory_power_W REAL NOT NULL,
+    disk_energy_J REAL NOT NULL,
+    power_W REAL NOT NULL,
+    energy_J REAL NOT NULL
+);";
+
+/// Estimated energy of a process over the interval before a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProcessEnergy {
+    /// PID of the process.
+    pub pid: usize,
+    /// Command name of the process.
+    pub name: Option<String>,
+    /// Share of the CPU package power in W.
+    pub cpu_power: f64,
+    /// Share of the GPU power in W.
+    pub gpu_power: f64,
+    /// Energy of the bytes read and written on disk in J.
+    pub disk_energy: f64,
+    /// Average power in W.
+    pub power: f64,
+    /// Energy consumed in J.
+    pub energy: f64,
+}
+
+/// Previous command name and disk counters in MB, by PID.
+type DiskCounters = HashMap<usize, (Option<String>, u64, u64)>;
+
+/// Per-process energy attribution, keeping the disk counters of each process between snapshots.
+/// Clones share the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessMeter {
+    counters: Arc<Mutex<DiskCounters>>,
+}
+
+impl ProcessMeter {
+    /// Attribute the energy of a snapshot to its processes.
+    /// The package power is the one of the top-level RAPL zones, the CPU usage of a process
+    /// being a share of the whole machine, so the idle power is not attributed.
+    ///
+    /// # Arguments
+    ///
+    /// - `snapshot` : Retrieved [`Snapshot`], with the system processes.
+    /// - `interval` : Length of the interval since the previous snapshot in seconds.
+    ///
+    /// # Returns
+    ///
+    /// The [`ProcessEnergy`] of the processes which consumed energy, the largest first.
+    pub fn attribute(&self, snapshot: &Snapshot, interval: f64) -> Vec<ProcessEnergy> {
+        let Some(processes) = snapshot.system.as_ref().and_then(|s| s.processes.as_ref()) else {
+            return Vec::new();
+        };
+        let package = package_power(snapshot);
+        let mut gpu = HashMap::new();
+        for device in snapshot.gpu.iter().flatten() {
+            let Some(power) = device.metrics.gpu_power_consumption else {
+                continue;
+            };
+            for process in &device.processes {
+                if let (Some(pid), Some(sm)) = (process.process_pid, process.process_sm) {
+                    *gpu.entry(pid as usize).or_insert(0.0) +=
+                        power as f64 / 1e3 * sm as f64 / 100.0;
+                }
+            }
+        }
+        self.account(processes, package, &gpu, interval)
+    }
+
+    /// Attribute the package and GPU powers to the processes.
+    /// The disk energy comes from the increase of the disk counters of a process since the
+    /// previous snapshot, none for a process seen for the first time or whose PID was reused.
+    pub(crate) fn account(
+        &self,
+        processes: &[ProcessInfo],
+        package: f64,
+        gpu: &HashMap<usize, f64>,
+        interval: f64,
+    ) -> Vec<ProcessEnergy> {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let previous = std::mem::take(&mut *counters);
+
+        let mut res = Vec::new();
+        for process in processes {
+            let (read, write) = (
+                process.disk_usage_read.unwrap_or(0),
+                process.disk_usage_write.unwrap_or(0),
+            );
+            let disk_energy = match previous.get(&process.pid) {
+                Some((name, r, w)) if *name == process.name && read >= *r && write >= *w => {
+                    estimate_energy("", read - r, write - w)
+                }
+                _ => 0.0,
+            };
+            counters.insert(process.pid, (process.name.clone(), read, write));
+
+            let cpu_power = package * process.cpu_usage.unwrap_or(0.0).max(0.0) as f64 / 100.0;
+            let gpu_power = gpu.get(&process.pid).copied().unwrap_or(0.0);
+            let energy = (cpu_power + gpu_power) * interval + disk_energy;
+            if energy <= 0.0 {
+                continue;
+            }
+            res.push(ProcessEnergy {
+                pid: process.pid,
+                name: process.name.clone(),
+                cpu_power,
+                gpu_power,
+                disk_energy,
+                power: if interval > 0.0 {
+                    energy / interval
+                } else {
+                    cpu_power + gpu_power
+                },
+                energy,
+            });
+        }
+        res.sort_by(|a, b| b.energy.total_cmp(&a.energy));
+        res
+    }
+}
+
+/// Power of the CPU packages in W, from the top-level RAPL zones.
+fn package_power(snapshot: &Snapshot) -> f64 {
+    readings(snapshot)
+        .iter()
+        .filter(|r| r.component == Component::Cpu)
+        .filter_map(|r| r.power)
+        .sum()
+}
+
+/// Estimated energy of a cgroup over the interval before a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CgroupEnergy {
+    /// Path of the cgroup from the hierarchy root.
+    pub path: String,
+    /// Kind of workload of the cgroup.
+    pub kind: Option<String>,
+    /// Identifier of the container running in the cgroup.
+    pub container_id: Option<String>,
+    /// UID of the Kubernetes pod of the cgroup.
+    pub pod: Option<String>,
+    /// Name of the systemd unit of the cgroup.
+    pub unit: Option<String>,
+    /// Share of the CPU package power in W.
+    pub cpu_power: f64,
+    /// Share of the memory power in W.
+    pub memory_power: f64,
+    /// Energy of the bytes read and written on disk in J.
+    pub disk_energy: f64,
+    /// Average power in W.
+    pub power: f64,
+    /// Energy consumed in J.
+    pub energy: f64,
+}
+
+/// Previous CPU time in µs and disk counters in bytes, by cgroup path.
+type CgroupCounters = HashMap<String, (u64, u64, u64)>;
+
+/// Per-cgroup energy attribution, keeping the counters of each cgroup between snapshots.
+/// Clones share the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct CgroupMeter {
+    counters: Arc<Mutex<CgroupCounters>>,
+}
+
+impl CgroupMeter {
+    /// Attribute the energy of a snapshot to its cgroups.
+    /// A cgroup includes its descendants, so the energies of nested cgroups overlap.
+    ///
+    /// # Arguments
+    ///
+    /// - `snapshot` : Retrieved [`Snapshot`], with the cgroups.
+    /// - `interval` : Length of the interval since the previous snapshot in seconds.
+    ///
+    /// # Returns
+    ///
+    /// The [`CgroupEnergy`] of the cgroups which consumed energy, sorted by path.
+    pub fn attribute(&self, snapshot: &Snapshot, interval: f64) -> Vec<CgroupEnergy> {
+        let Some(cgroups) = &snapshot.cgroup else {
+            return Vec::new();
+        };
+        let cpus = snapshot
+            .cpu
+            .as_ref()
+            .and_then(|cpu| cpu.global.cores_logic)
+            .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+            .unwrap_or(1);
+        let memory = snapshot.memory.as_ref().and_then(|memory| {
+            Some((
+                memory.global.ram_power_consumption?,
+                memory.global.ram_total? as f64 * 1e6,
+            ))
+        });
+        self.account(
+            cgroups,
+            package_power(snapshot),
+            cpus as f64,
+            memory,
+            interval,
+        )
+    }
+
+    /// Attribute the package and memory powers to the cgroups.
+    /// The CPU share of a cgroup is its CPU time over the time of the logical CPUs during the
+    /// interval, and its memory share is its memory over the total memory. The CPU and disk
+    /// figures come from the increase of its counters, none for a cgroup seen for the first time.
+    pub(crate) fn account(
+        &self,
+        cgroups: &[CgroupInfo],
+        package: f64,
+        cpus: f64,
+        memory: Option<(f64, f64)>,
+        interval: f64,
+    ) -> Vec<CgroupEnergy> {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let previous = std::mem::take(&mut *counters);
+
+        let mut res = Vec::new();
+        for cgroup in cgroups {
+            let current = (
+                cgroup.cpu_usage.unwrap_or(0),
+                cgroup.io_read.unwrap_or(0),
+                cgroup.io_write.unwrap_or(0),
+            );
+            counters.insert(cgroup.path.clone(), current);
+            let delta = previous.get(&cgroup.path).map(|(cpu, read, write)| {
+                (
+                    current.0.saturating_sub(*cpu),
+                    current.1.saturating_sub(*read),
+                    current.2.saturating_sub(*write),
+                )
+            });
+
+            let cpu_power = match delta {
+                Some((usage, _, _)) if interval > 0.0 && cpus > 0.0 => {
+                    package * (usage as f64 / 1e6 / (interval * cpus)).min(1.0)
+                }
+                _ => 0.0,
+            };
+            let memory_power = match (memory, cgroup.memory_current) {
+                (Some((power, total)), Some(used)) if total > 0.0 => {
+                    power * (used as f64 / total).min(1.0)
+                }
+                _ => 0.0,
+            };
+            let disk_energy = delta
+                .map(|(_, read, write)| estimate_energy("", read / 1_000_000, write / 1_000_000))
+                .unwrap_or(0.0);
+            let energy = (cpu_power + memory_power) * interval + disk_energy;
+            if energy <= 0.0 {
+                continue;
+            }
+            res.push(CgroupEnergy {
+                path: cgroup.path.clone(),
+                kind: cgroup.kind.clone(),
+                container_id: cgroup.container_id.clone(),
+                pod: cgroup.pod.clone(),
+                unit: cgroup.unit.clone(),
+                cpu_power,
+                memory_power,
+                disk_energy,
+                power: if interval > 0.0 {
+                    energy / interval
+                } else {
+                    cpu_power + memory_power
+                },
+                energy,
+            });
+        }
+        res
+    }
+}
+
+/// Store the cgroup energies of a snapshot in database.
+///
+/// # Arguments
+///
+/// - `timestamp` : Date of the snapshot.
+/// - `interval` : Length of the interval of the energies in seconds.
+/// - `cgroups` : [`CgroupEnergy`] to insert.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert request failed.
+pub fn store_cgroup_energy(
+    timestamp: &str,
+    interval: f64,
+    cgroups: &[CgroupEnergy],
+) -> Result<(), Box<dyn StdError>> {
+    let conn = init_db(CGROUP_REQUEST)?;
+    let mut stmt = conn.prepare(
+        "INSERT INTO cgroup_energy (timestamp, interval_s, path, kind, container_id, pod, unit,
+            cpu_power_W, memory_power_W, disk_energy_J, power_W, energy_J)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+    )?;
+    for c in cgroups {
+        stmt.execute(params![
+            timestamp,
+            interval,
+            c.path,
+            c.kind,
+            c.container_id,
+            c.pod,
+            c.unit,
+            c.cpu_power,
+            c.memory_power,
+            c.disk_energy,
+            c.power,
+            c.energy
+        ])?;
+    }
+    Ok(())
+}
+
+/// Store the process energies of a snapshot in database.
+///
+/// # Arguments
+///
+/// - `timestamp` : Date of the snapshot.
+/// - `interval` : Length of the interval of the energies in seconds.
+/// - `processes` : [`ProcessEnergy`] to insert.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert request failed.
+pub fn store_process_energy(
+    timestamp: &str,
+    interval: f64,
+    processes: &[ProcessEnergy],
+) -> Result<(), Box<dyn StdError>> {
+    let conn = init_db(REQUEST)?;
+    let mut stmt = conn.prepare(
+        "INSERT INTO process_energy (timestamp, interval_s, pid, name,
+            cpu_power_W, gpu_power_W, disk_energy_J, power_W, energy_J)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    )?;
+    for p in processes {
+        stmt.execute(params![
+            timestamp,
+            interval,
+            p.pid,
+            p.name,
+            p.cpu_power,
+            p.gpu_power,
+            p.disk_energy,
+            p.power,
+            p.energy
+        ])?;
+    }
+    Ok(())
+}
+
+/// Energy consumed by a process over a time range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Consumer {
+    /// PID of the process.
+    pub pid: usize,
+    /// Command name of the process.
+    pub name: Option<String>,
+    /// Energy consumed in J.
+    pub energy: f64,
+    /// Average power in W over its snapshots.
+    pub power: f64,
+    /// Number of snapshots where the process consumed energy.
+    pub samples: u64,
+}
+
+/// Find the processes which consumed the most energy over a time range.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `filter` : Time range, and PID to keep.
+/// - `limit` : Maximum number of processes returned.
+///
+/// # Returns
+///
+/// - The [`Consumer`] of each PID and command name, the largest energy first, empty if none is stored.
+/// - An error if a time bound is invalid or the table can't be read.
+pub fn top_consumers(
+    conn: &Connection,
+    filter: &Filter,
+    limit: usize,
+) -> Result<Vec<Consumer>, Box<dyn StdError>> {
+    if !list_tables(conn)?.iter().any(|t| t == "process_energy") {
+        return Ok(Vec::new());
+    }
+    let selection = select(conn, "process_energy", filter)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT q.pid, q.name, TOTAL(q.energy_J), AVG(q.power_W), COUNT(*) FROM ({}) q
+        GROUP BY q.pid, q.name ORDER BY 3 DESC, q.pid LIMIT {limit}",
+        selection.query
+    ))?;
+    let consumers = stmt
+        .query_map(&*selection.params(), |row| {
+            Ok(Consumer {
+                pid: row.get(0)?,
+                name: row.get(1)?,
+                energy: row.get(2)?,
+                power: row.get(3)?,
+                samples: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(consumers)
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: usize, cpu: f32, read: u64, write: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: Some(format!("proc{pid}")),
+            cpu_usage: Some(cpu),
+            disk_usage_read: Some(read),
+            disk_usage_write: Some(write),
+            id_group: None,
+            id_session: None,
+            id_user: None,
+            memory_usage: None,
+            memory_virtual_usage: None,
+            status: None,
+            run_time: None,
+        }
+    }
+
+    // Test `account` function sharing CPU and GPU powers, and disk energy from the counters
+    #[test]
+    fn test_account() {
+        let meter = ProcessMeter::default();
+        let gpu = HashMap::from([(2, 30.0)]);
+
+        let res = meter.account(
+            &[
+                process(1, 50.0, 10, 0),
+                process(2, 0.0, 0, 0),
+                process(3, 0.0, 0, 0),
+            ],
+            40.0,
+            &gpu,
+            2.0,
+        );
+        assert_eq!(res.len(), 2);
+        assert_eq!((res[0].pid, res[0].energy), (2, 60.0));
+        assert_eq!((res[1].pid, res[1].cpu_power), (1, 20.0));
+        assert_eq!(res[1].disk_energy, 0.0);
+
+        let res = meter.account(
+            &[process(1, 0.0, 110, 100), process(3, 0.0, 0, 0)],
+            40.0,
+            &HashMap::new(),
+            2.0,
+        );
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].pid, 1);
+        assert!((res[0].disk_energy - 1.0).abs() < 1e-9);
+        assert!((res[0].power - 0.5).abs() < 1e-9);
+
+        // A reused PID starts its counters again.
+        let mut reused = process(1, 0.0, 500, 500);
+        reused.name = Some("other".to_string());
+        assert!(meter.account(&[reused], 40.0, &gpu, 2.0).is_empty());
+    }
+
+    // Test `CgroupMeter::account` function sharing CPU and memory powers from the counters
+    #[test]
+    fn test_cgroup_account() {
+        let meter = CgroupMeter::default();
+        let cgroup = |cpu: u64, memory: u64, read: u64| CgroupInfo {
+            path: "/system.slice/nginx.service".to_string(),
+            unit: Some("nginx.service".to_string()),
+            cpu_usage: Some(cpu),
+            memory_current: Some(memory),
+            io_read: Some(read),
+            io_write: Some(0),
+            ..Default::default()
+        };
+
+        let res = meter.account(&[cgroup(1_000_000, 0, 0)], 40.0, 4.0, Some((8.0, 1e9)), 2.0);
+        assert!(res.is_empty());
+
+        // 2 s of CPU time over 2 s on 4 CPUs, a quarter of the memory, 200 MB read.
+        let res = meter.account(
+            &[cgroup(3_000_000, 250_000_000, 200_000_000)],
+            40.0,
+            4.0,
+            Some((8.0, 1e9)),
+            2.0,
+        );
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].cpu_power, 10.0);
+        assert_eq!(res[0].memory_power, 2.0);
+        assert!((res[0].disk_energy - 1.0).abs() < 1e-9);
+        assert!((res[0].energy - 25.0).abs() < 1e-9);
+        assert_eq!(res[0].unit.as_deref(), Some("nginx.service"));
+    }
+
+    // Test `top_consumers` function summing the energy by process
+    #[test]
+    fn test_top_consumers() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(
+            top_consumers(&conn, &Filter::default(), 10)
+                .unwrap()
+                .is_empty()
+        );
+        conn.execute_batch(REQUEST).unwrap();
+        for (timestamp, pid, energy) in [
+            ("2025-01-01T00:00:00.000Z", 1, 10.0),
+            ("2025-01-01T00:00:00.000Z", 2, 25.0),
+            ("2025-01-01T00:01:00.000Z", 1, 20.0),
+            ("2025-01-01T00:01:00.000Z", 3, 5.0),
+        ] {
+            conn.execute(
+                "INSERT INTO process_energy (timestamp, interval_s, pid, name,
+                    cpu_power_W, gpu_power_W, disk_energy_J, power_W, energy_J)
+                VALUES (?1, 60, ?2, 'cmd', 0, 0, 0, ?3 / 60, ?3)",
+                params![timestamp, pid, energy],
+            )
+            .unwrap();
+        }
+
+        let res = top_consumers(&conn, &Filter::default(), 2).unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!((res[0].pid, res[0].energy, res[0].samples), (1, 30.0, 2));
+        assert_eq!((res[1].pid, res[1].energy), (2, 25.0));
+
+        let filter = Filter {
+            from: Some("2025-01-01T00:00:30Z".to_string()),
+            ..Default::default()
+        };
+        let res = top_consumers(&conn, &filter, 10).unwrap();
+        assert_eq!(res.iter().map(|c| c.pid).collect::<Vec<_>>(), vec![1, 3]);
+    }
+}
@@ -0,0 +1,628 @@
+//! # File baseline
+//!
+//! This file provides the calibration of the idle and peak powers of each component, sampling
+//! the machine at rest then under a synthetic load, and the split of the energy of each
+//! snapshot between its static part, consumed at rest, and its dynamic part, due to the workload.
+
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{Connection, OpenFlags, params};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    hint::black_box,
+    path::Path,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{JoinHandle, available_parallelism, spawn},
+    time::{Duration, Instant},
+};
+use userv_core::{
+    core::init_db,
+    query::{Filter, list_tables, select},
+};
+
+use crate::{
+    collector::{Collector, CollectorConfig},
+    component::Component,
+    energy::{COMPONENTS, EnergySummary},
+    tdp::CpuPowerModel,
+};
+
+/// Request creating the table of the calibrated baselines, one row by component.
+pub const REQUEST: &str = "CREATE TABLE IF NOT EXISTS baselines (
+    component TEXT PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    idle_power_W REAL NOT NULL,
+    peak_power_W REAL
+);";
+
+/// Request creating the table of the static and dynamic energies.
+pub const SPLIT_REQUEST: &str = "CREATE TABLE IF NOT EXISTS energy_split (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    interval_s REAL NOT NULL,
+    component TEXT NOT NULL,
+    energy_J REAL NOT NULL,
+    idle_power_W REAL NOT NULL,
+    static_energy_J REAL NOT NULL,
+    dynamic_energy_J REAL NOT NULL,
+    load_ratio REAL
+);";
+
+/// Number of 64-bit words written in loop by each thread of the synthetic load (8 MB),
+/// larger than the CPU caches to load the memory too.
+const LOAD_WORDS: usize = 1 << 20;
+
+/// Idle and peak powers of a component, measured by a calibration.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Baseline {
+    /// Component concerned.
+    pub component: Component,
+    /// Date of the calibration.
+    pub timestamp: String,
+    /// Average power at rest in W.
+    pub idle_power: f64,
+    /// Average power under the synthetic load in W, if it was run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_power: Option<f64>,
+}
+
+/// Parameters of a [`calibrate`] run.
+#[derive(Debug, Clone)]
+pub struct CalibrateOptions {
+    /// Length of the sampling at rest.
+    pub duration: Duration,
+    /// Length of the sampling under the synthetic load, none to not run it.
+    pub load: Option<Duration>,
+    /// Interval between two snapshots.
+    pub interval: Duration,
+    /// Estimate of the CPU power when no RAPL zone is readable.
+    pub cpu_power: CpuPowerModel,
+}
+
+impl Default for CalibrateOptions {
+    fn default() -> Self {
+        CalibrateOptions {
+            duration: Duration::from_secs(30),
+            load: None,
+            interval: Duration::from_secs(1),
+            cpu_power: CpuPowerModel::default(),
+        }
+    }
+}
+
+/// Average power of each component over the snapshots of a phase.
+/// The first snapshot, accounted over an interval overlapping the previous phase
+/// or without previous counters, is left aside.
+fn average_powers(
+    collector: &Collector,
+    duration: Duration,
+) -> Result<HashMap<Component, f64>, Box<dyn StdError>> {
+    let start = Instant::now();
+    let mut sums: HashMap<Component, (f64, f64)> = HashMap::new();
+    for (index, snapshot) in collector.sampling().enumerate() {
+        let snapshot = snapshot?;
+        if index > 0 {
+            for component in snapshot.energy.iter().flat_map(|e| &e.components) {
+                let sum = sums.entry(component.component).or_default();
+                sum.0 += component.power;
+                sum.1 += 1.0;
+            }
+            if start.elapsed() >= duration {
+                break;
+            }
+        }
+    }
+    Ok(sums
+        .into_iter()
+        .map(|(component, (power, count))| (component, power / count))
+        .collect())
+}
+
+/// Start the synthetic load, a thread by logical CPU writing pseudo-random words in a buffer.
+fn start_load(stop: &Arc<AtomicBool>) -> Vec<JoinHandle<()>> {
+    let threads = available_parallelism().map_or(1, |n| n.get());
+    (0..threads)
+        .map(|seed| {
+            let stop = stop.clone();
+            spawn(move || {
+                let mut buffer = vec![0u64; LOAD_WORDS];
+                let mut x = seed as u64 + 1;
+                while !stop.load(Ordering::Relaxed) {
+                    for word in buffer.iter_mut() {
+                        x = x
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        *word ^= x;
+                    }
+                    black_box(&buffer);
+                }
+            })
+        })
+        .collect()
+}
+
+/// Calibrate the idle power of each component accounted in the energy summary,
+/// sampling the machine at rest, then its peak power under a synthetic load if asked.
+/// The machine should run no other workload during the calibration.
+///
+/// # Arguments
+///
+/// - `options` : [`CalibrateOptions`] of the run.
+///
+/// # Returns
+///
+/// - The [`Baseline`] of each component with a power figure.
+/// - An error if a snapshot failed or no component gives a power figure.
+pub fn calibrate(options: &CalibrateOptions) -> Result<Vec<Baseline>, Box<dyn StdError>> {
+    let collector = Collector::new(CollectorConfig {
+        components: COMPONENTS.to_vec(),
+        interval: options.interval,
+        cpu_power: options.cpu_power.clone(),
+        ..Default::default()
+    });
+
+    let idle = average_powers(&collector, options.duration)?;
+    let peak = match options.load {
+        Some(duration) => {
+            let stop = Arc::new(AtomicBool::new(false));
+            let handles = start_load(&stop);
+            let res = average_powers(&collector, duration);
+            stop.store(true, Ordering::Relaxed);
+            for handle in handles {
+                let _ = handle.join();
+            }
+            Some(res?)
+        }
+        None => None,
+    };
+
+    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    let baselines: Vec<Baseline> = COMPONENTS
+        .iter()
+        .filter_map(|component| {
+            let idle_power = *idle.get(component)?;
+            Some(Baseline {
+                component: *component,
+                timestamp: timestamp.clone(),
+                idle_power,
+                peak_power: peak
+                    .as_ref()
+                    .and_then(|peak| peak.get(component))
+                    .map(|power| power.max(idle_power)),
+            })
+        })
+        .collect();
+    if baselines.is_empty() {
+        return Err("Data 'No power figure during the calibration'".into());
+    }
+    Ok(baselines)
+}
+
+/// Store the baselines of a calibration in database, replacing the previous ones of the
+/// same components. The previous peak power is kept when the calibration ran no load.
+///
+/// # Arguments
+///
+/// - `baselines` : [`Baseline`] to store.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert request failed.
+pub fn store_baselines(baselines: &[Baseline]) -> Result<(), Box<dyn StdError>> {
+    let conn = init_db(REQUEST)?;
+    insert_baselines(&conn, baselines)
+}
+
+/// Insert or replace the baselines of their components.
+fn insert_baselines(conn: &Connection, baselines: &[Baseline]) -> Result<(), Box<dyn StdError>> {
+    let mut stmt = conn.prepare(
+        "INSERT INTO baselines (component, timestamp, idle_power_W, peak_power_W)
+        VALUES (?1, ?2, ?3, ?4)
+        ON CONFLICT (component) DO UPDATE SET timestamp = excluded.timestamp,
+            idle_power_W = excluded.idle_power_W,
+            peak_power_W = COALESCE(excluded.peak_power_W, peak_power_W)",
+    )?;
+    for b in baselines {
+        stmt.execute(params![
+            b.component.label(),
+            b.timestamp,
+            b.idle_power,
+            b.peak_power
+        ])?;
+    }
+    Ok(())
+}
+
+/// Read the stored baselines.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+///
+/// # Returns
+///
+/// - The [`Baseline`] of each calibrated component, empty if no calibration is stored.
+/// - An error if the table can't be read.
+pub fn load_baselines(conn: &Connection) -> Result<Vec<Baseline>, Box<dyn StdError>> {
+    if !list_tables(conn)?.iter().any(|t| t == "baselines") {
+        return Ok(Vec::new());
+    }
+    let mut stmt =
+        conn.prepare("SELECT component, timestamp, idle_power_W, peak_power_W FROM baselines")?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut baselines: Vec<Baseline> = rows
+        .into_iter()
+        .filter_map(|(label, timestamp, idle_power, peak_power)| {
+            Some(Baseline {
+                component: *COMPONENTS.iter().find(|c| c.label() == label)?,
+                timestamp,
+                idle_power,
+                peak_power,
+            })
+        })
+        .collect();
+    baselines.sort_by_key(|b| COMPONENTS.iter().position(|c| *c == b.component));
+    Ok(baselines)
+}
+
+/// Read the baselines stored in a database file.
+///
+/// # Arguments
+///
+/// - `path` : Path of the SQLite database file.
+///
+/// # Returns
+///
+/// - The [`Baseline`] of each calibrated component, empty if the file does not exist.
+/// - An error if the database can't be read.
+pub fn stored_baselines(path: &Path) -> Result<Vec<Baseline>, Box<dyn StdError>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    load_baselines(&conn)
+}
+
+/// Static and dynamic energies of a component over an interval.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComponentSplit {
+    /// Component concerned.
+    pub component: Component,
+    /// Energy consumed in J.
+    pub energy: f64,
+    /// Calibrated idle power in W.
+    pub idle_power: f64,
+    /// Energy consumed at the idle power in J.
+    pub static_energy: f64,
+    /// Energy consumed above the idle power in J.
+    pub dynamic_energy: f64,
+    /// Position of the power between the idle and peak powers, from 0 to 1,
+    /// if the peak power was calibrated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_ratio: Option<f64>,
+}
+
+/// Energy of the machine split between its static and dynamic parts.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnergySplit {
+    /// Breakdown by calibrated component.
+    pub components: Vec<ComponentSplit>,
+    /// Static energy of the calibrated components in J.
+    pub static_energy: f64,
+    /// Dynamic energy of the calibrated components in J.
+    pub dynamic_energy: f64,
+}
+
+/// Split the energy of a summary with the calibrated baselines.
+/// The static energy is the idle power over the interval, bounded by the energy consumed.
+///
+/// # Arguments
+///
+/// - `summary` : [`EnergySummary`] to split.
+/// - `baselines` : Calibrated [`Baseline`] of the components.
+///
+/// # Returns
+///
+/// The [`EnergySplit`] of the calibrated components, none if no component of the summary is.
+pub fn split_energy(summary: &EnergySummary, baselines: &[Baseline]) -> Option<EnergySplit> {
+    let components: Vec<ComponentSplit> = summary
+        .components
+        .iter()
+        .filter_map(|c| {
+            let baseline = baselines.iter().find(|b| b.component == c.component)?;
+            let static_energy = (baseline.idle_power * summary.interval).min(c.energy);
+            Some(ComponentSplit {
+                component: c.component,
+                energy: c.energy,
+                idle_power: baseline.idle_power,
+                static_energy,
+                dynamic_energy: c.energy - static_energy,
+                load_ratio: baseline
+                    .peak_power
+                    .filter(|peak| *peak > baseline.idle_power)
+                    .map(|peak| {
+                        ((c.power - baseline.idle_power) / (peak - baseline.idle_power))
+                            .clamp(0.0, 1.0)
+                    }),
+            })
+        })
+        .collect();
+    if components.is_empty() {
+        return None;
+    }
+    Some(EnergySplit {
+        static_energy: components.iter().fold(0.0, |sum, c| sum + c.static_energy),
+        dynamic_energy: components.iter().fold(0.0, |sum, c| sum + c.dynamic_energy),
+        components,
+    })
+}
+
+/// Store the energy split of a snapshot in database.
+///
+/// # Arguments
+///
+/// - `timestamp` : Date of the snapshot.
+/// - `interval` : Length of the interval of the energies in seconds.
+/// - `split` : [`EnergySplit`] to insert.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert request failed.
+pub fn store_split(
+    timestamp: &str,
+    interval: f64,
+    split: &EnergySplit,
+) -> Result<(), Box<dyn StdError>> {
+    let conn = init_db(SPLIT_REQUEST)?;
+    insert_split(&conn, timestamp, interval, split)
+}
+
+/// Insert the components of an energy split.
+fn insert_split(
+    conn: &Connection,
+    timestamp: &str,
+    interval: f64,
+    split: &EnergySplit,
+) -> Result<(), Box<dyn StdError>> {
+    let mut stmt = conn.prepare(
+        "INSERT INTO energy_split (timestamp, interval_s, component, energy_J, idle_power_W,
+            static_energy_J, dynamic_energy_J, load_ratio)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )?;
+    for c in &split.components {
+        stmt.execute(params![
+            timestamp,
+            interval,
+            c.component.label(),
+            c.energy,
+            c.idle_power,
+            c.static_energy,
+            c.dynamic_energy,
+            c.load_ratio
+        ])?;
+    }
+    Ok(())
+}
+
+/// Static and dynamic energies of a component over a time range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SplitRollup {
+    /// Name of the component, `total` for the whole machine.
+    pub component: String,
+    /// Energy consumed in J.
+    pub energy: f64,
+    /// Energy consumed at the idle power in J.
+    pub static_energy: f64,
+    /// Energy consumed above the idle power in J.
+    pub dynamic_energy: f64,
+}
+
+/// Static and dynamic energies by component over a time range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SplitReport {
+    /// Lower bound of the time range, if given.
+    pub from: Option<String>,
+    /// Upper bound of the time range, if given.
+    pub to: Option<String>,
+    /// Energies of each calibrated component.
+    pub components: Vec<SplitRollup>,
+    /// Energies of the calibrated components together, none without stored split.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<SplitRollup>,
+}
+
+/// Sum the stored static and dynamic energies by component.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `filter` : Time range, and component to keep.
+///
+/// # Returns
+///
+/// - The [`SplitReport`], empty if no split is stored.
+/// - An error if a time bound is invalid or the table can't be read.
+pub fn split_report(conn: &Connection, filter: &Filter) -> Result<SplitReport, Box<dyn StdError>> {
+    let mut report = SplitReport {
+        from: filter.from.clone(),
+        to: filter.to.clone(),
+        components: Vec::new(),
+        total: None,
+    };
+    if !list_tables(conn)?.iter().any(|t| t == "energy_split") {
+        return Ok(report);
+    }
+
+    let selection = select(conn, "energy_split", filter)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT q.component, TOTAL(q.energy_J), TOTAL(q.static_energy_J), TOTAL(q.dynamic_energy_J)
+        FROM ({}) q GROUP BY q.component",
+        selection.query
+    ))?;
+    report.components = stmt
+        .query_map(&*selection.params(), |row| {
+            Ok(SplitRollup {
+                component: row.get(0)?,
+                energy: row.get(1)?,
+                static_energy: row.get(2)?,
+                dynamic_energy: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    report
+        .components
+        .sort_by_key(|r| COMPONENTS.iter().position(|c| c.label() == r.component));
+
+    report.total = (!report.components.is_empty()).then(|| SplitRollup {
+        component: "total".to_string(),
+        energy: report.components.iter().map(|r| r.energy).sum(),
+        static_energy: report.components.iter().map(|r| r.static_energy).sum(),
+        dynamic_energy: report.components.iter().map(|r| r.dynamic_energy).sum(),
+    });
+    Ok(report)
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::energy::{ComponentEnergy, Source};
+
+    fn baseline(component: Component, idle_power: f64, peak_power: Option<f64>) -> Baseline {
+        Baseline {
+            component,
+            timestamp: "2025-01-01T00:00:00.000Z".to_string(),
+            idle_power,
+            peak_power,
+        }
+    }
+
+    fn energy(component: Component, power: f64, interval: f64) -> ComponentEnergy {
+        ComponentEnergy {
+            component,
+            power,
+            energy: power * interval,
+            source: Source::Measured,
+        }
+    }
+
+    // Test `insert_baselines` and `load_baselines` functions keeping the previous peak power
+    #[test]
+    fn test_store_baselines() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(load_baselines(&conn).unwrap().is_empty());
+        conn.execute_batch(REQUEST).unwrap();
+
+        insert_baselines(
+            &conn,
+            &[
+                baseline(Component::Memory, 3.0, Some(6.0)),
+                baseline(Component::Cpu, 10.0, Some(65.0)),
+            ],
+        )
+        .unwrap();
+        insert_baselines(&conn, &[baseline(Component::Cpu, 12.0, None)]).unwrap();
+
+        let res = load_baselines(&conn).unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0], baseline(Component::Cpu, 12.0, Some(65.0)));
+        assert_eq!(res[1].component, Component::Memory);
+    }
+
+    // Test `split_energy` function bounding the static energy by the energy consumed
+    #[test]
+    fn test_split_energy() {
+        let summary = EnergySummary {
+            interval: 2.0,
+            components: vec![
+                energy(Component::Cpu, 30.0, 2.0),
+                energy(Component::Gpu, 40.0, 2.0),
+                energy(Component::Memory, 2.0, 2.0),
+            ],
+            total_power: 72.0,
+            total_energy: 144.0,
+            measured_ratio: Some(1.0),
+        };
+        let baselines = [
+            baseline(Component::Cpu, 10.0, Some(50.0)),
+            baseline(Component::Memory, 3.0, None),
+        ];
+
+        let res = split_energy(&summary, &baselines).unwrap();
+        assert_eq!(res.components.len(), 2);
+        assert_eq!(res.components[0].static_energy, 20.0);
+        assert_eq!(res.components[0].dynamic_energy, 40.0);
+        assert_eq!(res.components[0].load_ratio, Some(0.5));
+        assert_eq!(res.components[1].static_energy, 4.0);
+        assert_eq!(res.components[1].dynamic_energy, 0.0);
+        assert_eq!(res.components[1].load_ratio, None);
+        assert_eq!((res.static_energy, res.dynamic_energy), (24.0, 40.0));
+
+        assert_eq!(split_energy(&summary, &[]), None);
+    }
+
+    // Test `split_report` function summing the stored energies by component
+    #[test]
+    fn test_split_report() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(split_report(&conn, &Filter::default()).unwrap().total, None);
+        conn.execute_batch(SPLIT_REQUEST).unwrap();
+        let split = |cpu: f64, memory: f64| EnergySplit {
+            components: vec![
+                ComponentSplit {
+                    component: Component::Memory,
+                    energy: memory,
+                    idle_power: 1.0,
+                    static_energy: memory,
+                    dynamic_energy: 0.0,
+                    load_ratio: None,
+                },
+                ComponentSplit {
+                    component: Component::Cpu,
+                    energy: cpu,
+                    idle_power: 10.0,
+                    static_energy: 10.0,
+                    dynamic_energy: cpu - 10.0,
+                    load_ratio: None,
+                },
+            ],
+            static_energy: 0.0,
+            dynamic_energy: 0.0,
+        };
+        insert_split(&conn, "2025-01-01T00:00:00.000Z", 1.0, &split(15.0, 1.0)).unwrap();
+        insert_split(&conn, "2025-01-01T00:00:01.000Z", 1.0, &split(30.0, 1.0)).unwrap();
+
+        let res = split_report(&conn, &Filter::default()).unwrap();
+        assert_eq!(res.components.len(), 2);
+        assert_eq!(res.components[0].component, "cpu");
+        assert_eq!(res.components[0].static_energy, 20.0);
+        assert_eq!(res.components[0].dynamic_energy, 25.0);
+        let total = res.total.unwrap();
+        assert_eq!((total.energy, total.static_energy), (47.0, 22.0));
+
+        let filter = Filter {
+            entity: Some("memory".to_string()),
+            ..Default::default()
+        };
+        let res = split_report(&conn, &filter).unwrap();
+        assert_eq!(res.components.len(), 1);
+        assert_eq!(res.components[0].energy, 2.0);
+    }
+}
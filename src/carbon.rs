@@ -0,0 +1,526 @@
+//! # File carbon
+//!
+//! This file provides the carbon emissions estimation, multiplying the energy of the machine
+//! by the carbon intensity of its electricity grid, from a static value, an hourly profile
+//! or an HTTP provider.
+
+use chrono::{DateTime, Timelike};
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use ureq::{
+    Agent,
+    tls::{TlsConfig, TlsProvider},
+};
+use userv_core::core::init_db;
+
+use crate::energy::EnergySummary;
+
+/// Request creating the table of the emissions.
+pub const REQUEST: &str = "CREATE TABLE IF NOT EXISTS emissions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    interval_s REAL NOT NULL,
+    energy_J REAL NOT NULL,
+    intensity_gCO2e_kWh REAL NOT NULL,
+    emissions_gCO2e REAL NOT NULL,
+    source TEXT NOT NULL,
+    zone TEXT
+);";
+
+/// Number of joules in a kilowatt-hour.
+const JOULES_PER_KWH: f64 = 3.6e6;
+/// Time allowed to the carbon intensity provider to answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn default_refresh() -> u64 {
+    900
+}
+
+/// Origin of the carbon intensity of the grid, from the `[carbon]` section of the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum CarbonSource {
+    /// Constant intensity.
+    Static {
+        /// Carbon intensity in gCO2e/kWh.
+        intensity: f64,
+        /// Country or zone of the grid, for the reports.
+        zone: Option<String>,
+    },
+    /// Intensity by hour of the day (UTC), from a CSV file with `zone,hour,intensity` columns.
+    Profile {
+        /// Path of the CSV file.
+        path: PathBuf,
+        /// Country or zone whose rows are read.
+        zone: String,
+    },
+    /// Intensity given by an HTTP provider answering `{"carbonIntensity": <gCO2e/kWh>, ...}`,
+    /// in the shape of the ElectricityMaps `carbon-intensity/latest` endpoint.
+    Api {
+        /// URL of the endpoint.
+        url: String,
+        /// Country or zone, sent as `zone` query parameter.
+        zone: Option<String>,
+        /// Token sent in the `auth-token` header.
+        token: Option<String>,
+        /// Seconds during which an answer is reused.
+        #[serde(default = "default_refresh")]
+        refresh: u64,
+    },
+}
+
+impl CarbonSource {
+    /// Name of the source stored in database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CarbonSource::Static { .. } => "static",
+            CarbonSource::Profile { .. } => "profile",
+            CarbonSource::Api { .. } => "api",
+        }
+    }
+
+    /// Country or zone of the grid, if known.
+    pub fn zone(&self) -> Option<&str> {
+        match self {
+            CarbonSource::Static { zone, .. } | CarbonSource::Api { zone, .. } => zone.as_deref(),
+            CarbonSource::Profile { zone, .. } => Some(zone),
+        }
+    }
+}
+
+/// Carbon emissions of the machine over the interval before a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Emissions {
+    /// Length of the interval in seconds.
+    pub interval: f64,
+    /// Energy consumed by the machine in J.
+    pub energy: f64,
+    /// Carbon intensity of the grid in gCO2e/kWh.
+    pub intensity: f64,
+    /// Emissions in gCO2e.
+    pub emissions: f64,
+    /// Origin of the intensity.
+    pub source: String,
+    /// Country or zone of the grid, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zone: Option<String>,
+}
+
+/// Hourly profile loaded, last provider answer and last request to the provider.
+#[derive(Debug, Default)]
+struct Cache {
+    profile: Option<HashMap<u32, f64>>,
+    fetched: Option<f64>,
+    requested: Option<Instant>,
+    failure: Option<String>,
+}
+
+/// Carbon emissions estimation, keeping the loaded profile and the provider answer.
+/// Clones share the same cache.
+#[derive(Debug, Clone, Default)]
+pub struct Carbon {
+    source: Option<CarbonSource>,
+    cache: Arc<Mutex<Cache>>,
+}
+
+/// Read the intensity of each hour of a zone in a CSV profile.
+fn load_profile(path: &Path, zone: &str) -> Result<HashMap<u32, f64>, Box<dyn StdError>> {
+    #[derive(Deserialize)]
+    struct Row {
+        zone: String,
+        hour: u32,
+        intensity: f64,
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(|e| format!("Config 'Invalid carbon profile' : {} {e}", path.display()))?;
+    let mut profile = HashMap::new();
+    for row in reader.deserialize::<Row>() {
+        let row =
+            row.map_err(|e| format!("Config 'Invalid carbon profile' : {} {e}", path.display()))?;
+        if row.hour > 23 {
+            return Err(format!("Config 'Invalid carbon profile hour' : {}", row.hour).into());
+        }
+        if row.zone == zone {
+            profile.insert(row.hour, row.intensity);
+        }
+    }
+    if profile.is_empty() {
+        return Err(format!("Config 'Unknown carbon profile zone' : {zone}").into());
+    }
+    Ok(profile)
+}
+
+/// Ask the carbon intensity to an HTTP provider.
+fn fetch_intensity(
+    url: &str,
+    zone: Option<&str>,
+    token: Option<&str>,
+) -> Result<f64, Box<dyn StdError>> {
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .tls_config(
+            TlsConfig::builder()
+                .provider(TlsProvider::NativeTls)
+                .build(),
+        )
+        .build()
+        .into();
+    let mut request = agent.get(url);
+    if let Some(zone) = zone {
+        request = request.query("zone", zone);
+    }
+    if let Some(token) = token {
+        request = request.header("auth-token", token);
+    }
+    let body = request
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_string())
+        .map_err(|e| format!("Network 'Carbon intensity unavailable' : {url} {e}"))?;
+    let body: serde_json::Value = serde_json::from_str(&body)
+        .map_err(|e| format!("Data 'Invalid carbon intensity' : {e}"))?;
+    body["carbonIntensity"]
+        .as_f64()
+        .ok_or_else(|| format!("Data 'Invalid carbon intensity' : {body}").into())
+}
+
+impl Carbon {
+    /// Create the emissions estimation.
+    ///
+    /// # Arguments
+    ///
+    /// - `source` : Configured [`CarbonSource`], none to disable the estimation.
+    pub fn new(source: Option<CarbonSource>) -> Self {
+        Carbon {
+            source,
+            cache: Arc::default(),
+        }
+    }
+
+    /// Check if no carbon intensity source is configured.
+    pub fn is_empty(&self) -> bool {
+        self.source.is_none()
+    }
+
+    /// Give the carbon intensity of the grid.
+    /// A provider is asked again once its last request, answered or failed, is older than
+    /// its refresh delay, without holding the cache meanwhile. The previous answer is
+    /// given until then, and kept if the provider fails.
+    ///
+    /// # Arguments
+    ///
+    /// - `hour` : Hour of the day (UTC), for a profile.
+    /// - `now` : Instant of the request, for the provider answer age.
+    ///
+    /// # Returns
+    ///
+    /// - The intensity in gCO2e/kWh.
+    /// - An error if no source is configured, the profile can't be read or has no value
+    ///   for the hour, or the provider never answered yet.
+    pub fn intensity(&self, hour: u32, now: Instant) -> Result<f64, Box<dyn StdError>> {
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        match &self.source {
+            None => Err("Config 'No carbon intensity source'".into()),
+            Some(CarbonSource::Static { intensity, .. }) => Ok(*intensity),
+            Some(CarbonSource::Profile { path, zone }) => {
+                if cache.profile.is_none() {
+                    cache.profile = Some(load_profile(path, zone)?);
+                }
+                cache
+                    .profile
+                    .as_ref()
+                    .and_then(|profile| profile.get(&hour).copied())
+                    .ok_or_else(|| {
+                        format!("Data 'No carbon intensity for hour {hour}' : {zone}").into()
+                    })
+            }
+            Some(CarbonSource::Api {
+                url,
+                zone,
+                token,
+                refresh,
+            }) => {
+                if cache
+                    .requested
+                    .is_none_or(|at| now.duration_since(at) >= Duration::from_secs(*refresh))
+                {
+                    cache.requested = Some(now);
+                    drop(cache);
+                    let fetched = fetch_intensity(url, zone.as_deref(), token.as_deref());
+                    cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+                    match fetched {
+                        Ok(intensity) => {
+                            cache.fetched = Some(intensity);
+                            cache.failure = None;
+                        }
+                        Err(e) => cache.failure = Some(e.to_string()),
+                    }
+                }
+                cache.fetched.ok_or_else(|| {
+                    cache
+                        .failure
+                        .clone()
+                        .unwrap_or_else(|| format!("Network 'Carbon intensity pending' : {url}"))
+                        .into()
+                })
+            }
+        }
+    }
+
+    /// Estimate the emissions of the energy consumed by the machine.
+    ///
+    /// # Arguments
+    ///
+    /// - `timestamp` : Date of the snapshot (RFC 3339), giving the hour of a profile.
+    /// - `energy` : [`EnergySummary`] of the snapshot.
+    /// - `now` : Instant of the snapshot.
+    ///
+    /// # Returns
+    ///
+    /// - The [`Emissions`] over the interval of the energy summary.
+    /// - An error if the timestamp is invalid or the intensity unavailable.
+    pub fn emissions(
+        &self,
+        timestamp: &str,
+        energy: &EnergySummary,
+        now: Instant,
+    ) -> Result<Emissions, Box<dyn StdError>> {
+        let hour = DateTime::parse_from_rfc3339(timestamp)
+            .map_err(|e| format!("Data 'Invalid timestamp' : {timestamp} {e}"))?
+            .to_utc()
+            .hour();
+        let intensity = self.intensity(hour, now)?;
+        Ok(Emissions {
+            interval: energy.interval,
+            energy: energy.total_energy,
+            intensity,
+            emissions: energy.total_energy / JOULES_PER_KWH * intensity,
+            source: self
+                .source
+                .as_ref()
+                .map(|s| s.as_str().to_string())
+                .unwrap_or_default(),
+            zone: self
+                .source
+                .as_ref()
+                .and_then(|s| s.zone())
+                .map(str::to_string),
+        })
+    }
+}
+
+/// Store the emissions of a snapshot in database.
+///
+/// # Arguments
+///
+/// - `timestamp` : Date of the snapshot.
+/// - `emissions` : [`Emissions`] to insert.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert request failed.
+pub fn store_emissions(timestamp: &str, emissions: &Emissions) -> Result<(), Box<dyn StdError>> {
+    let conn = init_db(REQUEST)?;
+    conn.execute(
+        "INSERT INTO emissions (timestamp, interval_s, energy_J, intensity_gCO2e_kWh, emissions_gCO2e, source, zone)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            timestamp,
+            emissions.interval,
+            emissions.energy,
+            emissions.intensity,
+            emissions.emissions,
+            emissions.source,
+            emissions.zone
+        ],
+    )?;
+    Ok(())
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        thread::spawn,
+    };
+    use tempfile::NamedTempFile;
+    use userv_core::config::Config;
+
+    fn source(section: &str) -> CarbonSource {
+        Config::parse(&format!("[carbon]\n{section}"))
+            .unwrap()
+            .section::<Option<CarbonSource>>("carbon")
+            .unwrap()
+            .unwrap()
+    }
+
+    fn energy(joules: f64) -> EnergySummary {
+        EnergySummary {
+            interval: 60.0,
+            components: Vec::new(),
+            total_power: joules / 60.0,
+            total_energy: joules,
+            measured_ratio: None,
+        }
+    }
+
+    // Test `emissions` function with a static intensity
+    #[test]
+    fn test_emissions_static() {
+        let carbon = Carbon::new(Some(source(
+            "source = \"static\"\nintensity = 400.0\nzone = \"DE\"\n",
+        )));
+        let res = carbon
+            .emissions("2025-01-01T10:00:00.000Z", &energy(7.2e6), Instant::now())
+            .unwrap();
+        assert_eq!(res.emissions, 800.0);
+        assert_eq!(res.source, "static");
+        assert_eq!(res.zone.as_deref(), Some("DE"));
+
+        assert!(
+            Carbon::default()
+                .emissions("2025-01-01T10:00:00.000Z", &energy(1.0), Instant::now())
+                .is_err()
+        );
+    }
+
+    // Test `intensity` function with an hourly profile and its errors
+    #[test]
+    fn test_intensity_profile() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "zone,hour,intensity\nFR,0,40\nFR,10,55.5\nPL,10,700").unwrap();
+        let path = file.path().display();
+
+        let carbon = Carbon::new(Some(source(&format!(
+            "source = \"profile\"\npath = \"{path}\"\nzone = \"FR\"\n"
+        ))));
+        let res = carbon
+            .emissions("2025-01-01T11:30:00+01:00", &energy(3.6e6), Instant::now())
+            .unwrap();
+        assert_eq!(res.intensity, 55.5);
+        assert!(carbon.intensity(5, Instant::now()).is_err());
+
+        let carbon = Carbon::new(Some(source(&format!(
+            "source = \"profile\"\npath = \"{path}\"\nzone = \"US\"\n"
+        ))));
+        assert!(carbon.intensity(10, Instant::now()).is_err());
+    }
+
+    // Test `intensity` function waiting the refresh delay after a provider failure
+    #[test]
+    fn test_intensity_api_failure() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/latest", listener.local_addr().unwrap());
+        let server = spawn(move || {
+            for (status, body) in [
+                ("500 Error", "{}"),
+                ("200 OK", r#"{"carbonIntensity": 80}"#),
+            ] {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while line != "\r\n" {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                }
+                write!(
+                    &stream,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+                .unwrap();
+            }
+        });
+
+        let carbon = Carbon::new(Some(source(&format!(
+            "source = \"api\"\nurl = \"{url}\"\nrefresh = 60\n"
+        ))));
+        let start = Instant::now();
+        assert!(carbon.intensity(10, start).is_err());
+        assert!(
+            carbon
+                .intensity(10, start + Duration::from_secs(30))
+                .is_err()
+        );
+        assert_eq!(
+            carbon
+                .intensity(10, start + Duration::from_secs(60))
+                .unwrap(),
+            80.0
+        );
+        server.join().unwrap();
+    }
+
+    // Test `intensity` function with a mock provider, its cache and its failures
+    #[test]
+    fn test_intensity_api() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/v3/carbon-intensity/latest",
+            listener.local_addr().unwrap()
+        );
+        let server = spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                request.push(line.trim_end().to_lowercase());
+            }
+            let body =
+                r#"{"zone": "FR", "carbonIntensity": 52, "datetime": "2025-01-01T10:00:00.000Z"}"#;
+            write!(
+                &stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            request
+        });
+
+        let carbon = Carbon::new(Some(source(&format!(
+            "source = \"api\"\nurl = \"{url}\"\nzone = \"FR\"\ntoken = \"secret\"\nrefresh = 60\n"
+        ))));
+        let start = Instant::now();
+        assert_eq!(carbon.intensity(10, start).unwrap(), 52.0);
+        let request = server.join().unwrap();
+        assert!(request[0].starts_with("get /v3/carbon-intensity/latest?zone=fr "));
+        assert!(request.contains(&"auth-token: secret".to_string()));
+
+        assert_eq!(
+            carbon
+                .intensity(10, start + Duration::from_secs(30))
+                .unwrap(),
+            52.0
+        );
+        assert_eq!(
+            carbon
+                .intensity(10, start + Duration::from_secs(120))
+                .unwrap(),
+            52.0
+        );
+        let carbon = Carbon::new(Some(source(&format!(
+            "source = \"api\"\nurl = \"{url}\"\n"
+        ))));
+        assert!(carbon.intensity(10, start).is_err());
+    }
+}
@@ -0,0 +1,405 @@
+//! # File collector
+//!
+//! This file provides the entry point of the library, running the probes
+//! simultaneously in threaded tasks, once or periodically.
+
+use chrono::{SecondsFormat, Utc};
+use cpu::CpuPowerInfo;
+use exec::{ExecProbe, ExecSchedule};
+use log::error;
+use std::{
+    error::Error as StdError,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+    },
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+use sysinfo::System;
+use userv_core::{
+    alert::{Alert, Alerts, REQUEST, Transition, store_transitions},
+    anomaly::{Anomalies, Detector},
+    bus::{Sample, bus},
+    config::Config,
+    core::init_db,
+};
+
+use crate::{
+    attribution::{CgroupMeter, ProcessMeter},
+    baseline::{Baseline, split_energy},
+    carbon::{Carbon, CarbonSource},
+    component::Component,
+    cost::Tariff,
+    embodied::{EmbodiedMeter, Footprint},
+    energy::EnergyMeter,
+    error::Error,
+    health::{HealthMonitor, Thresholds},
+    notify::{Channel, Notifier},
+    snapshot::{Snapshot, collect_part},
+    tdp::{CpuPowerModel, ZONE},
+};
+
+const HEADER: &str = "COLLECTOR";
+
+/// Parameters of a [`Collector`].
+#[derive(Debug, Clone)]
+pub struct CollectorConfig {
+    /// Components retrieved by [`Collector::sampling`].
+    pub components: Vec<Component>,
+    /// Interval between each snapshot of [`Collector::sampling`].
+    pub interval: Duration,
+    /// Store each snapshot in the SQLite database.
+    pub persist: bool,
+    /// Publish the metrics of each snapshot on the live samples bus.
+    pub publish: bool,
+    /// External probes run by [`Component::Exec`].
+    pub exec: Vec<ExecProbe>,
+    /// Estimate of the CPU power when no RAPL zone is readable.
+    pub cpu_power: CpuPowerModel,
+    /// Alerts evaluated on the stored data after each snapshot, when persisted.
+    pub alerts: Vec<Alert>,
+    /// Anomaly detection run on the stored data after each snapshot, when persisted.
+    pub anomaly: Detector,
+    /// Carbon intensity of the grid, to estimate the emissions of the energy.
+    pub carbon: Option<CarbonSource>,
+    /// Electricity tariff, to estimate the cost of the energy.
+    pub cost: Option<Tariff>,
+    /// Manufacturing footprint of the hardware, to estimate its embodied emissions.
+    pub embodied: Option<Footprint>,
+    /// Thresholds of the health scoring of the components.
+    pub health: Thresholds,
+    /// Calibrated baselines, to split the energy between its static and dynamic parts.
+    pub baselines: Vec<Baseline>,
+    /// Channels notified of the alert transitions.
+    pub notify: Vec<Channel>,
+}
+
+impl CollectorConfig {
+    /// Read the parameters of the collector from the sections of a configuration,
+    /// the ones without section keeping their default value.
+    ///
+    /// # Arguments
+    ///
+    /// - `config` : Loaded [`Config`].
+    ///
+    /// # Returns
+    ///
+    /// - The [`CollectorConfig`] of the configuration.
+    /// - An error if a section is invalid.
+    pub fn from_config(config: &Config) -> Result<Self, Box<dyn StdError>> {
+        Ok(CollectorConfig {
+            exec: config.section("exec")?,
+            cpu_power: config.section("cpu_power")?,
+            alerts: config.section("alerts")?,
+            anomaly: config.section("anomaly")?,
+            carbon: config.section("carbon")?,
+            cost: config.section("cost")?,
+            embodied: config.section("embodied")?,
+            health: config.section("health")?,
+            notify: config.section("notify")?,
+            ..Default::default()
+        })
+    }
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        CollectorConfig {
+            components: Component::ALL.to_vec(),
+            interval: Duration::from_secs(1),
+            persist: false,
+            publish: false,
+            exec: Vec::new(),
+            cpu_power: CpuPowerModel::default(),
+            alerts: Vec::new(),
+            anomaly: Detector::default(),
+            carbon: None,
+            cost: None,
+            embodied: None,
+            health: Thresholds::default(),
+            baselines: Vec::new(),
+            notify: Vec::new(),
+        }
+    }
+}
+
+/// Collector of machine data, running the probes of the selected components.
+#[derive(Debug, Clone, Default)]
+pub struct Collector {
+    config: CollectorConfig,
+    schedule: ExecSchedule,
+    alerts: Alerts,
+    anomalies: Anomalies,
+    notifier: Option<Sender<Vec<Transition>>>,
+    meter: EnergyMeter,
+    processes: ProcessMeter,
+    cgroups: CgroupMeter,
+    carbon: Carbon,
+    embodied: EmbodiedMeter,
+    health: HealthMonitor,
+    /// The CPU power can't be estimated, already reported.
+    unestimated: Arc<AtomicBool>,
+}
+
+impl Collector {
+    /// Create a collector.
+    ///
+    /// # Arguments
+    ///
+    /// - `config` : [`CollectorConfig`] parameters of the collector.
+    pub fn new(config: CollectorConfig) -> Self {
+        Collector {
+            alerts: Alerts::new(config.alerts.clone()),
+            anomalies: Anomalies::new(config.anomaly.clone()),
+            notifier: (config.persist && !config.notify.is_empty())
+                .then(|| Notifier::new(config.notify.clone()).spawn()),
+            meter: EnergyMeter::default(),
+            processes: ProcessMeter::default(),
+            cgroups: CgroupMeter::default(),
+            carbon: Carbon::new(config.carbon.clone()),
+            embodied: EmbodiedMeter::new(config.embodied.clone()),
+            health: HealthMonitor::new(config.health.clone()),
+            config,
+            schedule: ExecSchedule::default(),
+            unestimated: Arc::default(),
+        }
+    }
+
+    /// Parameters of the collector.
+    pub fn config(&self) -> &CollectorConfig {
+        &self.config
+    }
+
+    /// Run simultaneously the probes of the given components, once.
+    /// A probe which failed does not prevent the others to be retrieved,
+    /// its error message is kept in [`Snapshot::errors`].
+    /// The energy, emissions, cost and health are then derived from the probes data,
+    /// and the alerts evaluated once the snapshot is stored.
+    ///
+    /// # Arguments
+    ///
+    /// - `components` : List of [`Component`] to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// - The [`Snapshot`] gathering the data of each probe.
+    /// - An [`Error`] if no component is given, or if the snapshot can't be stored.
+    pub fn collect(&self, components: &[Component]) -> Result<Snapshot, Error> {
+        if components.is_empty() {
+            return Err(Error::NoComponent);
+        }
+
+        let mut snapshot = Snapshot {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            hostname: System::host_name(),
+            ..Default::default()
+        };
+
+        let handles: Vec<_> = components
+            .iter()
+            .map(|&component| {
+                let probes = self.config.exec.clone();
+                let schedule = self.schedule.clone();
+                let handle = spawn(move || collect_part(component, &probes, &schedule));
+                (component, handle)
+            })
+            .collect();
+
+        for (component, handle) in handles {
+            let message = match handle.join() {
+                Ok(Ok(part)) => {
+                    snapshot.insert(part);
+                    continue;
+                }
+                Ok(Err(e)) => e,
+                Err(e) => format!("Process 'Failure in the thread' : {e:?}"),
+            };
+            error!(
+                "{}",
+                Error::Probe {
+                    component,
+                    message: message.clone()
+                }
+            );
+            snapshot
+                .errors
+                .insert(component.label().to_string(), message);
+        }
+
+        if let Some(cpu) = &mut snapshot.cpu
+            && cpu.power.is_none()
+        {
+            match self.config.cpu_power.estimate(cpu) {
+                Ok(power) => {
+                    cpu.power = Some(CpuPowerInfo {
+                        powers: vec![(ZONE.to_string(), power)],
+                    })
+                }
+                Err(e) => {
+                    if !self.unestimated.swap(true, Ordering::Relaxed) {
+                        error!("[{HEADER}] {e} : CPU power left unknown");
+                    }
+                }
+            }
+        }
+
+        snapshot.health = self.health.evaluate(&snapshot);
+
+        let now = Instant::now();
+        snapshot.energy = self.meter.measure(&snapshot, now, self.config.interval);
+        if let Some(energy) = &snapshot.energy {
+            snapshot.energy_split = split_energy(energy, &self.config.baselines);
+            snapshot.process_energy = self.processes.attribute(&snapshot, energy.interval);
+            snapshot.cgroup_energy = self.cgroups.attribute(&snapshot, energy.interval);
+        }
+        if let Some(energy) = &snapshot.energy
+            && !self.carbon.is_empty()
+        {
+            match self.carbon.emissions(&snapshot.timestamp, energy, now) {
+                Ok(emissions) => snapshot.emissions = Some(emissions),
+                Err(e) => {
+                    error!("[{HEADER}] {e}");
+                    snapshot.errors.insert("carbon".to_string(), e.to_string());
+                }
+            }
+        }
+        if let Some(energy) = &snapshot.energy
+            && !self.embodied.is_empty()
+        {
+            snapshot.embodied = self.embodied.amortise(&snapshot, energy.interval);
+        }
+        if let (Some(energy), Some(tariff)) = (&snapshot.energy, &self.config.cost) {
+            match tariff.cost(&snapshot.timestamp, energy) {
+                Ok(cost) => snapshot.cost = Some(cost),
+                Err(e) => {
+                    error!("[{HEADER}] {e}");
+                    snapshot.errors.insert("cost".to_string(), e.to_string());
+                }
+            }
+        }
+
+        if self.config.publish {
+            bus().publish(snapshot.metrics().into_iter().map(|metric| Sample {
+                timestamp: snapshot.timestamp.clone(),
+                topic: format!("{}.{}", metric.component, metric.name),
+                entity: metric.entity,
+                value: metric.value,
+                unit: metric.unit,
+            }));
+        }
+
+        if self.config.persist {
+            snapshot.store().inspect_err(|e| error!("[{HEADER}] {e}"))?;
+            if (!self.alerts.is_empty() || !self.anomalies.is_empty())
+                && let Err(e) = self.evaluate(&mut snapshot)
+            {
+                error!("[{HEADER}] {e}");
+                snapshot.errors.insert("alerts".to_string(), e.to_string());
+            }
+            if let Some(notifier) = &self.notifier {
+                let _ = notifier.send(snapshot.alerts.clone());
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Evaluate the alerts and detect the anomalies on the stored data,
+    /// then store the transitions of both.
+    fn evaluate(&self, snapshot: &mut Snapshot) -> Result<(), Box<dyn StdError>> {
+        let mut conn = init_db(REQUEST)?;
+        let mut transitions = self
+            .alerts
+            .evaluate(&conn, &snapshot.timestamp, Instant::now())?;
+        if !self.anomalies.is_empty() {
+            let (anomalies, flagged) = self.anomalies.evaluate(&mut conn, &snapshot.timestamp)?;
+            snapshot.anomalies = anomalies;
+            transitions.extend(flagged);
+        }
+        store_transitions(&mut conn, &transitions)?;
+        snapshot.alerts = transitions;
+        Ok(())
+    }
+
+    /// Periodic sampling of the configured components.
+    ///
+    /// # Returns
+    ///
+    /// An endless [`Sampling`] iterator, yielding a snapshot every configured interval.
+    pub fn sampling(&self) -> Sampling<'_> {
+        Sampling {
+            collector: self,
+            next: None,
+        }
+    }
+}
+
+/// Iterator yielding a [`Snapshot`] of the configured components every interval.
+pub struct Sampling<'a> {
+    collector: &'a Collector,
+    next: Option<Instant>,
+}
+
+impl Iterator for Sampling<'_> {
+    type Item = Result<Snapshot, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(next) = self.next {
+            sleep(next.saturating_duration_since(Instant::now()));
+        }
+        self.next = Some(Instant::now() + self.collector.config.interval);
+        Some(self.collector.collect(&self.collector.config.components))
+    }
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test `collect` function without component
+    #[test]
+    fn test_collect_no_component() {
+        let res = Collector::default().collect(&[]);
+        assert_eq!(res.err(), Some(Error::NoComponent));
+    }
+
+    // Test `CollectorConfig::from_config` function with configured and missing sections
+    #[test]
+    fn test_from_config() {
+        let config = Config::parse(
+            "[[alerts]]\nrule = \"cpu_power.power > 100\"\n[health]\ncpu_temperature = 80\n",
+        )
+        .unwrap();
+        let res = CollectorConfig::from_config(&config).unwrap();
+        assert_eq!(res.alerts.len(), 1);
+        assert_eq!(res.health.cpu_temperature, 80.0);
+        assert!(res.carbon.is_none() && !res.persist);
+
+        let config = Config::parse("[cost]\nprice = \"free\"\n").unwrap();
+        assert!(CollectorConfig::from_config(&config).is_err());
+    }
+
+    // Test `sampling` function keeping only the configured components at each interval
+    #[test]
+    fn test_sampling_interval() {
+        let collector = Collector::new(CollectorConfig {
+            components: vec![Component::Net],
+            interval: Duration::from_millis(50),
+            ..Default::default()
+        });
+
+        let start = Instant::now();
+        let res: Vec<_> = collector.sampling().take(2).collect();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        for snapshot in res {
+            let snapshot = snapshot.unwrap();
+            assert!(snapshot.network.is_some());
+            assert!(snapshot.cpu.is_none());
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! # File component
+//!
+//! This file provides the enumeration of the components analyzed by a probe.
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Enumeration of available arguments corresponding to a component
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Component {
+    /// Motherboard or principal system board probe data.
+    Board,
+    /// Control groups and containers probe data.
+    Cgroup,
+    /// CPU probe data.
+    Cpu,
+    /// External probes data, run as configured commands.
+    Exec,
+    /// GPU device probe data.
+    Gpu,
+    /// Network probe data.
+    Net,
+    /// Computing memory probe data.
+    Memory,
+    /// Storage device probe data.
+    Storage,
+    /// Operating system probe data.
+    System,
+}
+
+impl Component {
+    /// All available components.
+    pub const ALL: [Component; 9] = [
+        Component::Board,
+        Component::Cgroup,
+        Component::Cpu,
+        Component::Exec,
+        Component::Gpu,
+        Component::Net,
+        Component::Memory,
+        Component::Storage,
+        Component::System,
+    ];
+
+    /// Name of the component used to group its data in reports.
+    ///
+    /// # Returns
+    ///
+    /// The lowercase name of the component.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Component::Board => "board",
+            Component::Cgroup => "cgroup",
+            Component::Cpu => "cpu",
+            Component::Exec => "exec",
+            Component::Gpu => "gpu",
+            Component::Net => "network",
+            Component::Memory => "memory",
+            Component::Storage => "storage",
+            Component::System => "system",
+        }
+    }
+
+    /// Identification header for information loggers about the probe of the component.
+    ///
+    /// # Returns
+    ///
+    /// The uppercase header of the probe.
+    pub fn header(&self) -> &'static str {
+        match self {
+            Component::Board => "BOARD",
+            Component::Cgroup => "CGROUP",
+            Component::Cpu => "CPU",
+            Component::Exec => "EXEC",
+            Component::Gpu => "GPU",
+            Component::Net => "NETWORK",
+            Component::Memory => "MEMORY",
+            Component::Storage => "STORAGE",
+            Component::System => "SYSTEM",
+        }
+    }
+
+    /// Find a component from its [`Component::label`].
+    ///
+    /// # Arguments
+    ///
+    /// - `label` : Lowercase name of the component.
+    ///
+    /// # Returns
+    ///
+    /// The component, if the label is known.
+    pub fn from_label(label: &str) -> Option<Component> {
+        Component::ALL.into_iter().find(|c| c.label() == label)
+    }
+}
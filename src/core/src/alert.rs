@@ -0,0 +1,701 @@
+//! # Alert file
+//!
+//! This module provides the threshold alerting rules evaluated on the last rows stored
+//! by the probes, tracking a pending, firing and resolved state per rule and entity.
+
+use log::error;
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::query::{self, list_columns, list_tables};
+
+const HEADER: &str = "ALERT";
+
+/// Request creating the table of the alert transitions.
+pub const REQUEST: &str = "CREATE TABLE IF NOT EXISTS alerts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    name TEXT NOT NULL,
+    rule TEXT NOT NULL,
+    entity TEXT,
+    state TEXT NOT NULL,
+    value REAL
+);";
+
+/// Comparison of a rule between the value of a column and its threshold or previous value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    /// Value greater than the threshold.
+    Greater,
+    /// Value greater than or equal to the threshold.
+    GreaterEqual,
+    /// Value less than the threshold.
+    Less,
+    /// Value less than or equal to the threshold.
+    LessEqual,
+    /// Value equal to the threshold.
+    Equal,
+    /// Value different from the threshold.
+    NotEqual,
+    /// Value greater than the previous one of the entity.
+    Increases,
+    /// Value less than the previous one of the entity.
+    Decreases,
+}
+
+impl Operator {
+    /// Find an operator from its symbol or keyword.
+    fn parse(operator: &str) -> Option<Operator> {
+        match operator {
+            ">" => Some(Operator::Greater),
+            ">=" => Some(Operator::GreaterEqual),
+            "<" => Some(Operator::Less),
+            "<=" => Some(Operator::LessEqual),
+            "==" => Some(Operator::Equal),
+            "!=" => Some(Operator::NotEqual),
+            "increases" => Some(Operator::Increases),
+            "decreases" => Some(Operator::Decreases),
+            _ => None,
+        }
+    }
+
+    /// Check if the operator compares the value with the previous one, without threshold.
+    fn is_trend(&self) -> bool {
+        matches!(self, Operator::Increases | Operator::Decreases)
+    }
+}
+
+/// Parse a duration.
+///
+/// # Arguments
+///
+/// - `duration` : Number of seconds, optionally suffixed by `s`, `m`, `h` or `d`.
+///
+/// # Returns
+///
+/// The [`Duration`], if valid.
+pub fn parse_duration(duration: &str) -> Option<Duration> {
+    let duration = duration.trim();
+    let (value, factor) = match duration.char_indices().last()? {
+        (i, 's') => (&duration[..i], 1),
+        (i, 'm') => (&duration[..i], 60),
+        (i, 'h') => (&duration[..i], 3600),
+        (i, 'd') => (&duration[..i], 86400),
+        _ => (duration, 1),
+    };
+    value
+        .parse::<u64>()
+        .ok()
+        .and_then(|v| v.checked_mul(factor))
+        .map(Duration::from_secs)
+}
+
+/// Condition `<table>.<column> <operator> [<threshold>] [for <duration>]` on a stored column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    /// Table of the column.
+    pub table: String,
+    /// Name of the column, with or without its unit suffix.
+    pub column: String,
+    /// Comparison of the value.
+    pub operator: Operator,
+    /// Threshold compared to the value, none for the trend operators.
+    pub threshold: Option<f64>,
+    /// Time the condition must hold before the alert fires.
+    pub duration: Duration,
+}
+
+impl Rule {
+    /// Parse a rule.
+    ///
+    /// # Arguments
+    ///
+    /// - `rule` : Rule like `cpu_temperature.temperature > 90 for 2m` or `smart_data.sectors_reallocated increases`.
+    ///
+    /// # Returns
+    ///
+    /// - The parsed [`Rule`].
+    /// - An error message if the rule is malformed.
+    pub fn parse(rule: &str) -> Result<Rule, String> {
+        let invalid = |reason: &str| format!("Config 'Invalid alert rule' : {rule} ({reason})");
+        let mut tokens = rule.split_whitespace();
+
+        let (table, column) = tokens
+            .next()
+            .and_then(|target| target.split_once('.'))
+            .filter(|(table, column)| !table.is_empty() && !column.is_empty())
+            .ok_or_else(|| invalid("expected <table>.<column>"))?;
+        let operator = tokens
+            .next()
+            .and_then(Operator::parse)
+            .ok_or_else(|| invalid("unknown operator"))?;
+
+        let threshold = if operator.is_trend() {
+            None
+        } else {
+            let threshold = tokens
+                .next()
+                .and_then(|t| t.parse::<f64>().ok())
+                .ok_or_else(|| invalid("expected a numeric threshold"))?;
+            Some(threshold)
+        };
+        let duration = match tokens.next() {
+            Some("for") => tokens
+                .next()
+                .and_then(parse_duration)
+                .ok_or_else(|| invalid("expected a duration after for"))?,
+            Some(_) => return Err(invalid("unexpected token")),
+            None => Duration::ZERO,
+        };
+        if tokens.next().is_some() {
+            return Err(invalid("unexpected token"));
+        }
+
+        Ok(Rule {
+            table: table.to_string(),
+            column: column.to_string(),
+            operator,
+            threshold,
+            duration,
+        })
+    }
+
+    /// Check the condition of the rule.
+    ///
+    /// # Arguments
+    ///
+    /// - `value` : Current value of the column.
+    /// - `previous` : Previous value of the column for the same entity, if any.
+    /// - `hysteresis` : Margin by which the threshold is moved back while the alert is active.
+    fn check(&self, value: f64, previous: Option<f64>, hysteresis: f64) -> bool {
+        let threshold = self.threshold.unwrap_or_default();
+        match self.operator {
+            Operator::Greater => value > threshold - hysteresis,
+            Operator::GreaterEqual => value >= threshold - hysteresis,
+            Operator::Less => value < threshold + hysteresis,
+            Operator::LessEqual => value <= threshold + hysteresis,
+            Operator::Equal => value == threshold,
+            Operator::NotEqual => value != threshold,
+            Operator::Increases => previous.is_some_and(|p| value > p),
+            Operator::Decreases => previous.is_some_and(|p| value < p),
+        }
+    }
+
+    /// Find the column of the rule among the columns of the table,
+    /// by its exact name or its name followed by a unit suffix.
+    fn resolve<'a>(&self, columns: &'a [String]) -> Option<&'a String> {
+        query::resolve_column(columns, &self.column)
+    }
+}
+
+/// Alert rule, from the `[[alerts]]` sections of the configuration.
+#[derive(Debug, Clone, Deserialize)]
+struct AlertConfig {
+    name: Option<String>,
+    rule: String,
+    #[serde(default)]
+    hysteresis: f64,
+}
+
+/// Alert on a [`Rule`], identified by its name.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "AlertConfig")]
+pub struct Alert {
+    /// Name of the alert, its rule if not given.
+    pub name: String,
+    /// Text of the rule.
+    pub text: String,
+    /// Parsed rule.
+    pub rule: Rule,
+    /// Margin by which the threshold is moved back before the alert is resolved.
+    pub hysteresis: f64,
+}
+
+impl TryFrom<AlertConfig> for Alert {
+    type Error = String;
+
+    fn try_from(config: AlertConfig) -> Result<Self, Self::Error> {
+        if config.hysteresis < 0.0 {
+            return Err(format!(
+                "Config 'Invalid alert hysteresis' : {}",
+                config.hysteresis
+            ));
+        }
+        Ok(Alert {
+            name: config.name.unwrap_or_else(|| config.rule.clone()),
+            rule: Rule::parse(&config.rule)?,
+            text: config.rule,
+            hysteresis: config.hysteresis,
+        })
+    }
+}
+
+/// State of an alert for an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    /// Condition true, for less than the duration of the rule.
+    Pending,
+    /// Condition true for the duration of the rule.
+    Firing,
+    /// Condition false again after being pending or firing.
+    Resolved,
+}
+
+impl State {
+    /// Name of the state stored in database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            State::Pending => "pending",
+            State::Firing => "firing",
+            State::Resolved => "resolved",
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Change of state of an alert for an entity.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Transition {
+    /// Date of the evaluation.
+    pub timestamp: String,
+    /// Name of the alert.
+    pub name: String,
+    /// Text of the rule.
+    pub rule: String,
+    /// Entity concerned (interface, disk, GPU bus id, pid...), if the table has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity: Option<String>,
+    /// New state of the alert.
+    pub state: State,
+    /// Value of the column at the evaluation.
+    pub value: f64,
+}
+
+/// Evaluation state of an alert for an entity.
+#[derive(Debug, Default)]
+struct Track {
+    state: Option<State>,
+    since: Option<Instant>,
+    previous: Option<f64>,
+}
+
+/// State of each alert, by index and entity.
+type Tracks = HashMap<(usize, Option<String>), Track>;
+
+/// Evaluation of the alerts, keeping the state of each alert and entity between cycles.
+/// Clones share the same states.
+#[derive(Debug, Clone, Default)]
+pub struct Alerts {
+    alerts: Vec<Alert>,
+    tracks: Arc<Mutex<Tracks>>,
+    /// Index of the alerts whose column is unknown, already reported.
+    unknown: Arc<Mutex<HashSet<usize>>>,
+}
+
+impl Alerts {
+    /// Create the evaluation of alerts, all inactive.
+    ///
+    /// # Arguments
+    ///
+    /// - `alerts` : Configured [`Alert`] list.
+    pub fn new(alerts: Vec<Alert>) -> Self {
+        Alerts {
+            alerts,
+            tracks: Arc::default(),
+            unknown: Arc::default(),
+        }
+    }
+
+    /// Check if no alert is configured.
+    pub fn is_empty(&self) -> bool {
+        self.alerts.is_empty()
+    }
+
+    /// Evaluate the alerts on the last rows of their table.
+    /// Tables not stored yet and null values are ignored, an unknown column is
+    /// reported once and its alert skipped. The entities missing from the last rows
+    /// are forgotten, their firing alerts being resolved.
+    ///
+    /// # Arguments
+    ///
+    /// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+    /// - `timestamp` : Date of the evaluation.
+    /// - `now` : Instant of the evaluation, to measure the duration of the conditions.
+    ///
+    /// # Returns
+    ///
+    /// - The [`Transition`] list of the alerts changing of state.
+    /// - An error if a table can't be read.
+    pub fn evaluate(
+        &self,
+        conn: &Connection,
+        timestamp: &str,
+        now: Instant,
+    ) -> Result<Vec<Transition>, Box<dyn Error>> {
+        let tables = list_tables(conn)?;
+        let mut tracks = self.tracks.lock().unwrap_or_else(|e| e.into_inner());
+        let mut unknown = self.unknown.lock().unwrap_or_else(|e| e.into_inner());
+        let mut transitions = Vec::new();
+
+        for (index, alert) in self.alerts.iter().enumerate() {
+            let rule = &alert.rule;
+            if !tables.contains(&rule.table) {
+                continue;
+            }
+            let columns = list_columns(conn, &rule.table)?;
+            let Some(column) = rule.resolve(&columns) else {
+                if unknown.insert(index) {
+                    error!(
+                        "[{HEADER}] Data 'Unknown column {}.{}' : alert {} skipped",
+                        rule.table, rule.column, alert.name
+                    );
+                }
+                continue;
+            };
+            unknown.remove(&index);
+
+            let values = query::latest_values(conn, &rule.table, column)?;
+            tracks.retain(|(i, entity), track| {
+                if *i != index || values.iter().any(|(e, _)| e == entity) {
+                    return true;
+                }
+                if let (Some(State::Firing), Some(value)) = (track.state, track.previous) {
+                    transitions.push(Transition {
+                        timestamp: timestamp.to_string(),
+                        name: alert.name.clone(),
+                        rule: alert.text.clone(),
+                        entity: entity.clone(),
+                        state: State::Resolved,
+                        value,
+                    });
+                }
+                false
+            });
+
+            for (entity, value) in values {
+                let track = tracks.entry((index, entity.clone())).or_default();
+                let previous = track.previous.replace(value);
+                let hysteresis = if track.state.is_some() {
+                    alert.hysteresis
+                } else {
+                    0.0
+                };
+                let condition = rule.check(value, previous, hysteresis);
+
+                let state = match (track.state, condition) {
+                    (None, true) => {
+                        track.since = Some(now);
+                        Some(if rule.duration.is_zero() {
+                            State::Firing
+                        } else {
+                            State::Pending
+                        })
+                    }
+                    (Some(State::Pending), true)
+                        if track
+                            .since
+                            .is_some_and(|since| now.duration_since(since) >= rule.duration) =>
+                    {
+                        Some(State::Firing)
+                    }
+                    (Some(State::Pending), false) => {
+                        track.state = None;
+                        None
+                    }
+                    (Some(_), false) => Some(State::Resolved),
+                    _ => None,
+                };
+
+                if let Some(state) = state {
+                    track.state = (state != State::Resolved).then_some(state);
+                    transitions.push(Transition {
+                        timestamp: timestamp.to_string(),
+                        name: alert.name.clone(),
+                        rule: alert.text.clone(),
+                        entity,
+                        state,
+                        value,
+                    });
+                }
+            }
+        }
+        Ok(transitions)
+    }
+}
+
+/// Store the alert transitions in database.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `transitions` : [`Transition`] list to insert.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert requests failed.
+pub fn store_transitions(
+    conn: &mut Connection,
+    transitions: &[Transition],
+) -> Result<(), Box<dyn Error>> {
+    conn.execute_batch(REQUEST)?;
+    let tx = conn.transaction()?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO alerts (timestamp, name, rule, entity, state, value) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for t in transitions {
+            stmt.execute(params![
+                t.timestamp,
+                t.name,
+                t.rule,
+                t.entity,
+                t.state.as_str(),
+                t.value
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn alerts(rules: &str) -> Alerts {
+        let config = Config::parse(rules).unwrap();
+        Alerts::new(config.section("alerts").unwrap())
+    }
+
+    fn insert(conn: &Connection, timestamp: &str, values: &[(&str, f64)]) {
+        for (zone, value) in values {
+            conn.execute(
+                "INSERT INTO cpu_temperature (timestamp, zone_name, \"temperature_°C\") VALUES (?1, ?2, ?3)",
+                params![timestamp, zone, value],
+            )
+            .unwrap();
+        }
+    }
+
+    fn states(transitions: &[Transition]) -> Vec<(Option<&str>, State)> {
+        transitions
+            .iter()
+            .map(|t| (t.entity.as_deref(), t.state))
+            .collect()
+    }
+
+    // Test `Rule::parse` function with the available forms and errors
+    #[test]
+    fn test_rule_parse() {
+        let res = Rule::parse("cpu_temperature.temperature > 90 for 2m").unwrap();
+        assert_eq!(res.table, "cpu_temperature");
+        assert_eq!(res.operator, Operator::Greater);
+        assert_eq!(res.threshold, Some(90.0));
+        assert_eq!(res.duration, Duration::from_secs(120));
+
+        let res = Rule::parse("smart_data.sectors_reallocated increases").unwrap();
+        assert_eq!(res.operator, Operator::Increases);
+        assert_eq!(res.threshold, None);
+        assert_eq!(res.duration, Duration::ZERO);
+
+        for rule in [
+            "",
+            "temperature > 90",
+            "cpu_temperature.temperature >> 90",
+            "cpu_temperature.temperature > hot",
+            "cpu_temperature.temperature > 90 for",
+            "cpu_temperature.temperature > 90 during 2m",
+        ] {
+            assert!(Rule::parse(rule).is_err(), "{rule}");
+        }
+    }
+
+    // Test `Rule::resolve` function with unit suffixes
+    #[test]
+    fn test_rule_resolve() {
+        let columns: Vec<String> = [
+            "space_available_MB",
+            "sectors_pending",
+            "sectors_pending_current",
+        ]
+        .map(str::to_string)
+        .to_vec();
+        let rule = |column: &str| Rule::parse(&format!("t.{column} > 0")).unwrap();
+        assert_eq!(
+            rule("space_available")
+                .resolve(&columns)
+                .map(String::as_str),
+            Some("space_available_MB")
+        );
+        assert_eq!(
+            rule("sectors_pending")
+                .resolve(&columns)
+                .map(String::as_str),
+            Some("sectors_pending")
+        );
+        assert_eq!(rule("space").resolve(&columns), None);
+    }
+
+    // Test `evaluate` function through pending, firing and resolved states with hysteresis
+    #[test]
+    fn test_evaluate_states() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE cpu_temperature (id INTEGER PRIMARY KEY, timestamp TEXT, zone_name TEXT, \"temperature_°C\" REAL);",
+        )
+        .unwrap();
+        let alerts = alerts(
+            "[[alerts]]\nname = \"hot\"\nrule = \"cpu_temperature.temperature > 90 for 2m\"\nhysteresis = 5\n",
+        );
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        insert(
+            &conn,
+            "2025-01-01T10:00:00Z",
+            &[("zone0", 95.0), ("zone1", 50.0)],
+        );
+        let res = alerts.evaluate(&conn, "t0", at(0)).unwrap();
+        assert_eq!(states(&res), vec![(Some("zone0"), State::Pending)]);
+        assert_eq!(res[0].name, "hot");
+
+        insert(
+            &conn,
+            "2025-01-01T10:01:00Z",
+            &[("zone0", 96.0), ("zone1", 50.0)],
+        );
+        assert!(alerts.evaluate(&conn, "t1", at(60)).unwrap().is_empty());
+
+        insert(
+            &conn,
+            "2025-01-01T10:02:00Z",
+            &[("zone0", 88.0), ("zone1", 50.0)],
+        );
+        let res = alerts.evaluate(&conn, "t2", at(120)).unwrap();
+        assert_eq!(states(&res), vec![(Some("zone0"), State::Firing)]);
+
+        insert(
+            &conn,
+            "2025-01-01T10:03:00Z",
+            &[("zone0", 84.0), ("zone1", 50.0)],
+        );
+        let res = alerts.evaluate(&conn, "t3", at(180)).unwrap();
+        assert_eq!(states(&res), vec![(Some("zone0"), State::Resolved)]);
+        assert_eq!(res[0].value, 84.0);
+    }
+
+    // Test `evaluate` function with a cancelled pending alert, a vanished entity and an unknown column
+    #[test]
+    fn test_evaluate_entities() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE cpu_temperature (id INTEGER PRIMARY KEY, timestamp TEXT, zone_name TEXT, \"temperature_°C\" REAL);",
+        )
+        .unwrap();
+        let alerts = alerts(
+            "[[alerts]]\nrule = \"cpu_temperature.voltage > 1\"\n\
+            [[alerts]]\nrule = \"cpu_temperature.temperature > 90 for 2m\"\n\
+            [[alerts]]\nrule = \"cpu_temperature.temperature > 80\"\n",
+        );
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        insert(
+            &conn,
+            "2025-01-01T10:00:00Z",
+            &[("zone0", 95.0), ("zone1", 95.0)],
+        );
+        let res = alerts.evaluate(&conn, "t0", at(0)).unwrap();
+        assert_eq!(
+            states(&res),
+            vec![
+                (Some("zone0"), State::Pending),
+                (Some("zone1"), State::Pending),
+                (Some("zone0"), State::Firing),
+                (Some("zone1"), State::Firing),
+            ]
+        );
+
+        insert(&conn, "2025-01-01T10:01:00Z", &[("zone0", 50.0)]);
+        let res = alerts.evaluate(&conn, "t1", at(60)).unwrap();
+        assert_eq!(
+            states(&res),
+            vec![
+                (Some("zone1"), State::Resolved),
+                (Some("zone0"), State::Resolved)
+            ]
+        );
+        assert_eq!(res[0].value, 95.0);
+        assert_eq!(alerts.tracks.lock().unwrap().len(), 2);
+    }
+
+    // Test `evaluate` function with a trend rule and the storage of the transitions
+    #[test]
+    fn test_evaluate_increases() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE storage_data (id INTEGER PRIMARY KEY, timestamp TEXT, name TEXT);
+            CREATE TABLE smart_data (id INTEGER PRIMARY KEY, device_id INTEGER, sectors_reallocated INTEGER);",
+        )
+        .unwrap();
+        let alerts = alerts("[[alerts]]\nrule = \"smart_data.sectors_reallocated increases\"\n");
+        let mut all = Vec::new();
+
+        for (i, sectors) in [0, 0, 2, 2].into_iter().enumerate() {
+            conn.execute(
+                "INSERT INTO storage_data (timestamp, name) VALUES (?1, 'sda')",
+                [format!("2025-01-01T10:0{i}:00Z")],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO smart_data (device_id, sectors_reallocated) VALUES (?1, ?2)",
+                params![conn.last_insert_rowid(), sectors],
+            )
+            .unwrap();
+            all.extend(alerts.evaluate(&conn, "t", Instant::now()).unwrap());
+        }
+        assert_eq!(
+            states(&all),
+            vec![(Some("sda"), State::Firing), (Some("sda"), State::Resolved)]
+        );
+        assert_eq!(all[0].name, "smart_data.sectors_reallocated increases");
+
+        store_transitions(&mut conn, &all).unwrap();
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM alerts WHERE state = 'firing'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    // Test `Alert` deserialization errors
+    #[test]
+    fn test_alert_config_error() {
+        let config = Config::parse("[[alerts]]\nrule = \"cpu.power >\"\n").unwrap();
+        assert!(config.section::<Vec<Alert>>("alerts").is_err());
+        let config =
+            Config::parse("[[alerts]]\nrule = \"cpu_power.power > 1\"\nhysteresis = -1\n").unwrap();
+        assert!(config.section::<Vec<Alert>>("alerts").is_err());
+    }
+}
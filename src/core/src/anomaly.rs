@@ -0,0 +1,693 @@
+//! # Anomaly file
+//!
+//! This module provides the statistical anomaly detection on the last rows stored by the probes,
+//! keeping an exponentially weighted baseline of each metric and entity, optionally per time slot
+//! of a season, to flag the values deviating from it by a z-score.
+
+use log::error;
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    alert::{State, Transition},
+    query::{latest_values, list_columns, list_tables, resolve_column},
+};
+
+const HEADER: &str = "ANOMALY";
+
+/// Request creating the tables of the anomalies and of the persisted baselines.
+pub const REQUEST: &str = "CREATE TABLE IF NOT EXISTS anomalies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    metric TEXT NOT NULL,
+    entity TEXT,
+    value REAL NOT NULL,
+    expected REAL NOT NULL,
+    score REAL NOT NULL
+);
+CREATE TABLE IF NOT EXISTS anomaly_baselines (
+    metric TEXT NOT NULL,
+    entity TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    count INTEGER NOT NULL,
+    mean REAL NOT NULL,
+    variance REAL NOT NULL,
+    PRIMARY KEY (metric, entity, slot)
+);";
+
+/// Metrics watched by default : network errors and disk I/O rates, CPU and GPU power.
+pub const METRICS: &[&str] = &[
+    "network_data.errors_received rate",
+    "network_data.errors_transmitted rate",
+    "cpu_power.power",
+    "gpu_data.gpu_power_consumption",
+    "storage_data.bandwidth_read rate",
+    "storage_data.bandwidth_write rate",
+];
+
+/// Lowest standard deviation of a baseline, so a constant series still gives a finite score.
+const MIN_DEVIATION: f64 = 1e-9;
+
+/// Baseline kept for each metric and entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    /// One exponentially weighted mean and variance.
+    Ewma,
+    /// One exponentially weighted mean and variance per slot of the season,
+    /// like per hour of the day.
+    Seasonal,
+}
+
+/// Column `<table>.<column> [rate]` watched by the detection.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Metric {
+    /// Text of the metric, identifying its baselines.
+    pub text: String,
+    /// Table of the column.
+    pub table: String,
+    /// Name of the column, with or without its unit suffix.
+    pub column: String,
+    /// Watch the increase per second of a cumulative counter instead of its value.
+    pub rate: bool,
+}
+
+impl TryFrom<String> for Metric {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("Config 'Invalid anomaly metric' : {text}");
+        let mut tokens = text.split_whitespace();
+        let (table, column) = tokens
+            .next()
+            .and_then(|target| target.split_once('.'))
+            .filter(|(table, column)| !table.is_empty() && !column.is_empty())
+            .ok_or_else(invalid)?;
+        let rate = match tokens.next() {
+            Some("rate") => true,
+            Some(_) => return Err(invalid()),
+            None => false,
+        };
+        if tokens.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(Metric {
+            table: table.to_string(),
+            column: column.to_string(),
+            rate,
+            text,
+        })
+    }
+}
+
+/// Anomaly detection, from the `[anomaly]` section of the configuration.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct DetectorConfig {
+    enabled: bool,
+    method: Method,
+    alpha: f64,
+    threshold: f64,
+    warmup: u64,
+    season: u64,
+    slots: u64,
+    metrics: Vec<String>,
+}
+
+impl Default for DetectorConfig {
+    fn default() -> Self {
+        DetectorConfig {
+            enabled: false,
+            method: Method::Ewma,
+            alpha: 0.05,
+            threshold: 4.0,
+            warmup: 30,
+            season: 86400,
+            slots: 24,
+            metrics: METRICS.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+}
+
+/// Parameters of the anomaly detection.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "DetectorConfig")]
+pub struct Detector {
+    /// Run the detection after each snapshot stored.
+    pub enabled: bool,
+    /// Baseline kept for each metric and entity.
+    pub method: Method,
+    /// Weight of each new value in the baseline, between 0 excluded and 1.
+    pub alpha: f64,
+    /// Absolute z-score from which a value is anomalous.
+    pub threshold: f64,
+    /// Number of values in a baseline before its first anomaly.
+    pub warmup: u64,
+    /// Period in seconds of the [`Method::Seasonal`] baselines.
+    pub season: u64,
+    /// Number of slots dividing the season, each with its own baseline.
+    pub slots: u64,
+    /// Watched [`Metric`] list.
+    pub metrics: Vec<Metric>,
+}
+
+impl TryFrom<DetectorConfig> for Detector {
+    type Error = String;
+
+    fn try_from(config: DetectorConfig) -> Result<Self, Self::Error> {
+        if !(config.alpha > 0.0 && config.alpha <= 1.0) {
+            return Err(format!("Config 'Invalid anomaly alpha' : {}", config.alpha));
+        }
+        if config.threshold.is_nan() || config.threshold <= 0.0 {
+            return Err(format!(
+                "Config 'Invalid anomaly threshold' : {}",
+                config.threshold
+            ));
+        }
+        if config.slots == 0 || config.season < config.slots {
+            return Err(format!(
+                "Config 'Invalid anomaly season' : {} slots in {}s",
+                config.slots, config.season
+            ));
+        }
+        Ok(Detector {
+            enabled: config.enabled,
+            method: config.method,
+            alpha: config.alpha,
+            threshold: config.threshold,
+            warmup: config.warmup,
+            season: config.season,
+            slots: config.slots,
+            metrics: config
+                .metrics
+                .into_iter()
+                .map(Metric::try_from)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Detector::try_from(DetectorConfig::default()).expect("Valid default anomaly detector")
+    }
+}
+
+impl Detector {
+    /// Slot of the baseline used at a date.
+    ///
+    /// # Arguments
+    ///
+    /// - `epoch` : Date in seconds since the Unix epoch.
+    fn slot(&self, epoch: f64) -> u64 {
+        match self.method {
+            Method::Ewma => 0,
+            Method::Seasonal => {
+                let position = epoch.rem_euclid(self.season as f64);
+                ((position * self.slots as f64 / self.season as f64) as u64).min(self.slots - 1)
+            }
+        }
+    }
+}
+
+/// Value deviating from the baseline of its metric and entity.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Anomaly {
+    /// Date of the detection.
+    pub timestamp: String,
+    /// Text of the metric.
+    pub metric: String,
+    /// Entity concerned (interface, disk, GPU bus id, zone...), if the table has one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity: Option<String>,
+    /// Value of the metric, per second for a rate.
+    pub value: f64,
+    /// Mean of the baseline before the value.
+    pub expected: f64,
+    /// Z-score of the value, being its distance to the mean in standard deviations.
+    pub score: f64,
+}
+
+/// Exponentially weighted mean and variance of a metric and entity.
+#[derive(Debug, Clone, Copy, Default)]
+struct Baseline {
+    count: u64,
+    mean: f64,
+    variance: f64,
+}
+
+impl Baseline {
+    /// Standard deviation of the baseline.
+    fn deviation(&self) -> f64 {
+        self.variance.sqrt().max(MIN_DEVIATION)
+    }
+
+    /// Z-score of a value against the baseline.
+    fn score(&self, value: f64) -> f64 {
+        (value - self.mean) / self.deviation()
+    }
+
+    /// Bring a value back within a z-score of the baseline.
+    fn clamp(&self, value: f64, threshold: f64) -> f64 {
+        let margin = threshold * self.deviation();
+        value.clamp(self.mean - margin, self.mean + margin)
+    }
+
+    /// Add a value to the baseline.
+    fn update(&mut self, value: f64, alpha: f64) {
+        if self.count == 0 {
+            self.mean = value;
+            self.variance = 0.0;
+        } else {
+            let delta = value - self.mean;
+            let increment = alpha * delta;
+            self.mean += increment;
+            self.variance = (1.0 - alpha) * (self.variance + delta * increment);
+        }
+        self.count += 1;
+    }
+}
+
+/// Last counter value of a metric and entity, and whether it is flagged.
+#[derive(Debug, Default)]
+struct Series {
+    previous: Option<(f64, f64)>,
+    flagged: bool,
+}
+
+/// Baselines by metric index, entity and slot.
+type Baselines = HashMap<(usize, Option<String>, u64), Baseline>;
+
+/// Detection state kept between cycles.
+#[derive(Debug, Default)]
+struct Memory {
+    loaded: bool,
+    baselines: Baselines,
+    series: HashMap<(usize, Option<String>), Series>,
+    /// Index of the metrics whose column is unknown, already reported.
+    unknown: HashSet<usize>,
+}
+
+/// Anomaly detection, keeping the baselines of each metric and entity between cycles.
+/// Clones share the same baselines.
+#[derive(Debug, Clone, Default)]
+pub struct Anomalies {
+    detector: Detector,
+    memory: Arc<Mutex<Memory>>,
+}
+
+impl Anomalies {
+    /// Create the anomaly detection, its baselines being loaded from the database at first use.
+    ///
+    /// # Arguments
+    ///
+    /// - `detector` : Configured [`Detector`].
+    pub fn new(detector: Detector) -> Self {
+        Anomalies {
+            detector,
+            memory: Arc::default(),
+        }
+    }
+
+    /// Check if the detection is disabled or watches no metric.
+    pub fn is_empty(&self) -> bool {
+        !self.detector.enabled || self.detector.metrics.is_empty()
+    }
+
+    /// Compare the last rows of the watched metrics with their baselines, then update and store
+    /// the baselines and the anomalies. Tables not stored yet and null values are ignored,
+    /// an unknown column is reported once and its metric skipped, and a rate needs two values
+    /// of its counter, a decrease being taken as a reset. An anomalous value enters its baseline
+    /// clamped to the threshold, so a spike barely moves it while a lasting shift is learned.
+    ///
+    /// # Arguments
+    ///
+    /// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+    /// - `timestamp` : Date of the detection.
+    ///
+    /// # Returns
+    ///
+    /// - The [`Anomaly`] list, and the [`Transition`] of each metric and entity becoming
+    ///   anomalous (firing) or normal again (resolved), for the alerting.
+    /// - An error if the timestamp is invalid, or if a table can't be read or written.
+    pub fn evaluate(
+        &self,
+        conn: &mut Connection,
+        timestamp: &str,
+    ) -> Result<(Vec<Anomaly>, Vec<Transition>), Box<dyn Error>> {
+        conn.execute_batch(REQUEST)?;
+        let epoch: f64 = conn
+            .query_row(
+                "SELECT (julianday(?1) - 2440587.5) * 86400.0",
+                [timestamp],
+                |row| row.get::<_, Option<f64>>(0),
+            )?
+            .ok_or_else(|| format!("Data 'Invalid timestamp' : {timestamp}"))?;
+
+        let mut memory = self.memory.lock().unwrap_or_else(|e| e.into_inner());
+        if !memory.loaded {
+            memory.baselines = self.load(conn)?;
+            memory.loaded = true;
+        }
+        let Memory {
+            baselines,
+            series,
+            unknown,
+            ..
+        } = &mut *memory;
+
+        let detector = &self.detector;
+        let slot = detector.slot(epoch);
+        let tables = list_tables(conn)?;
+        let mut updated = Vec::new();
+        let mut anomalies = Vec::new();
+        let mut transitions = Vec::new();
+
+        for (index, metric) in detector.metrics.iter().enumerate() {
+            if !tables.contains(&metric.table) {
+                continue;
+            }
+            let columns = list_columns(conn, &metric.table)?;
+            let Some(column) = resolve_column(&columns, &metric.column) else {
+                if unknown.insert(index) {
+                    error!(
+                        "[{HEADER}] Data 'Unknown column {}.{}' : metric skipped",
+                        metric.table, metric.column
+                    );
+                }
+                continue;
+            };
+            unknown.remove(&index);
+
+            for (entity, raw) in latest_values(conn, &metric.table, column)? {
+                let series = series.entry((index, entity.clone())).or_default();
+                let value = if metric.rate {
+                    match series.previous.replace((raw, epoch)) {
+                        Some((previous, since)) if epoch > since && raw >= previous => {
+                            (raw - previous) / (epoch - since)
+                        }
+                        _ => continue,
+                    }
+                } else {
+                    raw
+                };
+
+                let key = (index, entity.clone(), slot);
+                let baseline = baselines.entry(key.clone()).or_default();
+                let expected = baseline.mean;
+                let score = baseline.score(value);
+                let anomalous =
+                    baseline.count >= detector.warmup.max(1) && score.abs() >= detector.threshold;
+                let learned = if anomalous {
+                    baseline.clamp(value, detector.threshold)
+                } else {
+                    value
+                };
+                baseline.update(learned, detector.alpha);
+                updated.push(key);
+
+                if anomalous {
+                    anomalies.push(Anomaly {
+                        timestamp: timestamp.to_string(),
+                        metric: metric.text.clone(),
+                        entity: entity.clone(),
+                        value,
+                        expected,
+                        score,
+                    });
+                }
+                let state = match (series.flagged, anomalous) {
+                    (false, true) => State::Firing,
+                    (true, false) => State::Resolved,
+                    _ => continue,
+                };
+                series.flagged = anomalous;
+                transitions.push(Transition {
+                    timestamp: timestamp.to_string(),
+                    name: "anomaly".to_string(),
+                    rule: format!("{} |z| >= {}", metric.text, detector.threshold),
+                    entity,
+                    state,
+                    value,
+                });
+            }
+        }
+
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO anomaly_baselines (metric, entity, slot, count, mean, variance) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for key in &updated {
+                let baseline = baselines[key];
+                stmt.execute(params![
+                    detector.metrics[key.0].text,
+                    key.1.as_deref().unwrap_or_default(),
+                    key.2 as i64,
+                    baseline.count as i64,
+                    baseline.mean,
+                    baseline.variance
+                ])?;
+            }
+            let mut stmt = tx.prepare(
+                "INSERT INTO anomalies (timestamp, metric, entity, value, expected, score) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for a in &anomalies {
+                stmt.execute(params![
+                    a.timestamp,
+                    a.metric,
+                    a.entity,
+                    a.value,
+                    a.expected,
+                    a.score
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok((anomalies, transitions))
+    }
+
+    /// Read the stored baselines of the watched metrics.
+    fn load(&self, conn: &Connection) -> Result<Baselines, Box<dyn Error>> {
+        let mut stmt = conn
+            .prepare("SELECT metric, entity, slot, count, mean, variance FROM anomaly_baselines")?;
+        let mut rows = stmt.query([])?;
+        let mut baselines = Baselines::new();
+        while let Some(row) = rows.next()? {
+            let metric: String = row.get(0)?;
+            let Some(index) = self.detector.metrics.iter().position(|m| m.text == metric) else {
+                continue;
+            };
+            let entity: String = row.get(1)?;
+            let key = (
+                index,
+                (!entity.is_empty()).then_some(entity),
+                row.get::<_, i64>(2)? as u64,
+            );
+            baselines.insert(
+                key,
+                Baseline {
+                    count: row.get::<_, i64>(3)? as u64,
+                    mean: row.get(4)?,
+                    variance: row.get(5)?,
+                },
+            );
+        }
+        Ok(baselines)
+    }
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn detector(section: &str) -> Result<Detector, Box<dyn Error>> {
+        Config::parse(&format!("[anomaly]\nenabled = true\n{section}"))?.section("anomaly")
+    }
+
+    fn database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE cpu_power (id INTEGER PRIMARY KEY, timestamp TEXT, zone_name TEXT, power_W REAL);
+            CREATE TABLE network_data (id INTEGER PRIMARY KEY, timestamp TEXT, name TEXT, errors_received_MB REAL);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn timestamp(second: u64) -> String {
+        format!("2025-01-01T10:{:02}:{:02}.000Z", second / 60, second % 60)
+    }
+
+    /// Insert a value of a table, then run the detection at its date.
+    fn step(
+        anomalies: &Anomalies,
+        conn: &mut Connection,
+        second: u64,
+        request: &str,
+        value: f64,
+    ) -> (Vec<Anomaly>, Vec<Transition>) {
+        conn.execute(request, params![timestamp(second), value])
+            .unwrap();
+        anomalies.evaluate(conn, &timestamp(second)).unwrap()
+    }
+
+    const POWER: &str =
+        "INSERT INTO cpu_power (timestamp, zone_name, power_W) VALUES (?1, 'package-0', ?2)";
+    const ERRORS: &str =
+        "INSERT INTO network_data (timestamp, name, errors_received_MB) VALUES (?1, 'eth0', ?2)";
+
+    // Test `Detector` deserialization with defaults and errors
+    #[test]
+    fn test_detector_config() {
+        let res = detector("").unwrap();
+        assert_eq!(res.method, Method::Ewma);
+        assert_eq!(res.metrics.len(), METRICS.len());
+        assert!(res.metrics[0].rate);
+        assert_eq!(res.metrics[2].column, "power");
+        assert!(!Detector::default().enabled);
+
+        for section in [
+            "alpha = 0.0",
+            "alpha = 1.5",
+            "threshold = -1.0",
+            "method = \"seasonal\"\nslots = 0",
+            "metrics = [\"cpu_power\"]",
+            "metrics = [\"cpu_power.power delta\"]",
+        ] {
+            assert!(detector(section).is_err(), "{section}");
+        }
+    }
+
+    // Test `Detector::slot` function of the seasonal baselines
+    #[test]
+    fn test_detector_slot() {
+        let res = detector("method = \"seasonal\"").unwrap();
+        assert_eq!(res.slot(0.0), 0);
+        assert_eq!(res.slot(3600.0 * 25.5), 1);
+        assert_eq!(res.slot(86399.9), 23);
+        assert_eq!(detector("").unwrap().slot(3600.0 * 5.0), 0);
+    }
+
+    // Test `evaluate` function flagging a spike after the warmup, then resolving it
+    #[test]
+    fn test_evaluate_spike() {
+        let mut conn = database();
+        let anomalies = Anomalies::new(
+            detector("warmup = 10\nthreshold = 3.0\nmetrics = [\"cpu_power.power\"]").unwrap(),
+        );
+
+        for second in 0..20 {
+            let value = if second % 2 == 0 { 10.0 } else { 11.0 };
+            let (res, transitions) = step(&anomalies, &mut conn, second, POWER, value);
+            assert!(res.is_empty() && transitions.is_empty(), "{second}");
+        }
+
+        let (res, transitions) = step(&anomalies, &mut conn, 20, POWER, 30.0);
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].entity.as_deref(), Some("package-0"));
+        assert!(res[0].score > 3.0);
+        assert!((res[0].expected - 10.5).abs() < 0.5);
+        assert_eq!(transitions[0].state, State::Firing);
+
+        let (res, transitions) = step(&anomalies, &mut conn, 21, POWER, 10.5);
+        assert!(res.is_empty());
+        assert_eq!(transitions[0].state, State::Resolved);
+
+        let stored: i64 = conn
+            .query_row("SELECT COUNT(*) FROM anomalies", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, 1);
+    }
+
+    // Test `evaluate` function skipping an unknown column and damping a lasting shift
+    #[test]
+    fn test_evaluate_shift() {
+        let mut conn = database();
+        let anomalies = Anomalies::new(
+            detector(
+                "warmup = 10\nthreshold = 3.0\nalpha = 0.2\nmetrics = [\"cpu_power.voltage\", \"cpu_power.power\"]",
+            )
+            .unwrap(),
+        );
+
+        for second in 0..20 {
+            let value = if second % 2 == 0 { 10.0 } else { 11.0 };
+            step(&anomalies, &mut conn, second, POWER, value);
+        }
+        let (res, _) = step(&anomalies, &mut conn, 20, POWER, 1000.0);
+        assert_eq!(res.len(), 1);
+        let baseline =
+            anomalies.memory.lock().unwrap().baselines[&(1, Some("package-0".into()), 0)];
+        assert!(baseline.mean < 12.0);
+
+        let mut second = 21;
+        while !step(&anomalies, &mut conn, second, POWER, 1000.0)
+            .0
+            .is_empty()
+        {
+            second += 1;
+            assert!(second < 200);
+        }
+    }
+
+    // Test `evaluate` function on the rate of a counter, ignoring its resets
+    #[test]
+    fn test_evaluate_rate() {
+        let mut conn = database();
+        let anomalies = Anomalies::new(
+            detector("warmup = 5\nmetrics = [\"network_data.errors_received rate\"]").unwrap(),
+        );
+
+        let mut counter = 0.0;
+        for second in 0..10 {
+            counter += 2.0;
+            let (res, _) = step(&anomalies, &mut conn, second * 10, ERRORS, counter);
+            assert!(res.is_empty(), "{second}");
+        }
+        let (res, _) = step(&anomalies, &mut conn, 100, ERRORS, 0.0);
+        assert!(res.is_empty());
+
+        let (res, _) = step(&anomalies, &mut conn, 110, ERRORS, 50.0);
+        assert_eq!(res.len(), 1);
+        assert!((res[0].value - 5.0).abs() < 1e-3);
+        assert!((res[0].expected - 0.2).abs() < 1e-3);
+    }
+
+    // Test baselines persisted in database and loaded by a new detection
+    #[test]
+    fn test_evaluate_persisted() {
+        let mut conn = database();
+        let config = "warmup = 10\nmetrics = [\"cpu_power.power\"]";
+        let anomalies = Anomalies::new(detector(config).unwrap());
+        for second in 0..10 {
+            step(
+                &anomalies,
+                &mut conn,
+                second,
+                POWER,
+                10.0 + (second % 2) as f64,
+            );
+        }
+
+        let anomalies = Anomalies::new(detector(config).unwrap());
+        let (res, _) = step(&anomalies, &mut conn, 10, POWER, 40.0);
+        assert_eq!(res.len(), 1);
+
+        let anomalies =
+            Anomalies::new(detector("warmup = 10\nmetrics = [\"cpu_power.power_W\"]").unwrap());
+        let (res, _) = step(&anomalies, &mut conn, 11, POWER, 40.0);
+        assert!(res.is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+//! # Bus file
+//!
+//! This module provides the in-process distribution of the samples produced by the probes
+//! to live consumers, with a bounded queue per subscriber, a history of the last samples
+//! and a cache of the latest value of each topic and entity.
+
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{Receiver, SyncSender, TrySendError, sync_channel},
+    },
+};
+
+/// Number of samples kept by the global bus for replay.
+pub const HISTORY: usize = 2048;
+
+/// Single value produced by a probe.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sample {
+    /// Date of the measurement.
+    pub timestamp: String,
+    /// Component and metric name, as `<component>.<metric>` (`cpu.power`).
+    pub topic: String,
+    /// Entity concerned (interface, disk, GPU bus id, pid...), if the component has several.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity: Option<String>,
+    /// Value of the metric.
+    pub value: f64,
+    /// Unit of the value.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+}
+
+impl Sample {
+    /// Component part of the topic.
+    pub fn component(&self) -> &str {
+        self.topic.split_once('.').map_or(&self.topic, |(c, _)| c)
+    }
+
+    /// Metric part of the topic.
+    pub fn metric(&self) -> &str {
+        self.topic.split_once('.').map_or("", |(_, m)| m)
+    }
+}
+
+/// Subscription pattern `<component>.<metric>[@<entity>]`,
+/// where each part may be `*`, and the metric omitted (`gpu` is `gpu.*`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    component: String,
+    metric: String,
+    entity: Option<String>,
+}
+
+impl Pattern {
+    /// Parse a subscription pattern.
+    ///
+    /// # Arguments
+    ///
+    /// - `pattern` : Pattern like `cpu.power`, `gpu.*` or `network.received@eth0`.
+    ///
+    /// # Returns
+    ///
+    /// The [`Pattern`], if not empty.
+    pub fn parse(pattern: &str) -> Option<Pattern> {
+        let (topic, entity) = match pattern.trim().split_once('@') {
+            Some((topic, entity)) => (topic, Some(entity.to_string())),
+            None => (pattern.trim(), None),
+        };
+        let (component, metric) = topic.split_once('.').unwrap_or((topic, "*"));
+        if component.is_empty() || metric.is_empty() {
+            return None;
+        }
+        Some(Pattern {
+            component: component.to_string(),
+            metric: metric.to_string(),
+            entity,
+        })
+    }
+
+    /// Check if a sample matches the pattern.
+    pub fn matches(&self, sample: &Sample) -> bool {
+        let part = |pattern: &str, value: &str| pattern == "*" || pattern == value;
+        part(&self.component, sample.component())
+            && part(&self.metric, sample.metric())
+            && self
+                .entity
+                .as_deref()
+                .is_none_or(|entity| entity == "*" || sample.entity.as_deref() == Some(entity))
+    }
+}
+
+/// Queue of a subscriber, and number of samples dropped because it was full.
+struct Subscriber {
+    sender: SyncSender<Sample>,
+    dropped: Arc<AtomicU64>,
+}
+
+#[derive(Default)]
+struct State {
+    history: VecDeque<Sample>,
+    latest: BTreeMap<(String, Option<String>), Sample>,
+    subscribers: Vec<Subscriber>,
+}
+
+/// Receiving side of a bus subscription. Dropping it unsubscribes.
+pub struct Subscription {
+    receiver: Receiver<Sample>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Subscription {
+    /// Next pending sample, without waiting.
+    pub fn try_recv(&self) -> Option<Sample> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Number of samples dropped since the last call, the queue being full.
+    pub fn take_dropped(&self) -> u64 {
+        self.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// Distribution of the samples to the subscribers.
+/// A slow subscriber loses the newest samples when its queue is full,
+/// without slowing down the publisher nor the other subscribers.
+pub struct Bus {
+    capacity: usize,
+    state: Mutex<State>,
+}
+
+impl Bus {
+    /// Create a bus.
+    ///
+    /// # Arguments
+    ///
+    /// - `capacity` : Number of the last samples kept for replay.
+    pub fn new(capacity: usize) -> Self {
+        Bus {
+            capacity,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Send samples to each subscriber, keep them in the history and update the latest values.
+    ///
+    /// # Arguments
+    ///
+    /// - `samples` : [`Sample`] list to publish.
+    pub fn publish<I: IntoIterator<Item = Sample>>(&self, samples: I) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        for sample in samples {
+            state.subscribers.retain(|subscriber| {
+                match subscriber.sender.try_send(sample.clone()) {
+                    Ok(()) => true,
+                    Err(TrySendError::Full(_)) => {
+                        subscriber.dropped.fetch_add(1, Ordering::Relaxed);
+                        true
+                    }
+                    Err(TrySendError::Disconnected(_)) => false,
+                }
+            });
+            state.latest.insert(
+                (sample.topic.clone(), sample.entity.clone()),
+                sample.clone(),
+            );
+            if self.capacity > 0 {
+                if state.history.len() == self.capacity {
+                    state.history.pop_front();
+                }
+                state.history.push_back(sample);
+            }
+        }
+    }
+
+    /// Subscribe to the next published samples.
+    ///
+    /// # Arguments
+    ///
+    /// - `capacity` : Number of samples the subscriber queue can hold.
+    ///
+    /// # Returns
+    ///
+    /// The [`Subscription`] receiving the samples.
+    pub fn subscribe(&self, capacity: usize) -> Subscription {
+        let (sender, receiver) = sync_channel(capacity);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.subscribers.push(Subscriber {
+            sender,
+            dropped: dropped.clone(),
+        });
+        Subscription { receiver, dropped }
+    }
+
+    /// Last published samples, from the oldest to the newest.
+    pub fn history(&self) -> Vec<Sample> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.history.iter().cloned().collect()
+    }
+
+    /// Latest published sample of each topic and entity, sorted by topic then entity.
+    pub fn latest(&self) -> Vec<Sample> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.latest.values().cloned().collect()
+    }
+}
+
+/// Bus shared by the probes and the live consumers of the process.
+pub fn bus() -> &'static Bus {
+    static BUS: OnceLock<Bus> = OnceLock::new();
+    BUS.get_or_init(|| Bus::new(HISTORY))
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(value: f64) -> Sample {
+        Sample {
+            timestamp: "2025-01-01T10:00:00.000Z".to_string(),
+            topic: "cpu.power".to_string(),
+            entity: Some("package-0".to_string()),
+            value,
+            unit: Some("W".to_string()),
+        }
+    }
+
+    // Test `publish` function with a subscriber and the history
+    #[test]
+    fn test_bus_publish() {
+        let bus = Bus::new(2);
+        let subscription = bus.subscribe(8);
+        bus.publish((0..3).map(|i| sample(i as f64)));
+
+        assert_eq!(subscription.try_recv().map(|s| s.value), Some(0.0));
+        assert_eq!(subscription.try_recv().map(|s| s.value), Some(1.0));
+        let history: Vec<f64> = bus.history().iter().map(|s| s.value).collect();
+        assert_eq!(history, vec![1.0, 2.0]);
+        assert_eq!(sample(0.0).component(), "cpu");
+        assert_eq!(sample(0.0).metric(), "power");
+    }
+
+    // Test `publish` function with a full and a dropped subscriber
+    #[test]
+    fn test_bus_backpressure() {
+        let bus = Bus::new(0);
+        let slow = bus.subscribe(1);
+        drop(bus.subscribe(1));
+        bus.publish((0..3).map(|i| sample(i as f64)));
+
+        assert_eq!(slow.try_recv().map(|s| s.value), Some(0.0));
+        assert!(slow.try_recv().is_none());
+        assert_eq!(slow.take_dropped(), 2);
+        assert_eq!(slow.take_dropped(), 0);
+        assert_eq!(bus.state.lock().unwrap().subscribers.len(), 1);
+        assert!(bus.history().is_empty());
+    }
+
+    // Test `latest` function keeping the last value of each topic and entity
+    #[test]
+    fn test_bus_latest() {
+        let bus = Bus::new(0);
+        let mut other = sample(5.0);
+        other.entity = Some("package-1".to_string());
+        bus.publish([sample(1.0), other, sample(2.0)]);
+
+        let latest: Vec<f64> = bus.latest().iter().map(|s| s.value).collect();
+        assert_eq!(latest, vec![2.0, 5.0]);
+    }
+
+    // Test `parse` function with the available forms
+    #[test]
+    fn test_pattern_parse() {
+        let res = Pattern::parse("gpu").unwrap();
+        assert_eq!(res.metric, "*");
+        let res = Pattern::parse("network.received@eth0").unwrap();
+        assert_eq!(res.component, "network");
+        assert_eq!(res.entity.as_deref(), Some("eth0"));
+        assert!(Pattern::parse("").is_none());
+        assert!(Pattern::parse("cpu.").is_none());
+    }
+
+    // Test `matches` function on topics and entities
+    #[test]
+    fn test_pattern_matches() {
+        let power = sample(1.0);
+        assert!(Pattern::parse("cpu.power").unwrap().matches(&power));
+        assert!(Pattern::parse("cpu.*").unwrap().matches(&power));
+        assert!(Pattern::parse("*.power@package-0").unwrap().matches(&power));
+        assert!(
+            !Pattern::parse("cpu.power@package-1")
+                .unwrap()
+                .matches(&power)
+        );
+        assert!(!Pattern::parse("gpu.*").unwrap().matches(&power));
+        assert!(!Pattern::parse("cpu.pow").unwrap().matches(&power));
+    }
+}
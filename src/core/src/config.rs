@@ -0,0 +1,115 @@
+//! # File config
+//!
+//! This file provides the loading of the TOML configuration file,
+//! each module reading its own section.
+
+use serde::de::DeserializeOwned;
+use std::{error::Error, fs::read_to_string, io::ErrorKind, path::Path};
+use toml::Table;
+
+/// Default configuration file path.
+pub const CONFIG: &str = "userv.toml";
+
+/// Content of the configuration file.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    table: Table,
+}
+
+impl Config {
+    /// Load the configuration file. A missing file gives an empty configuration.
+    ///
+    /// # Arguments
+    ///
+    /// - `path` : File path of the TOML configuration.
+    ///
+    /// # Returns
+    ///
+    /// - The loaded [`Config`].
+    /// - An error if the file can't be read or is not valid TOML.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn Error>> {
+        let path = path.as_ref();
+        match read_to_string(path) {
+            Ok(content) => Config::parse(&content)
+                .map_err(|e| format!("Config 'Invalid file' : {} {e}", path.display()).into()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("Config 'Unreadable file' : {} {e}", path.display()).into()),
+        }
+    }
+
+    /// Parse a configuration from its TOML content.
+    ///
+    /// # Arguments
+    ///
+    /// - `content` : TOML document.
+    ///
+    /// # Returns
+    ///
+    /// - The parsed [`Config`].
+    /// - An error if the content is not valid TOML.
+    pub fn parse(content: &str) -> Result<Config, Box<dyn Error>> {
+        Ok(Config {
+            table: content.parse::<Table>()?,
+        })
+    }
+
+    /// Read a section of the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// - `name` : Name of the section (table or array of tables).
+    ///
+    /// # Returns
+    ///
+    /// - The deserialized section, or its default value if the section is missing.
+    /// - An error if the section doesn't match the expected structure.
+    pub fn section<T: DeserializeOwned + Default>(&self, name: &str) -> Result<T, Box<dyn Error>> {
+        match self.table.get(name) {
+            Some(value) => value
+                .clone()
+                .try_into()
+                .map_err(|e| format!("Config 'Invalid section' : [{name}] {e}").into()),
+            None => Ok(T::default()),
+        }
+    }
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, Deserialize, PartialEq)]
+    struct Section {
+        name: String,
+        interval: u64,
+    }
+
+    // Test `section` function with present, missing and invalid sections
+    #[test]
+    fn test_config_section() {
+        let config = Config::parse(
+            "[server]\nname = \"a\"\ninterval = 5\n\n[[probe]]\nname = \"b\"\ninterval = 1\n",
+        )
+        .unwrap();
+
+        let server: Section = config.section("server").unwrap();
+        assert_eq!(server.interval, 5);
+        let probes: Vec<Section> = config.section("probe").unwrap();
+        assert_eq!(probes[0].name, "b");
+        let missing: Vec<Section> = config.section("other").unwrap();
+        assert!(missing.is_empty());
+        assert!(config.section::<Vec<Section>>("server").is_err());
+    }
+
+    // Test `load` function with a missing file
+    #[test]
+    fn test_config_missing_file() {
+        let config = Config::load("/nonexistent/userv.toml").unwrap();
+        assert!(config.section::<Vec<Section>>("probe").unwrap().is_empty());
+    }
+}
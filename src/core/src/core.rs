@@ -7,7 +7,7 @@ use std::{
 };
 
 /// SQLite database file path.
-const DATABASE: &str = "log/data.db";
+pub const DATABASE: &str = "log/data.db";
 
 /// SMBIOS provides a structure called Entry Point Structure (EPS) that contains a pointer to the SMBIOS Structure Table and some additional information.
 pub const ENTRY_BIN: &str = "/sys/firmware/dmi/tables/smbios_entry_point";
@@ -396,7 +396,7 @@ mod tests {
         let sql = "CREATE TABLE IF NOT EXISTS test_table (id INTEGER PRIMARY KEY, value TEXT);";
         let conn = db_config(path, sql).expect("init_db should succeed");
 
-        conn.execute("INSERT INTO test_table (value) VALUES (?1)", &[&"hello"])
+        conn.execute("INSERT INTO test_table (value) VALUES (?1)", [&"hello"])
             .expect("Insert should succeed");
 
         let count: i64 = conn
@@ -0,0 +1,403 @@
+//! # Export file
+//!
+//! This module provides the export of the stored tables in CSV or JSON Lines format,
+//! streaming each row to the output without loading a whole table in memory.
+
+use rusqlite::{Connection, OpenFlags, types::ValueRef};
+use serde_json::{Map, Number, Value};
+use std::{
+    error::Error,
+    fs::{File, create_dir_all},
+    io::{BufWriter, Write, stdout},
+    path::{Path, PathBuf},
+};
+
+use crate::query::{Filter, Selection, host_name, list_tables, select};
+
+/// Available output formats of an export.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Comma separated values, with a header line per table.
+    Csv,
+    /// One JSON object per line (NDJSON).
+    Ndjson,
+}
+
+impl Format {
+    /// File extension used when a table is exported in its own file.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Ndjson => "ndjson",
+        }
+    }
+}
+
+/// Parameters of an export.
+#[derive(Debug)]
+pub struct ExportOptions {
+    /// SQLite database files to read.
+    pub databases: Vec<PathBuf>,
+    /// Tables to export, all the stored tables when empty.
+    pub tables: Vec<String>,
+    /// Output [`Format`].
+    pub format: Format,
+    /// Row [`Filter`] applied on each table.
+    pub filter: Filter,
+    /// Hostname of the machine to keep, among the given databases.
+    pub host: Option<String>,
+    /// Output file, or directory when several tables are exported in CSV. Standard output if not set.
+    pub output: Option<PathBuf>,
+}
+
+/// Convert a SQLite value in CSV field.
+fn csv_field(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => String::new(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(f) => f.to_string(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).to_string(),
+        ValueRef::Blob(b) => b.iter().map(|byte| format!("{byte:02x}")).collect(),
+    }
+}
+
+/// Convert a SQLite value in JSON value.
+pub fn json_value(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).to_string()),
+        ValueRef::Blob(b) => Value::String(b.iter().map(|byte| format!("{byte:02x}")).collect()),
+    }
+}
+
+/// Run a selection and collect its rows as JSON objects.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `selection` : [`Selection`] built for a table.
+///
+/// # Returns
+///
+/// - The rows, as objects keyed by column name.
+/// - An error if the request failed.
+pub fn json_rows(
+    conn: &Connection,
+    selection: &Selection,
+) -> Result<Vec<Map<String, Value>>, Box<dyn Error>> {
+    let mut stmt = conn.prepare(&selection.query)?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let mut rows = stmt.query(&*selection.params())?;
+    let mut list = Vec::new();
+
+    while let Some(row) = rows.next()? {
+        let mut object = Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            object.insert(column.clone(), json_value(row.get_ref(i)?));
+        }
+        list.push(object);
+    }
+    Ok(list)
+}
+
+/// Write the rows of a table matching the filter in the chosen format.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `host` : Hostname of the machine added to each exported row.
+/// - `table` : Name of the table to export.
+/// - `filter` : [`Filter`] to apply on the rows.
+/// - `format` : Output [`Format`].
+/// - `header` : Write the CSV header line before the rows.
+/// - `writer` : Output receiving the rows.
+///
+/// # Returns
+///
+/// - The number of exported rows.
+/// - An error if the request or the writing failed.
+pub fn export_table<W: Write>(
+    conn: &Connection,
+    host: &str,
+    table: &str,
+    filter: &Filter,
+    format: Format,
+    header: bool,
+    writer: &mut W,
+) -> Result<u64, Box<dyn Error>> {
+    let selection = select(conn, table, filter)?;
+    let mut stmt = conn.prepare(&selection.query)?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let mut rows = stmt.query(&*selection.params())?;
+    let mut count = 0;
+
+    match format {
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            if header {
+                csv.write_record(
+                    ["host"]
+                        .into_iter()
+                        .chain(columns.iter().map(String::as_str)),
+                )?;
+            }
+            while let Some(row) = rows.next()? {
+                let mut record = vec![host.to_string()];
+                for i in 0..columns.len() {
+                    record.push(csv_field(row.get_ref(i)?));
+                }
+                csv.write_record(&record)?;
+                count += 1;
+            }
+            csv.flush()?;
+        }
+        Format::Ndjson => {
+            while let Some(row) = rows.next()? {
+                let mut object = Map::new();
+                object.insert("host".to_string(), Value::from(host));
+                object.insert("table".to_string(), Value::from(table));
+                for (i, column) in columns.iter().enumerate() {
+                    object.insert(column.clone(), json_value(row.get_ref(i)?));
+                }
+                serde_json::to_writer(&mut *writer, &object)?;
+                writer.write_all(b"\n")?;
+                count += 1;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// Open an output file, creating its parent directories if needed.
+fn create_output(path: &Path) -> Result<Box<dyn Write>, Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    Ok(Box::new(BufWriter::new(File::create(path)?)))
+}
+
+/// Export the selected tables of the given databases.
+/// A selected table must be stored in one of the databases at least.
+/// In CSV, several tables can't share the same output,
+/// so `output` is used as directory receiving a file per table.
+///
+/// # Arguments
+///
+/// - `options` : [`ExportOptions`] of the export.
+///
+/// # Returns
+///
+/// - The number of exported rows.
+/// - An error if a database, a table or the output is not usable.
+pub fn export(options: &ExportOptions) -> Result<u64, Box<dyn Error>> {
+    let mut count = 0;
+    if !options.tables.is_empty() {
+        let mut stored = Vec::new();
+        for database in &options.databases {
+            let conn = Connection::open_with_flags(database, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+            stored.extend(list_tables(&conn)?);
+        }
+        if let Some(table) = options.tables.iter().find(|t| !stored.contains(t)) {
+            return Err(format!("Arguments 'Unknown table' : {table}").into());
+        }
+    }
+    let split = options.format == Format::Csv && options.tables.len() != 1;
+    if split && options.output.is_none() {
+        return Err("Arguments 'CSV export of several tables requires an output directory'".into());
+    }
+
+    let mut shared: Option<Box<dyn Write>> = None;
+    let mut headers: Vec<String> = Vec::new();
+
+    for database in &options.databases {
+        let conn = Connection::open_with_flags(database, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let host = host_name(&conn).unwrap_or_default();
+        if options.host.as_ref().is_some_and(|h| *h != host) {
+            continue;
+        }
+
+        let stored = list_tables(&conn)?;
+        let tables = if options.tables.is_empty() {
+            stored
+        } else {
+            options
+                .tables
+                .iter()
+                .filter(|t| stored.contains(t))
+                .cloned()
+                .collect()
+        };
+
+        for table in tables {
+            let header = !headers.contains(&table);
+            if split {
+                let dir = options
+                    .output
+                    .as_ref()
+                    .ok_or("Arguments 'No output directory'")?;
+                let path = dir.join(format!("{table}.{}", options.format.extension()));
+                let mut writer: Box<dyn Write> = if header {
+                    create_output(&path)?
+                } else {
+                    Box::new(BufWriter::new(File::options().append(true).open(&path)?))
+                };
+                count += export_table(
+                    &conn,
+                    &host,
+                    &table,
+                    &options.filter,
+                    options.format,
+                    header,
+                    &mut writer,
+                )?;
+                writer.flush()?;
+            } else {
+                if shared.is_none() {
+                    shared = Some(match &options.output {
+                        Some(path) => create_output(path)?,
+                        None => Box::new(BufWriter::new(stdout().lock())),
+                    });
+                }
+                if let Some(writer) = shared.as_mut() {
+                    count += export_table(
+                        &conn,
+                        &host,
+                        &table,
+                        &options.filter,
+                        options.format,
+                        header,
+                        writer,
+                    )?;
+                }
+            }
+            if header {
+                headers.push(table);
+            }
+        }
+    }
+
+    if let Some(mut writer) = shared {
+        writer.flush()?;
+    }
+    Ok(count)
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::read_to_string;
+    use tempfile::{NamedTempFile, tempdir};
+
+    fn database() -> NamedTempFile {
+        let temp = NamedTempFile::new().expect("Temp file creation failed");
+        let conn = Connection::open(temp.path()).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE system_data (id INTEGER PRIMARY KEY, hostname TEXT);
+            INSERT INTO system_data (hostname) VALUES ('node-1');
+            CREATE TABLE cpu_power (id INTEGER PRIMARY KEY, timestamp TEXT, zone_name TEXT, power_W REAL);
+            INSERT INTO cpu_power (timestamp, zone_name, power_W) VALUES
+                ('2025-01-01T10:00:00.000Z', 'intel-rapl:0', 12.5),
+                ('2025-01-02T10:00:00.000Z', 'intel-rapl:0', 14.0);",
+        )
+        .unwrap();
+        temp
+    }
+
+    // Test `export_table` function in NDJSON format
+    #[test]
+    fn test_export_table_ndjson() {
+        let temp = database();
+        let conn = Connection::open(temp.path()).unwrap();
+        let filter = Filter {
+            to: Some("2025-01-01T23:59:59Z".to_string()),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let res = export_table(
+            &conn,
+            "node-1",
+            "cpu_power",
+            &filter,
+            Format::Ndjson,
+            true,
+            &mut out,
+        );
+        assert_eq!(res.unwrap(), 1);
+
+        let line: Value = serde_json::from_slice(out.trim_ascii_end()).unwrap();
+        assert_eq!(line["host"], "node-1");
+        assert_eq!(line["power_W"], 12.5);
+    }
+
+    // Test `export_table` function in CSV format
+    #[test]
+    fn test_export_table_csv() {
+        let temp = database();
+        let conn = Connection::open(temp.path()).unwrap();
+        let mut out = Vec::new();
+        let res = export_table(
+            &conn,
+            "node-1",
+            "cpu_power",
+            &Filter::default(),
+            Format::Csv,
+            true,
+            &mut out,
+        );
+        assert_eq!(res.unwrap(), 2);
+
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("host,id,timestamp,zone_name,power_W\n"));
+        assert_eq!(text.lines().count(), 3);
+    }
+
+    // Test `export` function with host filter and CSV directory output
+    #[test]
+    fn test_export_host_and_directory() {
+        let temp = database();
+        let dir = tempdir().unwrap();
+        let mut options = ExportOptions {
+            databases: vec![temp.path().to_path_buf()],
+            tables: Vec::new(),
+            format: Format::Csv,
+            filter: Filter::default(),
+            host: Some("node-2".to_string()),
+            output: Some(dir.path().to_path_buf()),
+        };
+        assert_eq!(export(&options).unwrap(), 0);
+
+        options.host = Some("node-1".to_string());
+        assert_eq!(export(&options).unwrap(), 3);
+        let text = read_to_string(dir.path().join("cpu_power.csv")).unwrap();
+        assert_eq!(text.lines().count(), 3);
+
+        options.output = None;
+        assert!(export(&options).is_err());
+    }
+
+    // Test `export` function rejecting a table stored in no database
+    #[test]
+    fn test_export_unknown_table() {
+        let temp = database();
+        let options = ExportOptions {
+            databases: vec![temp.path().to_path_buf()],
+            tables: vec!["cpu_power".to_string(), "cpu_powr".to_string()],
+            format: Format::Ndjson,
+            filter: Filter::default(),
+            host: None,
+            output: None,
+        };
+        let res = export(&options);
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "Arguments 'Unknown table' : cpu_powr"
+        );
+    }
+}
@@ -1 +1,7 @@
+pub mod alert;
+pub mod anomaly;
+pub mod bus;
+pub mod config;
 pub mod core;
+pub mod export;
+pub mod query;
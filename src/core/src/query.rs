@@ -0,0 +1,648 @@
+//! # Query file
+//!
+//! This module provides the read side of the SQLite database filled by the probes,
+//! to select stored rows according time range and entity filters.
+
+use rusqlite::{Connection, ToSql, types::ValueRef};
+use std::error::Error;
+
+/// Column used by every probe table to trace the measurement date.
+pub const TIMESTAMP: &str = "timestamp";
+
+/// Column identifying the entity (interface, disk, GPU, process...) concerned by a row, per table.
+pub const ENTITY_COLUMNS: &[(&str, &str)] = &[
+    ("alerts", "entity"),
+    ("anomalies", "entity"),
+    ("board_data", "board_serial"),
+    ("cgroup_data", "path"),
+    ("cgroup_energy", "path"),
+    ("cpu_core", "core_name"),
+    ("cpu_power", "zone_name"),
+    ("cpu_temperature", "zone_name"),
+    ("energy_split", "component"),
+    ("exec_data", "entity"),
+    ("gpu_data", "gpu_bus_id"),
+    ("gpu_process_data", "process_pid"),
+    ("health", "entity"),
+    ("memory_modules", "device_id"),
+    ("network_data", "name"),
+    ("process_energy", "pid"),
+    ("storage_data", "name"),
+    ("system_data", "hostname"),
+    ("system_process_data", "pid"),
+];
+
+/// Tables describing the hardware and software of the machine, by component.
+pub const INVENTORY: &[(&str, &str)] = &[
+    ("board", "board_data"),
+    ("cpu", "cpu_data"),
+    ("gpu", "gpu_data"),
+    ("memory", "memory_modules"),
+    ("network", "network_data"),
+    ("storage", "storage_data"),
+    ("system", "system_data"),
+];
+
+/// Relation between a child table and the parent table carrying its timestamp and entity.
+#[derive(Debug, PartialEq)]
+pub struct Relation {
+    /// Name of the child table.
+    pub child: &'static str,
+    /// Column of the child table referencing the parent `id`.
+    pub foreign_key: &'static str,
+    /// Name of the parent table.
+    pub parent: &'static str,
+    /// Columns of the parent table joined to each child row.
+    pub columns: &'static [&'static str],
+}
+
+/// Child tables without own timestamp, to join with their parent table.
+pub const RELATIONS: &[Relation] = &[Relation {
+    child: "smart_data",
+    foreign_key: "device_id",
+    parent: "storage_data",
+    columns: &[TIMESTAMP, "name"],
+}];
+
+/// Filters to apply when selecting rows of a table.
+#[derive(Debug, Default, Clone)]
+pub struct Filter {
+    /// Lower bound of the time range (RFC 3339 or `YYYY-MM-DD`), inclusive.
+    pub from: Option<String>,
+    /// Upper bound of the time range (RFC 3339 or `YYYY-MM-DD`), inclusive.
+    /// A date alone includes the whole day.
+    pub to: Option<String>,
+    /// Entity identifier to keep (interface name, disk name, GPU bus id, pid...).
+    pub entity: Option<String>,
+}
+
+/// SQL selection built for a table according a [`Filter`].
+#[derive(Debug)]
+pub struct Selection {
+    /// Formatted `SELECT` request.
+    pub query: String,
+    /// Values bound to the request placeholders.
+    pub values: Vec<String>,
+    /// SQL expression of the timestamp of a row, if the table has one.
+    pub time: Option<String>,
+    /// SQL expression of the entity of a row, if the table has one.
+    pub entity: Option<String>,
+    /// Name of the result column carrying the timestamp, if the table has one.
+    pub time_column: Option<String>,
+    /// Name of the result column carrying the entity, if the table has one.
+    pub entity_column: Option<String>,
+}
+
+impl Selection {
+    /// Values bound to the request, usable with [`rusqlite`] statements.
+    pub fn params(&self) -> Vec<&dyn ToSql> {
+        self.values.iter().map(|v| v as &dyn ToSql).collect()
+    }
+}
+
+/// List the tables stored in the database, ignoring the SQLite internal ones.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+///
+/// # Returns
+///
+/// - Sorted names of the available tables.
+/// - An error if the SQLite schema can't be read.
+pub fn list_tables(conn: &Connection) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+    )?;
+    let tables = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(tables)
+}
+
+/// List the columns of a table.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `table` : Name of the table.
+///
+/// # Returns
+///
+/// - Column names in declaration order.
+/// - An error if the table does not exist.
+pub fn list_columns(conn: &Connection, table: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", quote(table)))?;
+    let columns = stmt
+        .query_map([], |row| row.get(1))?
+        .collect::<Result<Vec<String>, _>>()?;
+    if columns.is_empty() {
+        return Err(format!("Data 'Unknown table {table}'").into());
+    }
+    Ok(columns)
+}
+
+/// Retrieves the hostname of the machine which filled the database.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+///
+/// # Returns
+///
+/// The last hostname stored by the system probe, if any.
+pub fn host_name(conn: &Connection) -> Option<String> {
+    conn.query_row(
+        "SELECT hostname FROM system_data WHERE hostname IS NOT NULL ORDER BY id DESC LIMIT 1",
+        [],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+/// Find a column by its exact name or its name followed by a unit suffix.
+///
+/// # Arguments
+///
+/// - `columns` : Column names of a table.
+/// - `column` : Name searched, like `space_available` for `space_available_MB`.
+///
+/// # Returns
+///
+/// The matching column, if it's the only one.
+pub fn resolve_column<'a>(columns: &'a [String], column: &str) -> Option<&'a String> {
+    columns.iter().find(|c| *c == column).or_else(|| {
+        let mut matching = columns
+            .iter()
+            .filter(|c| c.rsplit_once('_').is_some_and(|(name, _)| name == column));
+        matching.next().filter(|_| matching.next().is_none())
+    })
+}
+
+/// Quote an SQL identifier coming from the database schema or a user input.
+pub fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Check if a time bound is a date alone, formatted `YYYY-MM-DD`.
+fn is_date(bound: &str) -> bool {
+    let bytes = bound.as_bytes();
+    bytes.len() == 10
+        && bytes.iter().enumerate().all(|(i, b)| {
+            if i == 4 || i == 7 {
+                *b == b'-'
+            } else {
+                b.is_ascii_digit()
+            }
+        })
+}
+
+/// Build the selection of the rows of a table matching a [`Filter`].
+/// Child tables declared in [`RELATIONS`] are joined to their parent table,
+/// which provides the timestamp and the entity of each row.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `table` : Name of the table to select.
+/// - `filter` : [`Filter`] to apply on the rows.
+///
+/// # Returns
+///
+/// - The [`Selection`] ordered by time when possible.
+/// - An error if the table does not exist or if a time bound is invalid.
+pub fn select(
+    conn: &Connection,
+    table: &str,
+    filter: &Filter,
+) -> Result<Selection, Box<dyn Error>> {
+    for bound in [&filter.from, &filter.to].into_iter().flatten() {
+        let valid: Option<f64> =
+            conn.query_row("SELECT julianday(?1)", [bound], |row| row.get(0))?;
+        if valid.is_none() {
+            return Err(format!("Arguments 'Invalid time bound {bound}'").into());
+        }
+    }
+
+    let columns = list_columns(conn, table)?;
+    let relation = RELATIONS.iter().find(|r| r.child == table);
+
+    let mut fields = vec!["t.*".to_string()];
+    let mut from = format!("{} t", quote(table));
+    let mut time_column = columns
+        .iter()
+        .any(|c| c == TIMESTAMP)
+        .then(|| TIMESTAMP.to_string());
+    let mut time = time_column.as_ref().map(|_| format!("t.{TIMESTAMP}"));
+    let mut entity_column = ENTITY_COLUMNS
+        .iter()
+        .find(|(name, _)| *name == table)
+        .map(|(_, column)| column.to_string());
+    let mut entity = entity_column
+        .as_ref()
+        .map(|column| format!("t.{}", quote(column)));
+
+    if let Some(relation) = relation {
+        from.push_str(&format!(
+            " JOIN {} p ON t.{} = p.id",
+            quote(relation.parent),
+            quote(relation.foreign_key)
+        ));
+        for column in relation.columns {
+            fields.push(format!(
+                "p.{} AS {}",
+                quote(column),
+                quote(&format!("{}_{column}", relation.parent))
+            ));
+        }
+        if time.is_none() && relation.columns.contains(&TIMESTAMP) {
+            time = Some(format!("p.{TIMESTAMP}"));
+            time_column = Some(format!("{}_{TIMESTAMP}", relation.parent));
+        }
+        if entity.is_none()
+            && let Some((_, column)) = ENTITY_COLUMNS.iter().find(|(name, column)| {
+                *name == relation.parent && relation.columns.contains(column)
+            })
+        {
+            entity = Some(format!("p.{}", quote(column)));
+            entity_column = Some(format!("{}_{column}", relation.parent));
+        }
+    }
+
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+    if let Some(time) = &time {
+        if let Some(bound) = &filter.from {
+            values.push(bound.clone());
+            conditions.push(format!("julianday({time}) >= julianday(?{})", values.len()));
+        }
+        if let Some(bound) = &filter.to {
+            values.push(bound.clone());
+            if is_date(bound) {
+                conditions.push(format!(
+                    "julianday({time}) < julianday(?{}, '+1 day')",
+                    values.len()
+                ));
+            } else {
+                conditions.push(format!("julianday({time}) <= julianday(?{})", values.len()));
+            }
+        }
+    }
+    if let Some(value) = &filter.entity {
+        match &entity {
+            Some(entity) => {
+                values.push(value.clone());
+                conditions.push(format!("CAST({entity} AS TEXT) = ?{}", values.len()));
+            }
+            None => return Err(format!("Arguments 'No entity column for table {table}'").into()),
+        }
+    }
+
+    let mut query = format!("SELECT {} FROM {from}", fields.join(", "));
+    if !conditions.is_empty() {
+        query.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+    }
+    match &time {
+        Some(time) => query.push_str(&format!(" ORDER BY {time}, t.rowid")),
+        None => query.push_str(" ORDER BY t.rowid"),
+    }
+
+    Ok(Selection {
+        query,
+        values,
+        time,
+        entity,
+        time_column,
+        entity_column,
+    })
+}
+
+/// List the numeric columns of a table which can be averaged,
+/// ignoring its identifiers and entity column.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `table` : Name of the table.
+///
+/// # Returns
+///
+/// - Names of the `INTEGER` and `REAL` columns.
+/// - An error if the table does not exist.
+pub fn numeric_columns(conn: &Connection, table: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", quote(table)))?;
+    let columns = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    if columns.is_empty() {
+        return Err(format!("Data 'Unknown table {table}'").into());
+    }
+
+    let foreign_key = RELATIONS
+        .iter()
+        .find(|r| r.child == table)
+        .map(|r| r.foreign_key);
+    let entity = ENTITY_COLUMNS
+        .iter()
+        .find(|(name, _)| *name == table)
+        .map(|(_, column)| *column);
+
+    Ok(columns
+        .into_iter()
+        .filter(|(name, kind)| {
+            let kind = kind.to_uppercase();
+            (kind == "INTEGER" || kind == "REAL")
+                && name != "id"
+                && Some(name.as_str()) != foreign_key
+                && Some(name.as_str()) != entity
+        })
+        .map(|(name, _)| name)
+        .collect())
+}
+
+/// Build the selection of the rows of a table matching a [`Filter`], averaged by time step.
+/// Each row gives the start of its step, the entity if the table has one,
+/// and the mean of each numeric column.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `table` : Name of the table to select.
+/// - `filter` : [`Filter`] to apply on the rows.
+/// - `step` : Duration of a step in seconds.
+///
+/// # Returns
+///
+/// - The [`Selection`] ordered by step and entity.
+/// - An error if the table has no timestamp, or if the step or a filter is invalid.
+pub fn aggregate(
+    conn: &Connection,
+    table: &str,
+    filter: &Filter,
+    step: u64,
+) -> Result<Selection, Box<dyn Error>> {
+    if step == 0 {
+        return Err("Arguments 'Step must be greater than 0'".into());
+    }
+    let inner = select(conn, table, filter)?;
+    let Some(time_column) = &inner.time_column else {
+        return Err(format!("Arguments 'No timestamp column for table {table}'").into());
+    };
+
+    let mut fields = vec![format!(
+        "strftime('%Y-%m-%dT%H:%M:%SZ', (CAST(strftime('%s', q.{}) AS INTEGER) / {step}) * {step}, 'unixepoch') AS {}",
+        quote(time_column),
+        quote(TIMESTAMP)
+    )];
+    let mut groups = vec!["1"];
+    if let Some(entity) = &inner.entity_column {
+        fields.push(format!("q.{}", quote(entity)));
+        groups.push("2");
+    }
+    for column in numeric_columns(conn, table)? {
+        fields.push(format!("AVG(q.{0}) AS {0}", quote(&column)));
+    }
+
+    let groups = groups.join(", ");
+    let query = format!(
+        "SELECT {} FROM ({}) q GROUP BY {groups} ORDER BY {groups}",
+        fields.join(", "),
+        inner.query
+    );
+
+    Ok(Selection {
+        query,
+        values: inner.values,
+        time: Some(format!("q.{}", quote(TIMESTAMP))),
+        entity: inner
+            .entity_column
+            .as_ref()
+            .map(|e| format!("q.{}", quote(e))),
+        time_column: Some(TIMESTAMP.to_string()),
+        entity_column: inner.entity_column,
+    })
+}
+
+/// Build the selection of the last measurement of a table,
+/// being all the rows sharing the most recent timestamp, one per entity.
+/// A table without timestamp gives its last inserted row.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `table` : Name of the table to select.
+///
+/// # Returns
+///
+/// - The [`Selection`] of the last rows.
+/// - An error if the table does not exist.
+pub fn latest(conn: &Connection, table: &str) -> Result<Selection, Box<dyn Error>> {
+    let mut selection = select(conn, table, &Filter::default())?;
+    let column = match &selection.time_column {
+        Some(time) => time.clone(),
+        None if list_columns(conn, table)?.iter().any(|c| c == "id") => "id".to_string(),
+        None => return Ok(selection),
+    };
+
+    selection.query = format!(
+        "SELECT * FROM ({0}) q WHERE q.{1} = (SELECT MAX(l.{1}) FROM ({0}) l)",
+        selection.query,
+        quote(&column)
+    );
+    Ok(selection)
+}
+
+/// Numeric value of a column for an entity, none if the table has no entity column.
+pub type EntityValue = (Option<String>, f64);
+
+/// Read the numeric values of a column in the last measurement of a table.
+/// Null and non numeric values are ignored.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `table` : Name of the table.
+/// - `column` : Exact name of the column.
+///
+/// # Returns
+///
+/// - The value of each entity, with no entity if the table has none.
+/// - An error if the table can't be read.
+pub fn latest_values(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+) -> Result<Vec<EntityValue>, Box<dyn Error>> {
+    let selection = latest(conn, table)?;
+    let entity = match &selection.entity_column {
+        Some(entity) => format!("CAST(q.{} AS TEXT)", quote(entity)),
+        None => "NULL".to_string(),
+    };
+    let request = format!(
+        "SELECT {entity}, q.{} FROM ({}) q",
+        quote(column),
+        selection.query
+    );
+    let mut stmt = conn.prepare(&request)?;
+    let mut rows = stmt.query(&*selection.params())?;
+
+    let mut values = Vec::new();
+    while let Some(row) = rows.next()? {
+        let value = match row.get_ref(1)? {
+            ValueRef::Integer(i) => i as f64,
+            ValueRef::Real(f) => f,
+            _ => continue,
+        };
+        values.push((row.get(0)?, value));
+    }
+    Ok(values)
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE network_data (id INTEGER PRIMARY KEY, timestamp TEXT, name TEXT, received_MB REAL);
+            INSERT INTO network_data (timestamp, name, received_MB) VALUES
+                ('2025-01-01T10:00:00.000Z', 'eth0', 1.0),
+                ('2025-01-01T11:00:00.000Z', 'eth0', 2.0),
+                ('2025-01-01T11:00:00.000Z', 'wlan0', 3.0);
+            CREATE TABLE storage_data (id INTEGER PRIMARY KEY, timestamp TEXT, name TEXT);
+            CREATE TABLE smart_data (id INTEGER PRIMARY KEY, device_id INTEGER, temperature INTEGER);
+            INSERT INTO storage_data (timestamp, name) VALUES ('2025-01-01T10:00:00.000Z', 'sda');
+            INSERT INTO smart_data (device_id, temperature) VALUES (1, 40);",
+        )
+        .unwrap();
+        conn
+    }
+
+    fn count(conn: &Connection, selection: &Selection) -> usize {
+        let mut stmt = conn.prepare(&selection.query).unwrap();
+        stmt.query_map(&*selection.params(), |_| Ok(()))
+            .unwrap()
+            .count()
+    }
+
+    // Test `list_tables` function with created tables
+    #[test]
+    fn test_list_tables() {
+        let conn = database();
+        let res = list_tables(&conn).unwrap();
+        assert_eq!(res, vec!["network_data", "smart_data", "storage_data"]);
+    }
+
+    // Test `select` function with time range and entity filters
+    #[test]
+    fn test_select_filters() {
+        let conn = database();
+        let filter = Filter {
+            from: Some("2025-01-01T10:30:00Z".to_string()),
+            to: None,
+            entity: Some("eth0".to_string()),
+        };
+        let res = select(&conn, "network_data", &filter).unwrap();
+        assert_eq!(count(&conn, &res), 1);
+    }
+
+    // Test `select` function including the whole day of a date alone upper bound
+    #[test]
+    fn test_select_date_to() {
+        let conn = database();
+        let filter = |to: &str| Filter {
+            to: Some(to.to_string()),
+            ..Default::default()
+        };
+        let res = select(&conn, "network_data", &filter("2025-01-01")).unwrap();
+        assert_eq!(count(&conn, &res), 3);
+        let res = select(&conn, "network_data", &filter("2024-12-31")).unwrap();
+        assert_eq!(count(&conn, &res), 0);
+        let res = select(&conn, "network_data", &filter("2025-01-01T10:30:00Z")).unwrap();
+        assert_eq!(count(&conn, &res), 1);
+    }
+
+    // Test `select` function joining a child table to its parent
+    #[test]
+    fn test_select_relation() {
+        let conn = database();
+        let filter = Filter {
+            entity: Some("sda".to_string()),
+            ..Default::default()
+        };
+        let res = select(&conn, "smart_data", &filter).unwrap();
+        assert_eq!(res.time.as_deref(), Some("p.timestamp"));
+        assert_eq!(count(&conn, &res), 1);
+    }
+
+    // Test `aggregate` function averaging rows by step and entity
+    #[test]
+    fn test_aggregate_step() {
+        let conn = database();
+        conn.execute(
+            "INSERT INTO network_data (timestamp, name, received_MB) VALUES ('2025-01-01T11:20:00.000Z', 'eth0', 4.0)",
+            [],
+        )
+        .unwrap();
+
+        let res = aggregate(&conn, "network_data", &Filter::default(), 3600).unwrap();
+        let mut stmt = conn.prepare(&res.query).unwrap();
+        let rows: Vec<(String, String, f64)> = stmt
+            .query_map(&*res.params(), |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[1],
+            ("2025-01-01T11:00:00Z".to_string(), "eth0".to_string(), 3.0)
+        );
+
+        assert!(aggregate(&conn, "network_data", &Filter::default(), 0).is_err());
+    }
+
+    // Test `latest` function keeping the rows of the last timestamp
+    #[test]
+    fn test_latest() {
+        let conn = database();
+        let res = latest(&conn, "network_data").unwrap();
+        assert_eq!(count(&conn, &res), 2);
+        let res = latest(&conn, "smart_data").unwrap();
+        assert_eq!(count(&conn, &res), 1);
+    }
+
+    // Test `latest_values` function reading a column of the last rows by entity
+    #[test]
+    fn test_latest_values() {
+        let conn = database();
+        let res = latest_values(&conn, "network_data", "received_MB").unwrap();
+        assert_eq!(
+            res,
+            vec![
+                (Some("eth0".to_string()), 2.0),
+                (Some("wlan0".to_string()), 3.0)
+            ]
+        );
+    }
+
+    // Test `select` function with invalid time bound and unknown table
+    #[test]
+    fn test_select_error() {
+        let conn = database();
+        let filter = Filter {
+            from: Some("yesterday".to_string()),
+            ..Default::default()
+        };
+        assert!(select(&conn, "network_data", &filter).is_err());
+        assert!(select(&conn, "unknown", &Filter::default()).is_err());
+    }
+}
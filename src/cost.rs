@@ -0,0 +1,537 @@
+//! # File cost
+//!
+//! This file provides the electricity cost estimation, applying the tariff schedule and the
+//! power usage effectiveness (PUE) of the site to the energy of the machine, and the daily
+//! and monthly rollups of the stored costs.
+
+use chrono::{DateTime, Datelike, FixedOffset, Local, NaiveTime, Offset, Weekday};
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::error::Error as StdError;
+use userv_core::{
+    core::init_db,
+    query::{Filter, list_tables, select},
+};
+
+use crate::energy::EnergySummary;
+
+/// Request creating the table of the costs.
+pub const REQUEST: &str = "CREATE TABLE IF NOT EXISTS costs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    interval_s REAL NOT NULL,
+    energy_J REAL NOT NULL,
+    pue REAL NOT NULL,
+    billed_kWh REAL NOT NULL,
+    period TEXT NOT NULL,
+    rate REAL NOT NULL,
+    cost REAL NOT NULL,
+    currency TEXT NOT NULL
+);";
+
+/// Name of the period applied out of the configured ones.
+pub const BASE: &str = "base";
+
+/// Number of joules in a kilowatt-hour.
+const JOULES_PER_KWH: f64 = 3.6e6;
+
+/// Time-of-use period of a tariff, from the `[[cost.periods]]` sections.
+#[derive(Debug, Clone, Deserialize)]
+struct PeriodConfig {
+    name: String,
+    rate: f64,
+    #[serde(default)]
+    days: Vec<String>,
+    #[serde(default = "default_time")]
+    from: String,
+    #[serde(default = "default_time")]
+    to: String,
+}
+
+fn default_time() -> String {
+    "00:00".to_string()
+}
+
+/// Tariff schedule, from the `[cost]` section of the configuration.
+#[derive(Debug, Clone, Deserialize)]
+struct TariffConfig {
+    #[serde(default = "default_currency")]
+    currency: String,
+    #[serde(default = "default_pue")]
+    pue: f64,
+    rate: f64,
+    utc_offset: Option<String>,
+    #[serde(default)]
+    periods: Vec<PeriodConfig>,
+}
+
+fn default_currency() -> String {
+    "EUR".to_string()
+}
+
+fn default_pue() -> f64 {
+    1.0
+}
+
+/// Parse a day, or `weekday` and `weekend` for several days.
+fn parse_days(day: &str) -> Option<Vec<Weekday>> {
+    use Weekday::*;
+    Some(match day.to_lowercase().as_str() {
+        "weekday" => vec![Mon, Tue, Wed, Thu, Fri],
+        "weekend" => vec![Sat, Sun],
+        day => vec![day.parse().ok()?],
+    })
+}
+
+/// Time-of-use period of a tariff, from `from` included to `to` excluded,
+/// wrapping around midnight when `to` is before `from`, the whole day when they are equal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Period {
+    /// Name of the period, like `peak` or `off-peak`.
+    pub name: String,
+    /// Price of a kWh.
+    pub rate: f64,
+    /// Days of the period, every day if empty.
+    pub days: Vec<Weekday>,
+    /// Local start time.
+    pub from: NaiveTime,
+    /// Local end time.
+    pub to: NaiveTime,
+}
+
+impl Period {
+    /// Check if the period applies at a local date.
+    fn contains(&self, date: &DateTime<FixedOffset>) -> bool {
+        let time = date.time();
+        let (day, within) = if self.from == self.to {
+            (date.weekday(), true)
+        } else if self.from < self.to {
+            (date.weekday(), self.from <= time && time < self.to)
+        } else if time >= self.from {
+            (date.weekday(), true)
+        } else {
+            // After midnight, the period belongs to the day it started.
+            (date.weekday().pred(), time < self.to)
+        };
+        within && (self.days.is_empty() || self.days.contains(&day))
+    }
+}
+
+/// Tariff schedule applied to the energy of the machine.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "TariffConfig")]
+pub struct Tariff {
+    /// Currency of the prices.
+    pub currency: String,
+    /// Power usage effectiveness of the site, multiplying the energy of the machine.
+    pub pue: f64,
+    /// Price of a kWh out of the periods.
+    pub rate: f64,
+    /// Offset of the local time of the periods, the one of the machine if not set.
+    pub utc_offset: Option<FixedOffset>,
+    /// Time-of-use periods, the first matching one applying.
+    pub periods: Vec<Period>,
+}
+
+impl TryFrom<TariffConfig> for Tariff {
+    type Error = String;
+
+    fn try_from(config: TariffConfig) -> Result<Self, Self::Error> {
+        if config.pue.is_nan() || config.pue < 1.0 {
+            return Err(format!("Config 'Invalid PUE' : {}", config.pue));
+        }
+        let time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .map_err(|_| format!("Config 'Invalid tariff time' : {time}"))
+        };
+        let mut periods = Vec::new();
+        for period in config.periods {
+            if period.rate.is_nan() || period.rate < 0.0 {
+                return Err(format!("Config 'Invalid tariff rate' : {}", period.rate));
+            }
+            let mut days = Vec::new();
+            for day in &period.days {
+                days.extend(
+                    parse_days(day)
+                        .ok_or_else(|| format!("Config 'Invalid tariff day' : {day}"))?,
+                );
+            }
+            periods.push(Period {
+                from: time(&period.from)?,
+                to: time(&period.to)?,
+                name: period.name,
+                rate: period.rate,
+                days,
+            });
+        }
+        if config.rate.is_nan() || config.rate < 0.0 {
+            return Err(format!("Config 'Invalid tariff rate' : {}", config.rate));
+        }
+        Ok(Tariff {
+            currency: config.currency,
+            pue: config.pue,
+            rate: config.rate,
+            utc_offset: config
+                .utc_offset
+                .map(|offset| {
+                    offset
+                        .parse()
+                        .map_err(|_| format!("Config 'Invalid UTC offset' : {offset}"))
+                })
+                .transpose()?,
+            periods,
+        })
+    }
+}
+
+/// Electricity cost of the machine over the interval before a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Cost {
+    /// Length of the interval in seconds.
+    pub interval: f64,
+    /// Energy consumed by the machine in J.
+    pub energy: f64,
+    /// Power usage effectiveness applied.
+    pub pue: f64,
+    /// Energy billed in kWh, including the site overhead.
+    pub billed: f64,
+    /// Name of the tariff period applied.
+    pub period: String,
+    /// Price of a kWh.
+    pub rate: f64,
+    /// Cost of the interval.
+    pub cost: f64,
+    /// Currency of the cost.
+    pub currency: String,
+}
+
+impl Tariff {
+    /// Offset of the local time, the one of the machine at the date if not configured.
+    fn offset(&self, date: &DateTime<FixedOffset>) -> FixedOffset {
+        self.utc_offset
+            .unwrap_or_else(|| date.with_timezone(&Local).offset().fix())
+    }
+
+    /// Find the period applying at a date.
+    ///
+    /// # Arguments
+    ///
+    /// - `date` : Date of the energy consumption.
+    ///
+    /// # Returns
+    ///
+    /// The name and price of a kWh of the first matching period, the base price otherwise.
+    pub fn rate_at(&self, date: &DateTime<FixedOffset>) -> (&str, f64) {
+        let local = date.with_timezone(&self.offset(date));
+        self.periods
+            .iter()
+            .find(|period| period.contains(&local))
+            .map(|period| (period.name.as_str(), period.rate))
+            .unwrap_or((BASE, self.rate))
+    }
+
+    /// Estimate the cost of the energy consumed by the machine, at the price of the date.
+    ///
+    /// # Arguments
+    ///
+    /// - `timestamp` : Date of the snapshot (RFC 3339).
+    /// - `energy` : [`EnergySummary`] of the snapshot.
+    ///
+    /// # Returns
+    ///
+    /// - The [`Cost`] over the interval of the energy summary.
+    /// - An error if the timestamp is invalid.
+    pub fn cost(&self, timestamp: &str, energy: &EnergySummary) -> Result<Cost, Box<dyn StdError>> {
+        let date = DateTime::parse_from_rfc3339(timestamp)
+            .map_err(|e| format!("Data 'Invalid timestamp' : {timestamp} {e}"))?;
+        let (period, rate) = self.rate_at(&date);
+        let billed = energy.total_energy * self.pue / JOULES_PER_KWH;
+        Ok(Cost {
+            interval: energy.interval,
+            energy: energy.total_energy,
+            pue: self.pue,
+            billed,
+            period: period.to_string(),
+            rate,
+            cost: billed * rate,
+            currency: self.currency.clone(),
+        })
+    }
+}
+
+/// Store the cost of a snapshot in database.
+///
+/// # Arguments
+///
+/// - `timestamp` : Date of the snapshot.
+/// - `cost` : [`Cost`] to insert.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert request failed.
+pub fn store_cost(timestamp: &str, cost: &Cost) -> Result<(), Box<dyn StdError>> {
+    let conn = init_db(REQUEST)?;
+    conn.execute(
+        "INSERT INTO costs (timestamp, interval_s, energy_J, pue, billed_kWh, period, rate, cost, currency)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            timestamp,
+            cost.interval,
+            cost.energy,
+            cost.pue,
+            cost.billed,
+            cost.period,
+            cost.rate,
+            cost.cost,
+            cost.currency
+        ],
+    )?;
+    Ok(())
+}
+
+/// Sum of the stored costs over a day, a month or the whole range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Rollup {
+    /// Day (`YYYY-MM-DD`) or month (`YYYY-MM`) in local time, `total` for the whole range.
+    pub period: String,
+    /// Energy billed in kWh.
+    pub billed: f64,
+    /// Cost.
+    pub cost: f64,
+    /// Currency of the cost.
+    pub currency: String,
+}
+
+/// Daily and monthly costs over a time range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CostReport {
+    /// Lower bound of the time range, if given.
+    pub from: Option<String>,
+    /// Upper bound of the time range, if given.
+    pub to: Option<String>,
+    /// Cost of each day.
+    pub daily: Vec<Rollup>,
+    /// Cost of each month.
+    pub monthly: Vec<Rollup>,
+    /// Cost of the whole range, per currency.
+    pub total: Vec<Rollup>,
+}
+
+/// Sum the stored costs by local day and month.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `filter` : Time range of the costs.
+/// - `offset` : Offset of the local time, deciding the day of each cost.
+///
+/// # Returns
+///
+/// - The [`CostReport`], empty if no cost is stored.
+/// - An error if a time bound is invalid or the table can't be read.
+pub fn cost_report(
+    conn: &Connection,
+    filter: &Filter,
+    offset: FixedOffset,
+) -> Result<CostReport, Box<dyn StdError>> {
+    let mut report = CostReport {
+        from: filter.from.clone(),
+        to: filter.to.clone(),
+        daily: Vec::new(),
+        monthly: Vec::new(),
+        total: Vec::new(),
+    };
+    if !list_tables(conn)?.iter().any(|t| t == "costs") {
+        return Ok(report);
+    }
+
+    let selection = select(conn, "costs", filter)?;
+    let shift = format!("{} minutes", offset.local_minus_utc() / 60);
+    let rollup = |period: &str| -> Result<Vec<Rollup>, Box<dyn StdError>> {
+        let request = format!(
+            "SELECT {period}, TOTAL(q.billed_kWh), TOTAL(q.cost), q.currency FROM ({}) q
+            GROUP BY 1, q.currency ORDER BY 1, q.currency",
+            selection.query
+        );
+        let mut stmt = conn.prepare(&request)?;
+        let mut params = selection.params();
+        params.push(&shift);
+        let rows = stmt
+            .query_map(&*params, |row| {
+                Ok(Rollup {
+                    period: row.get(0)?,
+                    billed: row.get(1)?,
+                    cost: row.get(2)?,
+                    currency: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    };
+    let index = selection.params().len() + 1;
+    report.daily = rollup(&format!("strftime('%Y-%m-%d', q.timestamp, ?{index})"))?;
+    report.monthly = rollup(&format!("strftime('%Y-%m', q.timestamp, ?{index})"))?;
+    for month in &report.monthly {
+        match report
+            .total
+            .iter_mut()
+            .find(|total| total.currency == month.currency)
+        {
+            Some(total) => {
+                total.billed += month.billed;
+                total.cost += month.cost;
+            }
+            None => report.total.push(Rollup {
+                period: "total".to_string(),
+                ..month.clone()
+            }),
+        }
+    }
+    Ok(report)
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use userv_core::config::Config;
+
+    fn tariff() -> Tariff {
+        Config::parse(
+            "[cost]
+            currency = \"EUR\"
+            pue = 1.5
+            rate = 0.2
+            utc_offset = \"+01:00\"
+            [[cost.periods]]
+            name = \"weekend\"
+            rate = 0.1
+            days = [\"weekend\"]
+            [[cost.periods]]
+            name = \"off-peak\"
+            rate = 0.15
+            from = \"22:00\"
+            to = \"06:00\"
+            [[cost.periods]]
+            name = \"peak\"
+            rate = 0.3
+            days = [\"weekday\"]
+            from = \"08:00\"
+            to = \"20:00\"",
+        )
+        .unwrap()
+        .section::<Option<Tariff>>("cost")
+        .unwrap()
+        .unwrap()
+    }
+
+    fn energy(joules: f64) -> EnergySummary {
+        EnergySummary {
+            interval: 60.0,
+            components: Vec::new(),
+            total_power: joules / 60.0,
+            total_energy: joules,
+            measured_ratio: None,
+        }
+    }
+
+    // Test `Tariff` deserialization errors
+    #[test]
+    fn test_tariff_config() {
+        for section in [
+            "rate = 0.2\npue = 0.5",
+            "rate = -1.0",
+            "rate = 0.2\nutc_offset = \"CET\"",
+            "rate = 0.2\n[[cost.periods]]\nname = \"x\"\nrate = 0.1\ndays = [\"funday\"]",
+            "rate = 0.2\n[[cost.periods]]\nname = \"x\"\nrate = 0.1\nfrom = \"25:00\"",
+        ] {
+            let config = Config::parse(&format!("[cost]\n{section}")).unwrap();
+            assert!(
+                config.section::<Option<Tariff>>("cost").is_err(),
+                "{section}"
+            );
+        }
+    }
+
+    // Test `rate_at` function with weekday, weekend and periods wrapping around midnight
+    #[test]
+    fn test_rate_at() {
+        let tariff = tariff();
+        let rate = |date: &str| {
+            let (period, rate) = tariff.rate_at(&DateTime::parse_from_rfc3339(date).unwrap());
+            (period.to_string(), rate)
+        };
+        // Wednesday 2025-01-01, in UTC+1.
+        assert_eq!(rate("2025-01-01T09:00:00Z"), ("peak".to_string(), 0.3));
+        assert_eq!(rate("2025-01-01T19:30:00Z"), ("base".to_string(), 0.2));
+        assert_eq!(rate("2025-01-01T22:30:00Z"), ("off-peak".to_string(), 0.15));
+        assert_eq!(rate("2025-01-02T03:00:00Z"), ("off-peak".to_string(), 0.15));
+        // Saturday 2025-01-04.
+        assert_eq!(rate("2025-01-04T09:00:00Z"), ("weekend".to_string(), 0.1));
+    }
+
+    // Test `cost` function applying the PUE and the period rate
+    #[test]
+    fn test_cost() {
+        let res = tariff()
+            .cost("2025-01-01T09:00:00.000Z", &energy(7.2e6))
+            .unwrap();
+        assert_eq!(res.billed, 3.0);
+        assert_eq!(res.period, "peak");
+        assert!((res.cost - 0.9).abs() < 1e-9);
+        assert!(tariff().cost("today", &energy(1.0)).is_err());
+    }
+
+    // Test `cost_report` function rolling up by local day and month
+    #[test]
+    fn test_cost_report() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert!(
+            cost_report(&conn, &Filter::default(), FixedOffset::east_opt(0).unwrap())
+                .unwrap()
+                .daily
+                .is_empty()
+        );
+        conn.execute_batch(REQUEST).unwrap();
+        for (timestamp, cost) in [
+            ("2025-01-31T10:00:00.000Z", 1.0),
+            ("2025-01-31T23:30:00.000Z", 2.0),
+            ("2025-02-01T10:00:00.000Z", 4.0),
+        ] {
+            conn.execute(
+                "INSERT INTO costs (timestamp, interval_s, energy_J, pue, billed_kWh, period, rate, cost, currency)
+                VALUES (?1, 60, 0, 1, ?2, 'base', 1, ?2, 'EUR')",
+                params![timestamp, cost],
+            )
+            .unwrap();
+        }
+
+        let offset = FixedOffset::east_opt(3600).unwrap();
+        let res = cost_report(&conn, &Filter::default(), offset).unwrap();
+        let periods = |rollups: &[Rollup]| -> Vec<(String, f64)> {
+            rollups.iter().map(|r| (r.period.clone(), r.cost)).collect()
+        };
+        assert_eq!(
+            periods(&res.daily),
+            vec![
+                ("2025-01-31".to_string(), 1.0),
+                ("2025-02-01".to_string(), 6.0)
+            ]
+        );
+        assert_eq!(
+            periods(&res.monthly),
+            vec![("2025-01".to_string(), 1.0), ("2025-02".to_string(), 6.0)]
+        );
+        assert_eq!(periods(&res.total), vec![("total".to_string(), 7.0)]);
+
+        let filter = Filter {
+            from: Some("2025-02-01".to_string()),
+            ..Default::default()
+        };
+        let res = cost_report(&conn, &filter, offset).unwrap();
+        assert_eq!(periods(&res.total), vec![("total".to_string(), 4.0)]);
+    }
+}
@@ -0,0 +1,678 @@
+//! # File embodied
+//!
+//! This file provides the embodied carbon estimation, mapping the hardware inventory of the
+//! machine to the carbon footprint of its manufacturing, amortised over its lifetime,
+//! and its comparison with the operational emissions.
+
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error as StdError,
+    sync::{Arc, Mutex},
+};
+use userv_core::{
+    core::init_db,
+    query::{Filter, list_tables, select},
+};
+
+use storage::DiskInfo;
+
+use crate::{component::Component, snapshot::Snapshot};
+
+/// Request creating the table of the embodied emissions.
+pub const REQUEST: &str = "CREATE TABLE IF NOT EXISTS embodied_emissions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    interval_s REAL NOT NULL,
+    footprint_kgCO2e REAL NOT NULL,
+    lifetime_years REAL NOT NULL,
+    rate_gCO2e_h REAL NOT NULL,
+    emissions_gCO2e REAL NOT NULL
+);";
+
+/// Components giving the hardware inventory, in the order of the reports.
+const INVENTORY: [Component; 4] = [
+    Component::Board,
+    Component::Memory,
+    Component::Storage,
+    Component::Gpu,
+];
+
+/// Number of days in a year.
+const DAYS_PER_YEAR: f64 = 365.25;
+
+/// Manufacturing footprints and lifetime, from the `[embodied]` section of the configuration.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct FootprintConfig {
+    lifetime: f64,
+    hours_per_day: f64,
+    mainboard: f64,
+    dram_per_gb: f64,
+    ssd_base: f64,
+    ssd_per_tb: f64,
+    hdd_base: f64,
+    hdd_per_tb: f64,
+    gpu_base: f64,
+    gpu_per_gb: f64,
+    gpu_models: BTreeMap<String, f64>,
+}
+
+/// Orders of magnitude of the Boavizta methodology.
+impl Default for FootprintConfig {
+    fn default() -> Self {
+        FootprintConfig {
+            lifetime: 4.0,
+            hours_per_day: 24.0,
+            mainboard: 66.1,
+            dram_per_gb: 1.5,
+            ssd_base: 6.34,
+            ssd_per_tb: 45.4,
+            hdd_base: 31.1,
+            hdd_per_tb: 0.0,
+            gpu_base: 25.0,
+            gpu_per_gb: 1.5,
+            gpu_models: BTreeMap::new(),
+        }
+    }
+}
+
+/// Manufacturing footprints of the hardware in kgCO2e, and lifetime over which they are amortised.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "FootprintConfig")]
+pub struct Footprint {
+    /// Lifetime of the machine in years.
+    pub lifetime: f64,
+    /// Hours of use per day.
+    pub hours_per_day: f64,
+    /// Footprint of the mainboard, with its chassis and power supply.
+    pub mainboard: f64,
+    /// Footprint of a GB of memory.
+    pub dram_per_gb: f64,
+    /// Footprint of a solid-state disk, besides its capacity.
+    pub ssd_base: f64,
+    /// Footprint of a TB of solid-state disk.
+    pub ssd_per_tb: f64,
+    /// Footprint of a hard disk, besides its capacity.
+    pub hdd_base: f64,
+    /// Footprint of a TB of hard disk.
+    pub hdd_per_tb: f64,
+    /// Footprint of a GPU, besides its memory.
+    pub gpu_base: f64,
+    /// Footprint of a GB of GPU memory.
+    pub gpu_per_gb: f64,
+    /// Footprint of the GPU models whose name includes the key, ignoring the case.
+    pub gpu_models: BTreeMap<String, f64>,
+}
+
+impl Default for Footprint {
+    fn default() -> Self {
+        Footprint::try_from(FootprintConfig::default()).expect("Valid default footprint")
+    }
+}
+
+impl TryFrom<FootprintConfig> for Footprint {
+    type Error = String;
+
+    fn try_from(config: FootprintConfig) -> Result<Self, Self::Error> {
+        if !(config.lifetime > 0.0 && config.lifetime.is_finite()) {
+            return Err(format!("Config 'Invalid lifetime' : {}", config.lifetime));
+        }
+        if !(config.hours_per_day > 0.0 && config.hours_per_day <= 24.0) {
+            return Err(format!(
+                "Config 'Invalid hours per day' : {}",
+                config.hours_per_day
+            ));
+        }
+        let factors = [
+            ("mainboard", config.mainboard),
+            ("dram_per_gb", config.dram_per_gb),
+            ("ssd_base", config.ssd_base),
+            ("ssd_per_tb", config.ssd_per_tb),
+            ("hdd_base", config.hdd_base),
+            ("hdd_per_tb", config.hdd_per_tb),
+            ("gpu_base", config.gpu_base),
+            ("gpu_per_gb", config.gpu_per_gb),
+        ];
+        for (name, value) in factors
+            .into_iter()
+            .chain(config.gpu_models.iter().map(|(k, v)| (k.as_str(), *v)))
+        {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(format!("Config 'Invalid footprint' : {name} = {value}"));
+            }
+        }
+        Ok(Footprint {
+            lifetime: config.lifetime,
+            hours_per_day: config.hours_per_day,
+            mainboard: config.mainboard,
+            dram_per_gb: config.dram_per_gb,
+            ssd_base: config.ssd_base,
+            ssd_per_tb: config.ssd_per_tb,
+            hdd_base: config.hdd_base,
+            hdd_per_tb: config.hdd_per_tb,
+            gpu_base: config.gpu_base,
+            gpu_per_gb: config.gpu_per_gb,
+            gpu_models: config.gpu_models,
+        })
+    }
+}
+
+/// Piece of hardware of the inventory, with its manufacturing footprint.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Item {
+    /// Kind of hardware : `mainboard`, `dram`, `ssd`, `hdd` or `gpu`.
+    pub kind: String,
+    /// Name of the piece, like a model or a device path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Capacity in GB, for the memories and disks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<f64>,
+    /// Manufacturing footprint in kgCO2e.
+    pub footprint: f64,
+}
+
+impl Footprint {
+    /// Map the hardware of the components retrieved by a snapshot to their footprints.
+    /// The memory is given by its modules when the DMI tables are readable, else by its total,
+    /// and the partitions of a disk are gathered, a partition mounted several times (bind mounts,
+    /// btrfs subvolumes) being counted once and the loop devices left aside.
+    ///
+    /// # Arguments
+    ///
+    /// - `snapshot` : Retrieved [`Snapshot`].
+    ///
+    /// # Returns
+    ///
+    /// The [`Item`] of each inventory component of the snapshot.
+    pub fn items(&self, snapshot: &Snapshot) -> Vec<(Component, Vec<Item>)> {
+        let mut res = Vec::new();
+
+        if let Some(board) = &snapshot.board {
+            let name = [&board.board_vendor, &board.board_name]
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ");
+            res.push((
+                Component::Board,
+                vec![Item {
+                    kind: "mainboard".to_string(),
+                    name: (!name.is_empty()).then_some(name),
+                    capacity: None,
+                    footprint: self.mainboard,
+                }],
+            ));
+        }
+
+        if let Some(memory) = &snapshot.memory {
+            let modules: Vec<_> = memory
+                .devices
+                .iter()
+                .flatten()
+                .filter_map(|device| Some((device.id.clone(), device.size? as f64 / 1024.0)))
+                .filter(|(_, size)| *size > 0.0)
+                .collect();
+            let capacities = if modules.is_empty() {
+                memory
+                    .global
+                    .ram_total
+                    .map(|total| vec![(None, total as f64 / 1e3)])
+                    .unwrap_or_default()
+            } else {
+                modules
+            };
+            res.push((
+                Component::Memory,
+                capacities
+                    .into_iter()
+                    .map(|(name, capacity)| Item {
+                        kind: "dram".to_string(),
+                        name,
+                        capacity: Some(capacity),
+                        footprint: self.dram_per_gb * capacity,
+                    })
+                    .collect(),
+            ));
+        }
+
+        if let Some(disks) = &snapshot.storage {
+            let mut devices: Vec<(String, bool, f64)> = Vec::new();
+            let mut partitions: Vec<&str> = Vec::new();
+            for disk in disks {
+                if !disk.name.starts_with("/dev/")
+                    || disk.name.starts_with("/dev/loop")
+                    || partitions.contains(&disk.name.as_str())
+                {
+                    continue;
+                }
+                partitions.push(&disk.name);
+                let path = DiskInfo::device_path(&disk.name);
+                let size = disk.space_total.unwrap_or(0) as f64;
+                match devices.iter_mut().find(|(name, _, _)| *name == path) {
+                    Some(device) => device.2 += size,
+                    None => devices.push((
+                        path,
+                        disk.kind.as_deref().is_some_and(|k| k.contains("HDD")),
+                        size,
+                    )),
+                }
+            }
+            res.push((
+                Component::Storage,
+                devices
+                    .into_iter()
+                    .map(|(name, hdd, capacity)| {
+                        let (kind, base, per_tb) = if hdd {
+                            ("hdd", self.hdd_base, self.hdd_per_tb)
+                        } else {
+                            ("ssd", self.ssd_base, self.ssd_per_tb)
+                        };
+                        Item {
+                            kind: kind.to_string(),
+                            name: Some(name),
+                            capacity: Some(capacity),
+                            footprint: base + per_tb * capacity / 1e3,
+                        }
+                    })
+                    .collect(),
+            ));
+        }
+
+        if let Some(gpus) = &snapshot.gpu {
+            res.push((
+                Component::Gpu,
+                gpus.iter()
+                    .map(|gpu| {
+                        let name = gpu.metrics.gpu_name.clone();
+                        let capacity = gpu.metrics.gpu_memory_total.map(|b| b as f64 / 1e9);
+                        Item {
+                            kind: "gpu".to_string(),
+                            footprint: self.gpu_footprint(name.as_deref(), capacity),
+                            name,
+                            capacity,
+                        }
+                    })
+                    .collect(),
+            ));
+        }
+        res
+    }
+
+    /// Footprint of a GPU, from its model when configured, the longest matching name winning,
+    /// else from its memory.
+    fn gpu_footprint(&self, name: Option<&str>, capacity: Option<f64>) -> f64 {
+        let name = name.unwrap_or_default().to_lowercase();
+        self.gpu_models
+            .iter()
+            .filter(|(model, _)| name.contains(&model.to_lowercase()))
+            .max_by_key(|(model, _)| model.len())
+            .map(|(_, footprint)| *footprint)
+            .unwrap_or_else(|| self.gpu_base + self.gpu_per_gb * capacity.unwrap_or(0.0))
+    }
+
+    /// Embodied emissions amortised by hour of use.
+    ///
+    /// # Arguments
+    ///
+    /// - `footprint` : Manufacturing footprint in kgCO2e.
+    ///
+    /// # Returns
+    ///
+    /// The emissions in gCO2e per hour.
+    pub fn rate(&self, footprint: f64) -> f64 {
+        footprint * 1e3 / (self.lifetime * DAYS_PER_YEAR * self.hours_per_day)
+    }
+}
+
+/// Embodied emissions of the hardware over the interval before a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Embodied {
+    /// Length of the interval in seconds.
+    pub interval: f64,
+    /// Manufacturing footprint of the hardware in kgCO2e.
+    pub footprint: f64,
+    /// Lifetime of the machine in years.
+    pub lifetime: f64,
+    /// Emissions amortised by hour of use in gCO2e/h.
+    pub rate: f64,
+    /// Emissions amortised over the interval in gCO2e.
+    pub emissions: f64,
+    /// Hardware inventory.
+    pub items: Vec<Item>,
+}
+
+/// Embodied emissions estimation, keeping the last inventory of each component,
+/// so that the hardware is accounted when its probe does not run.
+/// Clones share the same inventory.
+#[derive(Debug, Clone, Default)]
+pub struct EmbodiedMeter {
+    footprint: Option<Footprint>,
+    inventory: Arc<Mutex<HashMap<Component, Vec<Item>>>>,
+}
+
+impl EmbodiedMeter {
+    /// Create an embodied emissions estimation.
+    ///
+    /// # Arguments
+    ///
+    /// - `footprint` : [`Footprint`] of the hardware, none to disable the estimation.
+    pub fn new(footprint: Option<Footprint>) -> Self {
+        EmbodiedMeter {
+            footprint,
+            inventory: Arc::default(),
+        }
+    }
+
+    /// Check if no footprint is configured.
+    pub fn is_empty(&self) -> bool {
+        self.footprint.is_none()
+    }
+
+    /// Update the inventory with the hardware of a snapshot, and amortise its footprint.
+    ///
+    /// # Arguments
+    ///
+    /// - `snapshot` : Retrieved [`Snapshot`].
+    /// - `interval` : Length of the interval since the previous snapshot in seconds.
+    ///
+    /// # Returns
+    ///
+    /// The [`Embodied`] emissions over the interval, none without footprint or inventory.
+    pub fn amortise(&self, snapshot: &Snapshot, interval: f64) -> Option<Embodied> {
+        let footprint = self.footprint.as_ref()?;
+        let mut inventory = self.inventory.lock().unwrap_or_else(|e| e.into_inner());
+        inventory.extend(footprint.items(snapshot));
+
+        let items: Vec<Item> = INVENTORY
+            .iter()
+            .filter_map(|component| inventory.get(component))
+            .flatten()
+            .cloned()
+            .collect();
+        if items.is_empty() {
+            return None;
+        }
+        let total = items.iter().map(|item| item.footprint).sum();
+        let rate = footprint.rate(total);
+        Some(Embodied {
+            interval,
+            footprint: total,
+            lifetime: footprint.lifetime,
+            rate,
+            emissions: rate * interval / 3600.0,
+            items,
+        })
+    }
+}
+
+/// Store the embodied emissions of a snapshot in database.
+///
+/// # Arguments
+///
+/// - `timestamp` : Date of the snapshot.
+/// - `embodied` : [`Embodied`] emissions to insert.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert request failed.
+pub fn store_embodied(timestamp: &str, embodied: &Embodied) -> Result<(), Box<dyn StdError>> {
+    let conn = init_db(REQUEST)?;
+    conn.execute(
+        "INSERT INTO embodied_emissions (timestamp, interval_s, footprint_kgCO2e, lifetime_years,
+            rate_gCO2e_h, emissions_gCO2e)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            timestamp,
+            embodied.interval,
+            embodied.footprint,
+            embodied.lifetime,
+            embodied.rate,
+            embodied.emissions
+        ],
+    )?;
+    Ok(())
+}
+
+/// Operational and embodied emissions over a time range.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CarbonReport {
+    /// Lower bound of the time range, if given.
+    pub from: Option<String>,
+    /// Upper bound of the time range, if given.
+    pub to: Option<String>,
+    /// Emissions of the energy consumed in gCO2e.
+    pub operational: f64,
+    /// Emissions of the manufacturing amortised in gCO2e.
+    pub embodied: f64,
+    /// Sum of both in gCO2e.
+    pub total: f64,
+    /// Share of the embodied emissions, none without emissions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embodied_share: Option<f64>,
+}
+
+/// Sum the stored operational and embodied emissions.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `filter` : Time range of the emissions.
+///
+/// # Returns
+///
+/// - The [`CarbonReport`], with zero emissions for a table not stored.
+/// - An error if a time bound is invalid or a table can't be read.
+pub fn carbon_report(
+    conn: &Connection,
+    filter: &Filter,
+) -> Result<CarbonReport, Box<dyn StdError>> {
+    let tables = list_tables(conn)?;
+    let sum = |table: &str| -> Result<f64, Box<dyn StdError>> {
+        if !tables.iter().any(|t| t == table) {
+            return Ok(0.0);
+        }
+        let selection = select(conn, table, filter)?;
+        let request = format!(
+            "SELECT TOTAL(q.emissions_gCO2e) FROM ({}) q",
+            selection.query
+        );
+        Ok(conn.query_row(&request, &*selection.params(), |row| row.get(0))?)
+    };
+    let operational = sum("emissions")?;
+    let embodied = sum("embodied_emissions")?;
+    let total = operational + embodied;
+    Ok(CarbonReport {
+        from: filter.from.clone(),
+        to: filter.to.clone(),
+        operational,
+        embodied,
+        total,
+        embodied_share: (total > 0.0).then(|| embodied / total),
+    })
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use board::BoardInfo;
+    use memory::{MemData, MemInfo};
+    use userv_core::config::Config;
+
+    fn disk(name: &str, kind: &str, space: u64) -> DiskInfo {
+        DiskInfo {
+            bandwidth_read: None,
+            bandwidth_write: None,
+            energy_consumed: None,
+            file_mount: None,
+            file_system: None,
+            kind: Some(kind.to_string()),
+            name: name.to_string(),
+            space_available: None,
+            space_total: Some(space),
+            smart_info: None,
+        }
+    }
+
+    fn memory(total: u64) -> MemData {
+        MemData {
+            global: MemInfo {
+                bandwidth_read: None,
+                bandwidth_write: None,
+                ram_available: None,
+                ram_free: None,
+                ram_power_consumption: None,
+                ram_total: Some(total),
+                ram_used: None,
+                swap_free: None,
+                swap_total: None,
+                swap_used: None,
+            },
+            devices: None,
+            errors: None,
+        }
+    }
+
+    // Test `items` function gathering the partitions of a disk once and using the memory total
+    #[test]
+    fn test_items() {
+        let footprint = Footprint::default();
+        let snapshot = Snapshot {
+            board: Some(BoardInfo {
+                board_vendor: Some("Dell".to_string()),
+                board_name: Some("0H47HH".to_string()),
+                ..Default::default()
+            }),
+            memory: Some(memory(32_000)),
+            storage: Some(vec![
+                disk("/dev/nvme0n1p1", "SSD", 500),
+                disk("/dev/nvme0n1p2", "SSD", 1500),
+                disk("/dev/nvme0n1p2", "SSD", 1500),
+                disk("/dev/sda1", "HDD", 4000),
+                disk("/dev/loop0", "SSD", 1),
+                disk("overlay", "Unknown(-1)", 100),
+            ]),
+            ..Default::default()
+        };
+
+        let res = footprint.items(&snapshot);
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[0].1[0].name.as_deref(), Some("Dell 0H47HH"));
+        assert_eq!(res[1].1[0].capacity, Some(32.0));
+        assert_eq!(res[1].1[0].footprint, 48.0);
+        let disks = &res[2].1;
+        assert_eq!(disks.len(), 2);
+        assert_eq!(disks[0].name.as_deref(), Some("/dev/nvme0n1"));
+        assert_eq!(disks[0].capacity, Some(2000.0));
+        assert!((disks[0].footprint - (6.34 + 2.0 * 45.4)).abs() < 1e-9);
+        assert_eq!((disks[1].kind.as_str(), disks[1].footprint), ("hdd", 31.1));
+    }
+
+    // Test `gpu_footprint` function preferring the longest configured model
+    #[test]
+    fn test_gpu_footprint() {
+        let footprint = Footprint {
+            gpu_models: BTreeMap::from([("A100".to_string(), 150.0), ("A10".to_string(), 60.0)]),
+            ..Default::default()
+        };
+        assert_eq!(
+            footprint.gpu_footprint(Some("NVIDIA A100-SXM4-80GB"), Some(80.0)),
+            150.0
+        );
+        assert_eq!(footprint.gpu_footprint(Some("NVIDIA A10"), None), 60.0);
+        assert_eq!(footprint.gpu_footprint(Some("Tesla T4"), Some(16.0)), 49.0);
+    }
+
+    // Test `amortise` function keeping the inventory of the components not retrieved
+    #[test]
+    fn test_amortise() {
+        let meter = EmbodiedMeter::new(Some(Footprint {
+            lifetime: 1.0,
+            ..Default::default()
+        }));
+        assert_eq!(meter.amortise(&Snapshot::default(), 60.0), None);
+
+        let snapshot = Snapshot {
+            memory: Some(memory(87_660)),
+            ..Default::default()
+        };
+        let res = meter.amortise(&snapshot, 3600.0).unwrap();
+        assert!((res.footprint - 131.49).abs() < 1e-9);
+        assert!((res.rate - 15.0).abs() < 1e-9);
+        assert!((res.emissions - 15.0).abs() < 1e-9);
+
+        let res = meter.amortise(&Snapshot::default(), 1800.0).unwrap();
+        assert_eq!(res.items.len(), 1);
+        assert!((res.emissions - 7.5).abs() < 1e-9);
+
+        assert!(EmbodiedMeter::default().amortise(&snapshot, 60.0).is_none());
+    }
+
+    // Test `Footprint` deserialization with defaults and invalid values
+    #[test]
+    fn test_footprint_config() {
+        let section = |section: &str| {
+            Config::parse(&format!("[embodied]\n{section}"))
+                .unwrap()
+                .section::<Option<Footprint>>("embodied")
+        };
+        let footprint = section("lifetime = 6\n[embodied.gpu_models]\nA100 = 150")
+            .unwrap()
+            .unwrap();
+        assert_eq!(footprint.lifetime, 6.0);
+        assert_eq!(footprint.mainboard, 66.1);
+        assert_eq!(footprint.gpu_models["A100"], 150.0);
+        for invalid in ["lifetime = 0", "hours_per_day = 25", "dram_per_gb = -1"] {
+            assert!(section(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    // Test `carbon_report` function summing both emissions tables
+    #[test]
+    fn test_carbon_report() {
+        let conn = Connection::open_in_memory().unwrap();
+        let res = carbon_report(&conn, &Filter::default()).unwrap();
+        assert_eq!((res.total, res.embodied_share), (0.0, None));
+
+        conn.execute_batch(REQUEST).unwrap();
+        conn.execute_batch(crate::carbon::REQUEST).unwrap();
+        for (timestamp, operational, embodied) in [
+            ("2025-01-01T00:00:00.000Z", 30.0, 10.0),
+            ("2025-01-02T00:00:00.000Z", 50.0, 10.0),
+        ] {
+            conn.execute(
+                "INSERT INTO emissions (timestamp, interval_s, energy_J, intensity_gCO2e_kWh,
+                    emissions_gCO2e, source) VALUES (?1, 3600, 0, 0, ?2, 'static')",
+                params![timestamp, operational],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO embodied_emissions (timestamp, interval_s, footprint_kgCO2e,
+                    lifetime_years, rate_gCO2e_h, emissions_gCO2e) VALUES (?1, 3600, 0, 4, 0, ?2)",
+                params![timestamp, embodied],
+            )
+            .unwrap();
+        }
+
+        let res = carbon_report(&conn, &Filter::default()).unwrap();
+        assert_eq!(
+            (res.operational, res.embodied, res.total),
+            (80.0, 20.0, 100.0)
+        );
+        assert_eq!(res.embodied_share, Some(0.2));
+
+        let filter = Filter {
+            from: Some("2025-01-02".to_string()),
+            ..Default::default()
+        };
+        let res = carbon_report(&conn, &filter).unwrap();
+        assert_eq!((res.operational, res.embodied), (50.0, 10.0));
+    }
+}
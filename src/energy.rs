@@ -0,0 +1,573 @@
+//! # File energy
+//!
+//! This file provides the whole machine energy accounting, normalising the power and energy
+//! figures of each probe to watts and joules over the interval between two snapshots.
+
+use rusqlite::params;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use userv_core::core::init_db;
+
+use cpu::get_rapl_zone_name;
+use network::NetworkType;
+
+use crate::{component::Component, snapshot::Snapshot, tdp::ZONE};
+
+/// Request creating the table of the energy summaries.
+pub const REQUEST: &str = "CREATE TABLE IF NOT EXISTS energy_summary (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    interval_s REAL NOT NULL,
+    cpu_power_W REAL,
+    cpu_energy_J REAL,
+    cpu_source TEXT,
+    gpu_power_W REAL,
+    gpu_energy_J REAL,
+    gpu_source TEXT,
+    memory_power_W REAL,
+    memory_energy_J REAL,
+    memory_source TEXT,
+    network_power_W REAL,
+    network_energy_J REAL,
+    network_source TEXT,
+    storage_power_W REAL,
+    storage_energy_J REAL,
+    storage_source TEXT,
+    total_power_W REAL NOT NULL,
+    total_energy_J REAL NOT NULL,
+    measured_ratio REAL
+);";
+
+/// Components accounted, in the order of the `energy_summary` columns.
+pub const COMPONENTS: [Component; 5] = [
+    Component::Cpu,
+    Component::Gpu,
+    Component::Memory,
+    Component::Net,
+    Component::Storage,
+];
+
+/// Origin of a power or energy figure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// Read from a hardware counter (RAPL, NVML).
+    Measured,
+    /// Computed by a model from the activity of the component.
+    Estimated,
+}
+
+impl Source {
+    /// Name of the source stored in database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::Measured => "measured",
+            Source::Estimated => "estimated",
+        }
+    }
+}
+
+/// Power or energy figure of an entity, as given by a probe.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Reading {
+    pub(crate) component: Component,
+    pub(crate) entity: String,
+    pub(crate) source: Source,
+    /// Instantaneous power in W, if the probe gives one.
+    pub(crate) power: Option<f64>,
+    /// Cumulative energy counter in J, if the probe gives one.
+    pub(crate) counter: Option<f64>,
+    /// Constant power in W added over the interval, like the idle power of an interface.
+    pub(crate) idle: f64,
+}
+
+/// Power and energy of a component over an interval.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComponentEnergy {
+    /// Component concerned.
+    pub component: Component,
+    /// Average power in W.
+    pub power: f64,
+    /// Energy consumed in J.
+    pub energy: f64,
+    /// Origin of the figures, estimated if any entity is.
+    pub source: Source,
+}
+
+/// Energy consumed by the machine over the interval before a snapshot.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnergySummary {
+    /// Length of the interval in seconds.
+    pub interval: f64,
+    /// Breakdown by component, for the components with a figure.
+    pub components: Vec<ComponentEnergy>,
+    /// Average power of the machine in W.
+    pub total_power: f64,
+    /// Energy consumed by the machine in J.
+    pub total_energy: f64,
+    /// Share of the energy read from hardware counters, none without energy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub measured_ratio: Option<f64>,
+}
+
+/// Previous cumulative counters by component and entity, and date of the previous snapshot.
+#[derive(Debug, Default)]
+struct Counters {
+    at: Option<Instant>,
+    values: HashMap<(Component, String), f64>,
+}
+
+/// Energy accounting, keeping the cumulative counters between snapshots.
+/// Clones share the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct EnergyMeter {
+    counters: Arc<Mutex<Counters>>,
+}
+
+/// Component measured by a RAPL zone, from its name.
+/// The core, uncore and psys zones, included in a package or covering the whole platform,
+/// have none. A zone without name is a package when at the top level.
+///
+/// # Arguments
+///
+/// - `zone` : Zone folder name, or the zone of the TDP estimate.
+fn rapl_component(zone: &str) -> Option<Component> {
+    if zone == ZONE {
+        return Some(Component::Cpu);
+    }
+    match get_rapl_zone_name(zone).as_deref() {
+        Some("dram") => Some(Component::Memory),
+        Some(name) if name.starts_with("package") => Some(Component::Cpu),
+        Some(_) => None,
+        None => (zone.matches(':').count() <= 1).then_some(Component::Cpu),
+    }
+}
+
+/// Extract the power and energy figures of each probe of a snapshot, normalised to W and J.
+/// The RAPL zones other than the packages and the DRAM, the estimated memory power when
+/// the DRAM is measured, the virtual network interfaces, whose estimate is a CPU overhead,
+/// and the partitions of a disk already read are left aside to not be counted twice.
+/// The idle power of the physical network interfaces is added to their traffic energy.
+pub(crate) fn readings(snapshot: &Snapshot) -> Vec<Reading> {
+    let mut readings = Vec::new();
+
+    if let Some(cpu) = &snapshot.cpu {
+        for (zone, power) in cpu.power.iter().flat_map(|p| &p.powers) {
+            if let Some(component) = rapl_component(zone) {
+                readings.push(Reading {
+                    component,
+                    entity: zone.clone(),
+                    source: if zone == ZONE {
+                        Source::Estimated
+                    } else {
+                        Source::Measured
+                    },
+                    power: Some(*power),
+                    counter: None,
+                    idle: 0.0,
+                });
+            }
+        }
+    }
+
+    for (index, gpu) in snapshot.gpu.iter().flatten().enumerate() {
+        let g = &gpu.metrics;
+        readings.push(Reading {
+            component: Component::Gpu,
+            entity: g.gpu_bus_id.clone().unwrap_or_else(|| index.to_string()),
+            source: Source::Measured,
+            power: g.gpu_power_consumption.map(|mw| mw as f64 / 1e3),
+            counter: g.gpu_energy_consumption.map(|mj| mj / 1e3),
+            idle: 0.0,
+        });
+    }
+
+    if let Some(memory) = &snapshot.memory
+        && readings.iter().all(|r| r.component != Component::Memory)
+    {
+        readings.push(Reading {
+            component: Component::Memory,
+            entity: String::new(),
+            source: Source::Estimated,
+            power: memory.global.ram_power_consumption,
+            counter: None,
+            idle: 0.0,
+        });
+    }
+
+    for interface in snapshot.network.iter().flatten() {
+        if matches!(
+            interface.network_type,
+            NetworkType::Virtual | NetworkType::Loopback
+        ) {
+            continue;
+        }
+        readings.push(Reading {
+            component: Component::Net,
+            entity: interface.name.clone(),
+            source: Source::Estimated,
+            power: None,
+            counter: interface.energy_consumed.map(|wh| wh * 3600.0),
+            idle: interface.network_type.idle_power(),
+        });
+    }
+
+    for disk in snapshot.storage.iter().flatten() {
+        if readings
+            .iter()
+            .any(|r| r.component == Component::Storage && r.entity == disk.name)
+        {
+            continue;
+        }
+        readings.push(Reading {
+            component: Component::Storage,
+            entity: disk.name.clone(),
+            source: Source::Estimated,
+            power: None,
+            counter: disk.energy_consumed,
+            idle: 0.0,
+        });
+    }
+    readings
+}
+
+impl EnergyMeter {
+    /// Account the energy of a snapshot over the interval since the previous one.
+    ///
+    /// # Arguments
+    ///
+    /// - `snapshot` : Retrieved [`Snapshot`].
+    /// - `now` : Instant of the snapshot.
+    /// - `nominal` : Interval assumed for the first snapshot.
+    ///
+    /// # Returns
+    ///
+    /// The [`EnergySummary`], none if no probe gives a power or energy figure.
+    pub fn measure(
+        &self,
+        snapshot: &Snapshot,
+        now: Instant,
+        nominal: Duration,
+    ) -> Option<EnergySummary> {
+        self.account(readings(snapshot), now, nominal)
+    }
+
+    /// Account the energy of the readings of a snapshot.
+    /// The energy of an entity comes from the increase of its counter when it has a previous
+    /// value, a decrease being taken as a reset, else from its power over the interval,
+    /// plus its idle power over the interval.
+    /// The power of an entity with a counter is its energy over the interval.
+    pub(crate) fn account(
+        &self,
+        readings: Vec<Reading>,
+        now: Instant,
+        nominal: Duration,
+    ) -> Option<EnergySummary> {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let interval = counters
+            .at
+            .replace(now)
+            .map(|at| now.duration_since(at))
+            .filter(|interval| !interval.is_zero())
+            .unwrap_or(nominal)
+            .as_secs_f64();
+
+        let mut components: Vec<ComponentEnergy> = Vec::new();
+        for reading in readings {
+            let delta = reading.counter.and_then(|counter| {
+                let previous = counters
+                    .values
+                    .insert((reading.component, reading.entity.clone()), counter)?;
+                (counter >= previous).then_some(counter - previous)
+            });
+            let energy = match (delta, reading.power) {
+                (Some(delta), _) => delta,
+                (None, Some(power)) => power * interval,
+                (None, None) if reading.idle > 0.0 => 0.0,
+                (None, None) => continue,
+            } + reading.idle * interval;
+            let power = match (delta, reading.power) {
+                (Some(_), _) if interval > 0.0 => energy / interval,
+                (_, Some(power)) => power + reading.idle,
+                _ => reading.idle,
+            };
+
+            match components
+                .iter_mut()
+                .find(|c| c.component == reading.component)
+            {
+                Some(component) => {
+                    component.power += power;
+                    component.energy += energy;
+                    if reading.source == Source::Estimated {
+                        component.source = Source::Estimated;
+                    }
+                }
+                None => components.push(ComponentEnergy {
+                    component: reading.component,
+                    power,
+                    energy,
+                    source: reading.source,
+                }),
+            }
+        }
+
+        if components.is_empty() {
+            return None;
+        }
+        components.sort_by_key(|c| COMPONENTS.iter().position(|k| *k == c.component));
+        let total_energy: f64 = components.iter().map(|c| c.energy).sum();
+        let measured: f64 = components
+            .iter()
+            .filter(|c| c.source == Source::Measured)
+            .map(|c| c.energy)
+            .sum();
+        Some(EnergySummary {
+            interval,
+            total_power: components.iter().map(|c| c.power).sum(),
+            total_energy,
+            measured_ratio: (total_energy > 0.0).then(|| measured / total_energy),
+            components,
+        })
+    }
+}
+
+/// Store an energy summary in database.
+///
+/// # Arguments
+///
+/// - `timestamp` : Date of the snapshot.
+/// - `summary` : [`EnergySummary`] to insert.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert request failed.
+pub fn store_energy(timestamp: &str, summary: &EnergySummary) -> Result<(), Box<dyn StdError>> {
+    let conn = init_db(REQUEST)?;
+    let part = |kind: Component| {
+        let c = summary.components.iter().find(|c| c.component == kind);
+        (
+            c.map(|c| c.power),
+            c.map(|c| c.energy),
+            c.map(|c| c.source.as_str()),
+        )
+    };
+    let (cpu, gpu, memory, network, storage) = (
+        part(Component::Cpu),
+        part(Component::Gpu),
+        part(Component::Memory),
+        part(Component::Net),
+        part(Component::Storage),
+    );
+    conn.execute(
+        "INSERT INTO energy_summary (timestamp, interval_s,
+            cpu_power_W, cpu_energy_J, cpu_source, gpu_power_W, gpu_energy_J, gpu_source,
+            memory_power_W, memory_energy_J, memory_source, network_power_W, network_energy_J, network_source,
+            storage_power_W, storage_energy_J, storage_source, total_power_W, total_energy_J, measured_ratio)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
+        params![
+            timestamp,
+            summary.interval,
+            cpu.0,
+            cpu.1,
+            cpu.2,
+            gpu.0,
+            gpu.1,
+            gpu.2,
+            memory.0,
+            memory.1,
+            memory.2,
+            network.0,
+            network.1,
+            network.2,
+            storage.0,
+            storage.1,
+            storage.2,
+            summary.total_power,
+            summary.total_energy,
+            summary.measured_ratio
+        ],
+    )?;
+    Ok(())
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use network::NetworkInterface;
+    use storage::DiskInfo;
+
+    fn reading(component: Component, power: Option<f64>, counter: Option<f64>) -> Reading {
+        Reading {
+            component,
+            entity: "0".to_string(),
+            source: if component == Component::Cpu || component == Component::Gpu {
+                Source::Measured
+            } else {
+                Source::Estimated
+            },
+            power,
+            counter,
+            idle: 0.0,
+        }
+    }
+
+    fn interface(name: &str, network_type: NetworkType, energy: f64) -> NetworkInterface {
+        NetworkInterface {
+            address_mac: None,
+            energy_consumed: Some(energy),
+            name: name.to_string(),
+            network_type,
+            received: None,
+            transmitted: None,
+            errors_received: None,
+            errors_transmitted: None,
+            packet_received: None,
+            packet_transmitted: None,
+            drops_received: None,
+            drops_transmitted: None,
+        }
+    }
+
+    fn disk(name: &str, mount: &str, energy: f64) -> DiskInfo {
+        DiskInfo {
+            bandwidth_read: None,
+            bandwidth_write: None,
+            energy_consumed: Some(energy),
+            file_mount: Some(mount.to_string()),
+            file_system: None,
+            kind: None,
+            name: name.to_string(),
+            space_available: None,
+            space_total: None,
+            smart_info: None,
+        }
+    }
+
+    // Test `readings` function normalising units and skipping double counted entities
+    #[test]
+    fn test_readings() {
+        let snapshot = Snapshot {
+            network: Some(vec![
+                interface("eth0", NetworkType::Ethernet, 0.5),
+                interface("docker0", NetworkType::Virtual, 2.0),
+            ]),
+            storage: Some(vec![
+                disk("/dev/sda1", "/", 10.0),
+                disk("/dev/sda1", "/home", 10.0),
+            ]),
+            ..Default::default()
+        };
+        let res = readings(&snapshot);
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].entity, "eth0");
+        assert_eq!(res[0].counter, Some(1800.0));
+        assert_eq!(res[0].idle, 2.0);
+        assert_eq!(res[1].component, Component::Storage);
+        assert_eq!(res[1].counter, Some(10.0));
+    }
+
+    // Test `rapl_component` function on the estimate and unnamed zones
+    #[test]
+    fn test_rapl_component() {
+        assert_eq!(rapl_component(ZONE), Some(Component::Cpu));
+        assert_eq!(rapl_component("unknown-rapl:0"), Some(Component::Cpu));
+        assert_eq!(rapl_component("unknown-rapl:0:1"), None);
+    }
+
+    // Test `account` function adding the idle power to a counter
+    #[test]
+    fn test_account_idle() {
+        let meter = EnergyMeter::default();
+        let start = Instant::now();
+        let nominal = Duration::from_secs(10);
+        let nic = |counter: f64| Reading {
+            idle: 2.0,
+            ..reading(Component::Net, None, Some(counter))
+        };
+
+        let res = meter.account(vec![nic(100.0)], start, nominal).unwrap();
+        assert_eq!(res.total_energy, 20.0);
+        assert_eq!(res.total_power, 2.0);
+        let res = meter
+            .account(vec![nic(130.0)], start + Duration::from_secs(10), nominal)
+            .unwrap();
+        assert_eq!(res.total_energy, 50.0);
+        assert_eq!(res.total_power, 5.0);
+    }
+
+    // Test `account` function with powers and counters over two snapshots
+    #[test]
+    fn test_account() {
+        let meter = EnergyMeter::default();
+        let start = Instant::now();
+        let nominal = Duration::from_secs(1);
+
+        let res = meter
+            .account(
+                vec![
+                    reading(Component::Cpu, Some(10.0), None),
+                    reading(Component::Net, None, Some(3600.0)),
+                ],
+                start,
+                nominal,
+            )
+            .unwrap();
+        assert_eq!(res.interval, 1.0);
+        assert_eq!(res.components.len(), 1);
+        assert_eq!(res.total_energy, 10.0);
+        assert_eq!(res.measured_ratio, Some(1.0));
+
+        let res = meter
+            .account(
+                vec![
+                    reading(Component::Net, None, Some(3960.0)),
+                    reading(Component::Cpu, Some(20.0), None),
+                ],
+                start + Duration::from_secs(2),
+                nominal,
+            )
+            .unwrap();
+        assert_eq!(res.interval, 2.0);
+        assert_eq!(res.components[0].component, Component::Cpu);
+        assert_eq!(res.components[0].energy, 40.0);
+        assert_eq!(res.components[1].power, 180.0);
+        assert_eq!(res.components[1].source, Source::Estimated);
+        assert_eq!(res.total_power, 200.0);
+        assert_eq!(res.total_energy, 400.0);
+        assert_eq!(res.measured_ratio, Some(0.1));
+    }
+
+    // Test `account` function preferring counters, and falling back to power after a reset
+    #[test]
+    fn test_account_counter_reset() {
+        let meter = EnergyMeter::default();
+        let start = Instant::now();
+        let nominal = Duration::from_secs(1);
+        let gpu = |power, counter| vec![reading(Component::Gpu, Some(power), Some(counter))];
+
+        let res = meter.account(gpu(50.0, 1000.0), start, nominal).unwrap();
+        assert_eq!(res.total_energy, 50.0);
+        let res = meter
+            .account(gpu(50.0, 1300.0), start + Duration::from_secs(5), nominal)
+            .unwrap();
+        assert_eq!(res.total_energy, 300.0);
+        assert_eq!(res.total_power, 60.0);
+        let res = meter
+            .account(gpu(40.0, 10.0), start + Duration::from_secs(10), nominal)
+            .unwrap();
+        assert_eq!(res.total_energy, 200.0);
+        assert_eq!(res.total_power, 40.0);
+
+        assert_eq!(meter.account(Vec::new(), start, nominal), None);
+    }
+}
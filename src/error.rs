@@ -0,0 +1,38 @@
+//! # File error
+//!
+//! This file provides the typed errors returned by the library.
+
+use std::fmt;
+
+use crate::component::Component;
+
+/// Errors returned by the collection of the machine data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// No component selected for the collection.
+    NoComponent,
+    /// A probe failed to retrieve the data of its component.
+    Probe {
+        /// Component of the failed probe.
+        component: Component,
+        /// Error message returned by the probe.
+        message: String,
+    },
+    /// The collected data can't be stored in database.
+    Storage(String),
+    /// The collected data can't be serialized.
+    Serialization(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoComponent => write!(f, "Arguments 'No probe specified'"),
+            Error::Probe { component, message } => write!(f, "[{}] {message}", component.header()),
+            Error::Storage(message) => write!(f, "DataBase '{message}'"),
+            Error::Serialization(message) => write!(f, "Data 'Serialization failed' : {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,29 @@
+//! # Build script
+//!
+//! Generate the C header of the library in the build directory. The header versioned in
+//! `include/userv.h` is only replaced when `USERV_UPDATE_HEADER` is set, the tests checking
+//! that it matches the generated one.
+
+use std::{env, fs::copy, path::Path};
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = env::var("OUT_DIR").unwrap();
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=USERV_UPDATE_HEADER");
+
+    let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml"))
+        .expect("Invalid cbindgen configuration");
+    let header = Path::new(&out_dir).join("userv.h");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("Unable to generate C header")
+        .write_to_file(&header);
+
+    if env::var_os("USERV_UPDATE_HEADER").is_some() {
+        copy(&header, format!("{crate_dir}/include/userv.h")).expect("Unable to update C header");
+    }
+}
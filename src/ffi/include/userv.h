@@ -0,0 +1,130 @@
+/* Userv C ABI, generated by cbindgen from src/ffi. Do not edit by hand. */
+
+#ifndef USERV_H
+#define USERV_H
+
+/* Generated with cbindgen:0.29.4 */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// Major version of the C ABI, incremented on incompatible changes.
+#define USERV_ABI_VERSION_MAJOR 1
+
+// Minor version of the C ABI, incremented on compatible additions.
+#define USERV_ABI_VERSION_MINOR 0
+
+// Status codes returned by the functions of the library.
+typedef enum UservStatus {
+  // Success.
+  USERV_STATUS_OK = 0,
+  // No component selected for the collection.
+  USERV_STATUS_NO_COMPONENT = 1,
+  // At least one probe failed, the others data are still available.
+  USERV_STATUS_PROBE = 2,
+  // The collected data can't be stored in database.
+  USERV_STATUS_STORAGE = 3,
+  // The collected data can't be serialized.
+  USERV_STATUS_SERIALIZATION = 4,
+  // A pointer is null or a string is not valid UTF-8, or a component is unknown.
+  USERV_STATUS_INVALID_ARGUMENT = 5,
+  // No snapshot has been collected by the handle.
+  USERV_STATUS_NO_SNAPSHOT = 6,
+  // The requested metric is not in the snapshot.
+  USERV_STATUS_NOT_FOUND = 7,
+  // The given buffer is too small, the required size is returned.
+  USERV_STATUS_BUFFER_TOO_SMALL = 8,
+  // The library panicked, the handle should be released.
+  USERV_STATUS_PANIC = 9,
+} UservStatus;
+
+// Opaque handle of a collector, keeping its last snapshot and error message.
+typedef struct UservCollector UservCollector;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Version of the userv library, as a static null-terminated string.
+const char *userv_version(void);
+
+// Open a collector handle, with the default configuration of the collector.
+// The configuration file is not read, so no exec probe is configured
+// and the "exec" component gives no data.
+//
+// - `persist` : Store each snapshot in the SQLite database.
+// - `handle` : Output pointer receiving the handle, to release with `userv_collector_free`.
+//
+// # Safety
+//
+// `handle` must be null or valid for writes.
+enum UservStatus userv_collector_new(bool persist, struct UservCollector **handle);
+
+// Release a collector handle and its snapshot. A null handle is ignored.
+//
+// # Safety
+//
+// `handle` must be null or returned by `userv_collector_new`, and not used afterwards.
+void userv_collector_free(struct UservCollector *handle);
+
+// Collect a snapshot of the given components, replacing the previous one.
+//
+// - `components` : Comma separated labels among "board", "cgroup", "cpu", "exec", "gpu",
+//   "network", "memory", "storage" and "system", or null for all of them, "cgroup"
+//   walking the whole `/sys/fs/cgroup` tree.
+//
+// Returns `USERV_STATUS_PROBE` if a probe failed, the data of the others being kept.
+//
+// # Safety
+//
+// `handle` must be null or a live handle, `components` null or a null-terminated string.
+enum UservStatus userv_collector_snapshot(struct UservCollector *handle, const char *components);
+
+// Read a value of the last snapshot.
+//
+// - `probe` : Label of the component, like "cpu" or "network".
+// - `entity` : Entity of the metric (interface, disk, pid...), or null if the component has only one.
+// - `metric` : Name of the metric, as stored in database without unit, like "power".
+// - `value` : Output pointer receiving the value.
+//
+// # Safety
+//
+// `handle` must be null or a live handle, the names null or null-terminated strings,
+// `value` null or valid for writes.
+enum UservStatus userv_collector_value(struct UservCollector *handle,
+                                       const char *probe,
+                                       const char *entity,
+                                       const char *metric,
+                                       double *value);
+
+// Write the last snapshot as a null-terminated JSON string.
+//
+// - `buffer` : Output buffer, may be null to only query the required size.
+// - `size` : Size of the buffer in bytes.
+// - `required` : Output pointer receiving the size needed, null terminator included. May be null.
+//
+// Returns `USERV_STATUS_BUFFER_TOO_SMALL` if the JSON does not fit in the buffer.
+//
+// # Safety
+//
+// `handle` must be null or a live handle, `buffer` null or valid for `size` bytes,
+// `required` null or valid for writes.
+enum UservStatus userv_collector_json(struct UservCollector *handle,
+                                      char *buffer,
+                                      size_t size,
+                                      size_t *required);
+
+// Message of the last error of the handle, or null if the last call succeeded.
+// The string remains valid until the next call on the handle.
+//
+// # Safety
+//
+// `handle` must be null or a live handle.
+const char *userv_collector_error(const struct UservCollector *handle);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* USERV_H */
@@ -0,0 +1,442 @@
+//! # Userv C ABI
+//!
+//! This library exposes the userv collector to C and C++ programs through a
+//! stable ABI. The header `include/userv.h` is generated by the build script.
+//! A panic in the library is caught at the boundary and returned as an error status.
+//!
+//! A program opens a collector handle, triggers a snapshot, then reads its values
+//! by probe, entity and metric name, or as a JSON buffer. Each function returns an
+//! [`UservStatus`] code, the message of the last error being kept by the handle.
+
+use std::{
+    any::Any,
+    ffi::{CStr, CString, c_char},
+    panic::{AssertUnwindSafe, catch_unwind},
+    ptr,
+};
+
+use userv::{Collector, CollectorConfig, Component, Error, Snapshot};
+
+/// Major version of the C ABI, incremented on incompatible changes.
+pub const USERV_ABI_VERSION_MAJOR: u32 = 1;
+/// Minor version of the C ABI, incremented on compatible additions.
+pub const USERV_ABI_VERSION_MINOR: u32 = 0;
+
+/// Status codes returned by the functions of the library.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UservStatus {
+    /// Success.
+    Ok = 0,
+    /// No component selected for the collection.
+    NoComponent = 1,
+    /// At least one probe failed, the others data are still available.
+    Probe = 2,
+    /// The collected data can't be stored in database.
+    Storage = 3,
+    /// The collected data can't be serialized.
+    Serialization = 4,
+    /// A pointer is null or a string is not valid UTF-8, or a component is unknown.
+    InvalidArgument = 5,
+    /// No snapshot has been collected by the handle.
+    NoSnapshot = 6,
+    /// The requested metric is not in the snapshot.
+    NotFound = 7,
+    /// The given buffer is too small, the required size is returned.
+    BufferTooSmall = 8,
+    /// The library panicked, the handle should be released.
+    Panic = 9,
+}
+
+impl From<&Error> for UservStatus {
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::NoComponent => UservStatus::NoComponent,
+            Error::Probe { .. } => UservStatus::Probe,
+            Error::Storage(_) => UservStatus::Storage,
+            Error::Serialization(_) => UservStatus::Serialization,
+        }
+    }
+}
+
+/// Opaque handle of a collector, keeping its last snapshot and error message.
+pub struct UservCollector {
+    collector: Collector,
+    snapshot: Option<Snapshot>,
+    error: Option<CString>,
+}
+
+impl UservCollector {
+    /// Keep the message of an error and return its status.
+    fn fail(&mut self, status: UservStatus, message: impl ToString) -> UservStatus {
+        self.error = CString::new(message.to_string().replace('\0', " ")).ok();
+        status
+    }
+}
+
+/// Message of a panic payload.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// Run the body of an entry point, catching a panic so that it does not unwind in the
+/// calling program. A caught panic is kept as the error message of the handle, which
+/// must be null or the live handle given to the entry point.
+fn guard(handle: *mut UservCollector, body: impl FnOnce() -> UservStatus) -> UservStatus {
+    match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(status) => status,
+        Err(payload) => {
+            let message = format!("Process 'Panic' : {}", panic_message(payload.as_ref()));
+            match unsafe { handle.as_mut() } {
+                Some(handle) => handle.fail(UservStatus::Panic, message),
+                None => UservStatus::Panic,
+            }
+        }
+    }
+}
+
+/// Read an optional C string.
+///
+/// # Returns
+///
+/// - `None` if the pointer is null.
+/// - An error if the string is not valid UTF-8.
+unsafe fn read_str<'a>(value: *const c_char) -> Result<Option<&'a str>, ()> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    unsafe { CStr::from_ptr(value) }
+        .to_str()
+        .map(Some)
+        .map_err(|_| ())
+}
+
+/// Parse a comma separated list of component labels.
+fn parse_components(list: &str) -> Result<Vec<Component>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(|label| {
+            Component::from_label(label)
+                .ok_or_else(|| format!("Arguments 'Unknown component' : {label}"))
+        })
+        .collect()
+}
+
+/// Version of the userv library, as a static null-terminated string.
+#[unsafe(no_mangle)]
+pub extern "C" fn userv_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+/// Open a collector handle, with the default configuration of the collector.
+/// The configuration file is not read, so no exec probe is configured
+/// and the "exec" component gives no data.
+///
+/// - `persist` : Store each snapshot in the SQLite database.
+/// - `handle` : Output pointer receiving the handle, to release with `userv_collector_free`.
+///
+/// # Safety
+///
+/// `handle` must be null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn userv_collector_new(
+    persist: bool,
+    handle: *mut *mut UservCollector,
+) -> UservStatus {
+    guard(ptr::null_mut(), || {
+        if handle.is_null() {
+            return UservStatus::InvalidArgument;
+        }
+        let collector = Box::new(UservCollector {
+            collector: Collector::new(CollectorConfig {
+                persist,
+                ..Default::default()
+            }),
+            snapshot: None,
+            error: None,
+        });
+        unsafe { *handle = Box::into_raw(collector) };
+        UservStatus::Ok
+    })
+}
+
+/// Release a collector handle and its snapshot. A null handle is ignored.
+///
+/// # Safety
+///
+/// `handle` must be null or returned by `userv_collector_new`, and not used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn userv_collector_free(handle: *mut UservCollector) {
+    if !handle.is_null() {
+        // The handle is released, so a panic in its drop can't be reported.
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(unsafe { Box::from_raw(handle) })));
+    }
+}
+
+/// Collect a snapshot of the given components, replacing the previous one.
+///
+/// - `components` : Comma separated labels among "board", "cgroup", "cpu", "exec", "gpu",
+///   "network", "memory", "storage" and "system", or null for all of them, "cgroup"
+///   walking the whole `/sys/fs/cgroup` tree.
+///
+/// Returns `USERV_STATUS_PROBE` if a probe failed, the data of the others being kept.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, `components` null or a null-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn userv_collector_snapshot(
+    handle: *mut UservCollector,
+    components: *const c_char,
+) -> UservStatus {
+    guard(handle, || {
+        let Some(handle) = (unsafe { handle.as_mut() }) else {
+            return UservStatus::InvalidArgument;
+        };
+        let components = match unsafe { read_str(components) } {
+            Ok(Some(list)) => match parse_components(list) {
+                Ok(components) => components,
+                Err(e) => return handle.fail(UservStatus::InvalidArgument, e),
+            },
+            Ok(None) => Component::ALL.to_vec(),
+            Err(()) => {
+                return handle.fail(UservStatus::InvalidArgument, "Arguments 'Invalid UTF-8'");
+            }
+        };
+
+        match handle.collector.collect(&components) {
+            Ok(snapshot) => {
+                let failure = snapshot.errors.iter().next().map(|(component, message)| {
+                    format!("[{}] {message}", component.to_uppercase())
+                });
+                handle.snapshot = Some(snapshot);
+                match failure {
+                    Some(message) => handle.fail(UservStatus::Probe, message),
+                    None => {
+                        handle.error = None;
+                        UservStatus::Ok
+                    }
+                }
+            }
+            Err(e) => handle.fail(UservStatus::from(&e), e),
+        }
+    })
+}
+
+/// Read a value of the last snapshot.
+///
+/// - `probe` : Label of the component, like "cpu" or "network".
+/// - `entity` : Entity of the metric (interface, disk, pid...), or null if the component has only one.
+/// - `metric` : Name of the metric, as stored in database without unit, like "power".
+/// - `value` : Output pointer receiving the value.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, the names null or null-terminated strings,
+/// `value` null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn userv_collector_value(
+    handle: *mut UservCollector,
+    probe: *const c_char,
+    entity: *const c_char,
+    metric: *const c_char,
+    value: *mut f64,
+) -> UservStatus {
+    guard(handle, || {
+        let Some(handle) = (unsafe { handle.as_mut() }) else {
+            return UservStatus::InvalidArgument;
+        };
+        handle.error = None;
+        let names = unsafe { (read_str(probe), read_str(entity), read_str(metric)) };
+        let (Ok(Some(probe)), Ok(entity), Ok(Some(metric))) = names else {
+            return handle.fail(UservStatus::InvalidArgument, "Arguments 'Invalid name'");
+        };
+        if value.is_null() {
+            return handle.fail(
+                UservStatus::InvalidArgument,
+                "Arguments 'Null value pointer'",
+            );
+        }
+        let Some(snapshot) = &handle.snapshot else {
+            return handle.fail(UservStatus::NoSnapshot, "Data 'No snapshot collected'");
+        };
+
+        let found = snapshot
+            .metrics()
+            .into_iter()
+            .find(|m| m.component == probe && m.name == metric && m.entity.as_deref() == entity);
+        match found {
+            Some(m) => {
+                unsafe { *value = m.value };
+                UservStatus::Ok
+            }
+            None => handle.fail(
+                UservStatus::NotFound,
+                format!("Data 'Metric not found' : {probe}.{metric} {entity:?}"),
+            ),
+        }
+    })
+}
+
+/// Write the last snapshot as a null-terminated JSON string.
+///
+/// - `buffer` : Output buffer, may be null to only query the required size.
+/// - `size` : Size of the buffer in bytes.
+/// - `required` : Output pointer receiving the size needed, null terminator included. May be null.
+///
+/// Returns `USERV_STATUS_BUFFER_TOO_SMALL` if the JSON does not fit in the buffer.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle, `buffer` null or valid for `size` bytes,
+/// `required` null or valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn userv_collector_json(
+    handle: *mut UservCollector,
+    buffer: *mut c_char,
+    size: usize,
+    required: *mut usize,
+) -> UservStatus {
+    guard(handle, || {
+        let Some(handle) = (unsafe { handle.as_mut() }) else {
+            return UservStatus::InvalidArgument;
+        };
+        handle.error = None;
+        let Some(snapshot) = &handle.snapshot else {
+            return handle.fail(UservStatus::NoSnapshot, "Data 'No snapshot collected'");
+        };
+        let json = match serde_json::to_string(snapshot) {
+            Ok(json) => json,
+            Err(e) => {
+                let e = Error::Serialization(e.to_string());
+                return handle.fail(UservStatus::from(&e), e);
+            }
+        };
+
+        let needed = json.len() + 1;
+        if !required.is_null() {
+            unsafe { *required = needed };
+        }
+        if buffer.is_null() || size < needed {
+            return handle.fail(UservStatus::BufferTooSmall, "Data 'Buffer too small'");
+        }
+        unsafe {
+            ptr::copy_nonoverlapping(json.as_ptr(), buffer.cast(), json.len());
+            *buffer.add(json.len()) = 0;
+        }
+        UservStatus::Ok
+    })
+}
+
+/// Message of the last error of the handle, or null if the last call succeeded.
+/// The string remains valid until the next call on the handle.
+///
+/// # Safety
+///
+/// `handle` must be null or a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn userv_collector_error(handle: *const UservCollector) -> *const c_char {
+    match unsafe { handle.as_ref() }.and_then(|h| h.error.as_ref()) {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test a whole cycle of the handle on the network component
+    #[test]
+    fn test_collector_cycle() {
+        let mut handle = ptr::null_mut();
+        unsafe {
+            assert_eq!(userv_collector_new(false, &mut handle), UservStatus::Ok);
+
+            let mut value = 0.0;
+            let status = userv_collector_value(
+                handle,
+                c"network".as_ptr(),
+                ptr::null(),
+                c"received".as_ptr(),
+                &mut value,
+            );
+            assert_eq!(status, UservStatus::NoSnapshot);
+            assert!(!userv_collector_error(handle).is_null());
+
+            let status = userv_collector_snapshot(handle, c"network".as_ptr());
+            assert_eq!(status, UservStatus::Ok);
+            assert!(userv_collector_error(handle).is_null());
+
+            let mut required = 0;
+            let status = userv_collector_json(handle, ptr::null_mut(), 0, &mut required);
+            assert_eq!(status, UservStatus::BufferTooSmall);
+            let mut buffer = vec![0 as c_char; required];
+            let status = userv_collector_json(handle, buffer.as_mut_ptr(), required, &mut required);
+            assert_eq!(status, UservStatus::Ok);
+            let json = CStr::from_ptr(buffer.as_ptr()).to_str().unwrap();
+            assert!(json.contains("\"network\""));
+            assert!(!json.contains("\"cpu\""));
+
+            userv_collector_free(handle);
+        }
+    }
+
+    // Test the error codes of invalid arguments
+    #[test]
+    fn test_collector_invalid() {
+        let mut handle = ptr::null_mut();
+        unsafe {
+            assert_eq!(
+                userv_collector_snapshot(ptr::null_mut(), ptr::null()),
+                UservStatus::InvalidArgument
+            );
+            assert_eq!(userv_collector_new(false, &mut handle), UservStatus::Ok);
+            assert_eq!(
+                userv_collector_snapshot(handle, c"network,fan".as_ptr()),
+                UservStatus::InvalidArgument
+            );
+            assert_eq!(
+                userv_collector_snapshot(handle, c" , ".as_ptr()),
+                UservStatus::NoComponent
+            );
+            userv_collector_free(handle);
+        }
+    }
+
+    // Test `guard` function returning a panic as an error status of the handle
+    #[test]
+    fn test_guard_panic() {
+        let mut handle = ptr::null_mut();
+        unsafe {
+            assert_eq!(userv_collector_new(false, &mut handle), UservStatus::Ok);
+            assert_eq!(guard(handle, || panic!("probe bug")), UservStatus::Panic);
+            let message = CStr::from_ptr(userv_collector_error(handle));
+            assert_eq!(message.to_str().unwrap(), "Process 'Panic' : probe bug");
+            userv_collector_free(handle);
+        }
+        assert_eq!(
+            guard(ptr::null_mut(), || panic!("no handle")),
+            UservStatus::Panic
+        );
+    }
+
+    // Test the versioned header matching the one generated by the build,
+    // refreshed with `USERV_UPDATE_HEADER=1 cargo build -p userv_ffi`
+    #[test]
+    fn test_header_up_to_date() {
+        assert_eq!(
+            include_str!("../include/userv.h"),
+            include_str!(concat!(env!("OUT_DIR"), "/userv.h"))
+        );
+    }
+}
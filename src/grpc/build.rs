@@ -0,0 +1,14 @@
+//! # Build script
+//!
+//! Generate the gRPC messages and service of `proto/userv.proto`,
+//! compiled in Rust without depending on an installed `protoc`.
+
+fn main() {
+    println!("cargo:rerun-if-changed=proto/userv.proto");
+
+    let descriptors =
+        protox::compile(["proto/userv.proto"], ["proto"]).expect("Invalid proto file");
+    tonic_prost_build::configure()
+        .compile_fds(descriptors)
+        .expect("Unable to generate gRPC code");
+}
@@ -0,0 +1,578 @@
+//! # gRPC library
+//!
+//! This library provides the gRPC service of `proto/userv.proto`, running the probes
+//! on demand, streaming the samples of the bus and querying the SQLite database.
+
+use log::error;
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap, error::Error, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration,
+};
+use tokio::{
+    net::TcpListener,
+    sync::mpsc,
+    task::spawn_blocking,
+    time::{interval, sleep_until},
+};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Request, Response, Status, transport::Server};
+use userv::{Collector, Component};
+use userv_core::{
+    bus::{self, Pattern, bus},
+    export::json_rows,
+    query::{self, Filter, INVENTORY, TIMESTAMP, host_name, list_tables},
+};
+
+/// Messages and service generated from `proto/userv.proto`.
+pub mod proto {
+    tonic::include_proto!("userv.v1");
+}
+use proto::{
+    Inventory, InventoryRequest, RangeRequest, RangeResponse, Row, Rows, SampleFilter,
+    SnapshotRequest,
+    metrics_server::{Metrics, MetricsServer},
+};
+
+const HEADER: &str = "GRPC";
+
+/// Interval between two flushes of the pending samples to a stream.
+const FLUSH: Duration = Duration::from_millis(100);
+/// Number of samples waiting for a stream before the newest ones are dropped.
+const QUEUE: usize = 4096;
+/// Rows returned by `QueryRange` when no limit is given.
+const DEFAULT_LIMIT: u64 = 10_000;
+/// Highest number of rows returned by `QueryRange` in one response.
+const MAX_LIMIT: u64 = 100_000;
+
+/// Parameters of the gRPC server, from the `[grpc]` section of the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct GrpcConfig {
+    /// Address the server listens on.
+    pub bind: String,
+    /// Port the server listens on.
+    pub port: u16,
+}
+
+impl Default for GrpcConfig {
+    fn default() -> Self {
+        GrpcConfig {
+            bind: "localhost".to_string(),
+            port: 50051,
+        }
+    }
+}
+
+impl From<bus::Sample> for proto::Sample {
+    fn from(sample: bus::Sample) -> Self {
+        proto::Sample {
+            timestamp: sample.timestamp,
+            topic: sample.topic,
+            entity: sample.entity,
+            value: sample.value,
+            unit: sample.unit,
+        }
+    }
+}
+
+/// Split a JSON row in its timestamp, numeric values and text labels.
+fn row(object: Map<String, Value>) -> Row {
+    let mut row = Row::default();
+    for (column, value) in object {
+        match value {
+            Value::String(text) if column == TIMESTAMP => row.timestamp = Some(text),
+            Value::Number(number) => {
+                if let Some(number) = number.as_f64() {
+                    row.values.insert(column, number);
+                }
+            }
+            Value::String(text) => {
+                row.labels.insert(column, text);
+            }
+            Value::Bool(flag) => {
+                row.labels.insert(column, flag.to_string());
+            }
+            _ => (),
+        }
+    }
+    row
+}
+
+/// Implementation of the `Metrics` service.
+#[derive(Debug, Clone)]
+pub struct MetricsService {
+    /// SQLite database file filled by the probes.
+    pub database: PathBuf,
+    /// Collector of [`Metrics::get_snapshot`], configured like the sampler
+    /// but neither storing nor publishing its snapshots.
+    pub collector: Collector,
+}
+
+impl MetricsService {
+    /// Open the database in read only mode, and run a request in a blocking thread.
+    async fn with_database<T, F>(&self, request: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> Result<T, Status> + Send + 'static,
+    {
+        let database = self.database.clone();
+        spawn_blocking(move || {
+            let conn = Connection::open_with_flags(&database, OpenFlags::SQLITE_OPEN_READ_ONLY)
+                .map_err(|e| {
+                    Status::unavailable(format!(
+                        "DataBase 'Unavailable' : {} {e}",
+                        database.display()
+                    ))
+                })?;
+            request(&conn)
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+    }
+}
+
+#[tonic::async_trait]
+impl Metrics for MetricsService {
+    async fn get_snapshot(
+        &self,
+        request: Request<SnapshotRequest>,
+    ) -> Result<Response<proto::Snapshot>, Status> {
+        let components = request
+            .into_inner()
+            .components
+            .iter()
+            .map(|label| {
+                Component::from_label(label).ok_or_else(|| {
+                    Status::invalid_argument(format!("Arguments 'Unknown component {label}'"))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let components = if components.is_empty() {
+            Component::ALL.to_vec()
+        } else {
+            components
+        };
+
+        let collector = self.collector.clone();
+        let snapshot = spawn_blocking(move || collector.collect(&components))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let metrics = snapshot
+            .metrics()
+            .into_iter()
+            .map(|metric| proto::Sample {
+                timestamp: snapshot.timestamp.clone(),
+                topic: format!("{}.{}", metric.component, metric.name),
+                entity: metric.entity,
+                value: metric.value,
+                unit: metric.unit,
+            })
+            .collect();
+        Ok(Response::new(proto::Snapshot {
+            timestamp: snapshot.timestamp,
+            hostname: snapshot.hostname,
+            metrics,
+            errors: snapshot.errors.into_iter().collect(),
+        }))
+    }
+
+    type StreamSamplesStream = ReceiverStream<Result<proto::Sample, Status>>;
+
+    async fn stream_samples(
+        &self,
+        request: Request<SampleFilter>,
+    ) -> Result<Response<Self::StreamSamplesStream>, Status> {
+        let filter = request.into_inner();
+        let patterns = filter
+            .patterns
+            .iter()
+            .map(|p| Pattern::parse(p))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| Status::invalid_argument("Arguments 'Invalid subscription pattern'"))?;
+        let matches = move |sample: &bus::Sample| {
+            patterns.is_empty() || patterns.iter().any(|p| p.matches(sample))
+        };
+
+        let subscription = bus().subscribe(QUEUE);
+        let history: Vec<bus::Sample> = bus().history().into_iter().filter(&matches).collect();
+        let replay = history.len().saturating_sub(filter.replay as usize);
+        let (sender, receiver) = mpsc::channel(QUEUE);
+
+        tokio::spawn(async move {
+            for sample in history.into_iter().skip(replay) {
+                if sender.send(Ok(sample.into())).await.is_err() {
+                    return;
+                }
+            }
+            let mut flush = interval(FLUSH);
+            loop {
+                flush.tick().await;
+                if sender.is_closed() {
+                    return;
+                }
+                while let Some(sample) = subscription.try_recv() {
+                    if matches(&sample) && sender.send(Ok(sample.into())).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn query_range(
+        &self,
+        request: Request<RangeRequest>,
+    ) -> Result<Response<RangeResponse>, Status> {
+        let request = request.into_inner();
+        let table = request.table;
+        let step = request.step;
+        let limit = request.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            return Err(Status::invalid_argument(format!(
+                "Arguments 'Invalid limit {limit}, between 1 and {MAX_LIMIT}'"
+            )));
+        }
+        let offset = request.offset;
+        let filter = Filter {
+            from: request.from,
+            to: request.to,
+            entity: request.entity,
+        };
+
+        let response = self
+            .with_database(move |conn| {
+                let tables = list_tables(conn).map_err(|e| Status::internal(e.to_string()))?;
+                if !tables.contains(&table) {
+                    return Err(Status::not_found(format!("Data 'Unknown table {table}'")));
+                }
+                let mut selection = match step {
+                    0 => query::select(conn, &table, &filter),
+                    step => query::aggregate(conn, &table, &filter, step),
+                }
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+                // One more row tells if a next page remains.
+                selection
+                    .query
+                    .push_str(&format!(" LIMIT {} OFFSET {offset}", limit + 1));
+                let mut rows =
+                    json_rows(conn, &selection).map_err(|e| Status::internal(e.to_string()))?;
+                let next = (rows.len() as u64 > limit).then(|| offset + limit);
+                rows.truncate(limit as usize);
+                Ok(RangeResponse {
+                    table,
+                    step,
+                    rows: rows.into_iter().map(row).collect(),
+                    limit,
+                    offset,
+                    next,
+                })
+            })
+            .await?;
+        Ok(Response::new(response))
+    }
+
+    async fn get_inventory(
+        &self,
+        _request: Request<InventoryRequest>,
+    ) -> Result<Response<Inventory>, Status> {
+        let inventory = self
+            .with_database(|conn| {
+                let internal = |e: Box<dyn Error>| Status::internal(e.to_string());
+                let tables = list_tables(conn).map_err(internal)?;
+                let mut components = HashMap::new();
+                for (component, table) in INVENTORY {
+                    if tables.iter().any(|t| t == table) {
+                        let selection = query::latest(conn, table).map_err(internal)?;
+                        let rows = json_rows(conn, &selection).map_err(internal)?;
+                        components.insert(
+                            component.to_string(),
+                            Rows {
+                                rows: rows.into_iter().map(row).collect(),
+                            },
+                        );
+                    }
+                }
+                Ok(Inventory {
+                    host: host_name(conn),
+                    components,
+                })
+            })
+            .await?;
+        Ok(Response::new(inventory))
+    }
+}
+
+/// Run the gRPC server on a bound listener, until it fails.
+///
+/// # Arguments
+///
+/// - `service` : [`MetricsService`] answering the requests.
+/// - `listener` : [`TcpListener`] accepting the connections.
+///
+/// # Returns
+///
+/// An error if the server fails.
+pub async fn run(service: MetricsService, listener: TcpListener) -> Result<(), Box<dyn Error>> {
+    Server::builder()
+        .add_service(MetricsServer::new(service))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await?;
+    Ok(())
+}
+
+/// Run the gRPC server and a sampler in the same runtime, until the server fails.
+/// The sampler runs in a blocking task every interval, and the samples it publishes
+/// are streamed to the `StreamSamples` clients. Without interval, only the server runs.
+///
+/// # Arguments
+///
+/// - `config` : [`GrpcConfig`] parameters of the server.
+/// - `service` : [`MetricsService`] answering the requests.
+/// - `sampler` : [`Collector`] running its components every configured interval, if any.
+///
+/// # Returns
+///
+/// An error if the address can't be bound, or the server fails.
+#[tokio::main]
+pub async fn serve(
+    config: GrpcConfig,
+    service: MetricsService,
+    sampler: Option<Collector>,
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind((config.bind.as_str(), config.port))
+        .await
+        .map_err(|e| {
+            format!(
+                "Server 'Unable to bind' : {}:{} {e}",
+                config.bind, config.port
+            )
+        })?;
+    let address: SocketAddr = listener.local_addr()?;
+    println!("[{HEADER}] Listening on {address}");
+
+    let sampler = sampler.map(|collector| {
+        let collector = Arc::new(collector);
+        tokio::spawn(async move {
+            loop {
+                let next = tokio::time::Instant::now() + collector.config().interval;
+                let collector = collector.clone();
+                let components = collector.config().components.clone();
+                match spawn_blocking(move || collector.collect(&components)).await {
+                    Ok(_) => (),
+                    Err(e) => error!("[{HEADER}] Process 'Failure in the sampler' : {e}"),
+                }
+                sleep_until(next).await;
+            }
+        })
+    });
+    let res = run(service, listener).await;
+    if let Some(sampler) = sampler {
+        sampler.abort();
+    }
+    res
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::metrics_client::MetricsClient;
+    use tempfile::NamedTempFile;
+    use tokio_stream::StreamExt;
+    use tonic::{Code, transport::Channel};
+
+    fn database() -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(file.path()).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE network_data (id INTEGER PRIMARY KEY, timestamp TEXT, name TEXT, received_MB REAL);
+            INSERT INTO network_data (timestamp, name, received_MB) VALUES
+                ('2025-01-01T10:00:00.000Z', 'eth0', 1.0),
+                ('2025-01-01T10:00:30.000Z', 'eth0', 3.0),
+                ('2025-01-01T10:01:00.000Z', 'eth0', 5.0),
+                ('2025-01-01T10:01:00.000Z', 'lo', 0.0);
+            CREATE TABLE system_data (id INTEGER PRIMARY KEY, hostname TEXT, os_name TEXT);
+            INSERT INTO system_data (hostname, os_name) VALUES ('node1', 'Linux');",
+        )
+        .unwrap();
+        file
+    }
+
+    /// Start a server on a loopback port, and connect a client to it.
+    async fn client(file: &NamedTempFile) -> MetricsClient<Channel> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let service = MetricsService {
+            database: file.path().to_path_buf(),
+            collector: Collector::default(),
+        };
+        tokio::spawn(async move { run(service, listener).await.unwrap() });
+        MetricsClient::connect(format!("http://{address}"))
+            .await
+            .unwrap()
+    }
+
+    fn sample(topic: &str, entity: &str, value: f64) -> bus::Sample {
+        bus::Sample {
+            timestamp: "2025-01-01T10:00:00.000Z".to_string(),
+            topic: topic.to_string(),
+            entity: Some(entity.to_string()),
+            value,
+            unit: None,
+        }
+    }
+
+    // Test `row` function splitting the columns by type
+    #[test]
+    fn test_row() {
+        let object = serde_json::json!({
+            "timestamp": "2025-01-01T10:00:00Z", "name": "eth0", "received_MB": 2.0, "id": null
+        });
+        let res = row(object.as_object().unwrap().clone());
+        assert_eq!(res.timestamp.as_deref(), Some("2025-01-01T10:00:00Z"));
+        assert_eq!(res.values["received_MB"], 2.0);
+        assert_eq!(res.labels["name"], "eth0");
+        assert!(!res.values.contains_key("id"));
+    }
+
+    // Test `QueryRange` and `GetInventory` methods over a loopback port
+    #[tokio::test]
+    async fn test_query_range_inventory() {
+        let file = database();
+        let mut client = client(&file).await;
+
+        let res = client
+            .query_range(RangeRequest {
+                table: "network_data".to_string(),
+                from: Some("2025-01-01T10:00:10Z".to_string()),
+                entity: Some("eth0".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(res.rows.len(), 2);
+
+        let res = client
+            .query_range(RangeRequest {
+                table: "network_data".to_string(),
+                entity: Some("eth0".to_string()),
+                step: 60,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(
+            res.rows[0].timestamp.as_deref(),
+            Some("2025-01-01T10:00:00Z")
+        );
+        assert_eq!(res.rows[0].values["received_MB"], 2.0);
+        assert_eq!(res.rows[1].values["received_MB"], 5.0);
+
+        let res = client
+            .query_range(RangeRequest {
+                table: "network_data".to_string(),
+                limit: Some(2),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!((res.rows.len(), res.limit, res.next), (2, 2, Some(2)));
+        let res = client
+            .query_range(RangeRequest {
+                table: "network_data".to_string(),
+                limit: Some(2),
+                offset: 2,
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!((res.rows.len(), res.offset, res.next), (2, 2, None));
+
+        let res = client
+            .get_inventory(InventoryRequest {})
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(res.host.as_deref(), Some("node1"));
+        assert_eq!(res.components["system"].rows[0].labels["os_name"], "Linux");
+    }
+
+    // Test `QueryRange` and `GetSnapshot` methods errors
+    #[tokio::test]
+    async fn test_errors() {
+        let file = database();
+        let mut client = client(&file).await;
+
+        for (table, from, code) in [
+            ("unknown", None, Code::NotFound),
+            ("network_data", Some("yesterday"), Code::InvalidArgument),
+        ] {
+            let res = client
+                .query_range(RangeRequest {
+                    table: table.to_string(),
+                    from: from.map(str::to_string),
+                    ..Default::default()
+                })
+                .await;
+            assert_eq!(res.unwrap_err().code(), code, "{table}");
+        }
+        let res = client
+            .query_range(RangeRequest {
+                table: "network_data".to_string(),
+                limit: Some(0),
+                ..Default::default()
+            })
+            .await;
+        assert_eq!(res.unwrap_err().code(), Code::InvalidArgument);
+
+        let res = client
+            .get_snapshot(SnapshotRequest {
+                components: vec!["unknown".to_string()],
+            })
+            .await;
+        assert_eq!(res.unwrap_err().code(), Code::InvalidArgument);
+
+        let res = client
+            .stream_samples(SampleFilter {
+                patterns: vec!["cpu.".to_string()],
+                replay: 0,
+            })
+            .await;
+        assert_eq!(res.unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    // Test `StreamSamples` method with replay and live samples matching the filter
+    #[tokio::test]
+    async fn test_stream_samples() {
+        let file = database();
+        let mut client = client(&file).await;
+
+        bus().publish([sample("grpc.replay", "a", 1.0)]);
+        let mut stream = client
+            .stream_samples(SampleFilter {
+                patterns: vec!["grpc.*@a".to_string()],
+                replay: 1,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        bus().publish([sample("grpc.live", "b", 2.0), sample("grpc.live", "a", 3.0)]);
+
+        let res = stream.next().await.unwrap().unwrap();
+        assert_eq!(res.topic, "grpc.replay");
+        let res = stream.next().await.unwrap().unwrap();
+        assert_eq!((res.topic.as_str(), res.value), ("grpc.live", 3.0));
+    }
+}
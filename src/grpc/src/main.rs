@@ -0,0 +1,88 @@
+//! # Main File
+//!
+//! This file provides the `userv-grpc` program, running the gRPC server
+//! alone or with the probes sampling in the same process.
+
+use clap::Parser;
+use std::{
+    path::{Path, PathBuf},
+    process::exit,
+    time::Duration,
+};
+use userv::{Collector, CollectorConfig, Component, baseline::stored_baselines, init_logger};
+use userv_core::{
+    config::{CONFIG, Config},
+    core::DATABASE,
+};
+use userv_grpc::{GrpcConfig, MetricsService, serve};
+
+const HEADER: &str = "GRPC";
+
+/// Data defining arguments of the gRPC server.
+#[derive(Parser, Debug)]
+struct Arg {
+    /// List of [`Component`] sampled with the server, all if not set.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    active: Vec<Component>,
+    /// Interval in seconds between each probe run. If not set, the probes
+    /// only run on `GetSnapshot` requests.
+    #[arg(long, default_value_t = 0)]
+    freq: u64,
+    /// TOML configuration file.
+    #[arg(long, default_value = CONFIG)]
+    config: PathBuf,
+}
+
+fn main() {
+    if let Err(e) = init_logger() {
+        eprintln!("[{HEADER}] INIT 'Failed to initialize error logger' : {e}");
+        return;
+    }
+
+    let arg = Arg::parse();
+    let config = Config::load(&arg.config).and_then(|config| {
+        Ok((
+            config.section::<GrpcConfig>("grpc")?,
+            CollectorConfig::from_config(&config)?,
+        ))
+    });
+    let (grpc, collector) = match config {
+        Ok(res) => res,
+        Err(e) => {
+            eprintln!("[{HEADER}] {e}");
+            exit(1);
+        }
+    };
+
+    let config = CollectorConfig {
+        components: if arg.active.is_empty() {
+            Component::ALL.to_vec()
+        } else {
+            arg.active
+        },
+        interval: Duration::from_secs(arg.freq),
+        persist: true,
+        publish: true,
+        baselines: stored_baselines(Path::new(DATABASE)).unwrap_or_else(|e| {
+            eprintln!("[{HEADER}] {e}");
+            Vec::new()
+        }),
+        ..collector
+    };
+    // On demand snapshots share the configuration of the sampler, but not its state,
+    // so that they don't split its energy intervals nor reach the stored and live data.
+    let service = MetricsService {
+        database: DATABASE.into(),
+        collector: Collector::new(CollectorConfig {
+            persist: false,
+            publish: false,
+            ..config.clone()
+        }),
+    };
+    let sampler = (arg.freq > 0).then(|| Collector::new(config));
+
+    if let Err(e) = serve(grpc, service, sampler) {
+        eprintln!("[{HEADER}] {e}");
+        exit(1);
+    }
+}
@@ -0,0 +1,685 @@
+"use strict";
+
+// Dashboard of the userv web server, reading the REST API for the history
+// and the WebSocket stream for the live values.
+
+const COLORS = ["#2563eb", "#dc2626", "#16a34a", "#d97706", "#7c3aed", "#0891b2", "#db2777", "#65a30d"];
+const POINTS = 240;
+const REFRESH = 30000;
+
+// Column identifying the entity of the rows, per table.
+const ENTITY = {
+  cpu_core: "core_name",
+  cpu_power: "zone_name",
+  cpu_temperature: "zone_name",
+  gpu_data: "gpu_bus_id",
+  network_data: "name",
+  storage_data: "name",
+};
+
+const state = {
+  span: 3600,
+  from: null,
+  to: null,
+  live: true,
+  timers: [],
+  socket: null,
+  generation: 0,
+};
+
+// ---------------------------------------------------------------- API
+
+async function api(path, params = {}) {
+  const query = new URLSearchParams();
+  for (const [key, value] of Object.entries(params)) {
+    if (value !== undefined && value !== null) query.set(key, value);
+  }
+  const res = await fetch(`/api/${path}${query.size ? "?" + query : ""}`);
+  const body = await res.json();
+  if (!res.ok) throw new Error(body.error || res.statusText);
+  return body;
+}
+
+function range() {
+  if (state.live) {
+    const to = new Date();
+    return { from: new Date(to.getTime() - state.span * 1000), to };
+  }
+  return { from: state.from, to: state.to };
+}
+
+function step() {
+  const { from, to } = range();
+  return Math.max(1, Math.floor((to - from) / 1000 / POINTS));
+}
+
+// Rows of a table over the selected range, averaged by step. Empty if the table is missing.
+async function history(table) {
+  const { from, to } = range();
+  try {
+    const res = await api(`metrics/${table}`, {
+      from: from.toISOString(),
+      to: to.toISOString(),
+      step: step(),
+      limit: 100000,
+    });
+    return res.rows;
+  } catch (e) {
+    return [];
+  }
+}
+
+async function latest() {
+  try {
+    return await api("latest");
+  } catch (e) {
+    return {};
+  }
+}
+
+// Points of a column by entity, as Map(entity => [[time, value]]).
+function split(rows, table, column, scale = 1) {
+  const key = ENTITY[table];
+  const series = new Map();
+  for (const row of rows) {
+    const value = row[column];
+    if (value === null || value === undefined) continue;
+    const name = key ? String(row[key]) : column;
+    if (!series.has(name)) series.set(name, []);
+    series.get(name).push([Date.parse(row.timestamp), value * scale]);
+  }
+  return series;
+}
+
+// Merge the points of several entities sharing a timestamp.
+function combine(series, reducer) {
+  const points = new Map();
+  for (const list of series.values()) {
+    for (const [t, v] of list) {
+      points.set(t, points.has(t) ? reducer(points.get(t), v) : v);
+    }
+  }
+  return [...points.entries()].sort((a, b) => a[0] - b[0]);
+}
+
+// RAPL top-level zones (packages), to avoid counting their sub-zones twice.
+function isPackage(zone, zones) {
+  const packages = zones.filter((z) => /^intel-rapl:\d+$/.test(z));
+  return packages.length === 0 || packages.includes(zone);
+}
+
+function packages(series) {
+  const zones = [...series.keys()];
+  return new Map([...series].filter(([zone]) => isPackage(zone, zones)));
+}
+
+const sum = (a, b) => a + b;
+const max = (a, b) => Math.max(a, b);
+
+// ---------------------------------------------------------------- Rendering
+
+function esc(text) {
+  return String(text).replace(/[&<>"']/g, (c) => `&#${c.charCodeAt(0)};`);
+}
+
+function fmt(value, digits = 1) {
+  if (value === null || value === undefined || Number.isNaN(value)) return "–";
+  if (typeof value !== "number") return String(value);
+  return Number.isInteger(value) ? String(value) : value.toFixed(digits);
+}
+
+function panel(title, wide = false) {
+  const section = document.createElement("section");
+  section.className = wide ? "panel wide" : "panel";
+  const h2 = document.createElement("h2");
+  h2.textContent = title;
+  section.append(h2);
+  document.getElementById("page").append(section);
+  return section;
+}
+
+function empty(container, text = "No data") {
+  const p = document.createElement("p");
+  p.className = "empty";
+  p.textContent = text;
+  container.append(p);
+}
+
+function timeLabel(t, span) {
+  const date = new Date(t);
+  if (span > 86400) return `${date.getMonth() + 1}/${date.getDate()} ${String(date.getHours()).padStart(2, "0")}h`;
+  return `${String(date.getHours()).padStart(2, "0")}:${String(date.getMinutes()).padStart(2, "0")}`;
+}
+
+// Cards of current values.
+class Cards {
+  constructor(container) {
+    this.root = document.createElement("div");
+    this.root.className = "cards";
+    container.append(this.root);
+    this.values = new Map();
+  }
+
+  card(label, unit = "") {
+    const card = document.createElement("div");
+    card.className = "card";
+    card.innerHTML = `<div class="value">–</div><div class="label">${esc(label)}</div>`;
+    this.root.append(card);
+    this.values.set(label, { element: card.querySelector(".value"), unit });
+  }
+
+  set(label, value, digits = 1) {
+    const card = this.values.get(label);
+    if (card) card.element.textContent = value === null || value === undefined ? "–" : `${fmt(value, digits)} ${card.unit}`;
+  }
+}
+
+// Time series chart drawn in SVG, one line per series.
+class LineChart {
+  constructor(container, unit = "") {
+    this.unit = unit;
+    this.series = new Map();
+    this.root = document.createElement("div");
+    container.append(this.root);
+    this.pending = false;
+  }
+
+  set(name, points) {
+    this.series.set(name, points);
+  }
+
+  // Add a live point, replacing the last one if it has the same time.
+  append(name, t, value) {
+    if (!this.series.has(name)) this.series.set(name, []);
+    const points = this.series.get(name);
+    if (points.length && points[points.length - 1][0] === t) points[points.length - 1][1] = value;
+    else points.push([t, value]);
+    const from = range().from.getTime();
+    while (points.length && points[0][0] < from) points.shift();
+    if (!this.pending) {
+      this.pending = true;
+      requestAnimationFrame(() => {
+        this.pending = false;
+        this.render();
+      });
+    }
+  }
+
+  render() {
+    const series = [...this.series].filter(([, points]) => points.length);
+    if (!series.length) {
+      this.root.innerHTML = `<p class="empty">No data</p>`;
+      return;
+    }
+    const width = Math.max(this.root.clientWidth, 300);
+    const height = 200;
+    const left = 52;
+    const bottom = 20;
+    const { from, to } = range();
+    const x0 = from.getTime();
+    const x1 = to.getTime();
+    const values = series.flatMap(([, points]) => points.map((p) => p[1]));
+    let y0 = Math.min(0, ...values);
+    let y1 = Math.max(...values);
+    if (y1 === y0) y1 = y0 + 1;
+    const x = (t) => left + ((t - x0) / (x1 - x0)) * (width - left - 8);
+    const y = (v) => 8 + (1 - (v - y0) / (y1 - y0)) * (height - bottom - 8);
+
+    let svg = `<svg width="${width}" height="${height}">`;
+    for (let i = 0; i <= 4; i++) {
+      const v = y0 + ((y1 - y0) * i) / 4;
+      svg += `<line x1="${left}" x2="${width - 8}" y1="${y(v)}" y2="${y(v)}" stroke="#eef0f3"/>`;
+      svg += `<text x="${left - 4}" y="${y(v) + 4}" text-anchor="end">${esc(fmt(v, v < 10 ? 2 : 0))}</text>`;
+    }
+    for (let i = 0; i <= 4; i++) {
+      const t = x0 + ((x1 - x0) * i) / 4;
+      svg += `<text x="${x(t)}" y="${height - 4}" text-anchor="middle">${timeLabel(t, (x1 - x0) / 1000)}</text>`;
+    }
+    series.forEach(([, points], i) => {
+      const path = points.map(([t, v], j) => `${j ? "L" : "M"}${x(t).toFixed(1)},${y(v).toFixed(1)}`).join("");
+      svg += `<path d="${path}" fill="none" stroke="${COLORS[i % COLORS.length]}" stroke-width="1.5"/>`;
+    });
+    svg += "</svg>";
+
+    const legend = series
+      .map(([name, points], i) => {
+        const last = points[points.length - 1][1];
+        return `<span><i style="background:${COLORS[i % COLORS.length]}"></i>${esc(name)} ${esc(fmt(last))} ${esc(this.unit)}</span>`;
+      })
+      .join("");
+    this.root.innerHTML = `${svg}<div class="legend">${legend}</div>`;
+  }
+}
+
+// Heatmap of a value by entity (rows) and time (columns), drawn on a canvas.
+function heatmap(container, series, maxValue = 100) {
+  const canvas = document.createElement("canvas");
+  container.append(canvas);
+  const rows = [...series.keys()].sort((a, b) => a.localeCompare(b, undefined, { numeric: true }));
+  if (!rows.length) {
+    canvas.remove();
+    empty(container);
+    return;
+  }
+  const times = [...new Set([...series.values()].flatMap((points) => points.map((p) => p[0])))].sort((a, b) => a - b);
+  const column = new Map(times.map((t, i) => [t, i]));
+  const left = 70;
+  const cell = Math.max(6, Math.min(16, 240 / rows.length));
+  const width = Math.max(container.clientWidth - 32, 300);
+  const height = rows.length * cell + 20;
+  const ratio = window.devicePixelRatio || 1;
+  canvas.width = width * ratio;
+  canvas.height = height * ratio;
+  canvas.style.height = `${height}px`;
+  const ctx = canvas.getContext("2d");
+  ctx.scale(ratio, ratio);
+  const cellWidth = (width - left) / times.length;
+
+  ctx.font = "11px system-ui";
+  ctx.fillStyle = "#6b7280";
+  rows.forEach((row, r) => {
+    if (cell >= 10 || r % 4 === 0) ctx.fillText(row, 0, r * cell + cell - 2);
+    for (const [t, v] of series.get(row)) {
+      const level = Math.min(Math.max(v / maxValue, 0), 1);
+      ctx.fillStyle = `hsl(${220 - 220 * level}, 80%, ${90 - 45 * level}%)`;
+      ctx.fillRect(left + column.get(t) * cellWidth, r * cell, Math.ceil(cellWidth), cell - 1);
+    }
+  });
+  ctx.fillStyle = "#6b7280";
+  const span = times.length > 1 ? (times[times.length - 1] - times[0]) / 1000 : 0;
+  for (let i = 0; i <= 4; i++) {
+    const index = Math.min(times.length - 1, Math.round(((times.length - 1) * i) / 4));
+    ctx.fillText(timeLabel(times[index], span), left + index * cellWidth - (i === 4 ? 30 : 0), height - 4);
+  }
+}
+
+// Table of rows, with columns as [key, label, digits].
+function table(container, rows, columns) {
+  if (!rows || !rows.length) {
+    empty(container);
+    return;
+  }
+  const element = document.createElement("table");
+  const head = element.createTHead().insertRow();
+  for (const [, label] of columns) {
+    const th = document.createElement("th");
+    th.textContent = label;
+    head.append(th);
+  }
+  const body = element.createTBody();
+  for (const row of rows) {
+    const tr = body.insertRow();
+    for (const [key, , digits] of columns) {
+      const td = tr.insertCell();
+      const value = typeof key === "function" ? key(row) : row[key];
+      td.textContent = fmt(value, digits ?? 1);
+      if (typeof value === "number") td.className = "number";
+    }
+  }
+  container.append(element);
+}
+
+// Panel content refreshed periodically while the range is relative.
+function refreshed(container, draw) {
+  const body = document.createElement("div");
+  container.append(body);
+  const run = async () => {
+    const generation = state.generation;
+    const content = document.createElement("div");
+    await draw(content);
+    if (generation === state.generation) body.replaceChildren(content);
+  };
+  run();
+  if (state.live) state.timers.push(setInterval(run, REFRESH));
+}
+
+// Chart of a column by entity, fed by the live samples of a topic.
+async function entityChart(title, table, column, topic, unit, scale = 1) {
+  const chart = new LineChart(panel(title), unit);
+  for (const [name, points] of split(await history(table), table, column, scale)) chart.set(name, points);
+  chart.render();
+  return { topics: [topic], onSample: (s) => s.topic === topic && chart.append(s.entity ?? column, Date.parse(s.timestamp), s.value * scale) };
+}
+
+// Chart of several columns of a table without entity, fed by the live samples of their topics.
+async function columnsChart(title, table, columns, unit) {
+  const chart = new LineChart(panel(title), unit);
+  const rows = await history(table);
+  for (const [column, label] of columns) {
+    const points = split(rows, table, column).get(column);
+    if (points) chart.set(label, points);
+  }
+  chart.render();
+  const labels = new Map(columns.map(([, label, topic]) => [topic, label]));
+  return { topics: [...labels.keys()], onSample: (s) => labels.has(s.topic) && chart.append(labels.get(s.topic), Date.parse(s.timestamp), s.value) };
+}
+
+// ---------------------------------------------------------------- Pages
+
+async function overview() {
+  const cards = new Cards(panel("Current state", true));
+  cards.card("Total estimated power", "W");
+  cards.card("CPU power", "W");
+  cards.card("GPU power", "W");
+  cards.card("Memory power", "W");
+  cards.card("CPU temperature", "°C");
+  cards.card("GPU temperature", "°C");
+  cards.card("Disk temperature", "°C");
+
+  const power = new LineChart(panel("Power by component"), "W");
+  const temperature = new LineChart(panel("Temperatures"), "°C");
+  const [cpuPower, gpuData, memoryData, cpuTemperature, last] = await Promise.all([
+    history("cpu_power"),
+    history("gpu_data"),
+    history("memory_data"),
+    history("cpu_temperature"),
+    latest(),
+  ]);
+  power.set("CPU", combine(packages(split(cpuPower, "cpu_power", "power_W")), sum));
+  power.set("GPU", combine(split(gpuData, "gpu_data", "gpu_power_consumption_mW", 1e-3), sum));
+  power.set("Memory", split(memoryData, "memory_data", "ram_power_consumption_W").get("ram_power_consumption_W") ?? []);
+  temperature.set("CPU", combine(split(cpuTemperature, "cpu_temperature", "temperature_°C"), max));
+  temperature.set("GPU", combine(split(gpuData, "gpu_data", "gpu_temperature_°C"), max));
+  power.render();
+  temperature.render();
+
+  // Current values by entity, updated by the live samples.
+  const current = {
+    cpu: new Map((last.cpu_power ?? []).map((r) => [r.zone_name, r.power_W])),
+    gpu: new Map((last.gpu_data ?? []).map((r) => [r.gpu_bus_id, (r.gpu_power_consumption_mW ?? 0) / 1000])),
+    memory: last.memory_data?.[0]?.ram_power_consumption_W ?? null,
+    cpuTemperature: new Map((last.cpu_temperature ?? []).map((r) => [r.zone_name, r["temperature_°C"]])),
+    gpuTemperature: new Map((last.gpu_data ?? []).map((r) => [r.gpu_bus_id, r["gpu_temperature_°C"]])),
+  };
+  const disks = (last.smart_data ?? []).map((r) => r.temperature).filter((t) => t !== null);
+  cards.set("Disk temperature", disks.length ? Math.max(...disks) : null, 0);
+
+  const total = (map, reducer) => (map.size ? [...map.values()].filter((v) => v !== null).reduce(reducer, reducer === sum ? 0 : -Infinity) : null);
+  const update = (t) => {
+    const zones = [...current.cpu.keys()];
+    const cpu = total(new Map([...current.cpu].filter(([zone]) => isPackage(zone, zones))), sum);
+    const gpu = total(current.gpu, sum);
+    cards.set("CPU power", cpu);
+    cards.set("GPU power", gpu);
+    cards.set("Memory power", current.memory);
+    cards.set("Total estimated power", cpu === null && gpu === null && current.memory === null ? null : (cpu ?? 0) + (gpu ?? 0) + (current.memory ?? 0));
+    cards.set("CPU temperature", total(current.cpuTemperature, max));
+    cards.set("GPU temperature", total(current.gpuTemperature, max));
+    if (t) {
+      if (cpu !== null) power.append("CPU", t, cpu);
+      if (gpu !== null) power.append("GPU", t, gpu);
+      if (current.memory !== null) power.append("Memory", t, current.memory);
+      if (current.cpuTemperature.size) temperature.append("CPU", t, total(current.cpuTemperature, max));
+      if (current.gpuTemperature.size) temperature.append("GPU", t, total(current.gpuTemperature, max));
+    }
+  };
+  update(null);
+
+  return [
+    {
+      topics: ["cpu.power", "cpu.temperature", "gpu.gpu_power_consumption", "gpu.gpu_temperature", "memory.ram_power_consumption"],
+      onSample: (s) => {
+        if (s.topic === "cpu.power") current.cpu.set(s.entity, s.value);
+        else if (s.topic === "cpu.temperature") current.cpuTemperature.set(s.entity, s.value);
+        else if (s.topic === "gpu.gpu_power_consumption") current.gpu.set(s.entity, s.value / 1000);
+        else if (s.topic === "gpu.gpu_temperature") current.gpuTemperature.set(s.entity, s.value);
+        else if (s.topic === "memory.ram_power_consumption") current.memory = s.value;
+        else return;
+        update(Date.parse(s.timestamp));
+      },
+    },
+  ];
+}
+
+async function cpu() {
+  const info = panel("Processor");
+  const inventory = await api("inventory").catch(() => ({}));
+  table(info, inventory.cpu ?? [], [
+    ["model", "Model"],
+    ["architecture", "Architecture"],
+    ["cores_physic", "Physical cores"],
+    ["cores_logic", "Logical cores"],
+    ["frequency_MHz", "Frequency (MHz)"],
+  ]);
+
+  const cores = panel("Cores usage (%)", true);
+  refreshed(cores, async (content) => heatmap(content, split(await history("cpu_core"), "cpu_core", "usage_percent")));
+
+  return Promise.all([
+    entityChart("RAPL zones", "cpu_power", "power_W", "cpu.power", "W"),
+    entityChart("Temperatures", "cpu_temperature", "temperature_°C", "cpu.temperature", "°C"),
+    columnsChart("Frequency", "cpu_data", [["frequency_MHz", "Frequency", "cpu.frequency"]], "MHz"),
+  ]);
+}
+
+async function gpu() {
+  const devices = panel("Devices", true);
+  refreshed(devices, async (content) =>
+    table(content, (await latest()).gpu_data, [
+      ["gpu_name", "Name"],
+      ["gpu_bus_id", "Bus"],
+      ["gpu_usage", "Usage (%)"],
+      ["gpu_temperature_°C", "Temperature (°C)"],
+      [(r) => (r.gpu_power_consumption_mW ?? null) && r.gpu_power_consumption_mW / 1000, "Power (W)"],
+      ["gpu_memory_stat", "Memory (%)"],
+      ["gpu_clock_graphic_MHz", "Graphic clock (MHz)"],
+    ]),
+  );
+
+  const live = await Promise.all([
+    entityChart("Usage", "gpu_data", "gpu_usage", "gpu.gpu_usage", "%"),
+    entityChart("Power", "gpu_data", "gpu_power_consumption_mW", "gpu.gpu_power_consumption", "W", 1e-3),
+    entityChart("Temperature", "gpu_data", "gpu_temperature_°C", "gpu.gpu_temperature", "°C"),
+    entityChart("Memory usage", "gpu_data", "gpu_memory_stat", "gpu.gpu_memory_stat", "%"),
+  ]);
+
+  const processes = panel("Processes", true);
+  refreshed(processes, async (content) =>
+    table(content, (await latest()).gpu_process_data, [
+      ["process_pid", "PID"],
+      ["gpu_bus_id", "Bus"],
+      ["process_streaming_multiprocessor", "SM (%)"],
+      ["process_memory", "Memory (%)"],
+      ["process_encoding", "Encoding (%)"],
+      ["process_decoding", "Decoding (%)"],
+    ]),
+  );
+  return live;
+}
+
+async function memory() {
+  const live = await Promise.all([
+    columnsChart(
+      "RAM and swap",
+      "memory_data",
+      [
+        ["ram_used_MB", "RAM used", "memory.ram_used"],
+        ["ram_available_MB", "RAM available", "memory.ram_available"],
+        ["swap_used_MB", "Swap used", "memory.swap_used"],
+      ],
+      "MB",
+    ),
+    columnsChart(
+      "Bandwidth",
+      "memory_data",
+      [
+        ["bandwidth_read_MB_s", "Read", "memory.bandwidth_read"],
+        ["bandwidth_write", "Write", "memory.bandwidth_write"],
+      ],
+      "MB/s",
+    ),
+  ]);
+  const modules = panel("Modules", true);
+  const inventory = await api("inventory").catch(() => ({}));
+  table(modules, inventory.memory ?? [], [
+    ["device_id", "Device"],
+    ["ram_type", "Type"],
+    ["size_MB", "Size (MB)"],
+    ["speed_mt_s", "Speed (MT/s)"],
+    ["voltage_mV", "Voltage (mV)"],
+  ]);
+  return live;
+}
+
+async function network() {
+  const interfaces = panel("Interfaces", true);
+  refreshed(interfaces, async (content) =>
+    table(content, (await latest()).network_data, [
+      ["name", "Name"],
+      ["network_type", "Type"],
+      ["address_mac", "MAC"],
+      ["received_MB", "Received (MB)", 3],
+      ["transmitted_MB", "Transmitted (MB)", 3],
+      ["energy_consumed_W", "Energy", 4],
+    ]),
+  );
+  return Promise.all([
+    entityChart("Received", "network_data", "received_MB", "network.received", "MB"),
+    entityChart("Transmitted", "network_data", "transmitted_MB", "network.transmitted", "MB"),
+  ]);
+}
+
+async function storage() {
+  const disks = panel("Disks", true);
+  refreshed(disks, async (content) =>
+    table(content, (await latest()).storage_data, [
+      ["name", "Name"],
+      ["kind", "Kind"],
+      ["file_system", "File system"],
+      ["file_mount", "Mount"],
+      ["space_total_MB", "Total space"],
+      ["space_available_MB", "Available space"],
+    ]),
+  );
+  const smart = panel("SMART", true);
+  refreshed(smart, async (content) =>
+    table(content, (await latest()).smart_data, [
+      ["storage_data_name", "Disk"],
+      ["temperature", "Temperature (°C)"],
+      ["uptime_hours", "Uptime (h)"],
+      ["sectors_reallocated", "Reallocated sectors"],
+      ["sectors_pending", "Pending sectors"],
+      ["sectors_pending_current", "Current pending sectors"],
+    ]),
+  );
+  return Promise.all([
+    entityChart("Read", "storage_data", "bandwidth_read_MB", "storage.bandwidth_read", "MB"),
+    entityChart("Write", "storage_data", "bandwidth_write_MB", "storage.bandwidth_write", "MB"),
+  ]);
+}
+
+async function processes() {
+  const system = panel("System", true);
+  refreshed(system, async (content) =>
+    table(content, (await latest()).system_data, [
+      ["hostname", "Host"],
+      ["system_name", "System"],
+      ["system_version", "Version"],
+      ["system_kernel", "Kernel"],
+      ["system_load", "Load"],
+      ["process_count", "Processes"],
+      ["uptime", "Uptime"],
+    ]),
+  );
+  const top = panel("Top processes by CPU usage", true);
+  refreshed(top, async (content) => {
+    const rows = ((await latest()).system_process_data ?? []).sort((a, b) => (b.cpu_usage ?? 0) - (a.cpu_usage ?? 0)).slice(0, 25);
+    table(content, rows, [
+      ["pid", "PID"],
+      ["name", "Name"],
+      ["status", "Status"],
+      ["cpu_usage", "CPU (%)"],
+      ["memory_usage_MB", "Memory (MB)"],
+      ["disk_usage_read_MB", "Disk read (MB)"],
+      ["disk_usage_write_MB", "Disk write (MB)"],
+      ["run_time_min", "Run time (min)"],
+      ["id_user", "User"],
+    ]);
+  });
+  return [];
+}
+
+const PAGES = { overview, cpu, gpu, memory, network, storage, processes };
+
+// ---------------------------------------------------------------- Live stream
+
+function connect(feeds) {
+  const topics = [...new Set(feeds.flatMap((f) => f.topics))];
+  if (!state.live || !topics.length) return;
+  const generation = state.generation;
+  const scheme = location.protocol === "https:" ? "wss" : "ws";
+  const socket = new WebSocket(`${scheme}://${location.host}/ws/?replay=0&subscribe=${encodeURIComponent(topics.join(","))}`);
+  const indicator = document.getElementById("live");
+  socket.onopen = () => indicator.classList.add("on");
+  socket.onclose = () => {
+    indicator.classList.remove("on");
+    if (generation === state.generation) setTimeout(() => generation === state.generation && connect(feeds), 5000);
+  };
+  socket.onmessage = (event) => {
+    const sample = JSON.parse(event.data);
+    if (sample.topic) feeds.forEach((f) => f.onSample(sample));
+  };
+  state.socket = socket;
+}
+
+async function render() {
+  state.generation += 1;
+  state.timers.forEach(clearInterval);
+  state.timers = [];
+  if (state.socket) state.socket.close();
+  state.socket = null;
+
+  const name = location.hash.slice(1) in PAGES ? location.hash.slice(1) : "overview";
+  document.querySelectorAll("nav a").forEach((a) => a.classList.toggle("active", a.hash === `#${name}`));
+  document.getElementById("page").replaceChildren();
+  const feeds = await PAGES[name]();
+  connect(feeds);
+}
+
+// ---------------------------------------------------------------- Range picker
+
+function localInput(date) {
+  const offset = date.getTimezoneOffset() * 60000;
+  return new Date(date.getTime() - offset).toISOString().slice(0, 16);
+}
+
+function initRange() {
+  const form = document.getElementById("range");
+  const preset = document.getElementById("range-preset");
+  const custom = document.getElementById("range-custom");
+  const from = document.getElementById("range-from");
+  const to = document.getElementById("range-to");
+
+  preset.addEventListener("change", () => {
+    custom.hidden = preset.value !== "custom";
+    if (!custom.hidden && !from.value) {
+      const now = new Date();
+      from.value = localInput(new Date(now.getTime() - state.span * 1000));
+      to.value = localInput(now);
+    }
+  });
+  form.addEventListener("submit", (event) => {
+    event.preventDefault();
+    if (preset.value === "custom") {
+      const start = new Date(from.value);
+      const end = new Date(to.value);
+      if (Number.isNaN(start.getTime()) || Number.isNaN(end.getTime()) || start >= end) return;
+      state.live = false;
+      state.from = start;
+      state.to = end;
+    } else {
+      state.live = true;
+      state.span = Number(preset.value);
+    }
+    render();
+  });
+}
+
+initRange();
+window.addEventListener("hashchange", render);
+api("inventory")
+  .then((inventory) => {
+    document.getElementById("host").textContent = inventory.host ?? "";
+  })
+  .catch(() => {});
+render();
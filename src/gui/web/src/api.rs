@@ -0,0 +1,540 @@
+//! # File API
+//!
+//! This file provides the JSON endpoints querying the metrics stored in the SQLite database.
+
+use actix_web::{HttpResponse, ResponseError, http::StatusCode, web};
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use std::{error::Error, fmt, path::PathBuf};
+use userv_core::{
+    bus::bus,
+    export::json_rows,
+    query::{self, Filter, INVENTORY, host_name, list_columns, list_tables},
+};
+
+/// Rows returned by `/api/metrics/{table}` when no limit is given.
+const DEFAULT_LIMIT: u64 = 10_000;
+
+/// Highest number of rows returned by `/api/metrics/{table}` in one response.
+const MAX_LIMIT: u64 = 100_000;
+
+/// Shared data of the API handlers.
+#[derive(Debug, Clone)]
+pub struct ApiState {
+    /// SQLite database file filled by the probes.
+    pub database: PathBuf,
+}
+
+/// Errors returned by the API handlers, as JSON `{"error": message}`.
+#[derive(Debug)]
+pub enum ApiError {
+    /// Invalid query parameter.
+    BadRequest(String),
+    /// Unknown table.
+    NotFound(String),
+    /// Database unavailable or request failure.
+    Internal(String),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::BadRequest(message)
+            | ApiError::NotFound(message)
+            | ApiError::Internal(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(json!({ "error": self.to_string() }))
+    }
+}
+
+impl From<Box<dyn Error>> for ApiError {
+    fn from(error: Box<dyn Error>) -> Self {
+        ApiError::Internal(error.to_string())
+    }
+}
+
+/// Query parameters of the `/api/metrics/{table}` endpoint.
+#[derive(Debug, Deserialize)]
+pub struct MetricsQuery {
+    /// Lower bound of the time range (RFC 3339 or `YYYY-MM-DD`).
+    pub from: Option<String>,
+    /// Upper bound of the time range (RFC 3339 or `YYYY-MM-DD`).
+    pub to: Option<String>,
+    /// Aggregation step, in seconds or suffixed by `s`, `m`, `h` or `d`.
+    pub step: Option<String>,
+    /// Entity to keep (interface, disk, GPU bus id, pid...).
+    pub entity: Option<String>,
+    /// Number of rows returned, [`DEFAULT_LIMIT`] if not set.
+    pub limit: Option<u64>,
+    /// Number of rows skipped, to read the next pages.
+    pub offset: Option<u64>,
+}
+
+/// Query parameters of the `/api/emissions` endpoint.
+#[derive(Debug, Deserialize)]
+pub struct RangeQuery {
+    /// Lower bound of the time range (RFC 3339 or `YYYY-MM-DD`).
+    pub from: Option<String>,
+    /// Upper bound of the time range (RFC 3339 or `YYYY-MM-DD`).
+    pub to: Option<String>,
+}
+
+/// Register the API endpoints under `/api`.
+///
+/// # Arguments
+///
+/// - `cfg` : Service configuration of the application, which must provide the [`ApiState`] data.
+pub fn api(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api")
+            .route("/probes", web::get().to(probes))
+            .route("/inventory", web::get().to(inventory))
+            .route("/latest", web::get().to(latest))
+            .route("/samples", web::get().to(samples))
+            .route("/metrics/{table}", web::get().to(metrics))
+            .route("/emissions", web::get().to(emissions)),
+    );
+}
+
+/// Parse an aggregation step.
+///
+/// # Arguments
+///
+/// - `step` : Number of seconds, optionally suffixed by `s`, `m`, `h` or `d`.
+///
+/// # Returns
+///
+/// The step in seconds, if valid and greater than 0.
+pub fn parse_step(step: &str) -> Option<u64> {
+    let step = step.trim();
+    let (value, factor) = match step.char_indices().last()? {
+        (i, 's') => (&step[..i], 1),
+        (i, 'm') => (&step[..i], 60),
+        (i, 'h') => (&step[..i], 3600),
+        (i, 'd') => (&step[..i], 86400),
+        _ => (step, 1),
+    };
+    value
+        .parse::<u64>()
+        .ok()
+        .and_then(|v| v.checked_mul(factor))
+        .filter(|v| *v > 0)
+}
+
+/// Name of the probe which fills a table.
+fn probe_of(table: &str) -> &str {
+    match table.split('_').next().unwrap_or(table) {
+        "smart" => "storage",
+        probe => probe,
+    }
+}
+
+/// Open the database in read only mode, and run a request in a blocking thread.
+pub(crate) async fn with_database<T, F>(state: &ApiState, request: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce(&Connection) -> Result<T, ApiError> + Send + 'static,
+{
+    let database = state.database.clone();
+    web::block(move || {
+        let conn = Connection::open_with_flags(&database, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| {
+                ApiError::Internal(format!(
+                    "DataBase 'Unavailable' : {} {e}",
+                    database.display()
+                ))
+            })?;
+        request(&conn)
+    })
+    .await
+    .map_err(|e| ApiError::Internal(e.to_string()))?
+}
+
+/// List the stored tables grouped by probe, with their columns and number of rows.
+async fn probes(state: web::Data<ApiState>) -> Result<HttpResponse, ApiError> {
+    let probes = with_database(&state, |conn| {
+        let mut probes: Vec<Value> = Vec::new();
+        for table in list_tables(conn)? {
+            let columns = list_columns(conn, &table)?;
+            let rows: i64 = conn
+                .query_row(
+                    &format!("SELECT COUNT(*) FROM {}", query::quote(&table)),
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| ApiError::Internal(e.to_string()))?;
+            let entry = json!({ "name": table, "columns": columns, "rows": rows });
+
+            let probe = probe_of(&table);
+            match probes.iter_mut().find(|p| p["name"] == probe) {
+                Some(p) => p["tables"].as_array_mut().unwrap().push(entry),
+                None => probes.push(json!({ "name": probe, "tables": [entry] })),
+            }
+        }
+        Ok(probes)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(probes))
+}
+
+/// Describe the machine with the last rows of the inventory tables.
+async fn inventory(state: web::Data<ApiState>) -> Result<HttpResponse, ApiError> {
+    let inventory = with_database(&state, |conn| {
+        let tables = list_tables(conn)?;
+        let mut inventory = Map::new();
+        inventory.insert("host".to_string(), json!(host_name(conn)));
+        for (component, table) in INVENTORY {
+            if tables.iter().any(|t| t == table) {
+                let rows = json_rows(conn, &query::latest(conn, table)?)?;
+                inventory.insert(component.to_string(), json!(rows));
+            }
+        }
+        Ok(inventory)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(inventory))
+}
+
+/// Give the last measurement of each stored table.
+async fn latest(state: web::Data<ApiState>) -> Result<HttpResponse, ApiError> {
+    let latest = with_database(&state, |conn| {
+        let mut latest = Map::new();
+        for table in list_tables(conn)? {
+            let rows = json_rows(conn, &query::latest(conn, &table)?)?;
+            latest.insert(table, json!(rows));
+        }
+        Ok(latest)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(latest))
+}
+
+/// Give the latest sample of each topic and entity published in the process,
+/// empty if the probes don't run with the server.
+async fn samples() -> HttpResponse {
+    HttpResponse::Ok().json(bus().latest())
+}
+
+/// Give the rows of a table matching the time range and entity,
+/// averaged by step if one is given, a page at a time.
+/// The offset of the next page is given while rows remain.
+async fn metrics(
+    state: web::Data<ApiState>,
+    table: web::Path<String>,
+    params: web::Query<MetricsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let table = table.into_inner();
+    let params = params.into_inner();
+    let step = match &params.step {
+        Some(step) => Some(
+            parse_step(step)
+                .ok_or_else(|| ApiError::BadRequest(format!("Arguments 'Invalid step {step}'")))?,
+        ),
+        None => None,
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(ApiError::BadRequest(format!(
+            "Arguments 'Invalid limit {limit}, between 1 and {MAX_LIMIT}'"
+        )));
+    }
+    let offset = params.offset.unwrap_or(0);
+    let filter = Filter {
+        from: params.from,
+        to: params.to,
+        entity: params.entity,
+    };
+
+    let response = with_database(&state, move |conn| {
+        if !list_tables(conn)?.contains(&table) {
+            return Err(ApiError::NotFound(format!("Data 'Unknown table {table}'")));
+        }
+        let mut selection = match step {
+            Some(step) => query::aggregate(conn, &table, &filter, step),
+            None => query::select(conn, &table, &filter),
+        }
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        // One more row tells if a next page remains.
+        selection
+            .query
+            .push_str(&format!(" LIMIT {} OFFSET {offset}", limit + 1));
+        let mut rows = json_rows(conn, &selection)?;
+        let next = (rows.len() as u64 > limit).then(|| offset + limit);
+        rows.truncate(limit as usize);
+        Ok(json!({
+            "table": table,
+            "step": step,
+            "limit": limit,
+            "offset": offset,
+            "next": next,
+            "rows": rows,
+        }))
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Sum the energy and the carbon emissions stored over the time range.
+async fn emissions(
+    state: web::Data<ApiState>,
+    params: web::Query<RangeQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let params = params.into_inner();
+    let filter = Filter {
+        from: params.from,
+        to: params.to,
+        entity: None,
+    };
+
+    let response = with_database(&state, move |conn| {
+        if !list_tables(conn)?.iter().any(|t| t == "emissions") {
+            return Err(ApiError::NotFound(
+                "Data 'Unknown table emissions'".to_string(),
+            ));
+        }
+        let selection = query::select(conn, "emissions", &filter)
+            .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        let request = format!(
+            "SELECT COUNT(*), MIN(q.timestamp), MAX(q.timestamp), TOTAL(q.energy_J), TOTAL(q.emissions_gCO2e) FROM ({}) q",
+            selection.query
+        );
+        let (rows, first, last, energy, emissions) = conn
+            .query_row(&request, &*selection.params(), |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, f64>(3)?,
+                    row.get::<_, f64>(4)?,
+                ))
+            })
+            .map_err(|e| ApiError::Internal(e.to_string()))?;
+        let kwh = energy / 3.6e6;
+        Ok(json!({
+            "from": first,
+            "to": last,
+            "rows": rows,
+            "energy_J": energy,
+            "energy_kWh": kwh,
+            "emissions_gCO2e": emissions,
+            "intensity_gCO2e_kWh": (kwh > 0.0).then(|| emissions / kwh),
+        }))
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        App,
+        test::{TestRequest, call_and_read_body_json, call_service, init_service},
+    };
+    use tempfile::NamedTempFile;
+
+    fn database() -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(file.path()).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE network_data (id INTEGER PRIMARY KEY, timestamp TEXT, name TEXT, received_MB REAL);
+            INSERT INTO network_data (timestamp, name, received_MB) VALUES
+                ('2025-01-01T10:00:00.000Z', 'eth0', 1.0),
+                ('2025-01-01T10:00:30.000Z', 'eth0', 3.0),
+                ('2025-01-01T10:01:00.000Z', 'eth0', 5.0),
+                ('2025-01-01T10:01:00.000Z', 'lo', 0.0);
+            CREATE TABLE system_data (id INTEGER PRIMARY KEY, hostname TEXT, os_name TEXT);
+            INSERT INTO system_data (hostname, os_name) VALUES ('node1', 'Linux');",
+        )
+        .unwrap();
+        file
+    }
+
+    macro_rules! app {
+        ($file:expr) => {
+            init_service(
+                App::new()
+                    .app_data(web::Data::new(ApiState {
+                        database: $file.path().to_path_buf(),
+                    }))
+                    .configure(api),
+            )
+            .await
+        };
+    }
+
+    // Test `parse_step` function with units and invalid values
+    #[test]
+    fn test_parse_step() {
+        assert_eq!(parse_step("30"), Some(30));
+        assert_eq!(parse_step("5m"), Some(300));
+        assert_eq!(parse_step("1d"), Some(86400));
+        assert_eq!(parse_step("0"), None);
+        assert_eq!(parse_step("m"), None);
+        assert_eq!(parse_step("1w"), None);
+    }
+
+    // Test `/api/probes` and `/api/inventory` endpoints
+    #[actix_web::test]
+    async fn test_probes_inventory() {
+        let file = database();
+        let app = app!(file);
+
+        let req = TestRequest::get().uri("/api/probes").to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res[0]["name"], "network");
+        assert_eq!(res[0]["tables"][0]["rows"], 4);
+
+        let req = TestRequest::get().uri("/api/inventory").to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res["host"], "node1");
+        assert_eq!(res["system"][0]["os_name"], "Linux");
+    }
+
+    // Test `/api/latest` endpoint
+    #[actix_web::test]
+    async fn test_latest() {
+        let file = database();
+        let app = app!(file);
+
+        let req = TestRequest::get().uri("/api/latest").to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res["network_data"].as_array().unwrap().len(), 2);
+        assert_eq!(res["system_data"].as_array().unwrap().len(), 1);
+    }
+
+    // Test `/api/metrics/{table}` endpoint with filters and step
+    #[actix_web::test]
+    async fn test_metrics() {
+        let file = database();
+        let app = app!(file);
+
+        let req = TestRequest::get()
+            .uri("/api/metrics/network_data?entity=eth0&from=2025-01-01T10:00:10Z")
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res["rows"].as_array().unwrap().len(), 2);
+
+        let req = TestRequest::get()
+            .uri("/api/metrics/network_data?step=1m&entity=eth0")
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res["step"], 60);
+        assert_eq!(res["rows"][0]["timestamp"], "2025-01-01T10:00:00Z");
+        assert_eq!(res["rows"][0]["received_MB"], 2.0);
+        assert_eq!(res["rows"][1]["received_MB"], 5.0);
+    }
+
+    // Test `/api/metrics/{table}` endpoint pages
+    #[actix_web::test]
+    async fn test_metrics_pages() {
+        let file = database();
+        let app = app!(file);
+
+        let req = TestRequest::get()
+            .uri("/api/metrics/network_data")
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res["limit"], DEFAULT_LIMIT);
+        assert_eq!(res["rows"].as_array().unwrap().len(), 4);
+        assert!(res["next"].is_null());
+
+        let req = TestRequest::get()
+            .uri("/api/metrics/network_data?limit=3")
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res["rows"].as_array().unwrap().len(), 3);
+        assert_eq!(res["next"], 3);
+
+        let req = TestRequest::get()
+            .uri("/api/metrics/network_data?limit=3&offset=3")
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res["rows"][0]["name"], "lo");
+        assert!(res["next"].is_null());
+
+        let req = TestRequest::get()
+            .uri("/api/metrics/network_data?limit=0")
+            .to_request();
+        assert_eq!(
+            call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    // Test `/api/emissions` endpoint sums over the time range
+    #[actix_web::test]
+    async fn test_emissions() {
+        let file = database();
+        let app = app!(file);
+        let req = TestRequest::get().uri("/api/emissions").to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        Connection::open(file.path())
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE emissions (id INTEGER PRIMARY KEY, timestamp TEXT, energy_J REAL, emissions_gCO2e REAL);
+                INSERT INTO emissions (timestamp, energy_J, emissions_gCO2e) VALUES
+                    ('2025-01-01T10:00:00.000Z', 3.6e6, 100.0),
+                    ('2025-01-01T11:00:00.000Z', 7.2e6, 500.0);",
+            )
+            .unwrap();
+
+        let req = TestRequest::get().uri("/api/emissions").to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res["rows"], 2);
+        assert_eq!(res["energy_kWh"], 3.0);
+        assert_eq!(res["emissions_gCO2e"], 600.0);
+        assert_eq!(res["intensity_gCO2e_kWh"], 200.0);
+
+        let req = TestRequest::get()
+            .uri("/api/emissions?from=2025-01-01T10:30:00Z")
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res["from"], "2025-01-01T11:00:00.000Z");
+        assert_eq!(res["emissions_gCO2e"], 500.0);
+    }
+
+    // Test `/api/metrics/{table}` endpoint errors
+    #[actix_web::test]
+    async fn test_metrics_error() {
+        let file = database();
+        let app = app!(file);
+
+        for (uri, status) in [
+            ("/api/metrics/unknown", StatusCode::NOT_FOUND),
+            (
+                "/api/metrics/network_data?step=abc",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "/api/metrics/network_data?from=yesterday",
+                StatusCode::BAD_REQUEST,
+            ),
+            ("/api/metrics/system_data?step=60", StatusCode::BAD_REQUEST),
+        ] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let res = call_service(&app, req).await;
+            assert_eq!(res.status(), status, "{uri}");
+        }
+    }
+}
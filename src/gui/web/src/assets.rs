@@ -0,0 +1,76 @@
+//! # File assets
+//!
+//! This file provides the dashboard pages, bundled in the binary
+//! so the server does not depend on a static folder at runtime.
+
+use actix_web::{HttpResponse, http::header::ContentType, web};
+
+/// Dashboard files, as path, content type and content.
+const ASSETS: &[(&str, &str, &str)] = &[
+    (
+        "/",
+        "text/html; charset=utf-8",
+        include_str!("../assets/index.html"),
+    ),
+    (
+        "/index.html",
+        "text/html; charset=utf-8",
+        include_str!("../assets/index.html"),
+    ),
+    (
+        "/app.js",
+        "text/javascript; charset=utf-8",
+        include_str!("../assets/app.js"),
+    ),
+    (
+        "/style.css",
+        "text/css; charset=utf-8",
+        include_str!("../assets/style.css"),
+    ),
+];
+
+/// Register the routes of the dashboard files.
+///
+/// # Arguments
+///
+/// - `cfg` : Service configuration of the application.
+pub fn assets(cfg: &mut web::ServiceConfig) {
+    for (path, content_type, content) in ASSETS {
+        cfg.route(
+            path,
+            web::get().to(move || async move {
+                HttpResponse::Ok()
+                    .insert_header(ContentType(content_type.parse().unwrap()))
+                    .body(*content)
+            }),
+        );
+    }
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        App,
+        http::{StatusCode, header},
+        test::{TestRequest, call_service, init_service},
+    };
+
+    // Test `assets` routes serving the bundled files
+    #[actix_web::test]
+    async fn test_assets() {
+        let app = init_service(App::new().configure(assets)).await;
+        for (path, content_type) in [("/", "text/html"), ("/app.js", "text/javascript")] {
+            let res = call_service(&app, TestRequest::get().uri(path).to_request()).await;
+            assert_eq!(res.status(), StatusCode::OK);
+            let header = res.headers().get(header::CONTENT_TYPE).unwrap();
+            assert!(header.to_str().unwrap().starts_with(content_type));
+        }
+        let res = call_service(&app, TestRequest::get().uri("/missing.js").to_request()).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
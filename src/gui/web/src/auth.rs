@@ -0,0 +1,564 @@
+//! # File auth
+//!
+//! This file provides the authentication of the clients by token or HTTP basic credentials,
+//! checked against hashed secrets, and the roles allowed to send mutating requests.
+//! The failed authentications are remembered and limited by client address, the hashes
+//! being slow to compute on purpose.
+
+use actix_web::{
+    Error, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{Method, header},
+    middleware::Next,
+    web,
+};
+use openssl::{base64, hash::MessageDigest, memcmp, pkcs5::pbkdf2_hmac, rand::rand_bytes, sha};
+use serde::Deserialize;
+use serde_json::json;
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error as StdError,
+    net::IpAddr,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use crate::grafana::SCOPE;
+
+/// Algorithm prefix of the hashed secrets.
+const SCHEME: &str = "pbkdf2-sha256";
+/// Number of PBKDF2 iterations of the hashed secrets generated.
+const ITERATIONS: usize = 100_000;
+/// Realm given to the browsers asking for basic credentials.
+const REALM: &str = "userv";
+/// Query parameter holding a token, for the clients unable to set headers (WebSocket).
+const TOKEN_PARAM: &str = "access_token";
+/// Number of failed credentials remembered, to reject them again without hashing.
+const FAILED: usize = 1024;
+/// Failed authentications allowed to a client address in a window, before rejecting it.
+const ATTEMPTS: u32 = 10;
+/// Duration of the window counting the failed authentications of a client address.
+const WINDOW: Duration = Duration::from_secs(60);
+/// Number of client addresses whose failed authentications are counted.
+const CLIENTS: usize = 4096;
+
+/// Rights of an authenticated client.
+/// No endpoint changes data yet, so both roles currently have the same access.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read-only requests (`GET`, `HEAD`, Grafana queries).
+    #[default]
+    Read,
+    /// All requests, including the mutating ones.
+    Admin,
+}
+
+/// User authenticated by HTTP basic credentials, from the `[[web.users]]` sections.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct UserConfig {
+    /// Name of the user.
+    pub name: String,
+    /// Hashed password, as given by [`hash_secret`].
+    pub password: String,
+    /// Rights of the user, read-only by default.
+    #[serde(default)]
+    pub role: Role,
+}
+
+/// Bearer token, from the `[[web.tokens]]` sections.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TokenConfig {
+    /// Hashed token, as given by [`hash_secret`].
+    pub token: String,
+    /// Rights of the token holders, read-only by default.
+    #[serde(default)]
+    pub role: Role,
+}
+
+/// Secret given by a client.
+#[derive(Debug, PartialEq)]
+enum Credentials {
+    Token(String),
+    Basic { name: String, password: String },
+}
+
+impl Credentials {
+    /// Digest of the credentials, identifying them in the caches of verified and failed ones.
+    fn digest(&self) -> [u8; 32] {
+        match self {
+            Credentials::Token(token) => sha::sha256(format!("token\0{token}").as_bytes()),
+            Credentials::Basic { name, password } => {
+                sha::sha256(format!("basic\0{name}\0{password}").as_bytes())
+            }
+        }
+    }
+}
+
+/// Hashed secret, as `pbkdf2-sha256$<iterations>$<salt hex>$<hash hex>`.
+struct Hashed {
+    iterations: usize,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+impl Hashed {
+    fn parse(hashed: &str) -> Option<Hashed> {
+        let mut parts = hashed.split('$');
+        if parts.next()? != SCHEME {
+            return None;
+        }
+        let iterations = parts.next()?.parse().ok().filter(|&i| i > 0)?;
+        let salt = from_hex(parts.next()?)?;
+        let hash = from_hex(parts.next()?)?;
+        if parts.next().is_some() || hash.is_empty() {
+            return None;
+        }
+        Some(Hashed {
+            iterations,
+            salt,
+            hash,
+        })
+    }
+
+    fn verify(&self, secret: &str) -> bool {
+        let mut derived = vec![0; self.hash.len()];
+        pbkdf2_hmac(
+            secret.as_bytes(),
+            &self.salt,
+            self.iterations,
+            MessageDigest::sha256(),
+            &mut derived,
+        )
+        .is_ok()
+            && memcmp::eq(&derived, &self.hash)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Hash a password or token, to be written in the configuration.
+///
+/// # Arguments
+///
+/// - `secret` : Password or token in clear.
+///
+/// # Returns
+///
+/// - The hashed secret, as `pbkdf2-sha256$<iterations>$<salt>$<hash>`.
+/// - An error if no random salt can be generated.
+pub fn hash_secret(secret: &str) -> Result<String, Box<dyn StdError>> {
+    let mut salt = [0; 16];
+    rand_bytes(&mut salt)?;
+    let mut hash = [0; 32];
+    pbkdf2_hmac(
+        secret.as_bytes(),
+        &salt,
+        ITERATIONS,
+        MessageDigest::sha256(),
+        &mut hash,
+    )?;
+    Ok(format!(
+        "{SCHEME}${ITERATIONS}${}${}",
+        to_hex(&salt),
+        to_hex(&hash)
+    ))
+}
+
+/// Credentials accepted by the server, shared by the workers.
+/// The hash of a secret being slow to compute on purpose, the credentials
+/// already verified and the last failed ones are kept by digest, to not derive
+/// them at each request, and the failures are counted by client address.
+pub struct Auth {
+    users: Vec<(String, Hashed, Role)>,
+    tokens: Vec<(Hashed, Role)>,
+    verified: Mutex<HashMap<[u8; 32], Role>>,
+    failed: Mutex<VecDeque<[u8; 32]>>,
+    attempts: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Auth {
+    /// Create the authentication of the server.
+    ///
+    /// # Arguments
+    ///
+    /// - `users` : [`UserConfig`] list of basic credentials.
+    /// - `tokens` : [`TokenConfig`] list of bearer tokens.
+    ///
+    /// # Returns
+    ///
+    /// - The [`Auth`], letting all requests pass if no credential is given.
+    /// - An error if a secret is not hashed by [`hash_secret`].
+    pub fn new(users: &[UserConfig], tokens: &[TokenConfig]) -> Result<Auth, Box<dyn StdError>> {
+        let invalid = |what: String| format!("Config 'Invalid hashed secret' : {what}");
+        Ok(Auth {
+            users: users
+                .iter()
+                .map(|u| {
+                    Hashed::parse(&u.password)
+                        .map(|h| (u.name.clone(), h, u.role))
+                        .ok_or_else(|| invalid(format!("user {}", u.name)))
+                })
+                .collect::<Result<_, _>>()?,
+            tokens: tokens
+                .iter()
+                .enumerate()
+                .map(|(i, t)| {
+                    Hashed::parse(&t.token)
+                        .map(|h| (h, t.role))
+                        .ok_or_else(|| invalid(format!("token {}", i + 1)))
+                })
+                .collect::<Result<_, _>>()?,
+            verified: Mutex::new(HashMap::new()),
+            failed: Mutex::new(VecDeque::new()),
+            attempts: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Check if the clients must authenticate.
+    pub fn enabled(&self) -> bool {
+        !self.users.is_empty() || !self.tokens.is_empty()
+    }
+
+    /// Result of the credentials already checked.
+    ///
+    /// # Returns
+    ///
+    /// - `Some(Some(role))` for verified credentials, `Some(None)` for recently failed ones.
+    /// - `None` if the credentials must be verified.
+    fn known(&self, credentials: &Credentials) -> Option<Option<Role>> {
+        let digest = credentials.digest();
+        if let Some(role) = lock(&self.verified).get(&digest) {
+            return Some(Some(*role));
+        }
+        let mut failed = lock(&self.failed);
+        let index = failed.iter().position(|d| *d == digest)?;
+        failed.remove(index);
+        failed.push_back(digest);
+        Some(None)
+    }
+
+    /// Verify credentials against the hashed secrets, and remember the result.
+    /// The user name being checked first, an unknown user is rejected without hashing.
+    fn verify(&self, credentials: &Credentials) -> Option<Role> {
+        let role = match credentials {
+            Credentials::Token(token) => self
+                .tokens
+                .iter()
+                .find(|(hashed, _)| hashed.verify(token))
+                .map(|(_, role)| *role),
+            Credentials::Basic { name, password } => self
+                .users
+                .iter()
+                .find(|(user, _, _)| user == name)
+                .filter(|(_, hashed, _)| hashed.verify(password))
+                .map(|(_, _, role)| *role),
+        };
+        let digest = credentials.digest();
+        match role {
+            Some(role) => {
+                lock(&self.verified).insert(digest, role);
+            }
+            None => {
+                let mut failed = lock(&self.failed);
+                if failed.len() >= FAILED {
+                    failed.pop_front();
+                }
+                failed.push_back(digest);
+            }
+        }
+        role
+    }
+
+    /// Check if a client address failed to authenticate too many times in the window.
+    fn limited(&self, client: IpAddr) -> bool {
+        lock(&self.attempts)
+            .get(&client)
+            .is_some_and(|(start, count)| start.elapsed() < WINDOW && *count >= ATTEMPTS)
+    }
+
+    /// Count a failed authentication of a client address,
+    /// forgetting the oldest address once [`CLIENTS`] are counted.
+    fn fail(&self, client: IpAddr) {
+        let now = Instant::now();
+        let mut attempts = lock(&self.attempts);
+        if attempts.len() >= CLIENTS && !attempts.contains_key(&client) {
+            attempts.retain(|_, (start, _)| now.duration_since(*start) < WINDOW);
+            if attempts.len() >= CLIENTS {
+                let oldest = attempts.iter().min_by_key(|(_, (start, _))| *start);
+                if let Some(oldest) = oldest.map(|(ip, _)| *ip) {
+                    attempts.remove(&oldest);
+                }
+            }
+        }
+        let (start, count) = attempts.entry(client).or_insert((now, 0));
+        if now.duration_since(*start) >= WINDOW {
+            (*start, *count) = (now, 0);
+        }
+        *count += 1;
+    }
+}
+
+/// Read the credentials of a request, from the `Authorization` header
+/// or the `access_token` query parameter.
+fn credentials(req: &ServiceRequest) -> Option<Credentials> {
+    if let Some(value) = req.headers().get(header::AUTHORIZATION) {
+        let (scheme, value) = value.to_str().ok()?.trim().split_once(' ')?;
+        return match scheme.to_ascii_lowercase().as_str() {
+            "bearer" => Some(Credentials::Token(value.trim().to_string())),
+            "basic" => {
+                let decoded = String::from_utf8(base64::decode_block(value.trim()).ok()?).ok()?;
+                let (name, password) = decoded.split_once(':')?;
+                Some(Credentials::Basic {
+                    name: name.to_string(),
+                    password: password.to_string(),
+                })
+            }
+            _ => None,
+        };
+    }
+    web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()?
+        .get(TOKEN_PARAM)
+        .map(|token| Credentials::Token(token.clone()))
+}
+
+/// Check a request only reads data: `GET` and `HEAD` ones,
+/// and the `POST` queries of the Grafana datasource.
+fn is_read(req: &ServiceRequest) -> bool {
+    match *req.method() {
+        Method::GET | Method::HEAD => true,
+        Method::POST => req.path().starts_with(&format!("{SCOPE}/")),
+        _ => false,
+    }
+}
+
+/// Middleware rejecting the requests without valid credentials (`401`),
+/// the clients failing to authenticate too often (`429`),
+/// and the mutating requests of the read-only clients (`403`).
+/// The CORS preflight requests pass, as browsers send them without credentials.
+/// The hashes are verified on the blocking thread pool, to not stall the workers.
+pub async fn authenticate(
+    auth: web::Data<Auth>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    if !auth.enabled() || req.method() == Method::OPTIONS {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+
+    let client = req.peer_addr().map(|addr| addr.ip());
+    let role = match credentials(&req) {
+        None => None,
+        Some(credentials) => {
+            let role = match auth.known(&credentials) {
+                Some(known) => known,
+                None if client.is_some_and(|ip| auth.limited(ip)) => {
+                    let response = HttpResponse::TooManyRequests()
+                        .json(json!({ "error": "Auth 'Too many failed attempts'" }));
+                    return Ok(req.into_response(response).map_into_right_body());
+                }
+                None => {
+                    let auth = auth.clone();
+                    web::block(move || auth.verify(&credentials)).await?
+                }
+            };
+            if let (None, Some(ip)) = (role, client) {
+                auth.fail(ip);
+            }
+            role
+        }
+    };
+
+    let response = match role {
+        None => {
+            let mut response = HttpResponse::Unauthorized();
+            if !auth.users.is_empty() {
+                response
+                    .insert_header((header::WWW_AUTHENTICATE, format!("Basic realm=\"{REALM}\"")));
+            }
+            response.json(json!({ "error": "Auth 'Missing or invalid credentials'" }))
+        }
+        Some(Role::Read) if !is_read(&req) => {
+            HttpResponse::Forbidden().json(json!({ "error": "Auth 'Admin role required'" }))
+        }
+        Some(_) => {
+            return next
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body);
+        }
+    };
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        App,
+        http::StatusCode,
+        middleware::from_fn,
+        test::{TestRequest, call_service, init_service},
+    };
+
+    // Test `hash_secret` function and the verification of the hashed secrets
+    #[test]
+    fn test_hash_secret() {
+        let hashed = hash_secret("secret").unwrap();
+        assert!(hashed.starts_with("pbkdf2-sha256$100000$"));
+        assert_ne!(hashed, hash_secret("secret").unwrap());
+
+        let parsed = Hashed::parse(&hashed).unwrap();
+        assert!(parsed.verify("secret"));
+        assert!(!parsed.verify("Secret"));
+        assert!(Hashed::parse("secret").is_none());
+        assert!(Hashed::parse("pbkdf2-sha256$0$00$00").is_none());
+        assert!(
+            Auth::new(
+                &[],
+                &[TokenConfig {
+                    token: "clear".to_string(),
+                    role: Role::Read
+                }]
+            )
+            .is_err()
+        );
+    }
+
+    // Test `authenticate` middleware with tokens, basic credentials and roles
+    #[actix_web::test]
+    async fn test_authenticate() {
+        let auth = Auth::new(
+            &[UserConfig {
+                name: "admin".to_string(),
+                password: hash_secret("pass").unwrap(),
+                role: Role::Admin,
+            }],
+            &[TokenConfig {
+                token: hash_secret("reader").unwrap(),
+                role: Role::Read,
+            }],
+        )
+        .unwrap();
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(auth))
+                .wrap(from_fn(authenticate))
+                .route("/data", web::get().to(HttpResponse::Ok))
+                .route("/data", web::post().to(HttpResponse::Ok))
+                .route("/grafana/query", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        let status = async |req: TestRequest, authorization: &str| {
+            let req = req.insert_header((header::AUTHORIZATION, authorization));
+            call_service(&app, req.to_request()).await.status()
+        };
+
+        let res = call_service(&app, TestRequest::get().uri("/data").to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert!(res.headers().contains_key(header::WWW_AUTHENTICATE));
+        let res = call_service(
+            &app,
+            TestRequest::get()
+                .uri("/data?access_token=reader")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let get = || TestRequest::get().uri("/data");
+        let post = || TestRequest::post().uri("/data");
+        assert_eq!(status(get(), "Bearer reader").await, StatusCode::OK);
+        assert_eq!(status(post(), "Bearer reader").await, StatusCode::FORBIDDEN);
+        let query = TestRequest::post().uri("/grafana/query");
+        assert_eq!(status(query, "Bearer reader").await, StatusCode::OK);
+        assert_eq!(
+            status(get(), "Bearer other").await,
+            StatusCode::UNAUTHORIZED
+        );
+        let admin = format!("Basic {}", base64::encode_block(b"admin:pass"));
+        assert_eq!(status(post(), &admin).await, StatusCode::OK);
+    }
+
+    // Test `authenticate` middleware remembering and limiting the failed authentications
+    #[actix_web::test]
+    async fn test_authenticate_failures() {
+        let auth = web::Data::new(
+            Auth::new(
+                &[UserConfig {
+                    name: "admin".to_string(),
+                    password: hash_secret("pass").unwrap(),
+                    role: Role::Admin,
+                }],
+                &[TokenConfig {
+                    token: hash_secret("reader").unwrap(),
+                    role: Role::Read,
+                }],
+            )
+            .unwrap(),
+        );
+        let app = init_service(
+            App::new()
+                .app_data(auth.clone())
+                .wrap(from_fn(authenticate))
+                .route("/data", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let status = async |client: &str, authorization: &str| {
+            let req = TestRequest::get()
+                .uri("/data")
+                .peer_addr(format!("{client}:4000").parse().unwrap())
+                .insert_header((header::AUTHORIZATION, authorization));
+            call_service(&app, req.to_request()).await.status()
+        };
+
+        let unknown = Credentials::Basic {
+            name: "other".to_string(),
+            password: "pass".to_string(),
+        };
+        assert_eq!(auth.known(&unknown), None);
+        assert_eq!(auth.verify(&unknown), None);
+        assert_eq!(auth.known(&unknown), Some(None));
+
+        for i in 0..ATTEMPTS {
+            let token = format!("Bearer wrong{i}");
+            assert_eq!(status("10.0.0.1", &token).await, StatusCode::UNAUTHORIZED);
+        }
+        assert!(auth.limited("10.0.0.1".parse().unwrap()));
+        assert_eq!(
+            status("10.0.0.1", "Bearer other").await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            status("10.0.0.1", "Bearer wrong0").await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status("10.0.0.2", "Bearer reader").await, StatusCode::OK);
+        assert_eq!(status("10.0.0.1", "Bearer reader").await, StatusCode::OK);
+    }
+}
@@ -0,0 +1,224 @@
+//! # File cors
+//!
+//! This file provides the Cross-Origin Resource Sharing headers of the endpoints,
+//! allowing the pages of other origins to read them according to rules by path.
+
+use actix_web::{
+    Error, HttpResponse,
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        Method,
+        header::{self, HeaderValue},
+    },
+    middleware::Next,
+    web,
+};
+use serde::Deserialize;
+
+/// Duration in seconds the browsers may keep a preflight response.
+const MAX_AGE: &str = "3600";
+
+/// Origins allowed to request the endpoints under a path, from the `[[web.cors]]` sections.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CorsRule {
+    /// Path prefix of the endpoints concerned (`/api`, `/api/metrics`).
+    pub path: String,
+    /// Allowed origins (`https://grafana.example.org`), `*` for any.
+    pub origins: Vec<String>,
+    /// Allowed methods.
+    #[serde(default = "default_methods")]
+    pub methods: Vec<String>,
+    /// Allowed request headers.
+    #[serde(default = "default_headers")]
+    pub headers: Vec<String>,
+}
+
+fn default_methods() -> Vec<String> {
+    vec!["GET".to_string()]
+}
+
+fn default_headers() -> Vec<String> {
+    vec!["Authorization".to_string(), "Content-Type".to_string()]
+}
+
+impl CorsRule {
+    fn matches(&self, path: &str) -> bool {
+        let prefix = self.path.trim_end_matches('/');
+        path.strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.origins.iter().any(|o| o == "*" || o == origin)
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.methods.iter().any(|m| m.eq_ignore_ascii_case(method))
+    }
+}
+
+/// CORS rules of the server, the most specific path applying.
+#[derive(Debug, Clone, Default)]
+pub struct Cors {
+    rules: Vec<CorsRule>,
+}
+
+impl Cors {
+    /// Create the CORS rules of the server.
+    ///
+    /// # Arguments
+    ///
+    /// - `rules` : [`CorsRule`] list, in any order.
+    pub fn new(rules: &[CorsRule]) -> Self {
+        let mut rules = rules.to_vec();
+        rules.sort_by_key(|r| std::cmp::Reverse(r.path.trim_end_matches('/').len()));
+        Cors { rules }
+    }
+
+    /// Rule of the most specific path matching, if it allows the origin.
+    fn rule(&self, path: &str, origin: &str) -> Option<&CorsRule> {
+        self.rules
+            .iter()
+            .find(|r| r.matches(path))
+            .filter(|r| r.allows(origin))
+    }
+}
+
+/// Middleware answering the preflight requests and adding the CORS headers
+/// to the responses of the allowed origins. Other requests are left unchanged,
+/// so the browsers keep blocking them.
+pub async fn cors(
+    cors: web::Data<Cors>,
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let origin = req
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|o| o.to_str().ok())
+        .map(str::to_string);
+    let Some((origin, rule)) = origin.and_then(|o| {
+        let rule = cors.rule(req.path(), &o)?.clone();
+        Some((o, rule))
+    }) else {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    };
+    let origin = HeaderValue::from_str(&origin)?;
+
+    let requested = req
+        .headers()
+        .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+        .and_then(|m| m.to_str().ok());
+    if req.method() == Method::OPTIONS
+        && let Some(requested) = requested
+    {
+        if !rule.allows_method(requested) {
+            return next
+                .call(req)
+                .await
+                .map(ServiceResponse::map_into_left_body);
+        }
+        let response = HttpResponse::NoContent()
+            .insert_header((header::ACCESS_CONTROL_ALLOW_ORIGIN, origin))
+            .insert_header((
+                header::ACCESS_CONTROL_ALLOW_METHODS,
+                rule.methods.join(", "),
+            ))
+            .insert_header((
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                rule.headers.join(", "),
+            ))
+            .insert_header((header::ACCESS_CONTROL_MAX_AGE, MAX_AGE))
+            .insert_header((header::VARY, "Origin"))
+            .finish();
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    if !rule.allows_method(req.method().as_str()) {
+        return next
+            .call(req)
+            .await
+            .map(ServiceResponse::map_into_left_body);
+    }
+    let mut res = next.call(req).await?;
+    let headers = res.headers_mut();
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+    headers.insert(header::VARY, HeaderValue::from_static("Origin"));
+    Ok(res.map_into_left_body())
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        App,
+        http::StatusCode,
+        middleware::from_fn,
+        test::{TestRequest, call_service, init_service},
+    };
+
+    // Test `cors` middleware with preflight and simple requests by path
+    #[actix_web::test]
+    async fn test_cors() {
+        let cors = Cors::new(&[
+            CorsRule {
+                path: "/api".to_string(),
+                origins: vec!["https://a.example".to_string()],
+                methods: default_methods(),
+                headers: default_headers(),
+            },
+            CorsRule {
+                path: "/api/public/".to_string(),
+                origins: vec!["*".to_string()],
+                methods: default_methods(),
+                headers: default_headers(),
+            },
+        ]);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(cors))
+                .wrap(from_fn(super::cors))
+                .route("/api/data", web::get().to(HttpResponse::Ok))
+                .route("/api/public/data", web::get().to(HttpResponse::Ok))
+                .route("/apidata", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let allowed = |headers: &header::HeaderMap| {
+            headers
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .map(|o| o.to_str().unwrap().to_string())
+        };
+
+        let req = TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/api/data")
+            .insert_header((header::ORIGIN, "https://a.example"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"));
+        let res = call_service(&app, req.to_request()).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(allowed(res.headers()).as_deref(), Some("https://a.example"));
+
+        let request = |uri: &str, origin: &str| {
+            TestRequest::get()
+                .uri(uri)
+                .insert_header((header::ORIGIN, origin))
+                .to_request()
+        };
+        let res = call_service(&app, request("/api/data", "https://a.example")).await;
+        assert_eq!(allowed(res.headers()).as_deref(), Some("https://a.example"));
+        let res = call_service(&app, request("/api/data", "https://b.example")).await;
+        assert_eq!(allowed(res.headers()), None);
+        let res = call_service(&app, request("/api/public/data", "https://b.example")).await;
+        assert_eq!(allowed(res.headers()).as_deref(), Some("https://b.example"));
+        let res = call_service(&app, request("/apidata", "https://a.example")).await;
+        assert_eq!(allowed(res.headers()), None);
+    }
+}
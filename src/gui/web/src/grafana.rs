@@ -0,0 +1,748 @@
+//! # File grafana
+//!
+//! This file provides the endpoints of the Grafana SimpleJSON datasource protocol,
+//! exposing the numeric fields of the probes tables as targets `<table>.<column>[@<entity>]`.
+
+use actix_web::{HttpResponse, web};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::{collections::BTreeSet, error::Error};
+use userv_core::{
+    core::{SQLiteKey, SQLiteType, SqlFieldDescriptor},
+    export::{json_rows, json_value},
+    query::{self, ENTITY_COLUMNS, Filter, TIMESTAMP, list_tables, numeric_columns},
+};
+
+use crate::api::{ApiError, ApiState, with_database};
+
+/// Path of the datasource endpoints, to give as URL of the Grafana datasource.
+pub const SCOPE: &str = "/grafana";
+/// Tag key filtering the series by entity.
+const ENTITY_TAG: &str = "entity";
+
+/// Numeric column of a table, selectable as target.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Target {
+    /// Name of the table.
+    pub table: String,
+    /// Name of the column, unit suffix included.
+    pub column: String,
+    /// Unit concatenated to the column name, if known.
+    pub unit: Option<String>,
+}
+
+impl Target {
+    /// Name of the target shown in Grafana, with its unit.
+    fn text(&self) -> String {
+        match &self.unit {
+            Some(unit) => {
+                let name = self
+                    .column
+                    .strip_suffix(&format!("_{unit}"))
+                    .unwrap_or(&self.column);
+                format!("{}.{name} ({unit})", self.table)
+            }
+            None => format!("{}.{}", self.table, self.column),
+        }
+    }
+
+    /// Identifier of the target in the queries.
+    fn value(&self) -> String {
+        format!("{}.{}", self.table, self.column)
+    }
+}
+
+/// Time range of a request.
+#[derive(Debug, Deserialize)]
+pub struct Range {
+    /// Lower bound (RFC 3339).
+    pub from: String,
+    /// Upper bound (RFC 3339).
+    pub to: String,
+}
+
+/// Body of the `/search` endpoint.
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchRequest {
+    /// Text to find in the targets, all targets if empty.
+    #[serde(default)]
+    pub target: String,
+}
+
+/// Target of a `/query` request.
+#[derive(Debug, Deserialize)]
+pub struct QueryTarget {
+    /// Target `<table>.<column>[@<entity>]`, or `<table>` for a table panel.
+    #[serde(default)]
+    pub target: String,
+    /// Panel format, `timeserie` or `table`.
+    #[serde(rename = "type", default)]
+    pub kind: Option<String>,
+    /// Target disabled in the panel.
+    #[serde(default)]
+    pub hide: bool,
+}
+
+/// Ad hoc filter of a `/query` request.
+#[derive(Debug, Deserialize)]
+pub struct AdhocFilter {
+    /// Tag key, among the `/tag-keys` ones.
+    pub key: String,
+    /// Comparison operator, only `=` is supported.
+    pub operator: String,
+    /// Tag value.
+    pub value: String,
+}
+
+/// Body of the `/query` endpoint.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryRequest {
+    /// Time range of the panel.
+    pub range: Range,
+    /// Interval between two points wanted by the panel, in milliseconds.
+    #[serde(default)]
+    pub interval_ms: Option<u64>,
+    /// Targets of the panel.
+    #[serde(default)]
+    pub targets: Vec<QueryTarget>,
+    /// Ad hoc filters of the dashboard.
+    #[serde(default)]
+    pub adhoc_filters: Vec<AdhocFilter>,
+}
+
+/// Body of the `/annotations` endpoint.
+#[derive(Debug, Deserialize)]
+pub struct AnnotationRequest {
+    /// Time range of the dashboard.
+    pub range: Range,
+    /// Annotation definition, its `query` being a target whose changes are annotated.
+    pub annotation: Value,
+}
+
+/// Body of the `/tag-values` endpoint.
+#[derive(Debug, Deserialize)]
+pub struct TagValuesRequest {
+    /// Tag key, among the `/tag-keys` ones.
+    pub key: String,
+}
+
+/// Register the datasource endpoints under [`SCOPE`].
+///
+/// # Arguments
+///
+/// - `cfg` : Service configuration of the application, which must provide the [`ApiState`] data.
+pub fn grafana(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope(SCOPE)
+            .route("", web::get().to(health))
+            .route("/", web::get().to(health))
+            .route("/search", web::post().to(search))
+            .route("/query", web::post().to(query))
+            .route("/annotations", web::post().to(annotations))
+            .route("/tag-keys", web::post().to(tag_keys))
+            .route("/tag-values", web::post().to(tag_values)),
+    );
+}
+
+/// Tables of the probes with the descriptors of their fields.
+fn schemas() -> Vec<(&'static str, Vec<SqlFieldDescriptor>)> {
+    [
+        board::schema(),
+        cgroup::schema(),
+        cpu::schema(),
+        exec::schema(),
+        gpu::schema(),
+        memory::schema(),
+        network::schema(),
+        storage::schema(),
+        system::schema(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Column identifying the entity of the rows of a table.
+fn entity_column(table: &str) -> Option<&'static str> {
+    ENTITY_COLUMNS
+        .iter()
+        .find(|(name, _)| *name == table)
+        .map(|(_, column)| *column)
+}
+
+/// List the targets, being the numeric fields of the probes tables
+/// and the numeric columns of the other stored tables.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+///
+/// # Returns
+///
+/// - The [`Target`] list, grouped by table.
+/// - An error if the stored tables can't be listed.
+pub fn catalog(conn: &Connection) -> Result<Vec<Target>, Box<dyn Error>> {
+    let mut targets = Vec::new();
+    for (table, fields) in schemas() {
+        for field in fields {
+            if matches!(field.field_type, SQLiteType::Integer | SQLiteType::Real)
+                && field.field_key == SQLiteKey::None
+                && Some(field.field_name) != entity_column(table)
+            {
+                let column = match field.field_unit {
+                    Some(unit) => format!("{}_{unit}", field.field_name),
+                    None => field.field_name.to_string(),
+                };
+                targets.push(Target {
+                    table: table.to_string(),
+                    column,
+                    unit: field.field_unit.map(str::to_string),
+                });
+            }
+        }
+    }
+    for table in list_tables(conn)? {
+        if targets.iter().all(|t| t.table != table) {
+            for column in numeric_columns(conn, &table)? {
+                targets.push(Target {
+                    table: table.clone(),
+                    column,
+                    unit: None,
+                });
+            }
+        }
+    }
+    Ok(targets)
+}
+
+/// Parse a target.
+///
+/// # Arguments
+///
+/// - `target` : Target like `network_data`, `cpu_power.power_W` or `network_data.received_MB@eth0`.
+///
+/// # Returns
+///
+/// The table, the column if given and the entity if given, if the table is not empty.
+pub fn parse_target(target: &str) -> Option<(&str, Option<&str>, Option<&str>)> {
+    let (path, entity) = match target.trim().split_once('@') {
+        Some((path, entity)) => (path, Some(entity)),
+        None => (target.trim(), None),
+    };
+    let (table, column) = match path.split_once('.') {
+        Some((table, column)) => (table, Some(column)),
+        None => (path, None),
+    };
+    if table.is_empty() || column == Some("") || entity == Some("") {
+        return None;
+    }
+    Some((table, column, entity))
+}
+
+/// SQL expression of the milliseconds since epoch of a timestamp expression.
+fn epoch_ms(time: &str) -> String {
+    format!("CAST(ROUND((julianday({time}) - 2440587.5) * 86400000) AS INTEGER)")
+}
+
+/// Text of a value, without the quotes of the strings.
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Check a table is stored and a column is one of its numeric ones.
+fn check_column(conn: &Connection, table: &str, column: &str) -> Result<(), ApiError> {
+    if !list_tables(conn)?.iter().any(|t| t == table) {
+        return Err(ApiError::NotFound(format!("Data 'Unknown table {table}'")));
+    }
+    if !numeric_columns(conn, table)?.iter().any(|c| c == column) {
+        return Err(ApiError::BadRequest(format!(
+            "Arguments 'Unknown numeric column {table}.{column}'"
+        )));
+    }
+    Ok(())
+}
+
+/// Answer the connection test of the datasource.
+async fn health() -> HttpResponse {
+    HttpResponse::Ok().finish()
+}
+
+/// List the targets containing the searched text.
+async fn search(
+    state: web::Data<ApiState>,
+    body: Option<web::Json<SearchRequest>>,
+) -> Result<HttpResponse, ApiError> {
+    let search = body.map(|b| b.into_inner()).unwrap_or_default();
+    let text = search.target.to_lowercase();
+    let targets = with_database(&state, |conn| Ok(catalog(conn)?)).await?;
+    let targets: Vec<Value> = targets
+        .iter()
+        .filter(|t| t.value().to_lowercase().contains(&text))
+        .map(|t| json!({ "text": t.text(), "value": t.value() }))
+        .collect();
+    Ok(HttpResponse::Ok().json(targets))
+}
+
+/// Series of a numeric column averaged by interval, one per entity.
+fn time_series(
+    conn: &Connection,
+    target: &str,
+    filter: &Filter,
+    step: u64,
+) -> Result<Vec<Value>, ApiError> {
+    let (table, column, entity) = parse_target(target)
+        .ok_or_else(|| ApiError::BadRequest(format!("Arguments 'Invalid target {target}'")))?;
+    let column = column
+        .ok_or_else(|| ApiError::BadRequest(format!("Arguments 'No column in target {target}'")))?;
+    check_column(conn, table, column)?;
+    let filter = Filter {
+        entity: entity.map(str::to_string).or(filter.entity.clone()),
+        ..filter.clone()
+    };
+
+    let selection = query::aggregate(conn, table, &filter, step)
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let entity = match &selection.entity_column {
+        Some(entity) => format!("CAST(q.{} AS TEXT)", query::quote(entity)),
+        None => "NULL".to_string(),
+    };
+    let request = format!(
+        "SELECT {}, {entity}, q.{} FROM ({}) q",
+        epoch_ms(&format!("q.{TIMESTAMP}")),
+        query::quote(column),
+        selection.query
+    );
+
+    let mut series: Vec<(Option<String>, Vec<Value>)> = Vec::new();
+    let mut stmt = conn
+        .prepare(&request)
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+    let mut rows = stmt
+        .query(&*selection.params())
+        .map_err(|e| ApiError::Internal(e.to_string()))?;
+    while let Some(row) = rows.next().map_err(|e| ApiError::Internal(e.to_string()))? {
+        let point = || -> rusqlite::Result<(i64, Option<String>, Value)> {
+            Ok((row.get(0)?, row.get(1)?, json_value(row.get_ref(2)?)))
+        };
+        let (time, entity, value) = point().map_err(|e| ApiError::Internal(e.to_string()))?;
+        let datapoint = json!([value, time]);
+        match series.iter_mut().find(|(e, _)| *e == entity) {
+            Some((_, points)) => points.push(datapoint),
+            None => series.push((entity, vec![datapoint])),
+        }
+    }
+
+    Ok(series
+        .into_iter()
+        .map(|(entity, datapoints)| {
+            let name = match entity {
+                Some(entity) => format!("{table}.{column}@{entity}"),
+                None => format!("{table}.{column}"),
+            };
+            json!({ "target": name, "datapoints": datapoints })
+        })
+        .collect())
+}
+
+/// Rows of a table matching the filter, with all their columns.
+fn table(conn: &Connection, target: &str, filter: &Filter) -> Result<Value, ApiError> {
+    let (table, _, entity) = parse_target(target)
+        .ok_or_else(|| ApiError::BadRequest(format!("Arguments 'Invalid target {target}'")))?;
+    if !list_tables(conn)?.iter().any(|t| t == table) {
+        return Err(ApiError::NotFound(format!("Data 'Unknown table {table}'")));
+    }
+    let filter = Filter {
+        entity: entity.map(str::to_string).or(filter.entity.clone()),
+        ..filter.clone()
+    };
+    let selection =
+        query::select(conn, table, &filter).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    let rows = json_rows(conn, &selection)?;
+
+    let columns: Vec<Value> = rows
+        .first()
+        .map(|row| {
+            row.iter()
+                .map(|(name, value)| {
+                    let kind = if Some(name) == selection.time_column.as_ref() {
+                        "time"
+                    } else if value.is_number() {
+                        "number"
+                    } else {
+                        "string"
+                    };
+                    json!({ "text": name, "type": kind })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let rows: Vec<Vec<Value>> = rows
+        .into_iter()
+        .map(|row| row.into_iter().map(|(_, value)| value).collect())
+        .collect();
+    Ok(json!({ "type": "table", "columns": columns, "rows": rows }))
+}
+
+/// Give the data of each target of a panel over its time range.
+async fn query(
+    state: web::Data<ApiState>,
+    body: web::Json<QueryRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let request = body.into_inner();
+    let mut entity = None;
+    for filter in &request.adhoc_filters {
+        if filter.key != ENTITY_TAG || filter.operator != "=" {
+            return Err(ApiError::BadRequest(format!(
+                "Arguments 'Unsupported filter {} {}'",
+                filter.key, filter.operator
+            )));
+        }
+        entity = Some(filter.value.clone());
+    }
+    let filter = Filter {
+        from: Some(request.range.from),
+        to: Some(request.range.to),
+        entity,
+    };
+    let step = request.interval_ms.map_or(1, |ms| (ms / 1000).max(1));
+
+    let response = with_database(&state, move |conn| {
+        let mut response = Vec::new();
+        for target in request.targets.iter().filter(|t| !t.hide) {
+            match target.kind.as_deref() {
+                Some("table") => response.push(table(conn, &target.target, &filter)?),
+                _ => response.extend(time_series(conn, &target.target, &filter, step)?),
+            }
+        }
+        Ok(response)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Annotate each change of value of the target of an annotation over the time range.
+async fn annotations(
+    state: web::Data<ApiState>,
+    body: web::Json<AnnotationRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let request = body.into_inner();
+    let target = request.annotation["query"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let filter = Filter {
+        from: Some(request.range.from),
+        to: Some(request.range.to),
+        entity: None,
+    };
+    let annotation = request.annotation;
+
+    let response = with_database(&state, move |conn| {
+        let (table, column, entity) = parse_target(&target)
+            .ok_or_else(|| ApiError::BadRequest(format!("Arguments 'Invalid target {target}'")))?;
+        let column = column.ok_or_else(|| {
+            ApiError::BadRequest(format!("Arguments 'No column in target {target}'"))
+        })?;
+        if !query::list_columns(conn, table)?
+            .iter()
+            .any(|c| c == column)
+        {
+            return Err(ApiError::NotFound(format!(
+                "Data 'Unknown column {table}.{column}'"
+            )));
+        }
+        let filter = Filter {
+            entity: entity.map(str::to_string),
+            ..filter
+        };
+        let selection =
+            query::select(conn, table, &filter).map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        let Some(time) = &selection.time_column else {
+            return Err(ApiError::BadRequest(format!(
+                "Arguments 'No timestamp column for table {table}'"
+            )));
+        };
+        let entity = match &selection.entity_column {
+            Some(entity) => format!("CAST(q.{} AS TEXT)", query::quote(entity)),
+            None => "NULL".to_string(),
+        };
+        let request = format!(
+            "SELECT {}, {entity}, q.{} FROM ({}) q",
+            epoch_ms(&format!("q.{}", query::quote(time))),
+            query::quote(column),
+            selection.query
+        );
+
+        let internal = |e: rusqlite::Error| ApiError::Internal(e.to_string());
+        let mut stmt = conn.prepare(&request).map_err(internal)?;
+        let mut rows = stmt.query(&*selection.params()).map_err(internal)?;
+        let mut previous: Map<String, Value> = Map::new();
+        let mut list = Vec::new();
+        while let Some(row) = rows.next().map_err(internal)? {
+            let time: i64 = row.get(0).map_err(internal)?;
+            let entity: Option<String> = row.get(1).map_err(internal)?;
+            let value = json_value(row.get_ref(2).map_err(internal)?);
+            let key = entity.clone().unwrap_or_default();
+            match previous.insert(key, value.clone()) {
+                Some(old) if old != value => {
+                    let mut tags = vec![json!(table)];
+                    tags.extend(entity.as_ref().map(|e| json!(e)));
+                    let title = match &entity {
+                        Some(entity) => format!("{table}.{column}@{entity}"),
+                        None => format!("{table}.{column}"),
+                    };
+                    list.push(json!({
+                        "annotation": annotation,
+                        "time": time,
+                        "title": title,
+                        "text": format!("{} → {}", text(&old), text(&value)),
+                        "tags": tags,
+                    }));
+                }
+                _ => (),
+            }
+        }
+        Ok(list)
+    })
+    .await?;
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// List the keys of the ad hoc filters.
+async fn tag_keys() -> HttpResponse {
+    HttpResponse::Ok().json(json!([{ "type": "string", "text": ENTITY_TAG }]))
+}
+
+/// List the values of an ad hoc filter key, being the entities of the stored tables.
+async fn tag_values(
+    state: web::Data<ApiState>,
+    body: web::Json<TagValuesRequest>,
+) -> Result<HttpResponse, ApiError> {
+    if body.key != ENTITY_TAG {
+        return Ok(HttpResponse::Ok().json(json!([])));
+    }
+    let values = with_database(&state, |conn| {
+        let tables = list_tables(conn)?;
+        let mut values = BTreeSet::new();
+        for (table, column) in ENTITY_COLUMNS {
+            if tables.iter().any(|t| t == table) {
+                let request = format!(
+                    "SELECT DISTINCT CAST({0} AS TEXT) FROM {1} WHERE {0} IS NOT NULL",
+                    query::quote(column),
+                    query::quote(table)
+                );
+                let internal = |e: rusqlite::Error| ApiError::Internal(e.to_string());
+                let mut stmt = conn.prepare(&request).map_err(internal)?;
+                for value in stmt.query_map([], |row| row.get(0)).map_err(internal)? {
+                    values.insert(value.map_err(internal)?);
+                }
+            }
+        }
+        Ok(values)
+    })
+    .await?;
+    let values: Vec<Value> = values
+        .into_iter()
+        .map(|value: String| json!({ "text": value }))
+        .collect();
+    Ok(HttpResponse::Ok().json(values))
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{
+        App,
+        http::StatusCode,
+        test::{TestRequest, call_and_read_body_json, call_service, init_service},
+    };
+    use tempfile::NamedTempFile;
+
+    fn database() -> NamedTempFile {
+        let file = NamedTempFile::new().unwrap();
+        let conn = Connection::open(file.path()).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE network_data (id INTEGER PRIMARY KEY, timestamp TEXT, name TEXT, received_MB REAL);
+            INSERT INTO network_data (timestamp, name, received_MB) VALUES
+                ('2025-01-01T10:00:00.000Z', 'eth0', 1.0),
+                ('2025-01-01T10:00:30.000Z', 'eth0', 3.0),
+                ('2025-01-01T10:01:00.000Z', 'eth0', 5.0),
+                ('2025-01-01T10:01:00.000Z', 'lo', 0.0);
+            CREATE TABLE storage_data (id INTEGER PRIMARY KEY, timestamp TEXT, name TEXT, file_mount TEXT, space_available_MB INTEGER);
+            INSERT INTO storage_data (timestamp, name, file_mount, space_available_MB) VALUES
+                ('2025-01-01T10:00:00.000Z', 'sda', '/', 10),
+                ('2025-01-01T10:01:00.000Z', 'sda', '/', 9),
+                ('2025-01-01T10:02:00.000Z', 'sda', '/mnt', 9);",
+        )
+        .unwrap();
+        file
+    }
+
+    macro_rules! app {
+        ($file:expr) => {
+            init_service(
+                App::new()
+                    .app_data(web::Data::new(ApiState {
+                        database: $file.path().to_path_buf(),
+                    }))
+                    .configure(grafana),
+            )
+            .await
+        };
+    }
+
+    const RANGE: &str = r#"{"from": "2025-01-01T00:00:00.000Z", "to": "2025-01-02T00:00:00.000Z"}"#;
+
+    // Test `parse_target` function with the available forms
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            parse_target("network_data.received_MB@eth0"),
+            Some(("network_data", Some("received_MB"), Some("eth0")))
+        );
+        assert_eq!(parse_target("cpu_data"), Some(("cpu_data", None, None)));
+        assert_eq!(parse_target(""), None);
+        assert_eq!(parse_target("cpu_data."), None);
+        assert_eq!(parse_target("cpu_power.power_W@"), None);
+    }
+
+    // Test `catalog` function with described and stored tables
+    #[test]
+    fn test_catalog() {
+        let file = database();
+        let conn = Connection::open(file.path()).unwrap();
+        let res = catalog(&conn).unwrap();
+
+        let received = res.iter().find(|t| t.value() == "network_data.received_MB");
+        assert_eq!(received.unwrap().text(), "network_data.received (MB)");
+        assert!(res.iter().any(|t| t.value() == "cpu_power.power_W"));
+        let space = res
+            .iter()
+            .find(|t| t.value() == "storage_data.space_available_MB");
+        assert_eq!(space.unwrap().unit.as_deref(), Some("MB"));
+        assert!(
+            res.iter()
+                .any(|t| t.value() == "system_process_data.cpu_usage")
+        );
+        assert!(!res.iter().any(|t| t.value() == "system_process_data.pid"));
+        assert!(
+            !res.iter()
+                .any(|t| t.column == "id" || t.column == "timestamp")
+        );
+        assert!(!res.iter().any(|t| t.value() == "gpu_data.gpu_bus_id"));
+    }
+
+    // Test `/search`, `/tag-keys` and `/tag-values` endpoints
+    #[actix_web::test]
+    async fn test_search_tags() {
+        let file = database();
+        let app = app!(file);
+
+        let req = TestRequest::get().uri("/grafana/").to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = TestRequest::post()
+            .uri("/grafana/search")
+            .set_json(json!({ "target": "network_data.rec" }))
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res.as_array().unwrap().len(), 1);
+        assert_eq!(res[0]["value"], "network_data.received_MB");
+
+        let req = TestRequest::post().uri("/grafana/tag-keys").to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res[0]["text"], "entity");
+
+        let req = TestRequest::post()
+            .uri("/grafana/tag-values")
+            .set_json(json!({ "key": "entity" }))
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            res,
+            json!([{ "text": "eth0" }, { "text": "lo" }, { "text": "sda" }])
+        );
+    }
+
+    // Test `/query` endpoint with time series, entity filters and table format
+    #[actix_web::test]
+    async fn test_query() {
+        let file = database();
+        let app = app!(file);
+
+        let body = format!(
+            r#"{{"range": {RANGE}, "intervalMs": 60000, "targets": [{{"target": "network_data.received_MB", "type": "timeserie"}}]}}"#
+        );
+        let req = TestRequest::post()
+            .uri("/grafana/query")
+            .insert_header(("content-type", "application/json"))
+            .set_payload(body)
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res[0]["target"], "network_data.received_MB@eth0");
+        assert_eq!(
+            res[0]["datapoints"],
+            json!([[2.0, 1735725600000_i64], [5.0, 1735725660000_i64]])
+        );
+        assert_eq!(res[1]["target"], "network_data.received_MB@lo");
+
+        let body = format!(
+            r#"{{"range": {RANGE}, "targets": [{{"target": "network_data.received_MB"}}, {{"target": "storage_data", "type": "table"}}],
+            "adhocFilters": [{{"key": "entity", "operator": "=", "value": "eth0"}}]}}"#
+        );
+        let req = TestRequest::post()
+            .uri("/grafana/query")
+            .insert_header(("content-type", "application/json"))
+            .set_payload(body)
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res[0]["datapoints"].as_array().unwrap().len(), 3);
+        assert_eq!(res[1]["type"], "table");
+        assert_eq!(res[1]["rows"].as_array().unwrap().len(), 0);
+
+        for (target, status) in [
+            ("unknown.value", StatusCode::NOT_FOUND),
+            ("network_data.name", StatusCode::BAD_REQUEST),
+            ("network_data", StatusCode::BAD_REQUEST),
+        ] {
+            let body = format!(r#"{{"range": {RANGE}, "targets": [{{"target": "{target}"}}]}}"#);
+            let req = TestRequest::post()
+                .uri("/grafana/query")
+                .insert_header(("content-type", "application/json"))
+                .set_payload(body)
+                .to_request();
+            assert_eq!(call_service(&app, req).await.status(), status, "{target}");
+        }
+    }
+
+    // Test `/annotations` endpoint on the changes of a column
+    #[actix_web::test]
+    async fn test_annotations() {
+        let file = database();
+        let app = app!(file);
+
+        let body = format!(
+            r#"{{"range": {RANGE}, "annotation": {{"name": "mounts", "query": "storage_data.file_mount"}}}}"#
+        );
+        let req = TestRequest::post()
+            .uri("/grafana/annotations")
+            .insert_header(("content-type", "application/json"))
+            .set_payload(body)
+            .to_request();
+        let res: Value = call_and_read_body_json(&app, req).await;
+        assert_eq!(res.as_array().unwrap().len(), 1);
+        assert_eq!(res[0]["time"], 1735725720000_i64);
+        assert_eq!(res[0]["title"], "storage_data.file_mount@sda");
+        assert_eq!(res[0]["text"], "/ → /mnt");
+        assert_eq!(res[0]["annotation"]["name"], "mounts");
+    }
+}
@@ -1,48 +1,171 @@
-use actix::ActorContext;
-use actix::{Actor, StreamHandler};
-use actix_web::{App, Error, HttpRequest, HttpResponse, HttpServer, Result, web};
-use actix_web_actors::ws;
+//! # Web GUI library
+//!
+//! This library provides the web server of the JSON API, the live WebSocket feed
+//! and the dashboard, alone or with the probes sampling in the same runtime.
 
-const ADDR: &str = "localhost";
-const PORT: u16 = 8080;
+use actix_web::{App, HttpServer, dev::Server, middleware::from_fn, rt, web};
+use log::error;
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
+use serde::Deserialize;
+use std::{error::Error, path::PathBuf, sync::Arc, time::Duration};
+use userv_core::core::DATABASE;
 
-// Handler pour la connexion WebSocket
-async fn ws_index(req: HttpRequest, stream: web::Payload) -> Result<HttpResponse, Error> {
-    ws::start(MyWebSocket {}, &req, stream)
-}
+pub mod api;
+pub mod assets;
+pub mod auth;
+pub mod cors;
+pub mod grafana;
+pub mod live;
+use api::{ApiState, api};
+use assets::assets;
+use auth::{Auth, TokenConfig, UserConfig, authenticate};
+use cors::{Cors, CorsRule, cors};
+use grafana::grafana;
+use live::live;
 
-// Structure WebSocket
-struct MyWebSocket;
+const HEADER: &str = "WEB";
 
-impl Actor for MyWebSocket {
-    type Context = ws::WebsocketContext<Self>;
+/// Parameters of the web server, from the `[web]` section of the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct WebConfig {
+    /// Address the server listens on.
+    pub bind: String,
+    /// Port the server listens on.
+    pub port: u16,
+    /// Certificate of the HTTPS server, plain HTTP if not set.
+    pub tls: Option<TlsConfig>,
+    /// Users authenticated by HTTP basic credentials.
+    pub users: Vec<UserConfig>,
+    /// Bearer tokens. Without users nor tokens, no authentication is required.
+    pub tokens: Vec<TokenConfig>,
+    /// Origins allowed to request the endpoints from other sites.
+    pub cors: Vec<CorsRule>,
 }
 
-impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for MyWebSocket {
-    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
-        match msg {
-            Ok(ws::Message::Text(text)) => ctx.text(text),
-            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
-            Ok(ws::Message::Pong(_)) => {}
-            Ok(ws::Message::Binary(bin)) => ctx.binary(bin),
-            Ok(ws::Message::Close(reason)) => {
-                ctx.close(reason);
-                ctx.stop();
-            }
-            _ => (),
+impl Default for WebConfig {
+    fn default() -> Self {
+        WebConfig {
+            bind: "localhost".to_string(),
+            port: 8080,
+            tls: None,
+            users: Vec::new(),
+            tokens: Vec::new(),
+            cors: Vec::new(),
         }
     }
 }
 
-/// Providing WebSocket route and all root WEB folder.
-#[actix_web::main]
-pub async fn web() -> std::io::Result<()> {
-    HttpServer::new(|| {
+/// Certificate and private key of the HTTPS server, from the `[web.tls]` section.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TlsConfig {
+    /// PEM file of the certificate chain.
+    pub cert: PathBuf,
+    /// PEM file of the private key.
+    pub key: PathBuf,
+}
+
+/// Build the TLS acceptor of the HTTPS server.
+fn acceptor(tls: &TlsConfig) -> Result<openssl::ssl::SslAcceptorBuilder, Box<dyn Error>> {
+    let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls())?;
+    builder.set_certificate_chain_file(&tls.cert).map_err(|e| {
+        format!(
+            "Config 'Invalid TLS certificate' : {} {e}",
+            tls.cert.display()
+        )
+    })?;
+    builder
+        .set_private_key_file(&tls.key, SslFiletype::PEM)
+        .map_err(|e| {
+            format!(
+                "Config 'Invalid TLS private key' : {} {e}",
+                tls.key.display()
+            )
+        })?;
+    builder.check_private_key()?;
+    Ok(builder)
+}
+
+/// Create the server providing JSON API, Grafana datasource, live WebSocket route
+/// and the bundled dashboard.
+/// It must be awaited inside an actix runtime to run.
+///
+/// # Arguments
+///
+/// - `config` : [`WebConfig`] parameters of the server.
+///
+/// # Returns
+///
+/// - The bound [`Server`], over HTTPS if a certificate is configured.
+/// - An error if the address can't be bound, or the certificate or credentials are invalid.
+pub fn server(config: &WebConfig) -> Result<Server, Box<dyn Error>> {
+    let state = web::Data::new(ApiState {
+        database: DATABASE.into(),
+    });
+    let auth = web::Data::new(Auth::new(&config.users, &config.tokens)?);
+    let rules = web::Data::new(Cors::new(&config.cors));
+    let server = HttpServer::new(move || {
         App::new()
-            .service(actix_files::Files::new("/", "./static/").index_file("index.html"))
-            .route("/ws/", web::get().to(ws_index))
-    })
-    .bind((ADDR, PORT))?
-    .run()
-    .await
+            .app_data(state.clone())
+            .app_data(auth.clone())
+            .app_data(rules.clone())
+            .wrap(from_fn(authenticate))
+            .wrap(from_fn(cors))
+            .configure(api)
+            .configure(grafana)
+            .route("/ws/", web::get().to(live))
+            .configure(assets)
+    });
+    let address = (config.bind.as_str(), config.port);
+    let server = match &config.tls {
+        Some(tls) => server.bind_openssl(address, acceptor(tls)?)?,
+        None => server.bind(address)?,
+    };
+    Ok(server.run())
+}
+
+/// Run the web server alone, until it is stopped.
+///
+/// # Arguments
+///
+/// - `config` : [`WebConfig`] parameters of the server.
+#[actix_web::main]
+pub async fn web(config: WebConfig) -> Result<(), Box<dyn Error>> {
+    server(&config)?.await?;
+    Ok(())
+}
+
+/// Run the web server and a sampler in the same runtime, until the server is stopped.
+/// The sampler runs in a blocking task every interval, so the server keeps answering
+/// during the probes run, and the samples it publishes are seen by the live routes.
+///
+/// # Arguments
+///
+/// - `config` : [`WebConfig`] parameters of the server.
+/// - `interval` : Interval between the start of two samplings.
+/// - `sample` : Function running the probes once.
+#[actix_web::main]
+pub async fn serve<F>(
+    config: WebConfig,
+    interval: Duration,
+    sample: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let server = server(&config)?;
+    let sample = Arc::new(sample);
+    let sampler = rt::spawn(async move {
+        loop {
+            let next = rt::time::Instant::now() + interval;
+            let sample = sample.clone();
+            if let Err(e) = web::block(move || sample()).await {
+                error!("[{HEADER}] Process 'Failure in the sampler' : {e}");
+            }
+            rt::time::sleep_until(next).await;
+        }
+    });
+    let res = server.await;
+    sampler.abort();
+    Ok(res?)
 }
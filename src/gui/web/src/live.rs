@@ -0,0 +1,213 @@
+//! # File live
+//!
+//! This file provides the WebSocket feed of the samples published by the probes,
+//! filtered by the topics each client subscribed to.
+
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_web::{Error, HttpRequest, HttpResponse, web};
+use actix_web_actors::ws;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use userv_core::bus::{Pattern, Sample, Subscription, bus};
+
+/// Interval between two pings sent to the client.
+const HEARTBEAT: Duration = Duration::from_secs(5);
+/// Delay without pong after which the client is disconnected.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(15);
+/// Interval between two flushes of the pending samples to the client.
+const FLUSH: Duration = Duration::from_millis(100);
+/// Number of samples waiting for a client before the newest ones are dropped.
+const QUEUE: usize = 4096;
+/// Number of samples sent to a client per flush, the others waiting in its queue.
+const BATCH: usize = 512;
+/// Number of samples replayed by default on subscription.
+const REPLAY: usize = 100;
+
+/// Message sent by a client to choose its topics.
+#[derive(Debug, Deserialize)]
+struct Subscribe {
+    /// Subscription patterns, replacing the previous ones.
+    subscribe: Vec<String>,
+    /// Number of the last matching samples to replay.
+    replay: Option<usize>,
+}
+
+/// Query parameters of the WebSocket route, to subscribe on connection.
+#[derive(Debug, Deserialize)]
+pub struct LiveQuery {
+    /// Comma separated subscription patterns.
+    pub subscribe: Option<String>,
+    /// Number of the last matching samples to replay.
+    pub replay: Option<usize>,
+}
+
+/// WebSocket session of a client receiving the live samples.
+pub struct LiveSocket {
+    patterns: Vec<Pattern>,
+    replay: usize,
+    subscription: Subscription,
+    heartbeat: Instant,
+}
+
+impl LiveSocket {
+    /// Create a session subscribed to the samples bus.
+    ///
+    /// # Arguments
+    ///
+    /// - `patterns` : Initial subscription [`Pattern`] list.
+    /// - `replay` : Number of the last matching samples sent on connection.
+    pub fn new(patterns: Vec<Pattern>, replay: usize) -> Self {
+        LiveSocket {
+            patterns,
+            replay,
+            subscription: bus().subscribe(QUEUE),
+            heartbeat: Instant::now(),
+        }
+    }
+
+    fn matches(&self, sample: &Sample) -> bool {
+        self.patterns.iter().any(|p| p.matches(sample))
+    }
+
+    /// Send the last samples matching the patterns.
+    fn replay(&self, count: usize, ctx: &mut ws::WebsocketContext<Self>) {
+        let history: Vec<Sample> = bus()
+            .history()
+            .into_iter()
+            .filter(|s| self.matches(s))
+            .collect();
+        for sample in &history[history.len().saturating_sub(count)..] {
+            ctx.text(json!(sample).to_string());
+        }
+    }
+
+    /// Send at most [`BATCH`] pending samples matching the patterns, and the number of dropped ones.
+    /// A slow client leaves the other samples in its queue, the newest being dropped once it is full.
+    fn flush(&mut self, ctx: &mut ws::WebsocketContext<Self>) {
+        let mut sent = 0;
+        while sent < BATCH
+            && let Some(sample) = self.subscription.try_recv()
+        {
+            if self.matches(&sample) {
+                ctx.text(json!(sample).to_string());
+                sent += 1;
+            }
+        }
+        let dropped = self.subscription.take_dropped();
+        if dropped > 0 {
+            ctx.text(json!({ "dropped": dropped }).to_string());
+        }
+    }
+}
+
+impl Actor for LiveSocket {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if !self.patterns.is_empty() {
+            self.replay(self.replay, ctx);
+        }
+        ctx.run_interval(HEARTBEAT, |act, ctx| {
+            if Instant::now().duration_since(act.heartbeat) > CLIENT_TIMEOUT {
+                ctx.stop();
+            } else {
+                ctx.ping(b"");
+            }
+        });
+        ctx.run_interval(FLUSH, |act, ctx| act.flush(ctx));
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for LiveSocket {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Text(text)) => match serde_json::from_str::<Subscribe>(&text) {
+                Ok(request) => {
+                    let patterns: Option<Vec<Pattern>> = request
+                        .subscribe
+                        .iter()
+                        .map(|p| Pattern::parse(p))
+                        .collect();
+                    match patterns {
+                        Some(patterns) => {
+                            self.patterns = patterns;
+                            self.replay(request.replay.unwrap_or(REPLAY), ctx);
+                        }
+                        None => ctx.text(
+                            json!({ "error": "Arguments 'Invalid subscription pattern'" })
+                                .to_string(),
+                        ),
+                    }
+                }
+                Err(e) => ctx.text(
+                    json!({ "error": format!("Arguments 'Invalid message' : {e}") }).to_string(),
+                ),
+            },
+            Ok(ws::Message::Ping(msg)) => {
+                self.heartbeat = Instant::now();
+                ctx.pong(&msg);
+            }
+            Ok(ws::Message::Pong(_)) => self.heartbeat = Instant::now(),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Err(_) => ctx.stop(),
+            _ => (),
+        }
+    }
+}
+
+/// Open a live WebSocket session, subscribed to the patterns of the query if any.
+pub async fn live(
+    req: HttpRequest,
+    stream: web::Payload,
+    query: web::Query<LiveQuery>,
+) -> Result<HttpResponse, Error> {
+    let patterns: Vec<Pattern> = query
+        .subscribe
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(Pattern::parse)
+        .collect();
+    let replay = query.replay.unwrap_or(REPLAY);
+    ws::start(LiveSocket::new(patterns, replay), &req, stream)
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{error::PayloadError, web::Bytes};
+    use futures_util::{StreamExt, stream};
+    use userv_core::bus::Sample;
+
+    // Test `LiveSocket` flushes to a client not reading, limited per flush with the dropped count
+    #[actix_web::test]
+    async fn test_flush_slow_client() {
+        let socket = LiveSocket::new(vec![Pattern::parse("slow").unwrap()], 0);
+        let input = stream::pending::<Result<Bytes, PayloadError>>();
+        let mut output = Box::pin(ws::WebsocketContext::create(socket, input));
+
+        let sample = |i: usize| Sample {
+            timestamp: i.to_string(),
+            topic: "slow.client".to_string(),
+            entity: None,
+            value: i as f64,
+            unit: None,
+        };
+        bus().publish((0..QUEUE + 10).map(sample));
+
+        let frames = output.next().await.unwrap().unwrap();
+        let frames = String::from_utf8_lossy(&frames);
+        assert_eq!(frames.matches("\"topic\":\"slow.client\"").count(), BATCH);
+        let dropped = frames.split("{\"dropped\":").nth(1).unwrap();
+        let dropped: u64 = dropped.split('}').next().unwrap().parse().unwrap();
+        assert!(dropped >= 10);
+    }
+}
@@ -0,0 +1,677 @@
+//! # File health
+//!
+//! This file provides the health scoring of the components, from the SMART data of the disks,
+//! the errors counted by the memory controllers, the thermal headroom of the CPU and GPUs,
+//! and the error and drop rates of the network interfaces.
+
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    sync::{Arc, Mutex},
+};
+use userv_core::core::init_db;
+
+use network::NetworkType;
+use storage::DiskInfo;
+
+use crate::{component::Component, snapshot::Snapshot};
+
+/// Request creating the table of the health scores.
+pub const REQUEST: &str = "CREATE TABLE IF NOT EXISTS health (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    component TEXT NOT NULL,
+    entity TEXT,
+    score INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    reasons TEXT NOT NULL
+);";
+
+/// Lowest score of a healthy component.
+const HEALTHY: u8 = 80;
+
+/// Lowest score of a degraded component, a lower one being critical.
+const DEGRADED: u8 = 50;
+
+/// Thresholds of the health scoring, from the `[health]` section of the configuration.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct ThresholdsConfig {
+    cpu_temperature: f64,
+    gpu_temperature: f64,
+    disk_temperature: f64,
+    headroom: f64,
+    disk_hours: f64,
+    error_rate: f64,
+}
+
+impl Default for ThresholdsConfig {
+    fn default() -> Self {
+        ThresholdsConfig {
+            cpu_temperature: 95.0,
+            gpu_temperature: 90.0,
+            disk_temperature: 60.0,
+            headroom: 15.0,
+            disk_hours: 43_800.0,
+            error_rate: 0.01,
+        }
+    }
+}
+
+/// Thresholds of the health scoring of the components.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "ThresholdsConfig")]
+pub struct Thresholds {
+    /// Highest temperature of the CPU in °C.
+    pub cpu_temperature: f64,
+    /// Highest temperature of a GPU in °C.
+    pub gpu_temperature: f64,
+    /// Highest temperature of a disk in °C.
+    pub disk_temperature: f64,
+    /// Margin under the highest temperatures in °C, below which the score decreases.
+    pub headroom: f64,
+    /// Power-on hours of a disk lifetime.
+    pub disk_hours: f64,
+    /// Share of the packets of an interface in error or dropped making it critical.
+    pub error_rate: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds::try_from(ThresholdsConfig::default()).expect("Valid default thresholds")
+    }
+}
+
+impl TryFrom<ThresholdsConfig> for Thresholds {
+    type Error = String;
+
+    fn try_from(config: ThresholdsConfig) -> Result<Self, Self::Error> {
+        for (name, value) in [
+            ("cpu_temperature", config.cpu_temperature),
+            ("gpu_temperature", config.gpu_temperature),
+            ("disk_temperature", config.disk_temperature),
+            ("headroom", config.headroom),
+            ("disk_hours", config.disk_hours),
+            ("error_rate", config.error_rate),
+        ] {
+            if !(value > 0.0 && value.is_finite()) {
+                return Err(format!(
+                    "Config 'Invalid health threshold' : {name} = {value}"
+                ));
+            }
+        }
+        if config.error_rate > 1.0 {
+            return Err(format!(
+                "Config 'Invalid health threshold' : error_rate = {}",
+                config.error_rate
+            ));
+        }
+        Ok(Thresholds {
+            cpu_temperature: config.cpu_temperature,
+            gpu_temperature: config.gpu_temperature,
+            disk_temperature: config.disk_temperature,
+            headroom: config.headroom,
+            disk_hours: config.disk_hours,
+            error_rate: config.error_rate,
+        })
+    }
+}
+
+/// State of a component, from its score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Healthy,
+    Degraded,
+    Critical,
+}
+
+impl Status {
+    /// State matching a score, from [`HEALTHY`] and [`DEGRADED`].
+    ///
+    /// # Arguments
+    ///
+    /// - `score` : Health score between 0 and 100.
+    pub fn from_score(score: u8) -> Self {
+        if score >= HEALTHY {
+            Status::Healthy
+        } else if score >= DEGRADED {
+            Status::Degraded
+        } else {
+            Status::Critical
+        }
+    }
+
+    /// Lowercase name of the state, as stored in database.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Status::Healthy => "healthy",
+            Status::Degraded => "degraded",
+            Status::Critical => "critical",
+        }
+    }
+}
+
+/// Health of a component, or of one of its entities.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Health {
+    /// Component concerned.
+    pub component: Component,
+    /// Entity concerned (disk, GPU bus id, interface), none for the whole component.
+    pub entity: Option<String>,
+    /// Score between 0 and 100, 100 being perfectly healthy.
+    pub score: u8,
+    /// State of the score.
+    pub status: Status,
+    /// Reasons of the lost points.
+    pub reasons: Vec<String>,
+}
+
+/// Points lost by an entity, with their reasons.
+#[derive(Default)]
+struct Penalties {
+    points: f64,
+    reasons: Vec<String>,
+}
+
+impl Penalties {
+    /// Lose points for a reason, if any.
+    fn add(&mut self, points: f64, reason: String) {
+        if points > 0.0 {
+            self.points += points;
+            self.reasons.push(reason);
+        }
+    }
+
+    /// Lose points for a temperature close to or above its highest value.
+    fn thermal(&mut self, temperature: f64, max: f64, headroom: f64) {
+        if temperature >= max {
+            self.add(
+                80.0,
+                format!("temperature {temperature:.0} °C above its {max:.0} °C limit"),
+            );
+        } else if max - temperature < headroom {
+            self.add(
+                50.0 * (headroom - (max - temperature)) / headroom,
+                format!(
+                    "temperature {temperature:.0} °C, {:.0} °C under its {max:.0} °C limit",
+                    max - temperature
+                ),
+            );
+        }
+    }
+
+    /// Health of an entity from its lost points.
+    fn health(self, component: Component, entity: Option<String>) -> Health {
+        let score = (100.0 - self.points).clamp(0.0, 100.0).round() as u8;
+        Health {
+            component,
+            entity,
+            score,
+            status: Status::from_score(score),
+            reasons: self.reasons,
+        }
+    }
+}
+
+/// Health of a component from its entities, the worst one giving its score.
+fn worst(component: Component, entities: &[Health]) -> Option<Health> {
+    let score = entities.iter().map(|h| h.score).min()?;
+    Some(Health {
+        component,
+        entity: None,
+        score,
+        status: Status::from_score(score),
+        reasons: entities
+            .iter()
+            .flat_map(|h| {
+                let entity = h.entity.as_deref().unwrap_or_default();
+                h.reasons.iter().map(move |r| format!("{entity}: {r}"))
+            })
+            .collect(),
+    })
+}
+
+/// Check if a temperature sensor measures the CPU, like the coretemp `Package id` and `Core`
+/// sensors or the k10temp `Tctl` and `Tdie` ones, unlike the disk, GPU or ACPI sensors.
+///
+/// # Arguments
+///
+/// - `label` : Label of the sensor.
+fn is_cpu_sensor(label: &str) -> bool {
+    ["Package id", "Core ", "Tctl", "Tdie"]
+        .iter()
+        .any(|name| label.contains(name))
+}
+
+/// Health scoring of the components, keeping the last packet counters of each interface
+/// to evaluate their error and drop rates over the interval between two snapshots.
+/// Clones share the same counters.
+#[derive(Debug, Clone, Default)]
+pub struct HealthMonitor {
+    thresholds: Thresholds,
+    counters: Arc<Mutex<HashMap<String, (f64, f64)>>>,
+}
+
+impl HealthMonitor {
+    /// Create a health scoring.
+    ///
+    /// # Arguments
+    ///
+    /// - `thresholds` : [`Thresholds`] of the scoring.
+    pub fn new(thresholds: Thresholds) -> Self {
+        HealthMonitor {
+            thresholds,
+            counters: Arc::default(),
+        }
+    }
+
+    /// Score the components retrieved by a snapshot.
+    /// A component is scored only when its probe gives a health data, so the CPU needs
+    /// the temperatures of its own sensors, the memory its EDAC controllers and a disk its SMART data.
+    ///
+    /// # Arguments
+    ///
+    /// - `snapshot` : Retrieved [`Snapshot`].
+    ///
+    /// # Returns
+    ///
+    /// The [`Health`] of each scored component, followed by the one of its entities.
+    pub fn evaluate(&self, snapshot: &Snapshot) -> Vec<Health> {
+        let t = &self.thresholds;
+        let mut res = Vec::new();
+
+        if let Some(temperature) = snapshot
+            .cpu
+            .iter()
+            .flat_map(|cpu| cpu.temperature.iter())
+            .flat_map(|t| &t.temperatures)
+            .filter(|(label, _)| is_cpu_sensor(label))
+            .map(|(_, temperature)| *temperature as f64)
+            .reduce(f64::max)
+        {
+            let mut p = Penalties::default();
+            p.thermal(temperature, t.cpu_temperature, t.headroom);
+            res.push(p.health(Component::Cpu, None));
+        }
+
+        if let Some(errors) = snapshot.memory.as_ref().and_then(|m| m.errors.as_ref()) {
+            let mut p = Penalties::default();
+            p.add(
+                (10.0 * (1.0 + errors.corrected as f64).log10()).min(40.0),
+                format!("{} corrected errors", errors.corrected),
+            );
+            if errors.uncorrected > 0 {
+                p.add(60.0, format!("{} uncorrected errors", errors.uncorrected));
+            }
+            res.push(p.health(Component::Memory, None));
+        }
+
+        let mut disks: Vec<Health> = Vec::new();
+        for disk in snapshot.storage.iter().flatten() {
+            let Some(smart) = &disk.smart_info else {
+                continue;
+            };
+            let path = DiskInfo::device_path(&disk.name);
+            if disks
+                .iter()
+                .any(|h| h.entity.as_deref() == Some(path.as_str()))
+            {
+                continue;
+            }
+            let mut p = Penalties::default();
+            if let Some(n) = smart.sectors_reallocated.filter(|n| *n > 0) {
+                p.add(
+                    (10.0 + 2.0 * n as f64).min(50.0),
+                    format!("{n} reallocated sectors"),
+                );
+            }
+            if let Some(n) = smart.sectors_pending_current.filter(|n| *n > 0) {
+                p.add(
+                    (20.0 + 5.0 * n as f64).min(60.0),
+                    format!("{n} pending sectors"),
+                );
+            }
+            if let Some(hours) = smart.uptime_hours {
+                let used = hours as f64 / t.disk_hours;
+                p.add(
+                    30.0 * ((used - 0.8) / 0.2).clamp(0.0, 1.0),
+                    format!(
+                        "{hours} power-on hours, {:.0} % of its lifetime",
+                        used * 100.0
+                    ),
+                );
+            }
+            if let Some(temperature) = smart.temperature {
+                p.thermal(temperature as f64, t.disk_temperature, t.headroom);
+            }
+            disks.push(p.health(Component::Storage, Some(path)));
+        }
+        res.extend(worst(Component::Storage, &disks));
+        res.extend(disks);
+
+        let gpus: Vec<Health> = snapshot
+            .gpu
+            .iter()
+            .flatten()
+            .filter_map(|gpu| {
+                let temperature = gpu.metrics.gpu_temperature?;
+                let mut p = Penalties::default();
+                p.thermal(temperature as f64, t.gpu_temperature, t.headroom);
+                Some(p.health(Component::Gpu, gpu.metrics.gpu_bus_id.clone()))
+            })
+            .collect();
+        res.extend(worst(Component::Gpu, &gpus));
+        res.extend(gpus);
+
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let interfaces: Vec<Health> = snapshot
+            .network
+            .iter()
+            .flatten()
+            .filter(|interface| interface.network_type != NetworkType::Loopback)
+            .filter_map(|interface| {
+                let packets = interface.packet_received? + interface.packet_transmitted?;
+                let faults = [
+                    interface.errors_received,
+                    interface.errors_transmitted,
+                    interface.drops_received,
+                    interface.drops_transmitted,
+                ]
+                .into_iter()
+                .flatten()
+                .fold(0.0, |sum, count| sum + count);
+                // Counters are cumulative since boot, the rate is taken since the previous
+                // snapshot, or since boot for the first one or after a counter reset.
+                let (delta_faults, delta_packets) =
+                    match counters.insert(interface.name.clone(), (faults, packets)) {
+                        Some((f, p)) if faults >= f && packets > p => (faults - f, packets - p),
+                        _ => (faults, packets),
+                    };
+                if delta_packets <= 0.0 {
+                    return None;
+                }
+                let rate = delta_faults / delta_packets;
+                let mut p = Penalties::default();
+                p.add(
+                    80.0 * (rate / t.error_rate).min(1.0),
+                    format!("{:.3} % of the packets in error or dropped", rate * 100.0),
+                );
+                Some(p.health(Component::Net, Some(interface.name.clone())))
+            })
+            .collect();
+        res.extend(worst(Component::Net, &interfaces));
+        res.extend(interfaces);
+        res
+    }
+}
+
+/// Insert the health scores of a snapshot.
+fn insert_health(
+    conn: &Connection,
+    timestamp: &str,
+    health: &[Health],
+) -> Result<(), Box<dyn StdError>> {
+    let mut stmt = conn.prepare(
+        "INSERT INTO health (timestamp, component, entity, score, status, reasons)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?;
+    for h in health {
+        stmt.execute(params![
+            timestamp,
+            h.component.label(),
+            h.entity,
+            h.score,
+            h.status.label(),
+            h.reasons.join("; ")
+        ])?;
+    }
+    Ok(())
+}
+
+/// Store the health scores of a snapshot in database.
+///
+/// # Arguments
+///
+/// - `timestamp` : Date of the snapshot.
+/// - `health` : [`Health`] of the components to insert.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert request failed.
+pub fn store_health(timestamp: &str, health: &[Health]) -> Result<(), Box<dyn StdError>> {
+    let conn = init_db(REQUEST)?;
+    insert_health(&conn, timestamp, health)
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::{CpuCoreInfo, CpuData, CpuGlobalInfo, CpuTemperatureInfo};
+    use network::NetworkInterface;
+    use storage::SmartInfo;
+    use userv_core::config::Config;
+
+    fn interface(name: &str, packets: f64, errors: f64, drops: f64) -> NetworkInterface {
+        NetworkInterface {
+            address_mac: None,
+            energy_consumed: None,
+            name: name.to_string(),
+            network_type: NetworkType::Ethernet,
+            received: None,
+            transmitted: None,
+            errors_received: Some(errors),
+            errors_transmitted: None,
+            packet_received: Some(packets),
+            packet_transmitted: Some(0.0),
+            drops_received: Some(drops),
+            drops_transmitted: None,
+        }
+    }
+
+    fn disk(name: &str, smart: SmartInfo) -> DiskInfo {
+        DiskInfo {
+            bandwidth_read: None,
+            bandwidth_write: None,
+            energy_consumed: None,
+            file_mount: None,
+            file_system: None,
+            kind: None,
+            name: name.to_string(),
+            space_available: None,
+            space_total: None,
+            smart_info: Some(smart),
+        }
+    }
+
+    // Test `Status::from_score` function bounds
+    #[test]
+    fn test_status() {
+        assert_eq!(Status::from_score(100), Status::Healthy);
+        assert_eq!(Status::from_score(80), Status::Healthy);
+        assert_eq!(Status::from_score(79), Status::Degraded);
+        assert_eq!(Status::from_score(50), Status::Degraded);
+        assert_eq!(Status::from_score(49), Status::Critical);
+    }
+
+    // Test `is_cpu_sensor` function with Intel, AMD and other sensors
+    #[test]
+    fn test_is_cpu_sensor() {
+        assert!(is_cpu_sensor("coretemp Package id 0"));
+        assert!(is_cpu_sensor("k10temp Tctl"));
+        assert!(!is_cpu_sensor("acpitz temp1"));
+        assert!(!is_cpu_sensor("iwlwifi_1 temp1"));
+    }
+
+    // Test `evaluate` function scoring the CPU headroom and the disks SMART data
+    #[test]
+    fn test_evaluate_thermal_and_disks() {
+        let snapshot = Snapshot {
+            cpu: Some(CpuData {
+                global: CpuGlobalInfo {
+                    architecture: None,
+                    model: None,
+                    family: None,
+                    frequency: None,
+                    cores_physic: None,
+                    cores_logic: None,
+                },
+                cores: CpuCoreInfo {
+                    cores_usage: Vec::new(),
+                },
+                power: None,
+                temperature: Some(CpuTemperatureInfo {
+                    temperatures: vec![
+                        ("coretemp Package id 0".to_string(), 60.0),
+                        ("coretemp Core 1".to_string(), 89.0),
+                        ("nvme Composite".to_string(), 99.0),
+                        ("amdgpu edge".to_string(), 99.0),
+                    ],
+                }),
+            }),
+            storage: Some(vec![
+                disk(
+                    "/dev/sda1",
+                    SmartInfo {
+                        sectors_reallocated: Some(5),
+                        sectors_pending: None,
+                        sectors_pending_current: Some(0),
+                        temperature: Some(40),
+                        uptime_hours: None,
+                    },
+                ),
+                disk(
+                    "/dev/sda2",
+                    SmartInfo {
+                        sectors_reallocated: Some(200),
+                        sectors_pending: None,
+                        sectors_pending_current: None,
+                        temperature: None,
+                        uptime_hours: None,
+                    },
+                ),
+                disk(
+                    "/dev/sdb",
+                    SmartInfo {
+                        sectors_reallocated: Some(0),
+                        sectors_pending: None,
+                        sectors_pending_current: Some(4),
+                        temperature: Some(65),
+                        uptime_hours: None,
+                    },
+                ),
+            ]),
+            ..Default::default()
+        };
+        let health = HealthMonitor::default().evaluate(&snapshot);
+
+        // 89 °C is 6 °C under 95 °C, losing 50 * 9 / 15 points.
+        assert_eq!(health[0].component, Component::Cpu);
+        assert_eq!((health[0].score, health[0].status), (70, Status::Degraded));
+
+        // The partitions of sda are scored once, from the first one.
+        let sda = health
+            .iter()
+            .find(|h| h.entity.as_deref() == Some("/dev/sda"))
+            .unwrap();
+        assert_eq!(sda.score, 80);
+        assert_eq!(sda.reasons, vec!["5 reallocated sectors"]);
+        let sdb = health
+            .iter()
+            .find(|h| h.entity.as_deref() == Some("/dev/sdb"))
+            .unwrap();
+        assert_eq!((sdb.score, sdb.status), (0, Status::Critical));
+        assert_eq!(sdb.reasons.len(), 2);
+
+        let storage = health
+            .iter()
+            .find(|h| h.component == Component::Storage && h.entity.is_none())
+            .unwrap();
+        assert_eq!(storage.score, 0);
+        assert_eq!(storage.reasons.len(), 3);
+        assert!(storage.reasons[0].starts_with("/dev/sda: "));
+        assert_eq!(health.len(), 4);
+    }
+
+    // Test `evaluate` function scoring the interfaces on the rate since the previous snapshot
+    #[test]
+    fn test_evaluate_network_rate() {
+        let monitor = HealthMonitor::default();
+        let snapshot = |packets: f64, errors: f64, drops: f64| Snapshot {
+            network: Some(vec![
+                interface("eth0", packets, errors, drops),
+                NetworkInterface {
+                    network_type: NetworkType::Loopback,
+                    ..interface("lo", 1.0, 1.0, 0.0)
+                },
+            ]),
+            ..Default::default()
+        };
+
+        // 0.5 % of the packets since boot, half of the critical rate.
+        let health = monitor.evaluate(&snapshot(2.0, 0.006, 0.004));
+        assert_eq!(health.len(), 2);
+        assert_eq!(health[1].entity.as_deref(), Some("eth0"));
+        assert_eq!(health[1].score, 60);
+
+        // No new fault over the next million packets.
+        let health = monitor.evaluate(&snapshot(3.0, 0.006, 0.004));
+        assert_eq!((health[1].score, health[1].status), (100, Status::Healthy));
+        assert!(health[1].reasons.is_empty());
+
+        // Every new packet in error.
+        let health = monitor.evaluate(&snapshot(3.5, 0.506, 0.004));
+        assert_eq!((health[0].score, health[0].status), (20, Status::Critical));
+    }
+
+    // Test `store_health` inserted rows
+    #[test]
+    fn test_insert_health() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(REQUEST).unwrap();
+        let mut p = Penalties::default();
+        p.add(30.0, "1 uncorrected errors".to_string());
+        p.add(0.0, "0 corrected errors".to_string());
+        let health = vec![p.health(Component::Memory, None)];
+        insert_health(&conn, "2026-01-01T00:00:00.000Z", &health).unwrap();
+
+        let row: (String, Option<String>, u8, String, String) = conn
+            .query_row(
+                "SELECT component, entity, score, status, reasons FROM health",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            (
+                "memory".to_string(),
+                None,
+                70,
+                "degraded".to_string(),
+                "1 uncorrected errors".to_string()
+            )
+        );
+    }
+
+    // Test `Thresholds` deserialization rejecting invalid values
+    #[test]
+    fn test_thresholds_config() {
+        let section = |section: &str| {
+            Config::parse(&format!("[health]\n{section}"))
+                .unwrap()
+                .section::<Thresholds>("health")
+        };
+        let thresholds = section("cpu_temperature = 100").unwrap();
+        assert_eq!(thresholds.cpu_temperature, 100.0);
+        assert_eq!(thresholds.headroom, Thresholds::default().headroom);
+        assert!(section("headroom = 0").is_err());
+        assert!(section("error_rate = 2").is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//! # Userv library
+//!
+//! This library provides the collection of the machine data by the probes,
+//! returning typed snapshots, with an optional persistence in SQLite database.
+//!
+//! ```no_run
+//! use userv::{Collector, CollectorConfig, Component};
+//!
+//! let collector = Collector::new(CollectorConfig::default());
+//! let snapshot = collector.collect(&[Component::Cpu, Component::Memory]).unwrap();
+//! for metric in snapshot.metrics() {
+//!     println!("{}.{} = {} {:?}", metric.component, metric.name, metric.value, metric.unit);
+//! }
+//! ```
+
+/// Examples of the README, compiled by the doc tests.
+#[cfg(doctest)]
+#[doc = include_str!("../README.md")]
+struct ReadmeDoctests;
+
+pub mod attribution;
+pub mod baseline;
+pub mod carbon;
+pub mod collector;
+pub mod component;
+pub mod cost;
+pub mod embodied;
+pub mod energy;
+pub mod error;
+pub mod health;
+pub mod logger;
+pub mod measure;
+pub mod notify;
+pub mod snapshot;
+pub mod tdp;
+
+pub use attribution::{CgroupEnergy, CgroupMeter, Consumer, ProcessEnergy, ProcessMeter};
+pub use baseline::{
+    Baseline, CalibrateOptions, ComponentSplit, EnergySplit, SplitReport, calibrate,
+};
+pub use carbon::{Carbon, CarbonSource, Emissions};
+pub use collector::{Collector, CollectorConfig, Sampling};
+pub use component::Component;
+pub use cost::{Cost, CostReport, Rollup, Tariff};
+pub use embodied::{CarbonReport, Embodied, EmbodiedMeter, Footprint, Item};
+pub use energy::{ComponentEnergy, EnergyMeter, EnergySummary, Source};
+pub use error::Error;
+pub use health::{Health, HealthMonitor, Status, Thresholds};
+pub use logger::init_logger;
+pub use measure::{Measurement, measure};
+pub use notify::{Backend, Channel, Email, Notifier, Security};
+pub use snapshot::{Metric, Snapshot};
+pub use tdp::CpuPowerModel;
+
+pub use board::BoardInfo;
+pub use cpu::{CpuCoreInfo, CpuData, CpuGlobalInfo, CpuPowerInfo, CpuTemperatureInfo};
+pub use exec::{ExecProbe, ExecSample, ExecValue};
+pub use gpu::{GpuData, GpuMetrics, GpuProcessMetrics};
+pub use memory::{MemData, MemDeviceInfo, MemErrors, MemInfo};
+pub use network::{NetworkInterface, NetworkType};
+pub use storage::{DiskInfo, SmartInfo};
+pub use system::{ProcessInfo, SystemInfo};
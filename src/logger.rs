@@ -0,0 +1,54 @@
+//! # File logger
+//!
+//! This file provides the logger of the programs, writing the error messages
+//! of the probes, the collector and the servers in a file.
+
+use log::LevelFilter;
+use log4rs::{
+    append::file::FileAppender,
+    config::{Appender, Config, Root},
+    encode::pattern::PatternEncoder,
+    filter::threshold::ThresholdFilter,
+    init_config,
+};
+use std::{
+    error::Error,
+    fs::{create_dir_all, write},
+    path::Path,
+};
+
+/// Log file path, emptied at each start.
+pub const LOGGER: &str = "log/error.log";
+
+/// Initialization and formatting information logger to store messages concerning microservices behavior.
+///
+/// # Returns
+///
+/// Writing the error in the log file.
+/// Print IO error message if log writing failed.
+pub fn init_logger() -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = Path::new(LOGGER).parent() {
+        create_dir_all(parent)?;
+    }
+
+    let logfile = FileAppender::builder()
+        .encoder(Box::new(PatternEncoder::new("{d} {m} {n}")))
+        .build(LOGGER)?;
+
+    let config = Config::builder()
+        .appender(
+            Appender::builder()
+                .filter(Box::new(ThresholdFilter::new(LevelFilter::Error)))
+                .build("logfile", Box::new(logfile)),
+        )
+        .build(
+            Root::builder()
+                .appender("logfile")
+                .build(LevelFilter::Error),
+        )?;
+
+    let _ = write(LOGGER, "");
+    init_config(config)?;
+
+    Ok(())
+}
@@ -3,30 +3,222 @@
 //! This file provides call the necessary to handle each probe,
 //! separately or simultaneously in threaded tasks.
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use log::error;
 use std::{
+    path::{Path, PathBuf},
     process::exit,
-    thread::{sleep, spawn},
     time::Duration,
 };
+use userv::{Collector, CollectorConfig, Component, baseline::stored_baselines, init_logger};
 
 mod utils;
 use utils::*;
-//use gui_web::web;
 
 /// Data defining arguments to active or not a probe to retrieve component data.
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Arg {
+    /// Command to run instead of the probes.
+    #[command(subcommand)]
+    command: Option<Command>,
     /// List of [`Component`] to active.
     #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "all")]
     active: Vec<Component>,
     /// Activation state of a probe.
     #[arg(long, conflicts_with = "active")]
     all: bool,
-    /// Interval in seconds between each probe run. If not set, probes run once.
+    /// Interval in seconds between each probe run. If not set, probes run once,
+    /// or every second with `--serve`.
     #[arg(long, default_value_t = 0)]
     freq: u64,
+    /// Run the web server with the probes, in the same process.
+    /// All probes are active if none is specified.
+    #[arg(long)]
+    serve: bool,
+    /// TOML configuration file.
+    #[arg(long, global = true, default_value = CONFIG)]
+    config: PathBuf,
+}
+
+/// Commands available in addition to the probes run.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Export stored tables in CSV or JSON Lines format.
+    Export(ExportArg),
+    /// Run the probes once and print a report of the machine on standard output.
+    Snapshot(SnapshotArg),
+    /// Print a report computed from the stored data.
+    #[command(subcommand)]
+    Report(ReportCommand),
+    /// Run a command and print the energy it used when it exits.
+    Measure(MeasureArg),
+    /// Measure the idle and peak powers of each component, and store them as baselines.
+    Calibrate(CalibrateArg),
+    /// Hash a password or token read on standard input, for the `[web]` configuration.
+    Hash,
+}
+
+/// Data defining arguments of the `snapshot` command.
+#[derive(Args, Debug)]
+pub struct SnapshotArg {
+    /// List of [`Component`] to retrieve, all if not set.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub active: Vec<Component>,
+    /// Output format of the report.
+    #[arg(long, value_enum, default_value_t = SnapshotFormat::Json)]
+    pub format: SnapshotFormat,
+}
+
+/// Data defining arguments of the `measure` command.
+#[derive(Args, Debug)]
+pub struct MeasureArg {
+    /// Interval in milliseconds between two samples of the powers.
+    #[arg(long, default_value_t = 100)]
+    pub interval: u64,
+    /// Output format of the summary.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+    pub format: ReportFormat,
+    /// Output file of the summary. Standard error if not set.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// Command to run, with its arguments, after `--`.
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+/// Data defining arguments of the `calibrate` command.
+#[derive(Args, Debug)]
+pub struct CalibrateArg {
+    /// Duration in seconds of the sampling at rest.
+    #[arg(long, default_value_t = 30)]
+    pub duration: u64,
+    /// Sample the machine under a synthetic load after the rest, for the same duration,
+    /// to measure the peak powers.
+    #[arg(long)]
+    pub load: bool,
+    /// Interval in milliseconds between two samples of the powers.
+    #[arg(long, default_value_t = 1000)]
+    pub interval: u64,
+    /// Output format of the baselines.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+    pub format: ReportFormat,
+}
+
+/// Reports available with the `report` command.
+#[derive(Subcommand, Debug)]
+pub enum ReportCommand {
+    /// Daily and monthly electricity cost of the machine, from the stored costs.
+    Cost(CostArg),
+    /// Processes which consumed the most energy, from the stored process energies.
+    Processes(ProcessesArg),
+    /// Static and dynamic energy of each component, from the stored energy split.
+    Energy(EnergyArg),
+    /// Operational and embodied emissions of the machine, from the stored emissions.
+    Carbon(CarbonArg),
+}
+
+/// Data defining arguments of the `report carbon` command.
+#[derive(Args, Debug)]
+pub struct CarbonArg {
+    /// Lower bound of the time range (RFC 3339 or YYYY-MM-DD).
+    #[arg(long)]
+    pub from: Option<String>,
+    /// Upper bound of the time range (RFC 3339 or YYYY-MM-DD).
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Output format of the report.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+    pub format: ReportFormat,
+    /// SQLite database file to read.
+    #[arg(long, default_value = DATABASE)]
+    pub db: PathBuf,
+}
+
+/// Data defining arguments of the `report energy` command.
+#[derive(Args, Debug)]
+pub struct EnergyArg {
+    /// Lower bound of the time range (RFC 3339 or YYYY-MM-DD).
+    #[arg(long)]
+    pub from: Option<String>,
+    /// Upper bound of the time range (RFC 3339 or YYYY-MM-DD).
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Component to keep, all if not set.
+    #[arg(long, value_enum)]
+    pub component: Option<Component>,
+    /// Output format of the report.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+    pub format: ReportFormat,
+    /// SQLite database file to read.
+    #[arg(long, default_value = DATABASE)]
+    pub db: PathBuf,
+}
+
+/// Data defining arguments of the `report cost` command.
+#[derive(Args, Debug)]
+pub struct CostArg {
+    /// Lower bound of the time range (RFC 3339 or YYYY-MM-DD).
+    #[arg(long)]
+    pub from: Option<String>,
+    /// Upper bound of the time range (RFC 3339 or YYYY-MM-DD).
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Output format of the report.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+    pub format: ReportFormat,
+    /// SQLite database file to read.
+    #[arg(long, default_value = DATABASE)]
+    pub db: PathBuf,
+}
+
+/// Data defining arguments of the `report processes` command.
+#[derive(Args, Debug)]
+pub struct ProcessesArg {
+    /// Lower bound of the time range (RFC 3339 or YYYY-MM-DD).
+    #[arg(long)]
+    pub from: Option<String>,
+    /// Upper bound of the time range (RFC 3339 or YYYY-MM-DD).
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Maximum number of processes printed.
+    #[arg(long, default_value_t = 10)]
+    pub limit: usize,
+    /// Output format of the report.
+    #[arg(long, value_enum, default_value_t = ReportFormat::Table)]
+    pub format: ReportFormat,
+    /// SQLite database file to read.
+    #[arg(long, default_value = DATABASE)]
+    pub db: PathBuf,
+}
+
+/// Data defining arguments of the `export` command.
+#[derive(Args, Debug)]
+pub struct ExportArg {
+    /// Tables to export, all stored tables if not set.
+    #[arg(long, value_delimiter = ',')]
+    pub tables: Vec<String>,
+    /// Output format of the exported rows.
+    #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+    pub format: ExportFormat,
+    /// Lower bound of the time range (RFC 3339 or YYYY-MM-DD).
+    #[arg(long)]
+    pub from: Option<String>,
+    /// Upper bound of the time range (RFC 3339 or YYYY-MM-DD).
+    #[arg(long)]
+    pub to: Option<String>,
+    /// Hostname of the machine to keep among the databases.
+    #[arg(long)]
+    pub host: Option<String>,
+    /// Entity to keep (interface, disk, GPU bus id, pid...).
+    #[arg(long)]
+    pub entity: Option<String>,
+    /// Output file, or directory for a CSV export of several tables. Standard output if not set.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+    /// SQLite database files to read.
+    #[arg(long, value_delimiter = ',', default_value = DATABASE)]
+    pub db: Vec<PathBuf>,
 }
 
 /// Main function of `userv` program that run in threading tasks each probes
@@ -38,7 +230,38 @@ fn main() {
     }
 
     let arg = Arg::parse();
-    if !arg.all && arg.active.is_empty() {
+    let settings = match load_settings(&arg.config) {
+        Ok(res) => res,
+        Err(e) => {
+            error!("[{HEADER}] {e}");
+            eprintln!("[{HEADER}] {e}");
+            exit(1);
+        }
+    };
+
+    if let Some(command) = arg.command {
+        let res = match command {
+            Command::Export(export) => run_export(export),
+            Command::Snapshot(snapshot) => run_snapshot(snapshot, settings.collector),
+            Command::Report(ReportCommand::Cost(cost)) => {
+                run_report_cost(cost, settings.collector.cost)
+            }
+            Command::Report(ReportCommand::Processes(processes)) => run_report_processes(processes),
+            Command::Report(ReportCommand::Energy(energy)) => run_report_energy(energy),
+            Command::Report(ReportCommand::Carbon(carbon)) => run_report_carbon(carbon),
+            Command::Measure(measure) => run_measure(measure),
+            Command::Calibrate(calibrate) => run_calibrate(calibrate, settings.collector.cpu_power),
+            Command::Hash => run_hash(),
+        };
+        if let Err(e) = res {
+            error!("[{HEADER}] {e}");
+            eprintln!("[{HEADER}] {e}");
+            exit(1);
+        }
+        return;
+    }
+
+    if !arg.all && !arg.serve && arg.active.is_empty() {
         error!("[{HEADER}] Arguments 'No probe specified'");
         eprintln!(
             "[{HEADER}] Arguments : No probe specified !\n\
@@ -48,48 +271,39 @@ fn main() {
         exit(1);
     }
 
-    let components = if arg.all {
-        vec![
-            Component::Board,
-            Component::Cpu,
-            Component::Gpu,
-            Component::Net,
-            Component::Memory,
-            Component::Storage,
-            Component::System,
-        ]
+    let components = if arg.all || arg.active.is_empty() {
+        Component::ALL.to_vec()
     } else {
         arg.active
     };
 
-    if arg.freq == 0 {
-        let mut handles = Vec::new();
-        for component in components {
-            let probe = Probe::get_probe(&component);
-            handles.push(spawn(move || Probe::run_probe(probe)));
-        }
-        for handle in handles {
-            match handle.join() {
-                Ok(_) => println!("Finished task with success"),
-                Err(e) => error!("[{HEADER}] Process 'Failure in the thread' : {e:?}"),
-            }
-        }
-    } else {
-        loop {
-            let mut handles = Vec::new();
-            for component in &components {
-                let probe = Probe::get_probe(component);
-                handles.push(spawn(move || Probe::run_probe(probe)));
-            }
-            for handle in handles {
-                match handle.join() {
-                    Ok(_) => println!("Finished task with success"),
-                    Err(e) => error!("[{HEADER}] Process 'Failure in the thread' : {e:?}"),
-                }
-            }
-            sleep(Duration::from_secs(arg.freq));
+    let baselines = stored_baselines(Path::new(DATABASE)).unwrap_or_else(|e| {
+        error!("[{HEADER}] {e}");
+        Vec::new()
+    });
+    let freq = if arg.serve { arg.freq.max(1) } else { arg.freq };
+    let collector = Collector::new(CollectorConfig {
+        components,
+        interval: Duration::from_secs(freq),
+        persist: true,
+        publish: arg.serve,
+        baselines,
+        ..settings.collector
+    });
+
+    if arg.serve {
+        if let Err(e) = run_serve(collector, settings.web) {
+            error!("[{HEADER}] {e}");
+            eprintln!("[{HEADER}] {e}");
+            exit(1);
         }
+        return;
     }
 
-    //web();
+    let runs = if arg.freq == 0 { 1 } else { usize::MAX };
+    for snapshot in collector.sampling().take(runs) {
+        if snapshot.is_ok() {
+            println!("Finished task with success");
+        }
+    }
 }
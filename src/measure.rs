@@ -0,0 +1,428 @@
+//! # File measure
+//!
+//! This file provides the energy profile of a single command run, sampling at high frequency
+//! the RAPL zones, the NVML devices and the process tree of the command until it exits.
+//! The energies come from the cumulative counters of the zones and devices when they have one,
+//! the powers giving the peak.
+
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error as StdError,
+    process::Command,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{available_parallelism, sleep, spawn},
+    time::{Duration, Instant},
+};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+use cpu::{get_rapl_consumption, get_rapl_energy, get_rapl_zone_name};
+use gpu::GpuSampler;
+
+/// Cumulative energy counter of a RAPL zone.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Counter {
+    /// Zone folder name.
+    pub(crate) zone: String,
+    /// Zone of the DRAM, else of a CPU package.
+    pub(crate) dram: bool,
+    /// Energy counted in J.
+    pub(crate) energy: f64,
+    /// Energy in J at which the counter wraps around to 0, if known.
+    pub(crate) range: Option<f64>,
+}
+
+/// Power figures and energy counters read at an instant of the run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Sample {
+    /// Power of the CPU packages in W, none without RAPL.
+    pub(crate) cpu: Option<f64>,
+    /// Power of the DRAM in W, none without RAPL DRAM zone.
+    pub(crate) dram: Option<f64>,
+    /// Energy counters of the CPU packages and DRAM RAPL zones.
+    pub(crate) counters: Vec<Counter>,
+    /// Power of the GPUs in W, none without NVML.
+    pub(crate) gpu: Option<f64>,
+    /// Cumulative energy counter of the GPUs in J, if each device gives one.
+    pub(crate) gpu_counter: Option<f64>,
+    /// Share of the CPUs used by the process tree of the command, between 0 and 1.
+    pub(crate) share: f64,
+    /// Power of the GPUs used by the process tree of the command in W.
+    pub(crate) gpu_attributable: f64,
+}
+
+/// Energy profile of a command run.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Measurement {
+    /// Command run, with its arguments.
+    pub command: Vec<String>,
+    /// Exit code of the command, none if killed by a signal.
+    pub exit_code: Option<i32>,
+    /// Wall time of the run in seconds.
+    pub wall_time: f64,
+    /// Number of samples taken.
+    pub samples: usize,
+    /// Energy of the CPU packages in J, none without RAPL.
+    pub cpu_energy: Option<f64>,
+    /// Energy of the GPUs in J, none without NVML.
+    pub gpu_energy: Option<f64>,
+    /// Energy of the DRAM in J, none without RAPL DRAM zone.
+    pub dram_energy: Option<f64>,
+    /// Energy of the CPU packages, GPUs and DRAM in J.
+    pub total_energy: f64,
+    /// Average power in W over the run.
+    pub average_power: f64,
+    /// Highest power sampled in W.
+    pub peak_power: f64,
+    /// Energy attributable to the process tree of the command in J.
+    pub attributable_energy: f64,
+    /// Share of the total energy attributable to the command, none without energy.
+    pub attributable_share: Option<f64>,
+}
+
+/// Accumulation of the samples of a run.
+#[derive(Debug, Default)]
+pub(crate) struct Profile {
+    samples: usize,
+    cpu: Option<f64>,
+    dram: Option<f64>,
+    gpu: Option<f64>,
+    gpu_counter: Option<f64>,
+    counters: HashMap<String, f64>,
+    attributable: f64,
+    peak: f64,
+}
+
+/// Add an energy to an optional total.
+fn accumulate(total: &mut Option<f64>, energy: Option<f64>) {
+    if let Some(energy) = energy {
+        *total = Some(total.unwrap_or(0.0) + energy);
+    }
+}
+
+impl Profile {
+    /// Add a sample taken the time elapsed after the previous one.
+    /// The CPU and DRAM energies come from the increase of the RAPL counters, wrapping around
+    /// at their range, and the GPU one from the increase of its counter when it has a previous
+    /// value, its power being held over the time elapsed otherwise.
+    pub(crate) fn add(&mut self, sample: &Sample, elapsed: f64) {
+        self.samples += 1;
+        let mut host = 0.0;
+        for counter in &sample.counters {
+            let energy = match self.counters.insert(counter.zone.clone(), counter.energy) {
+                None => 0.0,
+                Some(previous) if counter.energy >= previous => counter.energy - previous,
+                Some(previous) => match counter.range {
+                    Some(range) => counter.energy + range - previous,
+                    None => continue,
+                },
+            };
+            let total = if counter.dram {
+                &mut self.dram
+            } else {
+                &mut self.cpu
+            };
+            accumulate(total, Some(energy));
+            host += energy;
+        }
+        let counter = match (self.gpu_counter, sample.gpu_counter) {
+            (Some(previous), Some(counter)) if counter >= previous => Some(counter - previous),
+            _ => None,
+        };
+        self.gpu_counter = sample.gpu_counter;
+        accumulate(&mut self.gpu, counter.or(sample.gpu.map(|w| w * elapsed)));
+
+        self.attributable += host * sample.share + sample.gpu_attributable * elapsed;
+        let power = sample.cpu.unwrap_or(0.0) + sample.dram.unwrap_or(0.0);
+        self.peak = self.peak.max(power + sample.gpu.unwrap_or(0.0));
+    }
+
+    /// Summarize the profile of a run.
+    pub(crate) fn summary(
+        self,
+        command: &[String],
+        exit_code: Option<i32>,
+        wall_time: f64,
+    ) -> Measurement {
+        let total_energy = [self.cpu, self.gpu, self.dram]
+            .iter()
+            .flatten()
+            .fold(0.0, |total, energy| total + energy);
+        Measurement {
+            command: command.to_vec(),
+            exit_code,
+            wall_time,
+            samples: self.samples,
+            cpu_energy: self.cpu,
+            gpu_energy: self.gpu,
+            dram_energy: self.dram,
+            total_energy,
+            average_power: if wall_time > 0.0 {
+                total_energy / wall_time
+            } else {
+                0.0
+            },
+            peak_power: self.peak,
+            attributable_energy: self.attributable.min(total_energy),
+            attributable_share: (total_energy > 0.0)
+                .then(|| (self.attributable / total_energy).min(1.0)),
+        }
+    }
+}
+
+/// Find a process and its descendants.
+///
+/// # Arguments
+///
+/// - `parents` : Parent PID of each PID.
+/// - `root` : PID of the first process of the tree.
+///
+/// # Returns
+///
+/// The PID of each process of the tree.
+pub(crate) fn descendants(parents: &HashMap<u32, u32>, root: u32) -> HashSet<u32> {
+    let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+    for (&pid, &parent) in parents {
+        children.entry(parent).or_default().push(pid);
+    }
+    let mut tree = HashSet::from([root]);
+    let mut stack = vec![root];
+    while let Some(pid) = stack.pop() {
+        for &child in children.get(&pid).into_iter().flatten() {
+            if tree.insert(child) {
+                stack.push(child);
+            }
+        }
+    }
+    tree
+}
+
+/// Sampler of the powers, keeping the NVML session and the process list between samples.
+struct Sampler {
+    gpu: Option<GpuSampler>,
+    system: System,
+    cpus: f64,
+    zones: HashMap<String, Option<String>>,
+}
+
+impl Sampler {
+    fn new() -> Self {
+        Sampler {
+            gpu: GpuSampler::new().ok(),
+            system: System::new(),
+            cpus: available_parallelism().map_or(1, |n| n.get()) as f64,
+            zones: HashMap::new(),
+        }
+    }
+
+    /// Kind of a RAPL zone: `Some(true)` for the `dram` zones, `Some(false)` for the
+    /// top-level package ones, and none for the others, counted in their package.
+    fn dram(&mut self, zone: &str) -> Option<bool> {
+        let name = self
+            .zones
+            .entry(zone.to_string())
+            .or_insert_with(|| get_rapl_zone_name(zone));
+        match name.as_deref() {
+            Some("dram") => Some(true),
+            Some(name) if !name.starts_with("package") => None,
+            _ => (zone.matches(':').count() <= 1).then_some(false),
+        }
+    }
+
+    /// Read the energy counters and the powers, and the usage of the process tree of a PID.
+    /// The CPU figures are the ones of the top-level package zones, the DRAM ones of the `dram` zones.
+    fn sample(&mut self, root: u32) -> Sample {
+        let mut sample = Sample::default();
+
+        for (zone, energy, range) in get_rapl_energy().unwrap_or_default() {
+            if let Some(dram) = self.dram(&zone) {
+                sample.counters.push(Counter {
+                    zone,
+                    dram,
+                    energy,
+                    range,
+                });
+            }
+        }
+        for (zone, power) in get_rapl_consumption().unwrap_or_default() {
+            match self.dram(&zone) {
+                Some(true) => accumulate(&mut sample.dram, Some(power)),
+                Some(false) => accumulate(&mut sample.cpu, Some(power)),
+                None => {}
+            }
+        }
+
+        self.system.refresh_processes_specifics(
+            ProcessesToUpdate::All,
+            true,
+            ProcessRefreshKind::nothing().with_cpu(),
+        );
+        let parents = self
+            .system
+            .processes()
+            .iter()
+            .filter_map(|(pid, p)| Some((pid.as_u32(), p.parent()?.as_u32())))
+            .collect();
+        let tree = descendants(&parents, root);
+        let usage: f32 = tree
+            .iter()
+            .filter_map(|pid| self.system.process(sysinfo::Pid::from_u32(*pid)))
+            .map(|p| p.cpu_usage())
+            .sum();
+        sample.share = (usage as f64 / 100.0 / self.cpus).clamp(0.0, 1.0);
+
+        if let Some(devices) = self.gpu.as_ref().and_then(|gpu| gpu.collect().ok()) {
+            let mut counters = Some(0.0);
+            for device in &devices {
+                let power = device
+                    .metrics
+                    .gpu_power_consumption
+                    .map(|mw| mw as f64 / 1e3);
+                accumulate(&mut sample.gpu, power);
+                counters = counters
+                    .zip(device.metrics.gpu_energy_consumption)
+                    .map(|(total, mj)| total + mj / 1e3);
+                for process in &device.processes {
+                    if let (Some(pid), Some(sm), Some(power)) =
+                        (process.process_pid, process.process_sm, power)
+                        && tree.contains(&pid)
+                    {
+                        sample.gpu_attributable += power * sm as f64 / 100.0;
+                    }
+                }
+            }
+            sample.gpu_counter = counters.filter(|_| !devices.is_empty());
+        }
+        sample
+    }
+}
+
+/// Run a command and profile its energy, like `perf stat`.
+/// The standard streams of the command are inherited, and the powers are sampled
+/// in the background every interval until it exits.
+///
+/// # Arguments
+///
+/// - `command` : Program to run followed by its arguments.
+/// - `interval` : Interval between two samples.
+///
+/// # Returns
+///
+/// - The [`Measurement`] of the run.
+/// - An error if the command can't be started.
+pub fn measure(command: &[String], interval: Duration) -> Result<Measurement, Box<dyn StdError>> {
+    let (program, args) = command
+        .split_first()
+        .ok_or("Arguments 'No command to measure'")?;
+
+    let start = Instant::now();
+    let mut child = Command::new(program)
+        .args(args)
+        .spawn()
+        .map_err(|e| format!("Process 'Failed to run {program}' : {e}"))?;
+    let root = child.id();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let handle = spawn({
+        let stop = stop.clone();
+        move || {
+            let mut sampler = Sampler::new();
+            let mut profile = Profile::default();
+            let mut last = Instant::now();
+            loop {
+                let done = stop.load(Ordering::Acquire);
+                let sample = sampler.sample(root);
+                let now = Instant::now();
+                profile.add(&sample, now.duration_since(last).as_secs_f64());
+                last = now;
+                if done {
+                    return profile;
+                }
+                sleep(interval);
+            }
+        }
+    });
+
+    let status = child.wait()?;
+    let wall_time = start.elapsed().as_secs_f64();
+    stop.store(true, Ordering::Release);
+    let profile = handle
+        .join()
+        .map_err(|e| format!("Process 'Failure in the thread' : {e:?}"))?;
+    Ok(profile.summary(command, status.code(), wall_time))
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test `Profile::add` function integrating the RAPL and GPU counters
+    #[test]
+    fn test_profile() {
+        let mut profile = Profile::default();
+        let counter = |zone: &str, dram, energy| Counter {
+            zone: zone.to_string(),
+            dram,
+            energy,
+            range: Some(100.0),
+        };
+        let sample = |cpu, package, memory, gpu, counter_gpu, share, attributable| Sample {
+            cpu: Some(cpu),
+            dram: Some(5.0),
+            counters: vec![
+                counter("intel-rapl:0", false, package),
+                counter("intel-rapl:0:2", true, memory),
+            ],
+            gpu: Some(gpu),
+            gpu_counter: Some(counter_gpu),
+            share,
+            gpu_attributable: attributable,
+        };
+        profile.add(&sample(20.0, 80.0, 0.0, 100.0, 1000.0, 0.5, 0.0), 0.0);
+        profile.add(&sample(40.0, 20.0, 5.0, 100.0, 1150.0, 0.5, 50.0), 1.0);
+        profile.add(&sample(20.0, 40.0, 10.0, 60.0, 1210.0, 0.0, 0.0), 1.0);
+
+        let res = profile.summary(&["bench".to_string()], Some(0), 2.0);
+        assert_eq!(res.samples, 3);
+        assert_eq!(res.cpu_energy, Some(60.0));
+        assert_eq!(res.dram_energy, Some(10.0));
+        assert_eq!(res.gpu_energy, Some(210.0));
+        assert_eq!(res.total_energy, 280.0);
+        assert_eq!(res.average_power, 140.0);
+        assert_eq!(res.peak_power, 145.0);
+        assert_eq!(res.attributable_energy, 72.5);
+        assert_eq!(res.attributable_share, Some(72.5 / 280.0));
+
+        let res = Profile::default().summary(&[], None, 1.0);
+        assert_eq!(res.cpu_energy, None);
+        assert_eq!(res.attributable_share, None);
+    }
+
+    // Test `descendants` function following the parent links
+    #[test]
+    fn test_descendants() {
+        let parents = HashMap::from([(10, 1), (11, 10), (12, 11), (13, 1), (14, 12)]);
+        let res = descendants(&parents, 10);
+        assert_eq!(res, HashSet::from([10, 11, 12, 14]));
+    }
+
+    // Test `measure` function running a command and keeping its exit code
+    #[test]
+    fn test_measure() {
+        let command = ["sh", "-c", "sleep 0.2; exit 3"].map(str::to_string);
+        let res = measure(&command, Duration::from_millis(50)).unwrap();
+        assert_eq!(res.exit_code, Some(3));
+        assert!(res.wall_time >= 0.2);
+        assert!(res.samples >= 2);
+
+        assert!(measure(&[], Duration::from_millis(50)).is_err());
+        assert!(measure(&["/nonexistent".to_string()], Duration::from_millis(50)).is_err());
+    }
+}
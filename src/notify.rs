@@ -0,0 +1,737 @@
+//! # File notify
+//!
+//! This file provides the notification of the alert transitions by HTTP webhook,
+//! local command or email, with retries, rate limiting and grouping per channel.
+
+use lettre::{
+    Message, SmtpTransport, Transport,
+    message::{Mailbox, header::ContentType},
+    transport::smtp::{
+        authentication::Credentials,
+        client::{Tls, TlsParameters},
+    },
+};
+use log::error;
+use serde::Deserialize;
+use std::{
+    collections::VecDeque,
+    error::Error as StdError,
+    process::{Command, Stdio},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Sender},
+    },
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+use ureq::{
+    Agent,
+    tls::{TlsConfig, TlsProvider},
+};
+use userv_core::alert::{State, Transition};
+
+const HEADER: &str = "NOTIFY";
+
+/// Number of transitions a channel keeps waiting to be sent, the oldest being dropped beyond.
+const BUFFER: usize = 1000;
+/// Time allowed to a notification to be delivered, and to a command to end.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Interval between two checks of the end of a command.
+const POLL: Duration = Duration::from_millis(20);
+
+/// Default payload of the webhooks, accepted by the Slack and Mattermost incoming webhooks.
+/// `{{text}}` is replaced by the summary of the alerts escaped for a JSON string,
+/// `{{alerts}}` by their JSON list and `{{count}}` by their number.
+pub const TEMPLATE: &str = r#"{"text": "{{text}}"}"#;
+
+/// Security of the connection to an SMTP server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    /// Plain connection, on port 25 by default.
+    None,
+    /// Plain connection upgraded by `STARTTLS`, on port 587 by default.
+    Starttls,
+    /// TLS connection, on port 465 by default.
+    Tls,
+}
+
+/// Way of delivering the notifications of a channel.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum Backend {
+    /// JSON payload posted to an HTTP endpoint.
+    Webhook {
+        /// URL of the endpoint.
+        url: String,
+        /// Payload, [`TEMPLATE`] by default.
+        #[serde(default = "default_template")]
+        template: String,
+    },
+    /// Shell command run with the alerts in `USERV_ALERT*` environment variables.
+    Command {
+        /// Shell command.
+        command: String,
+    },
+    /// Email sent through an SMTP server.
+    Email(Email),
+}
+
+/// SMTP server and addresses of an email channel.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Email {
+    /// Hostname of the SMTP server.
+    pub server: String,
+    /// Port of the SMTP server, according the security by default.
+    pub port: Option<u16>,
+    /// Security of the connection.
+    #[serde(default = "default_security")]
+    pub security: Security,
+    /// User authenticated on the server, if any.
+    pub username: Option<String>,
+    /// Password of the user.
+    pub password: Option<String>,
+    /// Sender address.
+    pub from: String,
+    /// Recipient addresses.
+    pub to: Vec<String>,
+}
+
+fn default_template() -> String {
+    TEMPLATE.to_string()
+}
+
+fn default_security() -> Security {
+    Security::Starttls
+}
+
+/// Notification channel, from the `[[notify]]` sections of the configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Channel {
+    /// Delivery of the notifications.
+    #[serde(flatten)]
+    pub backend: Backend,
+    /// States of the transitions notified.
+    #[serde(default = "default_states")]
+    pub states: Vec<State>,
+    /// Number of new attempts after a failed delivery.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// Delay in seconds before the first new attempt, doubled at each one.
+    #[serde(default = "default_retry_delay")]
+    pub retry_delay: u64,
+    /// Maximal number of notifications sent per period, unlimited if 0.
+    /// The transitions exceeding it are grouped in the next notification.
+    #[serde(default)]
+    pub rate_limit: u32,
+    /// Period of the rate limit in seconds.
+    #[serde(default = "default_rate_period")]
+    pub rate_period: u64,
+    /// Delay in seconds after a first transition during which the next ones are grouped with it.
+    #[serde(default)]
+    pub group_wait: u64,
+}
+
+fn default_states() -> Vec<State> {
+    vec![State::Firing, State::Resolved]
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_retry_delay() -> u64 {
+    1
+}
+
+fn default_rate_period() -> u64 {
+    3600
+}
+
+/// Transitions waiting to be sent by a channel, and dates of its last notifications.
+#[derive(Debug, Default)]
+struct Queue {
+    pending: VecDeque<Transition>,
+    since: Option<Instant>,
+    sent: VecDeque<Instant>,
+}
+
+impl Queue {
+    /// Add a transition after the waiting ones, the oldest being dropped when full.
+    fn push(&mut self, transition: Transition) {
+        if self.pending.len() == BUFFER {
+            self.pending.pop_front();
+            error!("[{HEADER}] Data 'Notification queue full, oldest alert dropped'");
+        }
+        self.pending.push_back(transition);
+    }
+
+    /// Put back an undelivered transition before the waiting ones, dropped when full.
+    fn push_front(&mut self, transition: Transition) {
+        if self.pending.len() == BUFFER {
+            error!("[{HEADER}] Data 'Notification queue full, oldest alert dropped'");
+            return;
+        }
+        self.pending.push_front(transition);
+    }
+}
+
+/// Line describing a transition.
+fn line(transition: &Transition) -> String {
+    let entity = transition
+        .entity
+        .as_deref()
+        .map(|entity| format!(" on {entity}"))
+        .unwrap_or_default();
+    format!(
+        "[{}] {}{entity} : {} ({})",
+        transition.state.as_str().to_uppercase(),
+        transition.name,
+        transition.value,
+        transition.rule
+    )
+}
+
+/// Summary of the transitions, one line each.
+fn text(transitions: &[Transition]) -> String {
+    transitions.iter().map(line).collect::<Vec<_>>().join("\n")
+}
+
+/// Subject of the email of the transitions.
+fn subject(transitions: &[Transition]) -> String {
+    let subject = match transitions {
+        [transition] => line(transition),
+        _ => {
+            let counts: Vec<String> = [State::Firing, State::Resolved, State::Pending]
+                .iter()
+                .filter_map(|state| {
+                    let count = transitions.iter().filter(|t| t.state == *state).count();
+                    (count > 0).then(|| format!("{count} {state}"))
+                })
+                .collect();
+            format!("{} alerts : {}", transitions.len(), counts.join(", "))
+        }
+    };
+    format!("[userv] {}", subject.replace(['\r', '\n'], " "))
+}
+
+/// Fill a webhook payload template.
+fn payload(template: &str, transitions: &[Transition]) -> Result<String, Box<dyn StdError>> {
+    let text = serde_json::to_string(&text(transitions))?;
+    Ok(template
+        .replace("{{text}}", &text[1..text.len() - 1])
+        .replace("{{alerts}}", &serde_json::to_string(transitions)?)
+        .replace("{{count}}", &transitions.len().to_string()))
+}
+
+/// Post the payload of the transitions to a webhook.
+fn send_webhook(
+    url: &str,
+    template: &str,
+    transitions: &[Transition],
+) -> Result<(), Box<dyn StdError>> {
+    let agent: Agent = Agent::config_builder()
+        .timeout_global(Some(TIMEOUT))
+        .tls_config(
+            TlsConfig::builder()
+                .provider(TlsProvider::NativeTls)
+                .build(),
+        )
+        .build()
+        .into();
+    agent
+        .post(url)
+        .header("Content-Type", "application/json")
+        .send(payload(template, transitions)?)
+        .map_err(|e| format!("Network 'Webhook failed' : {url} {e}"))?;
+    Ok(())
+}
+
+/// Run a command with the transitions in its environment, killed if it doesn't end in time.
+fn send_command(command: &str, transitions: &[Transition]) -> Result<(), Box<dyn StdError>> {
+    let mut process = Command::new("sh");
+    process
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .env("USERV_ALERT_COUNT", transitions.len().to_string())
+        .env("USERV_ALERT_TEXT", text(transitions))
+        .env("USERV_ALERTS", serde_json::to_string(transitions)?);
+    if let [t] = transitions {
+        process
+            .env("USERV_ALERT_NAME", &t.name)
+            .env("USERV_ALERT_STATE", t.state.as_str())
+            .env(
+                "USERV_ALERT_ENTITY",
+                t.entity.as_deref().unwrap_or_default(),
+            )
+            .env("USERV_ALERT_VALUE", t.value.to_string())
+            .env("USERV_ALERT_RULE", &t.rule)
+            .env("USERV_ALERT_TIMESTAMP", &t.timestamp);
+    }
+    let mut child = process
+        .spawn()
+        .map_err(|e| format!("Process 'Spawn failed' : {command} {e}"))?;
+
+    let deadline = Instant::now() + TIMEOUT;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("Process 'Timeout' : {command}").into());
+        }
+        sleep(POLL);
+    };
+    if !status.success() {
+        return Err(format!("Process 'Command failed' : {command} {status}").into());
+    }
+    Ok(())
+}
+
+/// Send an email of the transitions through an SMTP server.
+fn send_email(email: &Email, transitions: &[Transition]) -> Result<(), Box<dyn StdError>> {
+    if email.to.is_empty() {
+        return Err("Config 'No email recipient'".into());
+    }
+    let mut message = Message::builder()
+        .from(email.from.parse::<Mailbox>()?)
+        .subject(subject(transitions))
+        .header(ContentType::TEXT_PLAIN);
+    for recipient in &email.to {
+        message = message.to(recipient.parse::<Mailbox>()?);
+    }
+    let message = message.body(text(transitions))?;
+
+    let port = email.port.unwrap_or(match email.security {
+        Security::None => 25,
+        Security::Starttls => 587,
+        Security::Tls => 465,
+    });
+    let parameters = || TlsParameters::new(email.server.clone());
+    let mut transport = SmtpTransport::builder_dangerous(&email.server)
+        .port(port)
+        .timeout(Some(TIMEOUT))
+        .tls(match email.security {
+            Security::None => Tls::None,
+            Security::Starttls => Tls::Required(parameters()?),
+            Security::Tls => Tls::Wrapper(parameters()?),
+        });
+    match (&email.username, &email.password) {
+        (Some(username), Some(password)) => {
+            transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        (None, None) => {}
+        _ => return Err("Config 'Email username and password go together'".into()),
+    }
+    transport
+        .build()
+        .send(&message)
+        .map_err(|e| format!("Network 'SMTP error' : {}:{port} {e}", email.server))?;
+    Ok(())
+}
+
+impl Channel {
+    /// Deliver the transitions once.
+    fn send(&self, transitions: &[Transition]) -> Result<(), Box<dyn StdError>> {
+        match &self.backend {
+            Backend::Webhook { url, template } => send_webhook(url, template, transitions),
+            Backend::Command { command } => send_command(command, transitions),
+            Backend::Email(email) => send_email(email, transitions),
+        }
+    }
+
+    /// Deliver the transitions, trying again with a doubled delay after each failure.
+    fn deliver(&self, transitions: &[Transition]) -> Result<(), Box<dyn StdError>> {
+        let mut delay = Duration::from_secs(self.retry_delay);
+        let mut attempt = 0;
+        loop {
+            match self.send(transitions) {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt >= self.retries => return Err(e),
+                Err(_) => {
+                    sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Notification of the alert transitions on the configured channels.
+/// Clones share the same queues.
+#[derive(Debug, Clone, Default)]
+pub struct Notifier {
+    channels: Arc<Vec<(Channel, Mutex<Queue>)>>,
+}
+
+impl Notifier {
+    /// Create the notification channels, without pending transitions.
+    ///
+    /// # Arguments
+    ///
+    /// - `channels` : Configured [`Channel`] list.
+    pub fn new(channels: Vec<Channel>) -> Self {
+        Notifier {
+            channels: Arc::new(
+                channels
+                    .into_iter()
+                    .map(|channel| (channel, Mutex::default()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Check if no channel is configured.
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// Queue the transitions on each channel notifying their state, then send the queued ones
+    /// of the channels whose group delay is elapsed and rate limit not reached.
+    /// The queue isn't locked while sending, and the transitions still undelivered after the
+    /// last attempt are queued again for the next call.
+    /// It must be called at each cycle, even without transitions, to send the waiting ones.
+    ///
+    /// # Arguments
+    ///
+    /// - `transitions` : New [`Transition`] list.
+    /// - `now` : Instant of the call, to measure the group delays and rate limits.
+    ///
+    /// # Returns
+    ///
+    /// The error message of each failed delivery, after its last attempt.
+    pub fn notify(&self, transitions: &[Transition], now: Instant) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (channel, queue) in self.channels.iter() {
+            let pending = {
+                let mut queue = queue.lock().unwrap_or_else(|e| e.into_inner());
+                for transition in transitions {
+                    if channel.states.contains(&transition.state) {
+                        queue.push(transition.clone());
+                    }
+                }
+                if queue.pending.is_empty() {
+                    continue;
+                }
+
+                let since = *queue.since.get_or_insert(now);
+                if now.duration_since(since) < Duration::from_secs(channel.group_wait) {
+                    continue;
+                }
+                let period = Duration::from_secs(channel.rate_period);
+                while queue
+                    .sent
+                    .front()
+                    .is_some_and(|sent| now.duration_since(*sent) >= period)
+                {
+                    queue.sent.pop_front();
+                }
+                if channel.rate_limit > 0 && queue.sent.len() >= channel.rate_limit as usize {
+                    continue;
+                }
+
+                queue.since = None;
+                queue.sent.push_back(now);
+                queue.pending.drain(..).collect::<Vec<_>>()
+            };
+
+            if let Err(e) = channel.deliver(&pending) {
+                error!("[{HEADER}] {e}");
+                errors.push(e.to_string());
+                let mut queue = queue.lock().unwrap_or_else(|e| e.into_inner());
+                for transition in pending.into_iter().rev() {
+                    queue.push_front(transition);
+                }
+                queue.since.get_or_insert(now);
+            }
+        }
+        errors
+    }
+
+    /// Notify from a background thread the transitions of each cycle, so that the deliveries
+    /// and their retries never delay the sampling.
+    ///
+    /// # Returns
+    ///
+    /// The [`Sender`] of the transitions of each cycle, the thread ending when it is dropped.
+    pub fn spawn(&self) -> Sender<Vec<Transition>> {
+        let (sender, receiver) = mpsc::channel::<Vec<Transition>>();
+        let notifier = self.clone();
+        spawn(move || {
+            for transitions in receiver {
+                notifier.notify(&transitions, Instant::now());
+            }
+        });
+        sender
+    }
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::base64;
+    use std::{
+        fs::read_to_string,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread::JoinHandle,
+    };
+    use tempfile::NamedTempFile;
+    use userv_core::config::Config;
+
+    fn transition(name: &str, state: State) -> Transition {
+        Transition {
+            timestamp: "2025-01-01T10:00:00.000Z".to_string(),
+            name: name.to_string(),
+            rule: "cpu_temperature.temperature > 90".to_string(),
+            entity: Some("zone0".to_string()),
+            state,
+            value: 95.0,
+        }
+    }
+
+    fn channel(section: &str) -> Channel {
+        let config = Config::parse(&format!("[[notify]]\n{section}")).unwrap();
+        config.section::<Vec<Channel>>("notify").unwrap().remove(0)
+    }
+
+    /// HTTP server answering the given statuses, and returning the bodies it received.
+    fn http_server(statuses: Vec<u16>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                write!(
+                    &stream,
+                    "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )
+                .unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    /// SMTP server accepting a message, and returning the commands and data it received.
+    fn smtp_server() -> (u16, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut received = Vec::new();
+            stream.write_all(b"220 fake ESMTP\r\n").unwrap();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let reply: &[u8] = match line.split_whitespace().next().unwrap_or_default() {
+                    "EHLO" => b"250-fake\r\n250 AUTH PLAIN\r\n",
+                    "AUTH" => b"235 Authenticated\r\n",
+                    "DATA" => {
+                        stream.write_all(b"354 Go ahead\r\n").unwrap();
+                        let mut data = String::new();
+                        while !data.ends_with("\r\n.\r\n") {
+                            reader.read_line(&mut data).unwrap();
+                        }
+                        received.push(data);
+                        b"250 Queued\r\n"
+                    }
+                    "QUIT" => {
+                        stream.write_all(b"221 Bye\r\n").unwrap();
+                        break;
+                    }
+                    _ => b"250 OK\r\n",
+                };
+                received.push(line.trim_end().to_string());
+                stream.write_all(reply).unwrap();
+            }
+            received
+        });
+        (port, handle)
+    }
+
+    // Test `Channel` deserialization with defaults
+    #[test]
+    fn test_channel_config() {
+        let res = channel("kind = \"webhook\"\nurl = \"http://localhost/hook\"\n");
+        assert_eq!(res.states, vec![State::Firing, State::Resolved]);
+        assert_eq!(res.retries, 3);
+        assert!(matches!(res.backend, Backend::Webhook { template, .. } if template == TEMPLATE));
+
+        let config = Config::parse("[[notify]]\nkind = \"pager\"\n").unwrap();
+        assert!(config.section::<Vec<Channel>>("notify").is_err());
+    }
+
+    // Test `payload` function with the default template
+    #[test]
+    fn test_payload() {
+        let res = payload(
+            TEMPLATE,
+            &[
+                transition("cpu_hot", State::Firing),
+                transition("disk \"full\"", State::Resolved),
+            ],
+        )
+        .unwrap();
+        let res: serde_json::Value = serde_json::from_str(&res).unwrap();
+        assert_eq!(
+            res["text"],
+            "[FIRING] cpu_hot on zone0 : 95 (cpu_temperature.temperature > 90)\n\
+            [RESOLVED] disk \"full\" on zone0 : 95 (cpu_temperature.temperature > 90)"
+        );
+    }
+
+    // Test webhook channel trying again after a server error
+    #[test]
+    fn test_webhook_retry() {
+        let (url, server) = http_server(vec![500, 200]);
+        let notifier = Notifier::new(vec![channel(&format!(
+            "kind = \"webhook\"\nurl = \"{url}\"\nretries = 1\nretry_delay = 0\n\
+            template = '{{\"alerts\": {{{{alerts}}}}, \"count\": {{{{count}}}}}}'\n"
+        ))]);
+
+        let res = notifier.notify(&[transition("cpu_hot", State::Firing)], Instant::now());
+        assert!(res.is_empty(), "{res:?}");
+        let bodies = server.join().unwrap();
+        assert_eq!(bodies.len(), 2);
+        let body: serde_json::Value = serde_json::from_str(&bodies[1]).unwrap();
+        assert_eq!(body["count"], 1);
+        assert_eq!(body["alerts"][0]["name"], "cpu_hot");
+    }
+
+    // Test command channel environment, and error after the last attempt
+    #[test]
+    fn test_command() {
+        let file = NamedTempFile::new().unwrap();
+        let command = format!(
+            "printf '%s %s %s %s' \"$USERV_ALERT_NAME\" \"$USERV_ALERT_STATE\" \"$USERV_ALERT_ENTITY\" \"$USERV_ALERT_COUNT\" > {}",
+            file.path().display()
+        );
+        let notifier = Notifier::new(vec![channel(&format!(
+            "kind = \"command\"\ncommand = '''{command}'''\n"
+        ))]);
+        let res = notifier.notify(&[transition("cpu_hot", State::Firing)], Instant::now());
+        assert!(res.is_empty(), "{res:?}");
+        assert_eq!(
+            read_to_string(file.path()).unwrap(),
+            "cpu_hot firing zone0 1"
+        );
+
+        let notifier = Notifier::new(vec![channel(
+            "kind = \"command\"\ncommand = \"exit 1\"\nretries = 1\nretry_delay = 0\n",
+        )]);
+        let res = notifier.notify(&[transition("cpu_hot", State::Firing)], Instant::now());
+        assert_eq!(res.len(), 1);
+    }
+
+    // Test undelivered transitions queued again for the next call
+    #[test]
+    fn test_requeue() {
+        let dir = tempfile::tempdir().unwrap();
+        let (ready, file) = (dir.path().join("ready"), dir.path().join("count"));
+        let command = format!(
+            "test -f {} && echo \"$USERV_ALERT_COUNT\" >> {}",
+            ready.display(),
+            file.display()
+        );
+        let notifier = Notifier::new(vec![channel(&format!(
+            "kind = \"command\"\ncommand = '{command}'\nretries = 0\n"
+        ))]);
+        let start = Instant::now();
+
+        let res = notifier.notify(&[transition("a", State::Firing)], start);
+        assert_eq!(res.len(), 1);
+        std::fs::write(&ready, "").unwrap();
+        let res = notifier.notify(
+            &[transition("b", State::Firing)],
+            start + Duration::from_secs(1),
+        );
+        assert!(res.is_empty(), "{res:?}");
+        assert_eq!(read_to_string(&file).unwrap(), "2\n");
+    }
+
+    // Test email channel with a fake SMTP server
+    #[test]
+    fn test_email() {
+        let (port, server) = smtp_server();
+        let notifier = Notifier::new(vec![channel(&format!(
+            "kind = \"email\"\nserver = \"127.0.0.1\"\nport = {port}\nsecurity = \"none\"\n\
+            username = \"user\"\npassword = \"pass\"\nretries = 0\n\
+            from = \"userv@example.org\"\nto = [\"ops@example.org\", \"dev@example.org\"]\n"
+        ))]);
+
+        let res = notifier.notify(
+            &[
+                transition("cpu_hot", State::Firing),
+                transition("disk_full", State::Firing),
+            ],
+            Instant::now(),
+        );
+        assert!(res.is_empty(), "{res:?}");
+        let received = server.join().unwrap();
+        assert!(received.contains(&format!(
+            "AUTH PLAIN {}",
+            base64::encode_block(b"\0user\0pass")
+        )));
+        assert!(received.contains(&"RCPT TO:<dev@example.org>".to_string()));
+        let data = received.iter().find(|r| r.contains("Subject")).unwrap();
+        assert!(data.contains("Subject: [userv] 2 alerts : 2 firing\r\n"));
+        assert!(data.contains("[FIRING] disk_full on zone0"));
+    }
+
+    // Test grouping delay, rate limit and states filter of a channel
+    #[test]
+    fn test_grouping_rate_limit() {
+        let file = NamedTempFile::new().unwrap();
+        let command = format!("echo \"$USERV_ALERT_COUNT\" >> {}", file.path().display());
+        let notifier = Notifier::new(vec![channel(&format!(
+            "kind = \"command\"\ncommand = '{command}'\ngroup_wait = 60\nrate_limit = 1\n"
+        ))]);
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        notifier.notify(&[transition("a", State::Firing)], at(0));
+        notifier.notify(&[transition("b", State::Pending)], at(30));
+        notifier.notify(&[transition("c", State::Resolved)], at(30));
+        assert_eq!(read_to_string(file.path()).unwrap(), "");
+        notifier.notify(&[], at(60));
+        assert_eq!(read_to_string(file.path()).unwrap(), "2\n");
+
+        notifier.notify(&[transition("d", State::Firing)], at(200));
+        notifier.notify(&[transition("e", State::Firing)], at(3600));
+        assert_eq!(read_to_string(file.path()).unwrap(), "2\n");
+        notifier.notify(&[], at(3660));
+        assert_eq!(read_to_string(file.path()).unwrap(), "2\n2\n");
+    }
+}
@@ -5,9 +5,13 @@ use std::{error::Error, fs::read};
 mod dbms;
 mod utils;
 
-use core::core::{DMIDECODE_BIN, ENTRY_BIN, db_insert_unique, db_table_query_creation, init_db};
+use core::core::{
+    DMIDECODE_BIN, ENTRY_BIN, SqlFieldDescriptor, db_insert_unique, db_table_query_creation,
+    init_db,
+};
 use dbms::*;
-use utils::*;
+pub use utils::BoardInfo;
+use utils::board_data_build;
 
 impl BoardInfo {
     /// Insert only one time main board and BIOS parameters in database.
@@ -59,21 +63,51 @@ impl BoardInfo {
     }
 }
 
-/// Push in SQLite memory database the data retrieve by [`BoardInfo`].
+/// Description of the main board tables stored in database.
 ///
 /// # Returns
 ///
-/// Failure if we can't retrieve information or push it in database.
-pub fn get_board_info() -> Result<(), Box<dyn Error>> {
+/// The name of each table with the [`SqlFieldDescriptor`] list of its fields.
+pub fn schema() -> Vec<(&'static str, Vec<SqlFieldDescriptor>)> {
+    vec![(TABLE_NAME, field_descriptor())]
+}
+
+/// Retrieves the main board and BIOS data from DMI tables, without storing it.
+///
+/// # Returns
+///
+/// - Completed [`BoardInfo`] structure.
+/// - An error if DMI tables can't be read or parsed.
+pub fn collect_board_info() -> Result<BoardInfo, Box<dyn Error>> {
     let entry_buf = read(ENTRY_BIN)?;
     let dmi_buf = read(DMIDECODE_BIN)?;
+    board_data_build(&entry_buf, &dmi_buf)
+}
 
-    let data = board_data_build(&entry_buf, &dmi_buf)?;
+/// Store main board data in SQLite database.
+///
+/// # Arguments
+///
+/// - `data` : [`BoardInfo`] information to insert in database.
+///
+/// # Returns
+///
+/// Failure if we can't push information in database.
+pub fn store_board_info(data: &BoardInfo) -> Result<(), Box<dyn Error>> {
     let query = db_table_query_creation(TABLE_NAME, &field_descriptor())?;
     let mut conn = init_db(&query)?;
 
     let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
-    BoardInfo::insert_db(&mut conn, &timestamp, &data)?;
+    BoardInfo::insert_db(&mut conn, &timestamp, data)?;
 
     Ok(())
 }
+
+/// Push in SQLite memory database the data retrieve by [`BoardInfo`].
+///
+/// # Returns
+///
+/// Failure if we can't retrieve information or push it in database.
+pub fn get_board_info() -> Result<(), Box<dyn Error>> {
+    store_board_info(&collect_board_info()?)
+}
@@ -0,0 +1,161 @@
+//! # Lib file for data base management system data module
+//!
+//! This module provides main functionality to set database parameters.
+
+use core::core::{SQLiteKey, SQLiteOption, SQLiteType, SqlFieldDescriptor};
+
+/// SQL table(s) available to create.
+pub const TABLE_NAME: &str = "cgroup_data";
+
+/// # Returns
+///
+/// - A tuple of [`SqlFieldDescriptor`] describing each field of the table to insert in database.
+pub fn field_descriptor() -> Vec<SqlFieldDescriptor> {
+    vec![
+        SqlFieldDescriptor {
+            field_name: "id",
+            field_unit: None,
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::Primary,
+            field_options: SQLiteOption::Autoincrement,
+        },
+        SqlFieldDescriptor {
+            field_name: "timestamp",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: true,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "path",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: true,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "kind",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "container_id",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "pod",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "unit",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "cpu_usage",
+            field_unit: Some("us"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "cpu_user",
+            field_unit: Some("us"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "cpu_system",
+            field_unit: Some("us"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "memory_current",
+            field_unit: Some("B"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "memory_anon",
+            field_unit: Some("B"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "memory_file",
+            field_unit: Some("B"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "io_read",
+            field_unit: Some("B"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "io_write",
+            field_unit: Some("B"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "pids_current",
+            field_unit: None,
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test `field_descriptor` function structure
+    #[test]
+    fn test_field_descriptor_info() {
+        let field = field_descriptor();
+        let res = field.iter().find(|f| f.field_name == "cpu_usage").unwrap();
+        assert_eq!(res.field_unit, Some("us"));
+        assert_eq!(res.field_type, SQLiteType::Integer);
+        assert!(!res.field_not_null);
+        assert_eq!(res.field_key, SQLiteKey::None);
+        assert_eq!(res.field_options, SQLiteOption::None);
+    }
+}
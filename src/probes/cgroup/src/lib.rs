@@ -0,0 +1,95 @@
+//! # Lib file for cgroup data module
+//!
+//! This module provides main functionality to retrieve the resources used by each cgroup
+//! of the cgroup v2 hierarchy, with the containers, pods and systemd units running in them.
+
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{Connection, params};
+use std::{error::Error, path::Path};
+
+mod dbms;
+mod utils;
+
+use core::core::{SqlFieldDescriptor, db_insert_query, db_table_query_creation, init_db};
+use dbms::*;
+pub use utils::{
+    CGROUP, CgroupInfo, HEADER, Workload, collect_cgroups, parse_io_stat, parse_keyed, resolve,
+};
+
+/// Insert cgroup parameters in the database.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `timestamp`: Timestamp of the measurement.
+/// - `data` : [`CgroupInfo`] information to insert in database.
+///
+/// # Returns
+///
+/// An error if the SQL insert request failed.
+fn insert_db(conn: &Connection, timestamp: &str, data: &CgroupInfo) -> Result<(), Box<dyn Error>> {
+    let query = db_insert_query(TABLE_NAME, &field_descriptor())?;
+    let mut stmt = conn.prepare(&query)?;
+
+    stmt.execute(params![
+        timestamp,
+        data.path,
+        data.kind,
+        data.container_id,
+        data.pod,
+        data.unit,
+        data.cpu_usage,
+        data.cpu_user,
+        data.cpu_system,
+        data.memory_current,
+        data.memory_anon,
+        data.memory_file,
+        data.io_read,
+        data.io_write,
+        data.pids_current,
+    ])?;
+    Ok(())
+}
+
+/// Description of the cgroup tables stored in database.
+///
+/// # Returns
+///
+/// The name of each table with the [`SqlFieldDescriptor`] list of its fields.
+pub fn schema() -> Vec<(&'static str, Vec<SqlFieldDescriptor>)> {
+    vec![(TABLE_NAME, field_descriptor())]
+}
+
+/// Retrieves the data of each cgroup of the system hierarchy, without storing it.
+///
+/// # Returns
+///
+/// - Completed [`CgroupInfo`] structures, sorted by path.
+/// - An error if the system has no cgroup v2 hierarchy.
+pub fn collect_cgroup_info() -> Result<Vec<CgroupInfo>, Box<dyn Error>> {
+    collect_cgroups(Path::new(CGROUP))
+}
+
+/// Store cgroups data in SQLite database.
+///
+/// # Arguments
+///
+/// - `cgroups` : [`CgroupInfo`] information to insert in database.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert requests failed.
+pub fn store_cgroup_info(cgroups: &[CgroupInfo]) -> Result<(), Box<dyn Error>> {
+    let query = db_table_query_creation(TABLE_NAME, &field_descriptor())?;
+    let mut conn = init_db(&query)?;
+
+    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    let tx = conn.transaction()?;
+
+    for cgroup in cgroups {
+        insert_db(&tx, &timestamp, cgroup)?;
+    }
+    tx.commit()?;
+
+    Ok(())
+}
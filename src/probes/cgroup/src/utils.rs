@@ -0,0 +1,387 @@
+//! # File utilities module
+//!
+//! This module provides the reading of the cgroup v2 interface files,
+//! and the resolution of the containers, pods and systemd units from the cgroup paths.
+
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{read_dir, read_to_string},
+    path::Path,
+};
+
+pub const HEADER: &str = "CGROUP";
+
+/// Mount point of the cgroup v2 unified hierarchy.
+pub const CGROUP: &str = "/sys/fs/cgroup";
+
+/// Collection of cgroup data.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CgroupInfo {
+    /// Path of the cgroup from the hierarchy root, `/` for the root.
+    pub path: String,
+    /// Kind of workload among `docker`, `podman`, `kubernetes` and `systemd`, if recognised.
+    pub kind: Option<String>,
+    /// Identifier of the container running in the cgroup.
+    pub container_id: Option<String>,
+    /// UID of the Kubernetes pod of the cgroup.
+    pub pod: Option<String>,
+    /// Name of the systemd unit of the cgroup.
+    pub unit: Option<String>,
+    /// Total CPU time consumed in µs.
+    pub cpu_usage: Option<u64>,
+    /// CPU time consumed in user mode in µs.
+    pub cpu_user: Option<u64>,
+    /// CPU time consumed in kernel mode in µs.
+    pub cpu_system: Option<u64>,
+    /// Memory currently used in bytes.
+    pub memory_current: Option<u64>,
+    /// Anonymous memory used in bytes.
+    pub memory_anon: Option<u64>,
+    /// Page cache memory used in bytes.
+    pub memory_file: Option<u64>,
+    /// Bytes read from the block devices.
+    pub io_read: Option<u64>,
+    /// Bytes written to the block devices.
+    pub io_write: Option<u64>,
+    /// Number of processes in the cgroup.
+    pub pids_current: Option<u64>,
+}
+
+/// Workload running in a cgroup, resolved from its path.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Workload {
+    /// Kind of workload among `docker`, `podman`, `kubernetes` and `systemd`.
+    pub kind: Option<String>,
+    /// Identifier of the container.
+    pub container_id: Option<String>,
+    /// UID of the Kubernetes pod.
+    pub pod: Option<String>,
+    /// Name of the systemd unit.
+    pub unit: Option<String>,
+}
+
+/// Parse a flat keyed file, like `cpu.stat` or `memory.stat`, with a `key value` pair per line.
+///
+/// # Arguments
+///
+/// - `content` : Content of the file.
+///
+/// # Returns
+///
+/// The numeric value of each key.
+pub fn parse_keyed(content: &str) -> HashMap<&str, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            Some((key, value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parse an `io.stat` file, with a `major:minor rbytes=.. wbytes=.. ...` line per device.
+///
+/// # Arguments
+///
+/// - `content` : Content of the file.
+///
+/// # Returns
+///
+/// The bytes read and written, summed over the devices.
+pub fn parse_io_stat(content: &str) -> (u64, u64) {
+    let mut res = (0, 0);
+    for field in content.lines().flat_map(|line| line.split_whitespace()) {
+        match field.split_once('=') {
+            Some(("rbytes", value)) => res.0 += value.parse().unwrap_or(0),
+            Some(("wbytes", value)) => res.1 += value.parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+    res
+}
+
+/// Check if a name is a container identifier, made of 12 to 64 hexadecimal characters.
+fn is_container_id(name: &str) -> bool {
+    (12..=64).contains(&name.len()) && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Resolve the workload of a cgroup from its path, with the systemd or cgroupfs layouts
+/// of Docker, Podman and the Kubernetes runtimes (containerd, CRI-O, cri-dockerd).
+///
+/// # Arguments
+///
+/// - `path` : Path of the cgroup from the hierarchy root.
+///
+/// # Returns
+///
+/// The [`Workload`] of the cgroup, with the kind, container identifier, pod UID and systemd unit found.
+pub fn resolve(path: &str) -> Workload {
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let (mut kind, mut container, mut pod, mut unit) = (None, None, None, None);
+
+    for (index, segment) in segments.iter().enumerate() {
+        let parent = index.checked_sub(1).map(|i| segments[i]);
+        let name = segment
+            .strip_suffix(".scope")
+            .or_else(|| segment.strip_suffix(".slice"))
+            .unwrap_or(segment);
+
+        // Pod slices are `kubepods-<qos>-pod<uid>.slice` with systemd, `pod<uid>` with cgroupfs.
+        let uid = if segment.starts_with("kubepods") {
+            kind = Some("kubernetes");
+            name.rsplit_once("-pod").map(|(_, uid)| uid)
+        } else if kind == Some("kubernetes") {
+            name.strip_prefix("pod")
+        } else {
+            None
+        };
+        if let Some(uid) = uid {
+            pod = Some(uid.replace('_', "-"));
+            continue;
+        }
+
+        let id = if let Some(id) = name.strip_prefix("docker-") {
+            kind = kind.or(Some("docker"));
+            Some(id)
+        } else if let Some(id) = name.strip_prefix("libpod-") {
+            kind = kind.or(Some("podman"));
+            Some(id)
+        } else if let Some(id) = ["cri-containerd-", "crio-", "cri-dockerd-"]
+            .iter()
+            .find_map(|prefix| name.strip_prefix(prefix))
+        {
+            kind = kind.or(Some("kubernetes"));
+            Some(id)
+        } else {
+            match parent {
+                Some("docker") => kind = kind.or(Some("docker")),
+                Some("libpod_parent") => kind = kind.or(Some("podman")),
+                _ => {}
+            }
+            Some(name)
+        };
+        if let Some(id) = id.filter(|id| is_container_id(id)) {
+            container = Some(id.to_string());
+        }
+
+        if [".service", ".scope", ".slice"]
+            .iter()
+            .any(|suffix| segment.ends_with(suffix))
+        {
+            unit = Some(segment.to_string());
+        }
+    }
+
+    if container.is_none() && kind != Some("kubernetes") {
+        kind = unit.as_ref().map(|_| "systemd");
+    }
+    Workload {
+        kind: kind.map(str::to_string),
+        container_id: container,
+        pod,
+        unit,
+    }
+}
+
+impl CgroupInfo {
+    /// Read the interface files of a cgroup.
+    ///
+    /// # Arguments
+    ///
+    /// - `root` : Mount point of the cgroup hierarchy.
+    /// - `dir` : Directory of the cgroup.
+    ///
+    /// # Returns
+    ///
+    /// Completed [`CgroupInfo`] structure, with the values of the readable files.
+    pub fn from_dir(root: &Path, dir: &Path) -> CgroupInfo {
+        let read = |name: &str| read_to_string(dir.join(name)).ok();
+        let path = format!(
+            "/{}",
+            dir.strip_prefix(root).unwrap_or(dir).to_string_lossy()
+        );
+        let workload = resolve(&path);
+
+        let cpu = read("cpu.stat").unwrap_or_default();
+        let cpu = parse_keyed(&cpu);
+        let memory = read("memory.stat").unwrap_or_default();
+        let memory = parse_keyed(&memory);
+        let io = read("io.stat").map(|content| parse_io_stat(&content));
+        let value = |name: &str| read(name).and_then(|v| v.trim().parse().ok());
+
+        CgroupInfo {
+            path,
+            kind: workload.kind,
+            container_id: workload.container_id,
+            pod: workload.pod,
+            unit: workload.unit,
+            cpu_usage: cpu.get("usage_usec").copied(),
+            cpu_user: cpu.get("user_usec").copied(),
+            cpu_system: cpu.get("system_usec").copied(),
+            memory_current: value("memory.current"),
+            memory_anon: memory.get("anon").copied(),
+            memory_file: memory.get("file").copied(),
+            io_read: io.map(|io| io.0),
+            io_write: io.map(|io| io.1),
+            pids_current: value("pids.current"),
+        }
+    }
+}
+
+/// Walk a cgroup v2 hierarchy and read each cgroup.
+///
+/// # Arguments
+///
+/// - `root` : Mount point of the cgroup hierarchy.
+///
+/// # Returns
+///
+/// - Completed [`CgroupInfo`] structures, sorted by path.
+/// - An error if the root is not a cgroup v2 hierarchy.
+pub fn collect_cgroups(root: &Path) -> Result<Vec<CgroupInfo>, Box<dyn Error>> {
+    if !root.join("cgroup.controllers").is_file() {
+        return Err(format!("Data 'No cgroup v2 hierarchy' : {}", root.display()).into());
+    }
+
+    let mut cgroups = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        cgroups.push(CgroupInfo::from_dir(root, &dir));
+        if let Ok(entries) = read_dir(&dir) {
+            dirs.extend(
+                entries
+                    .flatten()
+                    .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+                    .map(|e| e.path()),
+            );
+        }
+    }
+    cgroups.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(cgroups)
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, write};
+
+    const ID: &str = "4f1c2e3d5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d";
+
+    // Test `parse_keyed` and `parse_io_stat` functions
+    #[test]
+    fn test_parse() {
+        let res = parse_keyed("usage_usec 1500\nuser_usec 1000\nsystem_usec 500\nbad line\n");
+        assert_eq!(res.get("usage_usec"), Some(&1500));
+        assert_eq!(res.len(), 3);
+
+        let res = parse_io_stat(
+            "8:0 rbytes=1024 wbytes=2048 rios=1 wios=2 dbytes=0 dios=0\n\
+            259:0 rbytes=1000 wbytes=0 rios=3 wios=0 dbytes=0 dios=0\n",
+        );
+        assert_eq!(res, (2024, 2048));
+    }
+
+    // Test `resolve` function with the Docker, Podman, Kubernetes and systemd layouts
+    #[test]
+    fn test_resolve() {
+        let workload =
+            |kind: &str, id: Option<&str>, pod: Option<&str>, unit: Option<&str>| Workload {
+                kind: Some(kind.to_string()),
+                container_id: id.map(str::to_string),
+                pod: pod.map(str::to_string),
+                unit: unit.map(str::to_string),
+            };
+        let scope = format!("docker-{ID}.scope");
+        assert_eq!(
+            resolve(&format!("/system.slice/{scope}")),
+            workload("docker", Some(ID), None, Some(&scope))
+        );
+        assert_eq!(
+            resolve(&format!("/docker/{ID}")),
+            workload("docker", Some(ID), None, None)
+        );
+        assert_eq!(
+            resolve(&format!("/machine.slice/libpod-{ID}.scope/container")).kind,
+            Some("podman".to_string())
+        );
+        let scope = format!("cri-containerd-{ID}.scope");
+        assert_eq!(
+            resolve(&format!(
+                "/kubepods.slice/kubepods-burstable.slice/\
+                kubepods-burstable-pod1a2b3c4d_0000_1111_2222_333344445555.slice/{scope}"
+            )),
+            workload(
+                "kubernetes",
+                Some(ID),
+                Some("1a2b3c4d-0000-1111-2222-333344445555"),
+                Some(&scope)
+            )
+        );
+        assert_eq!(
+            resolve(&format!("/kubepods/besteffort/pod1234-abcd/{ID}")),
+            workload("kubernetes", Some(ID), Some("1234-abcd"), None)
+        );
+        assert_eq!(
+            resolve("/system.slice/my-podman.service"),
+            workload("systemd", None, None, Some("my-podman.service"))
+        );
+        assert_eq!(resolve("/"), Workload::default());
+    }
+
+    // Test `collect_cgroups` function on a fixture hierarchy
+    #[test]
+    fn test_collect_cgroups() {
+        let root = tempfile::tempdir().unwrap();
+        assert!(collect_cgroups(root.path()).is_err());
+
+        write(root.path().join("cgroup.controllers"), "cpu io memory pids").unwrap();
+        write(root.path().join("cpu.stat"), "usage_usec 9000\n").unwrap();
+        let container = root
+            .path()
+            .join("system.slice")
+            .join(format!("docker-{ID}.scope"));
+        create_dir_all(&container).unwrap();
+        write(
+            container.join("cpu.stat"),
+            "usage_usec 3000\nuser_usec 2000\nsystem_usec 1000\n",
+        )
+        .unwrap();
+        write(container.join("memory.current"), "4096\n").unwrap();
+        write(container.join("memory.stat"), "anon 1024\nfile 2048\n").unwrap();
+        write(
+            container.join("io.stat"),
+            "8:0 rbytes=10 wbytes=20 rios=1 wios=1\n",
+        )
+        .unwrap();
+        write(container.join("pids.current"), "3\n").unwrap();
+
+        let res = collect_cgroups(root.path()).unwrap();
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[0].path, "/");
+        assert_eq!(res[0].cpu_usage, Some(9000));
+        assert_eq!(res[0].memory_current, None);
+        assert_eq!(res[1].path, "/system.slice");
+        assert_eq!(res[1].kind.as_deref(), Some("systemd"));
+        let c = &res[2];
+        assert_eq!(c.kind.as_deref(), Some("docker"));
+        assert_eq!(c.container_id.as_deref(), Some(ID));
+        assert_eq!(
+            (c.cpu_usage, c.cpu_user, c.cpu_system),
+            (Some(3000), Some(2000), Some(1000))
+        );
+        assert_eq!(
+            (c.memory_current, c.memory_anon, c.memory_file),
+            (Some(4096), Some(1024), Some(2048))
+        );
+        assert_eq!(
+            (c.io_read, c.io_write, c.pids_current),
+            (Some(10), Some(20), Some(3))
+        );
+    }
+}
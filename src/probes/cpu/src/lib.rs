@@ -3,7 +3,9 @@
 //! This module provides functionalities to retrieve processor data on Unix-based systems.
 
 use chrono::{SecondsFormat, Utc};
+use log::error;
 use rusqlite::{Connection, params};
+use serde::Serialize;
 use std::{error::Error, thread::sleep};
 use sysinfo::{Components, CpuRefreshKind, MINIMUM_CPU_UPDATE_INTERVAL, RefreshKind, System};
 
@@ -12,12 +14,16 @@ mod utils;
 use crate::{
     dbms::*,
     utils::{
-        CpuCoreInfo, CpuGlobalInfo, CpuPowerInfo, CpuTemperatureInfo, collect_cpu_core_data,
-        collect_cpu_data, collect_cpu_power_data, collect_cpu_temperature_data,
+        HEADER, collect_cpu_core_data, collect_cpu_data, collect_cpu_power_data,
+        collect_cpu_temperature_data,
     },
 };
+pub use utils::{
+    CpuCoreInfo, CpuGlobalInfo, CpuPowerInfo, CpuTemperatureInfo, RaplCounter,
+    get_rapl_consumption, get_rapl_energy, get_rapl_zone_name,
+};
 
-use core::core::{db_insert_query, db_table_query_creation, init_db};
+use core::core::{SqlFieldDescriptor, db_insert_query, db_table_query_creation, init_db};
 
 impl CpuGlobalInfo {
     /// Insert global CPU data in database.
@@ -120,9 +126,41 @@ impl CpuTemperatureInfo {
     }
 }
 
-/// Public function used to send values in SQLite database,
-/// from [`collect_cpu_data`] function result.
-pub fn get_cpu_info() -> Result<(), Box<dyn Error>> {
+/// Collection of all CPU data retrieved by a probe run.
+#[derive(Debug, Serialize)]
+pub struct CpuData {
+    /// Global CPU information.
+    pub global: CpuGlobalInfo,
+    /// CPU cores usage.
+    pub cores: CpuCoreInfo,
+    /// CPU power consumption by RAPL zone, if available.
+    pub power: Option<CpuPowerInfo>,
+    /// CPU temperatures by thermal zone, if available.
+    pub temperature: Option<CpuTemperatureInfo>,
+}
+
+/// Description of the CPU tables stored in database.
+///
+/// # Returns
+///
+/// The name of each table with the [`SqlFieldDescriptor`] list of its fields.
+pub fn schema() -> Vec<(&'static str, Vec<SqlFieldDescriptor>)> {
+    vec![
+        (TABLE_NAME[0], field_descriptor_info()),
+        (TABLE_NAME[1], field_descriptor_core()),
+        (TABLE_NAME[2], field_descriptor_power()),
+        (TABLE_NAME[3], field_descriptor_temperature()),
+    ]
+}
+
+/// Retrieves all CPU data, without storing it.
+/// Power and temperature are optional, as RAPL and thermal zones are not available on every machine.
+///
+/// # Returns
+///
+/// - Completed [`CpuData`] structure with all CPU information.
+/// - An error when global CPU information can't be retrieved.
+pub fn collect_cpu_info() -> Result<CpuData, Box<dyn Error>> {
     let mut sys =
         System::new_with_specifics(RefreshKind::nothing().with_cpu(CpuRefreshKind::everything()));
     sleep(MINIMUM_CPU_UPDATE_INTERVAL);
@@ -135,11 +173,33 @@ pub fn get_cpu_info() -> Result<(), Box<dyn Error>> {
         return Err("Failed to get global CPUs information".to_string().into());
     }
 
-    let data_global = collect_cpu_data(cpu)?;
-    let data_cores = collect_cpu_core_data(cpu)?;
-    let data_power = collect_cpu_power_data()?;
-    let data_temperature = collect_cpu_temperature_data(component)?;
+    let global = collect_cpu_data(cpu)?;
+    let cores = collect_cpu_core_data(cpu)?;
+    let power = collect_cpu_power_data()
+        .map_err(|e| error!("[{HEADER}] {e}"))
+        .ok();
+    let temperature = collect_cpu_temperature_data(component)
+        .map_err(|e| error!("[{HEADER}] {e}"))
+        .ok();
 
+    Ok(CpuData {
+        global,
+        cores,
+        power,
+        temperature,
+    })
+}
+
+/// Store CPU data in SQLite database.
+///
+/// # Arguments
+///
+/// - `data` : [`CpuData`] information to insert in database.
+///
+/// # Returns
+///
+/// An error if the tables creation or the SQL insert requests failed.
+pub fn store_cpu_info(data: &CpuData) -> Result<(), Box<dyn Error>> {
     let query_info = db_table_query_creation(TABLE_NAME[0], &field_descriptor_info())?;
     let query_core = db_table_query_creation(TABLE_NAME[1], &field_descriptor_core())?;
     let query_power = db_table_query_creation(TABLE_NAME[2], &field_descriptor_power())?;
@@ -152,10 +212,21 @@ pub fn get_cpu_info() -> Result<(), Box<dyn Error>> {
     conn.execute_batch(&query_temperature)?;
 
     let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
-    CpuGlobalInfo::insert_db(&conn, &timestamp, &data_global)?;
-    CpuCoreInfo::insert_db(&conn, &timestamp, &data_cores)?;
-    CpuPowerInfo::insert_db(&conn, &timestamp, &data_power)?;
-    CpuTemperatureInfo::insert_db(&conn, &timestamp, &data_temperature)?;
+    CpuGlobalInfo::insert_db(&conn, &timestamp, &data.global)?;
+    CpuCoreInfo::insert_db(&conn, &timestamp, &data.cores)?;
+    if let Some(power) = &data.power {
+        CpuPowerInfo::insert_db(&conn, &timestamp, power)?;
+    }
+    if let Some(temperature) = &data.temperature {
+        CpuTemperatureInfo::insert_db(&conn, &timestamp, temperature)?;
+    }
 
     Ok(())
 }
+
+/// Public function used to send values in SQLite database,
+/// from [`collect_cpu_info`] function result.
+pub fn get_cpu_info() -> Result<(), Box<dyn Error>> {
+    let data = collect_cpu_info()?;
+    store_cpu_info(&data)
+}
@@ -1,6 +1,7 @@
 //! # File utilities module
 
 use log::error;
+use serde::Serialize;
 use std::{
     error::Error,
     fs::{read_dir, read_to_string},
@@ -11,13 +12,13 @@ use sysinfo::{Components, Cpu, System};
 
 use core::core::measure_point;
 
-const HEADER: &str = "CPU";
+pub const HEADER: &str = "CPU";
 
 /// RAPL directory providing power consumption for x86-64 CPU architectures (plus DRAM according the CPU version).
 const RAPL: &str = "/sys/class/powercap";
 
 /// Collection of collected CPU data.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct CpuGlobalInfo {
     /// CPU architecture label.
     pub architecture: Option<String>,
@@ -34,21 +35,21 @@ pub struct CpuGlobalInfo {
 }
 
 /// Collection of collected CPU cores usage data.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct CpuCoreInfo {
     /// CPU usage cores in percentage.
     pub cores_usage: Vec<(String, f32)>,
 }
 
 /// Collection of collected CPU power consumption data.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct CpuPowerInfo {
     /// CPU energy consumption by zone in uJ.
     pub powers: Vec<(String, f64)>,
 }
 
 /// Collection of collected CPU temperature data.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct CpuTemperatureInfo {
     /// CPU temperatures of various thermal zone in celsius.
     pub temperatures: Vec<(String, f32)>,
@@ -177,6 +178,54 @@ pub fn get_rapl_consumption() -> Result<Vec<(String, f64)>, Box<dyn Error>> {
     }
 }
 
+/// Zone name, cumulative energy counter in J, and value in J at which it wraps around to 0.
+pub type RaplCounter = (String, f64, Option<f64>);
+
+/// Reading in [`RAPL`] directory the cumulative energy counter of each zone.
+/// Unlike [`get_rapl_consumption`], the counters cover the whole time between two readings.
+///
+/// # Return
+///
+/// - Vector containing the [`RaplCounter`] of each zone, its range being none if not readable.
+/// - An error if no energy counter is found.
+pub fn get_rapl_energy() -> Result<Vec<RaplCounter>, Box<dyn Error>> {
+    let read = |path: &Path| read_to_string(path).ok()?.trim().parse::<f64>().ok();
+
+    let result: Vec<RaplCounter> = read_dir(RAPL)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let domain = path.file_name()?.to_str()?;
+            if !path.is_dir() || !domain.starts_with("intel-rapl:") {
+                return None;
+            }
+            let energy = read(&path.join("energy_uj"))?;
+            let range = read(&path.join("max_energy_range_uj"));
+            Some((domain.to_string(), energy / 1e6, range.map(|uj| uj / 1e6)))
+        })
+        .collect();
+
+    if result.is_empty() {
+        Err("Data 'Unable to get CPU RAPL energy information'".into())
+    } else {
+        Ok(result)
+    }
+}
+
+/// Read the name of a [`RAPL`] zone, like `package-0`, `core`, `uncore`, `dram` or `psys`.
+///
+/// # Arguments
+///
+/// - `zone` : Zone folder name, as given by [`get_rapl_consumption`].
+///
+/// # Returns
+///
+/// The name of the zone, if readable.
+pub fn get_rapl_zone_name(zone: &str) -> Option<String> {
+    read_to_string(Path::new(RAPL).join(zone).join("name"))
+        .ok()
+        .map(|name| name.trim().to_string())
+}
+
 /// Reading and using `/proc/cpuinfo` file values, to retrieve detailed CPU information.
 ///
 /// # Arguments
@@ -0,0 +1,126 @@
+//! # Lib file for external probes module
+//!
+//! This module provides main functionality to run site-specific probes as shell commands,
+//! each one printing its samples as JSON lines on its standard output.
+
+use chrono::{SecondsFormat, Utc};
+use log::error;
+use rusqlite::{Connection, params};
+use std::{error::Error, thread::spawn};
+
+mod dbms;
+mod utils;
+
+use core::core::{SqlFieldDescriptor, db_insert_query, db_table_query_creation, init_db};
+use dbms::*;
+pub use utils::{
+    ExecProbe, ExecSample, ExecSchedule, ExecValue, HEADER, parse_output, run_command,
+};
+
+/// Data retrieved by a run of the external probes.
+#[derive(Debug, Default)]
+pub struct ExecData {
+    /// Samples emitted by the probes which succeeded.
+    pub samples: Vec<ExecSample>,
+    /// Error message of each probe which failed, by probe name.
+    pub errors: Vec<(String, String)>,
+}
+
+/// Insert the values of an external probe sample in the database.
+///
+/// # Arguments
+///
+/// - `conn` : Allow by a [`Connection`] constructor type the connection with an SQLite database.
+/// - `timestamp`: Timestamp of the measurement.
+/// - `data` : [`ExecSample`] information to insert in database.
+///
+/// # Returns
+///
+/// An error if the SQL insert request failed.
+fn insert_db(conn: &Connection, timestamp: &str, data: &ExecSample) -> Result<(), Box<dyn Error>> {
+    let query = db_insert_query(TABLE_NAME, &field_descriptor())?;
+    let mut stmt = conn.prepare(&query)?;
+
+    for (metric, value) in &data.metrics {
+        stmt.execute(params![
+            timestamp,
+            data.probe,
+            data.entity,
+            metric,
+            value.value,
+            value.unit,
+        ])?;
+    }
+    Ok(())
+}
+
+/// Description of the external probes tables stored in database.
+///
+/// # Returns
+///
+/// The name of each table with the [`SqlFieldDescriptor`] list of its fields.
+pub fn schema() -> Vec<(&'static str, Vec<SqlFieldDescriptor>)> {
+    vec![(TABLE_NAME, field_descriptor())]
+}
+
+/// Run simultaneously the external probes whose interval is elapsed, without storing their data.
+/// A probe which failed, timed out or emitted a malformed output does not affect the others.
+///
+/// # Arguments
+///
+/// - `probes` : Configured [`ExecProbe`] list.
+/// - `schedule` : [`ExecSchedule`] keeping the last run of each probe.
+///
+/// # Returns
+///
+/// The [`ExecData`] gathering the samples and the errors of the probes run.
+pub fn collect_exec_info(probes: &[ExecProbe], schedule: &ExecSchedule) -> ExecData {
+    let handles: Vec<_> = schedule
+        .due(probes)
+        .into_iter()
+        .map(|probe| {
+            let probe = probe.clone();
+            spawn(move || {
+                let res = run_command(&probe).and_then(|output| parse_output(&probe.name, &output));
+                (probe.name, res.map_err(|e| e.to_string()))
+            })
+        })
+        .collect();
+
+    let mut data = ExecData::default();
+    for handle in handles {
+        match handle.join() {
+            Ok((_, Ok(samples))) => data.samples.extend(samples),
+            Ok((name, Err(e))) => {
+                error!("[{HEADER}] {e}");
+                data.errors.push((name, e));
+            }
+            Err(_) => error!("[{HEADER}] Process 'Failure in the thread'"),
+        }
+    }
+    data
+}
+
+/// Store external probes samples in SQLite database.
+///
+/// # Arguments
+///
+/// - `samples` : [`ExecSample`] information to insert in database.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert requests failed.
+pub fn store_exec_info(samples: &[ExecSample]) -> Result<(), Box<dyn Error>> {
+    let query = db_table_query_creation(TABLE_NAME, &field_descriptor())?;
+    let mut conn = init_db(&query)?;
+
+    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    let tx = conn.transaction()?;
+
+    for sample in samples {
+        insert_db(&tx, &timestamp, sample)?;
+    }
+    tx.commit()?;
+
+    Ok(())
+}
@@ -0,0 +1,251 @@
+//! # File utilities module
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    io::Read,
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+
+/// Identification header for information loggers about external probes.
+pub const HEADER: &str = "EXEC";
+
+/// Delay between two checks of the command termination.
+const POLL: Duration = Duration::from_millis(10);
+
+/// Configuration of an external probe, read from the `[[exec]]` sections.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ExecProbe {
+    /// Name of the probe, used as component of its metrics.
+    pub name: String,
+    /// Shell command printing the samples on its standard output.
+    pub command: String,
+    /// Minimal interval in seconds between two runs of the command.
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// Time in seconds after which the command is killed.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+fn default_interval() -> u64 {
+    60
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+/// Single value emitted by an external probe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecValue {
+    /// Value of the metric.
+    pub value: f64,
+    /// Unit of the value, if given.
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+/// Sample emitted by an external probe, as a JSON line
+/// `{"entity": "...", "metrics": {"name": {"value": 1.0, "unit": "W"}}}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecSample {
+    /// Name of the [`ExecProbe`] which emitted the sample.
+    #[serde(default)]
+    pub probe: String,
+    /// Entity measured (outlet, board...), if the probe has several.
+    #[serde(default)]
+    pub entity: Option<String>,
+    /// Values by metric name.
+    pub metrics: BTreeMap<String, ExecValue>,
+}
+
+/// Last run instant of each external probe, shared between the clones of a collector.
+#[derive(Debug, Clone, Default)]
+pub struct ExecSchedule {
+    last: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl ExecSchedule {
+    /// Select the probes whose interval is elapsed since their last run,
+    /// and mark them as run now.
+    ///
+    /// # Arguments
+    ///
+    /// - `probes` : Configured [`ExecProbe`] list.
+    ///
+    /// # Returns
+    ///
+    /// The probes to run.
+    pub fn due<'a>(&self, probes: &'a [ExecProbe]) -> Vec<&'a ExecProbe> {
+        let now = Instant::now();
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        probes
+            .iter()
+            .filter(|probe| {
+                let due = last.get(&probe.name).is_none_or(|run| {
+                    now.duration_since(*run) >= Duration::from_secs(probe.interval)
+                });
+                if due {
+                    last.insert(probe.name.clone(), now);
+                }
+                due
+            })
+            .collect()
+    }
+}
+
+/// Parse the standard output of an external probe, one JSON sample per line.
+///
+/// # Arguments
+///
+/// - `probe` : Name of the probe.
+/// - `output` : Standard output of the command.
+///
+/// # Returns
+///
+/// - The list of [`ExecSample`] emitted.
+/// - An error if a line is not a valid sample, the whole output being rejected.
+pub fn parse_output(probe: &str, output: &str) -> Result<Vec<ExecSample>, Box<dyn Error>> {
+    output
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let mut sample: ExecSample = serde_json::from_str(line)
+                .map_err(|e| format!("Data 'Malformed output' : {probe} line {} {e}", index + 1))?;
+            sample.probe = probe.to_string();
+            Ok(sample)
+        })
+        .collect()
+}
+
+/// Run the command of an external probe, killed if its timeout is reached.
+///
+/// # Arguments
+///
+/// - `probe` : [`ExecProbe`] to run.
+///
+/// # Returns
+///
+/// - The standard output of the command.
+/// - An error if the command can't be spawned, timed out or exited with a failure.
+pub fn run_command(probe: &ExecProbe) -> Result<String, Box<dyn Error>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&probe.command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Process 'Spawn failed' : {} {e}", probe.name))?;
+
+    let mut stdout = child.stdout.take().ok_or("Process 'No standard output'")?;
+    let mut stderr = child.stderr.take().ok_or("Process 'No error output'")?;
+    let out = spawn(move || {
+        let mut buffer = String::new();
+        stdout.read_to_string(&mut buffer).map(|_| buffer)
+    });
+    let err = spawn(move || {
+        let mut buffer = String::new();
+        let _ = stderr.read_to_string(&mut buffer);
+        buffer
+    });
+
+    let deadline = Instant::now() + Duration::from_secs(probe.timeout);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!(
+                "Process 'Timeout' : {} after {}s",
+                probe.name, probe.timeout
+            )
+            .into());
+        }
+        sleep(POLL);
+    };
+
+    let output = out
+        .join()
+        .map_err(|_| "Process 'Failure in the thread'")?
+        .map_err(|e| format!("Data 'Unreadable output' : {} {e}", probe.name))?;
+    let message = err.join().unwrap_or_default();
+    if !status.success() {
+        return Err(format!(
+            "Process 'Command failed' : {} {status} {}",
+            probe.name,
+            message.trim()
+        )
+        .into());
+    }
+    Ok(output)
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(command: &str, timeout: u64) -> ExecProbe {
+        ExecProbe {
+            name: "test".to_string(),
+            command: command.to_string(),
+            interval: 60,
+            timeout,
+        }
+    }
+
+    // Test `parse_output` function with valid and malformed lines
+    #[test]
+    fn test_parse_output() {
+        let output = "{\"entity\":\"outlet1\",\"metrics\":{\"power\":{\"value\":12.5,\"unit\":\"W\"}}}\n\n\
+            {\"metrics\":{\"temperature\":{\"value\":40}}}\n";
+        let res = parse_output("pdu", output).unwrap();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].probe, "pdu");
+        assert_eq!(res[0].entity.as_deref(), Some("outlet1"));
+        assert_eq!(res[0].metrics["power"].unit.as_deref(), Some("W"));
+        assert_eq!(res[1].metrics["temperature"].value, 40.0);
+
+        assert!(parse_output("pdu", "{\"metrics\":{\"power\":12}}").is_err());
+        assert!(parse_output("pdu", "not json").is_err());
+    }
+
+    // Test `run_command` function with success, failure and timeout
+    #[test]
+    fn test_run_command() {
+        assert_eq!(run_command(&probe("echo ok", 5)).unwrap(), "ok\n");
+        assert!(run_command(&probe("exit 3", 5)).is_err());
+
+        let start = Instant::now();
+        let res = run_command(&probe("sleep 5", 1));
+        assert!(res.unwrap_err().to_string().contains("Timeout"));
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+
+    // Test `due` function respecting the interval of each probe
+    #[test]
+    fn test_schedule_due() {
+        let schedule = ExecSchedule::default();
+        let mut fast = probe("true", 1);
+        fast.interval = 0;
+        fast.name = "fast".to_string();
+        let probes = vec![probe("true", 1), fast];
+
+        assert_eq!(schedule.due(&probes).len(), 2);
+        let res = schedule.clone().due(&probes);
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].name, "fast");
+    }
+}
@@ -5,14 +5,15 @@
 use chrono::{SecondsFormat, Utc};
 use nvml_wrapper::Nvml;
 use rusqlite::{Connection, params};
+use serde::Serialize;
 use std::error::Error;
 
 mod dbms;
 mod utils;
 
-use core::core::{db_insert_query, db_table_query_creation, init_db};
+use core::core::{SqlFieldDescriptor, db_insert_query, db_table_query_creation, init_db};
 use dbms::*;
-use utils::*;
+pub use utils::{GpuMetrics, GpuProcessMetrics};
 
 impl GpuMetrics {
     /// Insert GPU parameters in database.
@@ -94,34 +95,115 @@ impl GpuProcessMetrics {
     }
 }
 
-/// Public function used to send values in SQLite database.
-/// Retrieves the various NVIDIA GPUs devices on the machine and their associated data.
+/// Collection of data retrieved for a GPU device and its running processes.
+#[derive(Serialize)]
+pub struct GpuData {
+    /// Global hardware metrics of the device.
+    pub metrics: GpuMetrics,
+    /// Metrics of the processes running on the device.
+    pub processes: Vec<GpuProcessMetrics>,
+}
+
+/// Description of the GPU tables stored in database.
 ///
 /// # Returns
 ///
-/// - `result` : Completed [`GpuMetrics`] and [`GpuProcessMetrics`] information for GPUs devices detected.
-/// - An error when some important and critical metrics can't be retrieved.
-pub fn get_gpu_info() -> Result<(), Box<dyn Error>> {
-    let nvml = Nvml::init()?;
+/// The name of each table with the [`SqlFieldDescriptor`] list of its fields.
+pub fn schema() -> Vec<(&'static str, Vec<SqlFieldDescriptor>)> {
+    vec![
+        (TABLE_NAME[0], field_descriptor_gpu()),
+        (TABLE_NAME[1], field_descriptor_process()),
+    ]
+}
 
-    let query_gpu = db_table_query_creation(TABLE_NAME[0], &field_descriptor_gpu())?;
-    let query_process = db_table_query_creation(TABLE_NAME[1], &field_descriptor_process())?;
+/// NVML session kept open between several collections of the devices,
+/// for the high frequency sampling.
+pub struct GpuSampler {
+    nvml: Nvml,
+}
 
-    let conn = init_db(&query_gpu)?;
-    conn.execute_batch(&query_process)?;
+impl GpuSampler {
+    /// Open an NVML session.
+    ///
+    /// # Returns
+    ///
+    /// - The [`GpuSampler`].
+    /// - An error if the NVML library can't be loaded or initialized.
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(GpuSampler {
+            nvml: Nvml::init()?,
+        })
+    }
+
+    /// Retrieves the data of the devices with the open session.
+    ///
+    /// # Returns
+    ///
+    /// - Completed [`GpuData`] information for GPUs devices detected.
+    /// - An error when some important and critical metrics can't be retrieved.
+    pub fn collect(&self) -> Result<Vec<GpuData>, Box<dyn Error>> {
+        collect_devices(&self.nvml)
+    }
+}
+
+/// Retrieves the various NVIDIA GPUs devices on the machine and their associated data,
+/// without storing it.
+///
+/// # Returns
+///
+/// - `result` : Completed [`GpuData`] information for GPUs devices detected.
+/// - An error when some important and critical metrics can't be retrieved.
+pub fn collect_gpu_info() -> Result<Vec<GpuData>, Box<dyn Error>> {
+    collect_devices(&Nvml::init()?)
+}
+
+/// Retrieves the data of each device of an NVML session.
+fn collect_devices(nvml: &Nvml) -> Result<Vec<GpuData>, Box<dyn Error>> {
+    let mut result = Vec::new();
 
     for index in 0..nvml.device_count()? {
         let device = nvml.device_by_index(index)?;
         let bus_id = Some(device.pci_info()?.bus_id.clone());
-        let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
 
-        let metrics_gpu = GpuMetrics::from_device(&device, bus_id.clone())?;
-        GpuMetrics::insert_db(&conn, &timestamp, &metrics_gpu)?;
+        result.push(GpuData {
+            metrics: GpuMetrics::from_device(&device, bus_id.clone())?,
+            processes: GpuProcessMetrics::from_device(&device, bus_id)?,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Store GPU data in SQLite database.
+///
+/// # Arguments
+///
+/// - `data` : [`GpuData`] information of each device to insert in database.
+///
+/// # Returns
+///
+/// An error if the tables creation or the SQL insert requests failed.
+pub fn store_gpu_info(data: &[GpuData]) -> Result<(), Box<dyn Error>> {
+    let query_gpu = db_table_query_creation(TABLE_NAME[0], &field_descriptor_gpu())?;
+    let query_process = db_table_query_creation(TABLE_NAME[1], &field_descriptor_process())?;
+
+    let conn = init_db(&query_gpu)?;
+    conn.execute_batch(&query_process)?;
 
-        for process in GpuProcessMetrics::from_device(&device, bus_id.clone())? {
-            GpuProcessMetrics::insert_db(&conn, &timestamp, &process)?;
+    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    for gpu in data {
+        GpuMetrics::insert_db(&conn, &timestamp, &gpu.metrics)?;
+        for process in &gpu.processes {
+            GpuProcessMetrics::insert_db(&conn, &timestamp, process)?;
         }
     }
 
     Ok(())
 }
+
+/// Public function used to send values in SQLite database,
+/// from [`collect_gpu_info`] function result.
+pub fn get_gpu_info() -> Result<(), Box<dyn Error>> {
+    let data = collect_gpu_info()?;
+    store_gpu_info(&data)
+}
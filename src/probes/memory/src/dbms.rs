@@ -22,7 +22,7 @@ pub fn field_descriptor_info() -> Vec<SqlFieldDescriptor> {
         },
         SqlFieldDescriptor {
             field_name: "timestamp",
-            field_unit: Some("MB_s"),
+            field_unit: None,
             field_type: SQLiteType::Text,
             field_not_null: true,
             field_key: SQLiteKey::None,
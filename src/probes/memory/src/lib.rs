@@ -3,18 +3,22 @@
 //! This module provides main functionality to retrieve memories data on Unix-based systems.
 
 use chrono::{SecondsFormat, Utc};
+use log::error;
 use rusqlite::{Connection, ToSql, params};
-use std::{error::Error, fs::read};
+use serde::Serialize;
+use std::{error::Error, fs::read, path::Path};
 use sysinfo::{MemoryRefreshKind, System};
 
 mod dbms;
 mod utils;
 
 use core::core::{
-    DMIDECODE_BIN, ENTRY_BIN, db_insert_query, db_insert_unique, db_table_query_creation, init_db,
+    DMIDECODE_BIN, ENTRY_BIN, SqlFieldDescriptor, db_insert_query, db_insert_unique,
+    db_table_query_creation, init_db,
 };
 use dbms::*;
 use utils::*;
+pub use utils::{MemDeviceInfo, MemErrors, MemInfo};
 
 impl MemInfo {
     /// Insert memory global info parameters into the database.
@@ -118,27 +122,76 @@ impl MemDeviceInfo {
     }
 }
 
-/// Initialize the [`sysinfo`] library to start the collect by [`mem_data_build`].
-/// Push in SQLite memory database the data retrieve by:
-/// - [`MemDeviceInfo`]: Information about memory device(s) module(s) detected on OS.
-/// - [`MemInfo`]: Global information about memory.
+/// Collection of all memory data retrieved by a probe run.
+#[derive(Debug, Serialize)]
+pub struct MemData {
+    /// Global information about memory.
+    pub global: MemInfo,
+    /// Information about memory device(s) module(s) detected, if DMI tables are readable.
+    pub devices: Option<Vec<MemDeviceInfo>>,
+    /// Errors counted by the memory controllers, if EDAC is available.
+    pub errors: Option<MemErrors>,
+}
+
+/// Description of the memory tables stored in database.
 ///
 /// # Returns
 ///
-/// Failure if we can't retrieve information or push it in database.
-pub fn get_mem_info() -> Result<(), Box<dyn Error>> {
-    let entry_buf = read(ENTRY_BIN)?;
-    let dmi_buf = read(DMIDECODE_BIN)?;
+/// The name of each table with the [`SqlFieldDescriptor`] list of its fields.
+pub fn schema() -> Vec<(&'static str, Vec<SqlFieldDescriptor>)> {
+    vec![
+        (TABLE_NAME[0], field_descriptor_info()),
+        (TABLE_NAME[1], field_descriptor_device()),
+    ]
+}
 
+/// Initialize the [`sysinfo`] library to retrieve memory data, without storing it.
+/// Memory devices are optional, as DMI tables require root privileges,
+/// and so are the errors, counted only by the ECC memories controllers.
+///
+/// # Returns
+///
+/// - Completed [`MemData`] structure with all memory information.
+/// - An error if the memory bandwidth test failed.
+pub fn collect_mem_info() -> Result<MemData, Box<dyn Error>> {
     let mut sys = System::new_all();
     sys.refresh_memory_specifics(MemoryRefreshKind::everything());
 
     let ram_test = get_mem_test()?;
-    let ram_device = get_mem_device(&entry_buf, &dmi_buf)?;
+    let ram_device = match (read(ENTRY_BIN), read(DMIDECODE_BIN)) {
+        (Ok(entry_buf), Ok(dmi_buf)) => get_mem_device(&entry_buf, &dmi_buf).unwrap_or_else(|e| {
+            error!("[{HEADER}] {e}");
+            None
+        }),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("[{HEADER}] Data 'Failed to read DMI tables' : {e}");
+            None
+        }
+    };
+
+    let devices = collect_mem_devices(ram_device);
+    let global = collect_mem_data(ram_test, devices.as_ref(), &sys);
+    let errors = collect_mem_errors(Path::new(EDAC));
 
-    let data_devices = collect_mem_devices(ram_device);
-    let data_global = collect_mem_data(ram_test, data_devices.as_ref(), &sys);
+    Ok(MemData {
+        global,
+        devices,
+        errors,
+    })
+}
 
+/// Push in SQLite memory database the data retrieve by:
+/// - [`MemDeviceInfo`]: Information about memory device(s) module(s) detected on OS.
+/// - [`MemInfo`]: Global information about memory.
+///
+/// # Arguments
+///
+/// - `data` : [`MemData`] information to insert in database.
+///
+/// # Returns
+///
+/// Failure if we can't push information in database.
+pub fn store_mem_info(data: &MemData) -> Result<(), Box<dyn Error>> {
     let query_info = db_table_query_creation(TABLE_NAME[0], &field_descriptor_info())?;
     let query_device = db_table_query_creation(TABLE_NAME[1], &field_descriptor_device())?;
 
@@ -146,8 +199,17 @@ pub fn get_mem_info() -> Result<(), Box<dyn Error>> {
     conn.execute_batch(&query_device)?;
 
     let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
-    MemInfo::insert_db(&mut conn, &timestamp, &data_global)?;
-    MemDeviceInfo::insert_db(&mut conn, &timestamp, data_devices.as_ref())?;
+    MemInfo::insert_db(&mut conn, &timestamp, &data.global)?;
+    if data.devices.is_some() {
+        MemDeviceInfo::insert_db(&mut conn, &timestamp, data.devices.as_ref())?;
+    }
 
     Ok(())
 }
+
+/// Public function used to send values in SQLite database,
+/// from [`collect_mem_info`] function result.
+pub fn get_mem_info() -> Result<(), Box<dyn Error>> {
+    let data = collect_mem_info()?;
+    store_mem_info(&data)
+}
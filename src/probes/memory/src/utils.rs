@@ -4,16 +4,21 @@
 
 use dmidecode::{EntryPoint, Structure, structures::memory_device::Type};
 use log::error;
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 use std::{
     env::var,
     error::Error,
+    fs::{read_dir, read_to_string},
+    path::Path,
     ptr::{read_volatile, write_volatile},
     time::{Duration, Instant},
 };
 
 pub const HEADER: &str = "MEMORY";
 
+/// Directory of the EDAC memory controllers on the system.
+pub const EDAC: &str = "/sys/devices/system/edac/mc";
+
 const FACTOR: u64 = 1_000_000;
 const DEFAULT_ARRAY_SIZE: usize = 100_000_000;
 
@@ -63,10 +68,16 @@ impl TypeToStr for Type {
     }
 }
 
+/// Serialize a memory [`Type`] with its [`TypeToStr`] name.
+fn serialize_type<S: Serializer>(kind: &Type, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&kind.as_str())
+}
+
 /// Information about memory device info.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MemDeviceInfo {
     /// Type of computing memory.
+    #[serde(serialize_with = "serialize_type")]
     pub kind: Type,
     /// Serial number of the memory device.
     pub id: Option<String>,
@@ -103,6 +114,57 @@ pub struct MemInfo {
     pub swap_used: Option<u64>,
 }
 
+/// Errors detected by the memory controllers since the boot.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct MemErrors {
+    /// Corrected errors count.
+    pub corrected: u64,
+    /// Uncorrected errors count.
+    pub uncorrected: u64,
+}
+
+/// Retrieves the errors counted by the EDAC memory controllers.
+///
+/// # Arguments
+///
+/// - `root` : Directory of the memory controllers, [`EDAC`] on the system.
+///
+/// # Returns
+///
+/// - Completed [`MemErrors`] summed over the controllers.
+/// - None if no controller is registered, like on memories without ECC.
+pub fn collect_mem_errors(root: &Path) -> Option<MemErrors> {
+    let count = |path: &Path| {
+        read_to_string(path)
+            .ok()
+            .and_then(|count| count.trim().parse::<u64>().ok())
+            .unwrap_or(0)
+    };
+    let controllers: Vec<_> = read_dir(root)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("mc"))
+        })
+        .collect();
+    if controllers.is_empty() {
+        return None;
+    }
+    Some(MemErrors {
+        corrected: controllers
+            .iter()
+            .map(|mc| count(&mc.join("ce_count")))
+            .sum(),
+        uncorrected: controllers
+            .iter()
+            .map(|mc| count(&mc.join("ue_count")))
+            .sum(),
+    })
+}
+
 /// Estimation of power consumption by memory in W.
 /// Base on the typical power consumption per GB based on the memory type defined in [`Type::reference`].
 ///
@@ -291,7 +353,10 @@ pub fn collect_mem_devices(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::env::{remove_var, set_var, var};
+    use std::{
+        env::{remove_var, set_var, var},
+        fs::{create_dir, write},
+    };
     use sysinfo::{MemoryRefreshKind, System};
 
     // Test `get_mem_device` function with invalid data reading
@@ -491,4 +556,25 @@ mod tests {
         let res = Type::Ddr4;
         assert_eq!(res.as_str(), "Ddr4");
     }
+
+    // Test `collect_mem_errors` function summing the counts of the controllers
+    #[test]
+    fn test_collect_mem_errors() {
+        let root = tempfile::tempdir().unwrap();
+        assert_eq!(collect_mem_errors(root.path()), None);
+        assert_eq!(collect_mem_errors(&root.path().join("missing")), None);
+
+        for (mc, ce, ue) in [("mc0", "3\n", "0\n"), ("mc1", "2\n", "1\n")] {
+            create_dir(root.path().join(mc)).unwrap();
+            write(root.path().join(mc).join("ce_count"), ce).unwrap();
+            write(root.path().join(mc).join("ue_count"), ue).unwrap();
+        }
+        assert_eq!(
+            collect_mem_errors(root.path()),
+            Some(MemErrors {
+                corrected: 5,
+                uncorrected: 1
+            })
+        );
+    }
 }
@@ -10,9 +10,10 @@ use sysinfo::Networks;
 mod dbms;
 mod utils;
 
-use core::core::{db_insert_query, db_table_query_creation, init_db};
+use core::core::{SqlFieldDescriptor, db_insert_query, db_table_query_creation, init_db};
 use dbms::*;
-use utils::{NetworkInterface, collect_network_data};
+use utils::collect_network_data;
+pub use utils::{NetworkInterface, NetworkType};
 
 /// Insert network interface parameters in the database.
 ///
@@ -50,23 +51,52 @@ fn insert_db(
     Ok(())
 }
 
-/// Public function used to collecting network data,
-/// and stores [`collect_network_data`] function result in an SQLite database.
-pub fn get_net_info() -> Result<(), Box<dyn Error>> {
-    let query = db_table_query_creation(TABLE_NAME, &field_descriptor())?;
-    let mut conn = init_db(&query)?;
+/// Description of the network tables stored in database.
+///
+/// # Returns
+///
+/// The name of each table with the [`SqlFieldDescriptor`] list of its fields.
+pub fn schema() -> Vec<(&'static str, Vec<SqlFieldDescriptor>)> {
+    vec![(TABLE_NAME, field_descriptor())]
+}
 
+/// Retrieves the data of each network interface, without storing it.
+///
+/// # Returns
+///
+/// Completed [`NetworkInterface`] structures with all network information per interface.
+pub fn collect_net_info() -> Vec<NetworkInterface> {
     let mut networks = Networks::new_with_refreshed_list();
     networks.refresh(true);
+    collect_network_data(&networks)
+}
+
+/// Store network interfaces data in SQLite database.
+///
+/// # Arguments
+///
+/// - `interfaces` : [`NetworkInterface`] information to insert in database.
+///
+/// # Returns
+///
+/// An error if the table creation or the SQL insert requests failed.
+pub fn store_net_info(interfaces: &[NetworkInterface]) -> Result<(), Box<dyn Error>> {
+    let query = db_table_query_creation(TABLE_NAME, &field_descriptor())?;
+    let mut conn = init_db(&query)?;
 
     let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
-    let interfaces = collect_network_data(&networks);
     let tx = conn.transaction()?;
 
     for interface in interfaces {
-        insert_db(&tx, &timestamp, &interface)?;
+        insert_db(&tx, &timestamp, interface)?;
     }
     tx.commit()?;
 
     Ok(())
 }
+
+/// Public function used to collecting network data,
+/// and stores [`collect_net_info`] function result in an SQLite database.
+pub fn get_net_info() -> Result<(), Box<dyn Error>> {
+    store_net_info(&collect_net_info())
+}
@@ -1,10 +1,13 @@
 //! # File utilities module
 use serde::Serialize;
-use std::time::Duration;
+use std::{fs::read_to_string, time::Duration};
 use sysinfo::Networks;
 
 const FACTOR: f64 = 1e6;
 
+/// Directory of the network interfaces on the system.
+const SYS_NET: &str = "/sys/class/net";
+
 /// Existing network interface available.
 #[derive(Debug, Clone, Copy, Serialize, PartialEq)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
@@ -57,7 +60,7 @@ impl NetworkType {
     /// # Returns
     ///
     /// The idle power per interface.
-    fn idle_power(&self) -> f64 {
+    pub fn idle_power(&self) -> f64 {
         match self {
             NetworkType::Ethernet => 2.0,
             NetworkType::Infiniband => 1.5,
@@ -250,6 +253,29 @@ pub struct NetworkInterface {
     pub packet_received: Option<f64>,
     /// Number of outgoing packets in millions.
     pub packet_transmitted: Option<f64>,
+    /// Number of incoming packets dropped in millions, not stored in the network table.
+    pub drops_received: Option<f64>,
+    /// Number of outgoing packets dropped in millions, not stored in the network table.
+    pub drops_transmitted: Option<f64>,
+}
+
+/// Read a statistic counter of a network interface.
+///
+/// # Arguments
+///
+/// - `name` : Network interface name.
+/// - `statistic` : Name of the counter, like `rx_dropped`.
+///
+/// # Returns
+///
+/// The counter in millions, none if the interface doesn't expose it.
+fn read_statistic(name: &str, statistic: &str) -> Option<f64> {
+    read_to_string(format!("{SYS_NET}/{name}/statistics/{statistic}"))
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|count| count as f64 / FACTOR)
 }
 
 impl NetworkInterface {
@@ -306,6 +332,8 @@ impl NetworkInterface {
             errors_transmitted: Some(network.total_errors_on_transmitted() as f64 / FACTOR),
             packet_received: Some(packet_received),
             packet_transmitted: Some(packet_transmitted),
+            drops_received: read_statistic(name, "rx_dropped"),
+            drops_transmitted: read_statistic(name, "tx_dropped"),
         }
     }
 }
@@ -3,13 +3,14 @@
 //! This module provides functionalities to retrieve storage data on Unix-based systems.
 
 use chrono::{SecondsFormat, Utc};
-use rusqlite::Connection;
 use std::error::Error;
 use sysinfo::{DiskRefreshKind, Disks};
 
+mod dbms;
 mod utils;
-use core::core::init_db;
-use utils::*;
+use core::core::{SqlFieldDescriptor, init_db};
+use dbms::*;
+pub use utils::{DiskInfo, SmartInfo, estimate_energy};
 
 const REQUEST: &str = "
     CREATE TABLE IF NOT EXISTS storage_data (
@@ -33,35 +34,58 @@ const REQUEST: &str = "
         sectors_pending INTEGER,
         sectors_pending_current INTEGER,
         temperature INTEGER,
-        FOREIGN KEY(device_id) REFERENCES disk_data(id)
+        FOREIGN KEY(device_id) REFERENCES storage_data(id)
     );";
 
-/// Function that retrieves all detailed disk information.
+/// Description of the storage tables stored in database.
+///
+/// # Returns
+///
+/// The name of each table with the [`SqlFieldDescriptor`] list of its fields.
+pub fn schema() -> Vec<(&'static str, Vec<SqlFieldDescriptor>)> {
+    vec![
+        (TABLE_NAME[0], field_descriptor_storage()),
+        (TABLE_NAME[1], field_descriptor_smart()),
+    ]
+}
+
+/// Function that retrieves all detailed disk information, without storing it.
 ///
 /// # Returns
 ///
 /// The compilation of completed structures concerning all disk information.
 /// * [`DiskInfo`] concerning global system info of the device storage.
 /// * [`SmartInfo`] concerning smart info for the device storage if it's possible.
-fn collect_storage_data(conn: &Connection) -> Result<(), Box<dyn Error>> {
-    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+pub fn collect_storage_info() -> Result<Vec<DiskInfo>, Box<dyn Error>> {
     let disks = Disks::new_with_refreshed_list_specifics(DiskRefreshKind::everything());
+    disks.list().iter().map(DiskInfo::from_device).collect()
+}
 
-    for disk in disks.list() {
-        let disk_info = DiskInfo::from_device(disk)?;
-        disk_info.insert_db(conn, &timestamp)?;
+/// Store storage devices data in SQLite database.
+///
+/// # Arguments
+///
+/// - `disks` : [`DiskInfo`] information to insert in database.
+///
+/// # Returns
+///
+/// An error if the tables creation or the SQL insert requests failed.
+pub fn store_storage_info(disks: &[DiskInfo]) -> Result<(), Box<dyn Error>> {
+    let conn = init_db(REQUEST)?;
+    let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+
+    for disk_info in disks {
+        disk_info.insert_db(&conn, &timestamp)?;
         let id = conn.last_insert_rowid();
         if let Some(smart) = &disk_info.smart_info {
-            smart.insert_db(conn, id)?;
+            smart.insert_db(&conn, id)?;
         }
     }
     Ok(())
 }
 
-/// Public function used to send JSON formatted values,
-/// from [`collect_storage_data`] function result.
+/// Public function used to send values in SQLite database,
+/// from [`collect_storage_info`] function result.
 pub fn get_storage_info() -> Result<(), Box<dyn Error>> {
-    let conn = init_db(REQUEST)?;
-    collect_storage_data(&conn)?;
-    Ok(())
+    store_storage_info(&collect_storage_info()?)
 }
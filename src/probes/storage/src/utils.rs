@@ -187,7 +187,7 @@ impl DiskInfo {
     /// # Returns
     ///
     /// The default name of `name` input.
-    fn device_path(name: &str) -> String {
+    pub fn device_path(name: &str) -> String {
         // Pattern for /dev/nvme0n1p1
         let re_nvme = Regex::new(r"^(/dev/nvme\d+n\d+)p\d+$").unwrap();
         // Pattern for /dev/mmcblk0p1
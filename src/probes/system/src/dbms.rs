@@ -0,0 +1,253 @@
+//! # Lib file for data base management system data module
+//!
+//! This module provides main functionality to describe database parameters.
+
+use core::core::{SQLiteKey, SQLiteOption, SQLiteType, SqlFieldDescriptor};
+
+/// SQL table(s) available to create.
+pub const TABLE_NAME: [&str; 2] = ["system_data", "system_process_data"];
+
+/// # Returns
+///
+/// - A tuple of [`SqlFieldDescriptor`] describing each field of the system_data table.
+pub fn field_descriptor_system() -> Vec<SqlFieldDescriptor> {
+    vec![
+        SqlFieldDescriptor {
+            field_name: "id",
+            field_unit: None,
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::Primary,
+            field_options: SQLiteOption::Autoincrement,
+        },
+        SqlFieldDescriptor {
+            field_name: "hostname",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "system_load",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "system_kernel",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "system_name",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "system_version",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "open_files_limit",
+            field_unit: None,
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "process_count",
+            field_unit: None,
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "uptime",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+    ]
+}
+
+/// # Returns
+///
+/// - A tuple of [`SqlFieldDescriptor`] describing each field of the system_process_data table.
+pub fn field_descriptor_process() -> Vec<SqlFieldDescriptor> {
+    vec![
+        SqlFieldDescriptor {
+            field_name: "id",
+            field_unit: None,
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::Primary,
+            field_options: SQLiteOption::Autoincrement,
+        },
+        SqlFieldDescriptor {
+            field_name: "timestamp",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: true,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "pid",
+            field_unit: None,
+            field_type: SQLiteType::Integer,
+            field_not_null: true,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "name",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "cpu_usage",
+            field_unit: None,
+            field_type: SQLiteType::Real,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "disk_usage_read",
+            field_unit: Some("MB"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "disk_usage_write",
+            field_unit: Some("MB"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "id_group",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "id_session",
+            field_unit: None,
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "id_user",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "memory_usage",
+            field_unit: Some("MB"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "memory_virtual_usage",
+            field_unit: Some("MB"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "status",
+            field_unit: None,
+            field_type: SQLiteType::Text,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "run_time",
+            field_unit: Some("min"),
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::None,
+            field_options: SQLiteOption::None,
+        },
+        SqlFieldDescriptor {
+            field_name: "system_data_id",
+            field_unit: None,
+            field_type: SQLiteType::Integer,
+            field_not_null: false,
+            field_key: SQLiteKey::Foreign,
+            field_options: SQLiteOption::None,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    /// Name of the columns of a table created by the crate request.
+    fn columns(table: &str) -> Vec<String> {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(crate::REQUEST).unwrap();
+        let mut stmt = conn
+            .prepare(&format!("SELECT name FROM pragma_table_info('{table}')"))
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    /// Name of the described fields, unit suffix included.
+    fn names(fields: &[SqlFieldDescriptor]) -> Vec<String> {
+        fields
+            .iter()
+            .map(|f| match f.field_unit {
+                Some(unit) => format!("{}_{unit}", f.field_name),
+                None => f.field_name.to_string(),
+            })
+            .collect()
+    }
+
+    // Test the field descriptors against the columns of the created tables
+    #[test]
+    fn test_field_descriptor() {
+        assert_eq!(names(&field_descriptor_system()), columns(TABLE_NAME[0]));
+        assert_eq!(names(&field_descriptor_process()), columns(TABLE_NAME[1]));
+    }
+}
@@ -7,9 +7,12 @@ use log::error;
 use std::{error::Error, thread};
 use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
 
+mod dbms;
 mod utils;
-use core::core::init_db;
-use utils::*;
+use core::core::{SqlFieldDescriptor, init_db};
+use dbms::*;
+use utils::HEADER;
+pub use utils::{ProcessInfo, SystemInfo};
 
 const REQUEST: &str = "CREATE TABLE IF NOT EXISTS system_data (
         id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -41,13 +44,25 @@ const REQUEST: &str = "CREATE TABLE IF NOT EXISTS system_data (
         FOREIGN KEY (system_data_id) REFERENCES system_data(id)
     );";
 
+/// Description of the system tables stored in database.
+///
+/// # Returns
+///
+/// The name of each table with the [`SqlFieldDescriptor`] list of its fields.
+pub fn schema() -> Vec<(&'static str, Vec<SqlFieldDescriptor>)> {
+    vec![
+        (TABLE_NAME[0], field_descriptor_system()),
+        (TABLE_NAME[1], field_descriptor_process()),
+    ]
+}
+
 /// Retrieves information about the top resource-consuming process, system load and uptime.
 ///
 /// # Returns
 ///
 /// - Completed [`SystemInfo`] structure with all processes and system information.
 /// - An error when some important and critical metrics can't be retrieved.
-fn collect_system_data() -> Result<SystemInfo, Box<dyn Error>> {
+pub fn collect_system_info() -> Result<SystemInfo, Box<dyn Error>> {
     // Uptime
     let uptime = {
         let secs = System::uptime();
@@ -139,16 +154,28 @@ fn collect_system_data() -> Result<SystemInfo, Box<dyn Error>> {
     })
 }
 
-/// Public function used to send values in SQLite database,
-/// from [`collect_system_data`] function result.
-pub fn get_system_info() -> Result<(), Box<dyn Error>> {
+/// Store system data in SQLite database.
+///
+/// # Arguments
+///
+/// - `data` : [`SystemInfo`] information to insert in database.
+///
+/// # Returns
+///
+/// An error if the tables creation or the SQL insert requests failed.
+pub fn store_system_info(data: &SystemInfo) -> Result<(), Box<dyn Error>> {
     let conn = init_db(REQUEST)?;
-    let data = collect_system_data()?;
     let timestamp = Utc::now().to_rfc3339();
 
-    let system_data_id = SystemInfo::insert_db(&conn, &data)?;
+    let system_data_id = SystemInfo::insert_db(&conn, data)?;
     if let Some(ref processes) = data.processes {
         ProcessInfo::insert_db(&conn, processes, system_data_id, &timestamp)?;
     }
     Ok(())
 }
+
+/// Public function used to send values in SQLite database,
+/// from [`collect_system_info`] function result.
+pub fn get_system_info() -> Result<(), Box<dyn Error>> {
+    store_system_info(&collect_system_info()?)
+}
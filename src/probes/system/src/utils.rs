@@ -13,29 +13,29 @@ pub const HEADER: &str = "SYSTEM";
 #[derive(Debug, Serialize)]
 pub struct ProcessInfo {
     /// PID of a process.
-    pid: usize,
+    pub pid: usize,
     /// Identification name of a process, given by the system.
-    name: Option<String>,
+    pub name: Option<String>,
     /// CPU usage by a process in percentage.
-    cpu_usage: Option<f32>,
+    pub cpu_usage: Option<f32>,
     /// Reading disk usage by a process in MB.
-    disk_usage_read: Option<u64>,
+    pub disk_usage_read: Option<u64>,
     /// Writing disk usage by a process in MB.
-    disk_usage_write: Option<u64>,
+    pub disk_usage_write: Option<u64>,
     /// process group ID of the process.
-    id_group: Option<String>,
+    pub id_group: Option<String>,
     /// Session ID of a running process.
-    id_session: Option<usize>,
+    pub id_session: Option<usize>,
     /// ID of the owner user of this process.
-    id_user: Option<String>,
+    pub id_user: Option<String>,
     /// Memory usage by a process in MB.
-    memory_usage: Option<u64>,
+    pub memory_usage: Option<u64>,
     /// Virtual memory usage by a process in MB.
-    memory_virtual_usage: Option<u64>,
+    pub memory_virtual_usage: Option<u64>,
     /// State of a process on the system among `ProcessStatus`.
-    status: Option<String>,
+    pub status: Option<String>,
     /// Time the process has been running in minutes.
-    run_time: Option<u64>,
+    pub run_time: Option<u64>,
 }
 
 /// Collection of system load data.
@@ -0,0 +1,632 @@
+//! # File snapshot
+//!
+//! This file provides the typed report of a probes run, gathering their data
+//! grouped by component, and its flattening in metrics with units.
+
+use board::{BoardInfo, collect_board_info, store_board_info};
+use cgroup::{CgroupInfo, collect_cgroup_info, store_cgroup_info};
+use cpu::{CpuData, collect_cpu_info, store_cpu_info};
+use exec::{ExecData, ExecProbe, ExecSample, ExecSchedule, collect_exec_info, store_exec_info};
+use gpu::{GpuData, collect_gpu_info, store_gpu_info};
+use memory::{MemData, collect_mem_info, store_mem_info};
+use network::{NetworkInterface, collect_net_info, store_net_info};
+use storage::{DiskInfo, collect_storage_info, store_storage_info};
+use system::{SystemInfo, collect_system_info, store_system_info};
+
+use serde::Serialize;
+use std::{collections::BTreeMap, error::Error as StdError};
+use userv_core::{alert::Transition, anomaly::Anomaly};
+
+use crate::{
+    attribution::{CgroupEnergy, ProcessEnergy, store_cgroup_energy, store_process_energy},
+    baseline::{EnergySplit, store_split},
+    carbon::{Emissions, store_emissions},
+    component::Component,
+    cost::{Cost, store_cost},
+    embodied::{Embodied, store_embodied},
+    energy::{EnergySummary, store_energy},
+    error::Error,
+    health::{Health, store_health},
+};
+
+/// Report of the data retrieved by each selected probe, grouped by component.
+#[derive(Default, Serialize)]
+pub struct Snapshot {
+    /// Date of the report.
+    pub timestamp: String,
+    /// Hostname of the machine.
+    pub hostname: Option<String>,
+    /// Main board data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub board: Option<BoardInfo>,
+    /// Control groups data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cgroup: Option<Vec<CgroupInfo>>,
+    /// CPU data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<CpuData>,
+    /// External probes samples.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exec: Option<Vec<ExecSample>>,
+    /// GPU devices data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gpu: Option<Vec<GpuData>>,
+    /// Memory data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemData>,
+    /// Network interfaces data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<Vec<NetworkInterface>>,
+    /// Storage devices data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<Vec<DiskInfo>>,
+    /// Operating system data.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<SystemInfo>,
+    /// Energy consumed by the machine since the previous snapshot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub energy: Option<EnergySummary>,
+    /// Static and dynamic parts of this energy, from the calibrated baselines.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub energy_split: Option<EnergySplit>,
+    /// Energy attributed to each process over the same interval.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub process_energy: Vec<ProcessEnergy>,
+    /// Energy attributed to each cgroup over the same interval.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cgroup_energy: Vec<CgroupEnergy>,
+    /// Carbon emissions of this energy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emissions: Option<Emissions>,
+    /// Electricity cost of this energy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<Cost>,
+    /// Embodied emissions of the hardware amortised over the same interval.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub embodied: Option<Embodied>,
+    /// Health score of each component and entity with a health data.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub health: Vec<Health>,
+    /// Alerts changing of state after the snapshot was stored.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<Transition>,
+    /// Values deviating from their baseline after the snapshot was stored.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<Anomaly>,
+    /// Error message of each probe which failed, by component.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors: BTreeMap<String, String>,
+}
+
+/// Single numeric value of a snapshot, with its unit.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Metric {
+    /// Name of the component concerned, among [`Component::label`],
+    /// or name of the external probe for [`Component::Exec`].
+    pub component: String,
+    /// Entity concerned (interface, disk, GPU bus id, pid...), if the component has several.
+    pub entity: Option<String>,
+    /// Name of the metric, as stored in database without unit.
+    pub name: String,
+    /// Value of the metric.
+    pub value: f64,
+    /// Unit of the value, as concatenated to the database field name.
+    pub unit: Option<String>,
+}
+
+/// Data retrieved by a single probe.
+pub(crate) enum Part {
+    Board(BoardInfo),
+    Cgroup(Vec<CgroupInfo>),
+    Cpu(CpuData),
+    Exec(ExecData),
+    Gpu(Vec<GpuData>),
+    Memory(MemData),
+    Net(Vec<NetworkInterface>),
+    Storage(Vec<DiskInfo>),
+    System(SystemInfo),
+}
+
+/// Run the probe of a component and retrieves its data.
+///
+/// # Arguments
+///
+/// - `component` : The component that we want retrieves data.
+/// - `probes` : External probes configured for [`Component::Exec`].
+/// - `schedule` : Last run of each external probe.
+///
+/// # Returns
+///
+/// - The [`Part`] of data retrieved by the probe.
+/// - An error message if the probe failed.
+pub(crate) fn collect_part(
+    component: Component,
+    probes: &[ExecProbe],
+    schedule: &ExecSchedule,
+) -> Result<Part, String> {
+    let part: Result<Part, Box<dyn StdError>> = match component {
+        Component::Board => collect_board_info().map(Part::Board),
+        Component::Cgroup => collect_cgroup_info().map(Part::Cgroup),
+        Component::Cpu => collect_cpu_info().map(Part::Cpu),
+        Component::Exec => Ok(Part::Exec(collect_exec_info(probes, schedule))),
+        Component::Gpu => collect_gpu_info().map(Part::Gpu),
+        Component::Memory => collect_mem_info().map(Part::Memory),
+        Component::Net => Ok(Part::Net(collect_net_info())),
+        Component::Storage => collect_storage_info().map(Part::Storage),
+        Component::System => collect_system_info().map(Part::System),
+    };
+    part.map_err(|e| e.to_string())
+}
+
+/// Accumulator of the metrics of a component.
+struct Metrics<'a> {
+    component: &'static str,
+    list: &'a mut Vec<Metric>,
+}
+
+impl Metrics<'_> {
+    /// Add a metric if its value is available.
+    fn push<T: Into<f64>>(
+        &mut self,
+        entity: Option<&str>,
+        name: &str,
+        value: Option<T>,
+        unit: Option<&str>,
+    ) {
+        if let Some(value) = value {
+            self.list.push(Metric {
+                component: self.component.to_string(),
+                entity: entity.map(str::to_string),
+                name: name.to_string(),
+                value: value.into(),
+                unit: unit.map(str::to_string),
+            });
+        }
+    }
+}
+
+impl Snapshot {
+    /// Store the data of each retrieved component and the figures derived from them
+    /// in SQLite database.
+    ///
+    /// # Returns
+    ///
+    /// An [`Error::Storage`] if a component data can't be stored.
+    pub fn store(&self) -> Result<(), Error> {
+        let storage = |e: Box<dyn StdError>| Error::Storage(e.to_string());
+        if let Some(data) = &self.board {
+            store_board_info(data).map_err(storage)?;
+        }
+        if let Some(data) = &self.cgroup {
+            store_cgroup_info(data).map_err(storage)?;
+        }
+        if let Some(data) = &self.cpu {
+            store_cpu_info(data).map_err(storage)?;
+        }
+        if let Some(data) = &self.exec {
+            store_exec_info(data).map_err(storage)?;
+        }
+        if let Some(data) = &self.gpu {
+            store_gpu_info(data).map_err(storage)?;
+        }
+        if let Some(data) = &self.memory {
+            store_mem_info(data).map_err(storage)?;
+        }
+        if let Some(data) = &self.network {
+            store_net_info(data).map_err(storage)?;
+        }
+        if let Some(data) = &self.storage {
+            store_storage_info(data).map_err(storage)?;
+        }
+        if let Some(data) = &self.system {
+            store_system_info(data).map_err(storage)?;
+        }
+        if let Some(data) = &self.energy {
+            store_energy(&self.timestamp, data).map_err(storage)?;
+            if let Some(split) = &self.energy_split {
+                store_split(&self.timestamp, data.interval, split).map_err(storage)?;
+            }
+            if !self.process_energy.is_empty() {
+                store_process_energy(&self.timestamp, data.interval, &self.process_energy)
+                    .map_err(storage)?;
+            }
+            if !self.cgroup_energy.is_empty() {
+                store_cgroup_energy(&self.timestamp, data.interval, &self.cgroup_energy)
+                    .map_err(storage)?;
+            }
+        }
+        if let Some(data) = &self.emissions {
+            store_emissions(&self.timestamp, data).map_err(storage)?;
+        }
+        if let Some(data) = &self.cost {
+            store_cost(&self.timestamp, data).map_err(storage)?;
+        }
+        if let Some(data) = &self.embodied {
+            store_embodied(&self.timestamp, data).map_err(storage)?;
+        }
+        if !self.health.is_empty() {
+            store_health(&self.timestamp, &self.health).map_err(storage)?;
+        }
+        Ok(())
+    }
+
+    /// Add the data retrieved by a probe to the snapshot.
+    pub(crate) fn insert(&mut self, part: Part) {
+        match part {
+            Part::Board(data) => self.board = Some(data),
+            Part::Cgroup(data) => self.cgroup = Some(data),
+            Part::Cpu(data) => self.cpu = Some(data),
+            Part::Exec(data) => {
+                for (name, message) in data.errors {
+                    self.errors
+                        .insert(format!("{}.{name}", Component::Exec.label()), message);
+                }
+                if !data.samples.is_empty() {
+                    self.exec = Some(data.samples);
+                }
+            }
+            Part::Gpu(data) => self.gpu = Some(data),
+            Part::Memory(data) => self.memory = Some(data),
+            Part::Net(data) => self.network = Some(data),
+            Part::Storage(data) => self.storage = Some(data),
+            Part::System(data) => self.system = Some(data),
+        }
+    }
+
+    /// Flatten the numeric values of the snapshot in a list of metrics with units.
+    ///
+    /// # Returns
+    ///
+    /// The list of [`Metric`] of each retrieved component, with their health scores.
+    pub fn metrics(&self) -> Vec<Metric> {
+        let mut list = Vec::new();
+
+        for cgroup in self.cgroup.iter().flatten() {
+            let mut m = Metrics {
+                component: Component::Cgroup.label(),
+                list: &mut list,
+            };
+            let path = Some(cgroup.path.as_str());
+            let value = |v: Option<u64>| v.map(|v| v as f64);
+            m.push(path, "cpu_usage", value(cgroup.cpu_usage), Some("us"));
+            m.push(path, "cpu_user", value(cgroup.cpu_user), Some("us"));
+            m.push(path, "cpu_system", value(cgroup.cpu_system), Some("us"));
+            m.push(
+                path,
+                "memory_current",
+                value(cgroup.memory_current),
+                Some("B"),
+            );
+            m.push(path, "memory_anon", value(cgroup.memory_anon), Some("B"));
+            m.push(path, "memory_file", value(cgroup.memory_file), Some("B"));
+            m.push(path, "io_read", value(cgroup.io_read), Some("B"));
+            m.push(path, "io_write", value(cgroup.io_write), Some("B"));
+            m.push(path, "pids_current", value(cgroup.pids_current), None);
+        }
+
+        if let Some(cpu) = &self.cpu {
+            let mut m = Metrics {
+                component: Component::Cpu.label(),
+                list: &mut list,
+            };
+            let frequency = cpu
+                .global
+                .frequency
+                .as_deref()
+                .and_then(|f| f.parse::<f64>().ok());
+            m.push(None, "frequency", frequency, Some("MHz"));
+            m.push(
+                None,
+                "cores_physic",
+                cpu.global.cores_physic.map(|c| c as f64),
+                None,
+            );
+            m.push(
+                None,
+                "cores_logic",
+                cpu.global.cores_logic.map(|c| c as f64),
+                None,
+            );
+            for (core, usage) in &cpu.cores.cores_usage {
+                m.push(Some(core), "usage", Some(*usage), Some("percent"));
+            }
+            for (zone, power) in cpu.power.iter().flat_map(|p| &p.powers) {
+                m.push(Some(zone), "power", Some(*power), Some("W"));
+            }
+            for (zone, temperature) in cpu.temperature.iter().flat_map(|t| &t.temperatures) {
+                m.push(Some(zone), "temperature", Some(*temperature), Some("°C"));
+            }
+        }
+
+        for sample in self.exec.iter().flatten() {
+            for (name, value) in &sample.metrics {
+                list.push(Metric {
+                    component: sample.probe.clone(),
+                    entity: sample.entity.clone(),
+                    name: name.clone(),
+                    value: value.value,
+                    unit: value.unit.clone(),
+                });
+            }
+        }
+
+        for gpu in self.gpu.iter().flatten() {
+            let mut m = Metrics {
+                component: Component::Gpu.label(),
+                list: &mut list,
+            };
+            let g = &gpu.metrics;
+            let bus = g.gpu_bus_id.as_deref();
+            m.push(bus, "gpu_clock_graphic", g.gpu_clock_graphic, Some("MHz"));
+            m.push(bus, "gpu_clock_memory", g.gpu_clock_memory, Some("MHz"));
+            m.push(bus, "gpu_clock_sm", g.gpu_clock_sm, Some("MHz"));
+            m.push(bus, "gpu_clock_video", g.gpu_clock_video, Some("MHz"));
+            m.push(
+                bus,
+                "gpu_energy_consumption",
+                g.gpu_energy_consumption,
+                Some("mJ"),
+            );
+            m.push(bus, "gpu_usage", g.gpu_usage, Some("percent"));
+            m.push(bus, "gpu_temperature", g.gpu_temperature, Some("°C"));
+            m.push(
+                bus,
+                "gpu_memory_free",
+                g.gpu_memory_free.map(|v| v as f64),
+                Some("B"),
+            );
+            m.push(bus, "gpu_memory_stat", g.gpu_memory_stat, Some("percent"));
+            m.push(
+                bus,
+                "gpu_memory_total",
+                g.gpu_memory_total.map(|v| v as f64),
+                Some("B"),
+            );
+            m.push(
+                bus,
+                "gpu_memory_usage",
+                g.gpu_memory_usage.map(|v| v as f64),
+                Some("B"),
+            );
+            m.push(bus, "gpu_pci_data_sent", g.gpu_pci_data_sent, Some("B_s"));
+            m.push(
+                bus,
+                "gpu_pci_data_received",
+                g.gpu_pci_data_received,
+                Some("B_s"),
+            );
+            m.push(
+                bus,
+                "gpu_power_consumption",
+                g.gpu_power_consumption,
+                Some("mW"),
+            );
+            m.push(bus, "gpu_power_ratio", g.gpu_power_ratio, Some("percent"));
+            for p in &gpu.processes {
+                let pid = p.process_pid.map(|pid| pid.to_string());
+                let pid = pid.as_deref();
+                m.push(pid, "process_decoding", p.process_dec, Some("percent"));
+                m.push(pid, "process_encoding", p.process_enc, Some("percent"));
+                m.push(pid, "process_memory", p.process_mem, Some("percent"));
+                m.push(
+                    pid,
+                    "process_streaming_multiprocessor",
+                    p.process_sm,
+                    Some("percent"),
+                );
+            }
+        }
+
+        if let Some(memory) = &self.memory {
+            let mut m = Metrics {
+                component: Component::Memory.label(),
+                list: &mut list,
+            };
+            let g = &memory.global;
+            m.push(None, "bandwidth_read", g.bandwidth_read, Some("MB_s"));
+            m.push(None, "bandwidth_write", g.bandwidth_write, Some("MB_s"));
+            m.push(None, "ram_total", g.ram_total.map(|v| v as f64), Some("MB"));
+            m.push(None, "ram_used", g.ram_used.map(|v| v as f64), Some("MB"));
+            m.push(None, "ram_free", g.ram_free.map(|v| v as f64), Some("MB"));
+            m.push(
+                None,
+                "ram_available",
+                g.ram_available.map(|v| v as f64),
+                Some("MB"),
+            );
+            m.push(
+                None,
+                "ram_power_consumption",
+                g.ram_power_consumption,
+                Some("W"),
+            );
+            m.push(
+                None,
+                "swap_total",
+                g.swap_total.map(|v| v as f64),
+                Some("MB"),
+            );
+            m.push(None, "swap_used", g.swap_used.map(|v| v as f64), Some("MB"));
+            m.push(None, "swap_free", g.swap_free.map(|v| v as f64), Some("MB"));
+        }
+
+        for interface in self.network.iter().flatten() {
+            let mut m = Metrics {
+                component: Component::Net.label(),
+                list: &mut list,
+            };
+            let name = Some(interface.name.as_str());
+            m.push(name, "received", interface.received, Some("MB"));
+            m.push(name, "transmitted", interface.transmitted, Some("MB"));
+            m.push(
+                name,
+                "errors_received",
+                interface.errors_received,
+                Some("MB"),
+            );
+            m.push(
+                name,
+                "errors_transmitted",
+                interface.errors_transmitted,
+                Some("MB"),
+            );
+            m.push(
+                name,
+                "packet_received",
+                interface.packet_received,
+                Some("MB"),
+            );
+            m.push(
+                name,
+                "packet_transmitted",
+                interface.packet_transmitted,
+                Some("MB"),
+            );
+            m.push(
+                name,
+                "energy_consumed",
+                interface.energy_consumed,
+                Some("Wh"),
+            );
+        }
+
+        for disk in self.storage.iter().flatten() {
+            let mut m = Metrics {
+                component: Component::Storage.label(),
+                list: &mut list,
+            };
+            let name = Some(disk.name.as_str());
+            m.push(
+                name,
+                "bandwidth_read",
+                disk.bandwidth_read.map(|v| v as f64),
+                Some("MB"),
+            );
+            m.push(
+                name,
+                "bandwidth_write",
+                disk.bandwidth_write.map(|v| v as f64),
+                Some("MB"),
+            );
+            m.push(name, "energy_consumed", disk.energy_consumed, Some("J"));
+            m.push(
+                name,
+                "space_available",
+                disk.space_available.map(|v| v as f64),
+                Some("GB"),
+            );
+            m.push(
+                name,
+                "space_total",
+                disk.space_total.map(|v| v as f64),
+                Some("GB"),
+            );
+            if let Some(smart) = &disk.smart_info {
+                m.push(name, "uptime_hours", smart.uptime_hours, Some("h"));
+                m.push(name, "sectors_reallocated", smart.sectors_reallocated, None);
+                m.push(name, "sectors_pending", smart.sectors_pending, None);
+                m.push(
+                    name,
+                    "sectors_pending_current",
+                    smart.sectors_pending_current,
+                    None,
+                );
+                m.push(name, "temperature", smart.temperature, Some("°C"));
+            }
+        }
+
+        if let Some(system) = &self.system {
+            let mut m = Metrics {
+                component: Component::System.label(),
+                list: &mut list,
+            };
+            if let Some((one, five, fifteen)) = system.system_load {
+                m.push(None, "system_load_1", Some(one), None);
+                m.push(None, "system_load_5", Some(five), None);
+                m.push(None, "system_load_15", Some(fifteen), None);
+            }
+            m.push(None, "process_count", system.process_count, None);
+            let uptime = system
+                .uptime
+                .map(|(d, h, min, s)| (d * 86400 + h * 3600 + min * 60 + s) as f64);
+            m.push(None, "uptime", uptime, Some("s"));
+            for p in system.processes.iter().flatten() {
+                let pid = p.pid.to_string();
+                let pid = Some(pid.as_str());
+                m.push(pid, "cpu_usage", p.cpu_usage, Some("percent"));
+                m.push(
+                    pid,
+                    "memory_usage",
+                    p.memory_usage.map(|v| v as f64),
+                    Some("MB"),
+                );
+                m.push(
+                    pid,
+                    "disk_usage_read",
+                    p.disk_usage_read.map(|v| v as f64),
+                    Some("MB"),
+                );
+                m.push(
+                    pid,
+                    "disk_usage_write",
+                    p.disk_usage_write.map(|v| v as f64),
+                    Some("MB"),
+                );
+            }
+        }
+
+        for health in &self.health {
+            let mut m = Metrics {
+                component: health.component.label(),
+                list: &mut list,
+            };
+            m.push(
+                health.entity.as_deref(),
+                "health_score",
+                Some(health.score),
+                None,
+            );
+        }
+
+        list
+    }
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use network::NetworkType;
+
+    // Test `metrics` function flattening network interfaces with units
+    #[test]
+    fn test_snapshot_metrics() {
+        let snapshot = Snapshot {
+            network: Some(vec![NetworkInterface {
+                address_mac: None,
+                energy_consumed: Some(0.5),
+                name: "eth0".to_string(),
+                network_type: NetworkType::Ethernet,
+                received: Some(10.0),
+                transmitted: None,
+                errors_received: None,
+                errors_transmitted: None,
+                packet_received: None,
+                packet_transmitted: None,
+                drops_received: None,
+                drops_transmitted: None,
+            }]),
+            ..Default::default()
+        };
+
+        let res = snapshot.metrics();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].component, "network");
+        assert_eq!(res[0].entity.as_deref(), Some("eth0"));
+        assert_eq!(res[1].name, "energy_consumed");
+        assert_eq!(res[1].unit.as_deref(), Some("Wh"));
+    }
+}
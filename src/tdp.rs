@@ -0,0 +1,309 @@
+//! # File tdp
+//!
+//! This file provides the estimate of the CPU power on the machines without RAPL counters,
+//! applying a utilisation to power curve to the thermal design power of the CPU model,
+//! looked up in a bundled database or given in the configuration.
+
+use serde::Deserialize;
+
+use cpu::CpuData;
+
+/// Name of the power zone of the estimated CPU power.
+pub const ZONE: &str = "tdp-model";
+
+/// Share of the TDP drawn at each CPU usage in percent, from the Teads measures.
+pub const TEADS_CURVE: [(f64, f64); 4] = [(0.0, 0.12), (10.0, 0.32), (50.0, 0.75), (100.0, 1.02)];
+
+/// Thermal design power in W and logical CPUs of the known models, by name as written in
+/// `/proc/cpuinfo` without brand marks. A model given by core, like the ARM ones, has one CPU.
+const MODELS: &[(&str, f64, usize)] = &[
+    // Intel Xeon
+    ("xeon e5-2630 v4", 85.0, 20),
+    ("xeon e5-2680 v4", 120.0, 28),
+    ("xeon e5-2690 v4", 135.0, 28),
+    ("xeon e-2288g", 95.0, 16),
+    ("xeon gold 5218", 125.0, 32),
+    ("xeon gold 6130", 125.0, 32),
+    ("xeon gold 6148", 150.0, 40),
+    ("xeon gold 6230", 125.0, 40),
+    ("xeon gold 6248", 150.0, 40),
+    ("xeon gold 6338", 205.0, 64),
+    ("xeon platinum 8168", 205.0, 48),
+    ("xeon platinum 8280", 205.0, 56),
+    ("xeon platinum 8380", 270.0, 80),
+    ("xeon platinum 8480+", 350.0, 112),
+    // Intel Core
+    ("core i5-8250u", 15.0, 8),
+    ("core i7-8650u", 15.0, 8),
+    ("core i7-1165g7", 28.0, 8),
+    ("core i7-9700k", 95.0, 8),
+    ("core i9-9900k", 95.0, 16),
+    ("core i7-12700", 65.0, 20),
+    ("core i9-12900k", 125.0, 24),
+    ("core i9-13900k", 125.0, 32),
+    // AMD EPYC
+    ("epyc 7302", 155.0, 32),
+    ("epyc 7402", 180.0, 48),
+    ("epyc 7502", 180.0, 64),
+    ("epyc 7542", 225.0, 64),
+    ("epyc 7742", 225.0, 128),
+    ("epyc 7763", 280.0, 128),
+    ("epyc 9554", 360.0, 128),
+    ("epyc 9654", 360.0, 192),
+    // AMD Ryzen
+    ("ryzen 5 3600", 65.0, 12),
+    ("ryzen 7 3700x", 65.0, 16),
+    ("ryzen 9 3900x", 105.0, 24),
+    ("ryzen 7 5800x", 105.0, 16),
+    ("ryzen 9 5950x", 105.0, 32),
+    ("ryzen 9 7950x", 170.0, 32),
+    // ARM
+    ("altra q80-30", 210.0, 80),
+    ("altra max m128-30", 250.0, 128),
+    ("neoverse-n1", 2.6, 1),
+    ("cortex-a72", 1.5, 1),
+];
+
+/// Lowercase a CPU model name without its brand marks and punctuation.
+fn normalise(model: &str) -> String {
+    let model = model
+        .to_lowercase()
+        .replace("(r)", " ")
+        .replace("(tm)", " ");
+    model
+        .split(|c: char| !(c.is_alphanumeric() || c == '-' || c == '+'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Look up a CPU model in the bundled database, the longest name included in it winning.
+///
+/// # Arguments
+///
+/// - `model` : Model name of the CPU, as given by [`cpu::CpuGlobalInfo::model`].
+///
+/// # Returns
+///
+/// The TDP in W and the logical CPUs of the model, none if it is unknown.
+pub fn lookup(model: &str) -> Option<(f64, usize)> {
+    let model = format!(" {} ", normalise(model));
+    MODELS
+        .iter()
+        .filter(|(name, _, _)| model.contains(&format!(" {name} ")))
+        .max_by_key(|(name, _, _)| name.len())
+        .map(|(_, tdp, threads)| (*tdp, *threads))
+}
+
+/// CPU power model, from the `[cpu_power]` section of the configuration.
+#[derive(Debug, Clone, Deserialize)]
+struct CpuPowerConfig {
+    model: Option<String>,
+    tdp: Option<f64>,
+    threads: Option<usize>,
+    curve: Option<Vec<(f64, f64)>>,
+}
+
+/// Estimate of the CPU power from its usage, used when no RAPL zone is readable.
+/// The TDP is shared by logical CPU, so a virtual machine is given the share of its CPUs.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "CpuPowerConfig")]
+pub struct CpuPowerModel {
+    /// Model looked up in the database instead of the detected one.
+    pub model: Option<String>,
+    /// TDP in W, overriding the database.
+    pub tdp: Option<f64>,
+    /// Logical CPUs of the TDP, all the CPUs of the machine if not set.
+    pub threads: Option<usize>,
+    /// Share of the TDP drawn at each CPU usage in percent, by increasing usage.
+    pub curve: Vec<(f64, f64)>,
+}
+
+impl Default for CpuPowerModel {
+    fn default() -> Self {
+        CpuPowerModel {
+            model: None,
+            tdp: None,
+            threads: None,
+            curve: TEADS_CURVE.to_vec(),
+        }
+    }
+}
+
+impl TryFrom<CpuPowerConfig> for CpuPowerModel {
+    type Error = String;
+
+    fn try_from(config: CpuPowerConfig) -> Result<Self, Self::Error> {
+        if let Some(tdp) = config.tdp
+            && !(tdp > 0.0 && tdp.is_finite())
+        {
+            return Err(format!("Config 'Invalid TDP' : {tdp}"));
+        }
+        if config.threads == Some(0) {
+            return Err("Config 'Invalid TDP threads' : 0".to_string());
+        }
+        let curve = config.curve.unwrap_or_else(|| TEADS_CURVE.to_vec());
+        let increasing = curve.windows(2).all(|w| w[0].0 < w[1].0);
+        let valid = curve
+            .iter()
+            .all(|(usage, share)| (0.0..=100.0).contains(usage) && *share >= 0.0);
+        if curve.is_empty() || !increasing || !valid {
+            return Err(format!("Config 'Invalid power curve' : {curve:?}"));
+        }
+        Ok(CpuPowerModel {
+            model: config.model,
+            tdp: config.tdp,
+            threads: config.threads,
+            curve,
+        })
+    }
+}
+
+impl CpuPowerModel {
+    /// Share of the TDP drawn at a CPU usage, interpolated linearly between the points
+    /// of the curve and constant out of them.
+    ///
+    /// # Arguments
+    ///
+    /// - `usage` : CPU usage in percent.
+    pub fn share(&self, usage: f64) -> f64 {
+        let (first, last) = (self.curve[0], self.curve[self.curve.len() - 1]);
+        if usage <= first.0 {
+            return first.1;
+        }
+        self.curve
+            .windows(2)
+            .find(|w| usage <= w[1].0)
+            .map(|w| w[0].1 + (w[1].1 - w[0].1) * (usage - w[0].0) / (w[1].0 - w[0].0))
+            .unwrap_or(last.1)
+    }
+
+    /// Estimate the CPU power from the average usage of its cores.
+    ///
+    /// # Arguments
+    ///
+    /// - `cpu` : Retrieved [`CpuData`], with its model and cores usage.
+    ///
+    /// # Returns
+    ///
+    /// - The estimated power in W.
+    /// - An error if no TDP is configured and the CPU model is unknown.
+    pub fn estimate(&self, cpu: &CpuData) -> Result<f64, String> {
+        let usages = &cpu.cores.cores_usage;
+        let cpus = cpu.global.cores_logic.unwrap_or(usages.len()).max(1);
+        let (tdp, threads) = match self.tdp {
+            Some(tdp) => (tdp, self.threads.unwrap_or(cpus)),
+            None => {
+                let model = self
+                    .model
+                    .as_deref()
+                    .or(cpu.global.model.as_deref())
+                    .ok_or("Data 'Unknown CPU model'")?;
+                let (tdp, threads) = lookup(model)
+                    .ok_or_else(|| format!("Data 'CPU model not in the TDP database' : {model}"))?;
+                (tdp, self.threads.unwrap_or(threads))
+            }
+        };
+        let usage = if usages.is_empty() {
+            0.0
+        } else {
+            usages.iter().map(|(_, u)| *u as f64).sum::<f64>() / usages.len() as f64
+        };
+        Ok(tdp * cpus as f64 / threads as f64 * self.share(usage.clamp(0.0, 100.0)))
+    }
+}
+
+//----------------//
+// UNIT CODE TEST //
+//----------------//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpu::{CpuCoreInfo, CpuGlobalInfo};
+    use userv_core::config::Config;
+
+    fn cpu(model: &str, usages: &[f32]) -> CpuData {
+        CpuData {
+            global: CpuGlobalInfo {
+                architecture: None,
+                model: Some(model.to_string()),
+                family: None,
+                frequency: None,
+                cores_physic: None,
+                cores_logic: Some(usages.len()),
+            },
+            cores: CpuCoreInfo {
+                cores_usage: usages
+                    .iter()
+                    .enumerate()
+                    .map(|(i, u)| (format!("cpu{i}"), *u))
+                    .collect(),
+            },
+            power: None,
+            temperature: None,
+        }
+    }
+
+    // Test `lookup` function matching the model names of `/proc/cpuinfo`
+    #[test]
+    fn test_lookup() {
+        assert_eq!(
+            lookup("AMD EPYC 7763 64-Core Processor"),
+            Some((280.0, 128))
+        );
+        assert_eq!(
+            lookup("Intel(R) Xeon(R) Gold 6248 CPU @ 2.50GHz"),
+            Some((150.0, 40))
+        );
+        assert_eq!(
+            lookup("Intel(R) Xeon(R) Platinum 8480+"),
+            Some((350.0, 112))
+        );
+        assert_eq!(lookup("Neoverse-N1"), Some((2.6, 1)));
+        assert_eq!(lookup("AMD EPYC 77630"), None);
+        assert_eq!(lookup("Intel(R) Xeon(R) Processor"), None);
+    }
+
+    // Test `share` function interpolating the Teads curve
+    #[test]
+    fn test_share() {
+        let model = CpuPowerModel::default();
+        assert_eq!(model.share(0.0), 0.12);
+        assert!((model.share(30.0) - 0.535).abs() < 1e-9);
+        assert_eq!(model.share(100.0), 1.02);
+        assert_eq!(model.share(150.0), 1.02);
+    }
+
+    // Test `estimate` function sharing the TDP by logical CPU, or using the overrides
+    #[test]
+    fn test_estimate() {
+        let model = CpuPowerModel::default();
+        // 4 vCPUs of a 128 threads EPYC 7763, at 50 %.
+        let vm = cpu("AMD EPYC 7763 64-Core Processor", &[50.0; 4]);
+        assert!((model.estimate(&vm).unwrap() - 280.0 * 4.0 / 128.0 * 0.75).abs() < 1e-9);
+
+        let unknown = cpu("Intel(R) Xeon(R) Processor", &[100.0, 0.0]);
+        assert!(model.estimate(&unknown).is_err());
+        let model = CpuPowerModel {
+            tdp: Some(65.0),
+            ..Default::default()
+        };
+        assert!((model.estimate(&unknown).unwrap() - 65.0 * 0.75).abs() < 1e-9);
+    }
+
+    // Test `CpuPowerModel` deserialization rejecting an invalid TDP or curve
+    #[test]
+    fn test_config() {
+        let section = |section: &str| {
+            Config::parse(&format!("[cpu_power]\n{section}"))
+                .unwrap()
+                .section::<CpuPowerModel>("cpu_power")
+        };
+        let model = section("tdp = 95\nthreads = 16").unwrap();
+        assert_eq!((model.tdp, model.threads), (Some(95.0), Some(16)));
+        assert_eq!(model.curve, TEADS_CURVE.to_vec());
+        assert!(section("tdp = 0").is_err());
+        assert!(section("curve = [[50, 0.7], [10, 0.3]]").is_err());
+    }
+}
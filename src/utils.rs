@@ -1,144 +1,496 @@
 //! # File utilities
 
-use board::get_board_info;
-use cpu::get_cpu_info;
-use gpu::get_gpu_info;
-use memory::get_mem_info;
-use network::get_net_info;
-use storage::get_storage_info;
-use system::get_system_info;
+use gui_web::{WebConfig, auth::hash_secret, serve};
+use userv::{
+    CalibrateOptions, Collector, CollectorConfig, Component, CpuPowerModel, Tariff,
+    attribution::top_consumers,
+    baseline::{split_report, store_baselines},
+    calibrate,
+    cost::cost_report,
+    embodied::carbon_report,
+    measure,
+};
+use userv_core::{
+    export::{ExportOptions, Format, export},
+    query::Filter,
+};
 
+use chrono::{Local, Offset};
 use clap::ValueEnum;
-use log::{LevelFilter, error};
-use log4rs::{
-    append::file::FileAppender,
-    config::{Appender, Config, Root},
-    encode::pattern::PatternEncoder,
-    filter::threshold::ThresholdFilter,
-    init_config,
-};
+use rusqlite::{Connection, OpenFlags};
 use std::{
     error::Error,
-    fs::{create_dir_all, write},
+    fs::File,
+    io::{Write, stderr, stdin, stdout},
     path::Path,
+    process::exit,
+    time::Duration,
 };
 
-const LOGGER: &str = "log/error.log";
 pub const HEADER: &str = "MAIN";
+pub use userv_core::{config::CONFIG, core::DATABASE};
+
+use crate::{
+    CalibrateArg, CarbonArg, CostArg, EnergyArg, ExportArg, MeasureArg, ProcessesArg, SnapshotArg,
+};
+
+/// Enumeration of available output formats for the `export` command.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    /// Comma separated values.
+    Csv,
+    /// JSON Lines, one object per row.
+    Ndjson,
+}
 
-/// Enumeration of available arguments corresponding to a component
-#[derive(Debug, Clone, ValueEnum)]
-pub enum Component {
-    /// Motherboard or principal system board probe data.
-    Board,
-    /// CPU probe data.
-    Cpu,
-    /// GPU device probe data.
-    Gpu,
-    /// Network probe data.
-    Net,
-    /// Computing memory probe data.
-    Memory,
-    /// Storage device probe data.
-    Storage,
-    /// Operating system probe data.
-    System,
+/// Enumeration of available output formats for the `snapshot` command.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SnapshotFormat {
+    /// JSON document.
+    Json,
+    /// YAML document.
+    Yaml,
 }
 
-/// Parameters of probe that analyzing and retrieves data about a component.
-pub struct Probe {
-    /// Identification header for information loggers about a probe.
-    label: &'static str,
-    /// Function concerning data retrieves by a probe.
-    func: fn() -> Result<(), Box<dyn std::error::Error>>,
+/// Enumeration of available output formats for the `report` command.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ReportFormat {
+    /// Aligned text table.
+    Table,
+    /// JSON document.
+    Json,
+}
+
+/// Sections of the configuration file used by the program.
+#[derive(Debug, Default)]
+pub struct Settings {
+    /// Collector parameters of the `[[exec]]`, `[cpu_power]`, `[[alerts]]`, `[anomaly]`,
+    /// `[carbon]`, `[cost]`, `[embodied]`, `[health]` and `[[notify]]` sections.
+    pub collector: CollectorConfig,
+    /// Web server of the `[web]` section.
+    pub web: WebConfig,
+}
+
+/// Load the configuration file and its sections used by the program.
+///
+/// # Arguments
+///
+/// - `path` : File path of the TOML configuration.
+///
+/// # Returns
+///
+/// - The [`Settings`] read from the file, default ones if the file is missing.
+/// - An error if the file or a section is invalid.
+pub fn load_settings(path: &Path) -> Result<Settings, Box<dyn Error>> {
+    let config = userv_core::config::Config::load(path)?;
+    Ok(Settings {
+        collector: CollectorConfig::from_config(&config)?,
+        web: config.section("web")?,
+    })
 }
 
-impl Probe {
-    /// Define the probe and the label associated to a component,
-    /// and check if it is selected.
-    ///
-    /// # Arguments
-    ///
-    /// - `component` : The component that we want retrieves data.
-    ///
-    /// # Returns
-    ///
-    /// The selected component via [`Probe`] information.
-    pub fn get_probe(component: &Component) -> Probe {
-        match component {
-            Component::Board => Probe {
-                label: "BOARD",
-                func: get_board_info,
-            },
-            Component::Cpu => Probe {
-                label: "CPU",
-                func: get_cpu_info,
-            },
-            Component::Gpu => Probe {
-                label: "GPU",
-                func: get_gpu_info,
-            },
-            Component::Net => Probe {
-                label: "NETWORK",
-                func: get_net_info,
-            },
-            Component::Memory => Probe {
-                label: "MEMORY",
-                func: get_mem_info,
-            },
-            Component::Storage => Probe {
-                label: "STORAGE",
-                func: get_storage_info,
-            },
-            Component::System => Probe {
-                label: "SYSTEM",
-                func: get_system_info,
-            },
+/// Run the `snapshot` command, printing the report of the selected probes on standard output.
+///
+/// # Arguments
+///
+/// - `arg` : [`SnapshotArg`] given in command line.
+/// - `config` : [`CollectorConfig`] of the configuration file.
+///
+/// # Returns
+///
+/// An error if the report can't be serialized.
+pub fn run_snapshot(arg: SnapshotArg, config: CollectorConfig) -> Result<(), Box<dyn Error>> {
+    let components = if arg.active.is_empty() {
+        Component::ALL.to_vec()
+    } else {
+        arg.active
+    };
+
+    let collector = Collector::new(config);
+    let snapshot = collector.collect(&components)?;
+    match arg.format {
+        SnapshotFormat::Json => {
+            serde_json::to_writer_pretty(stdout().lock(), &snapshot)?;
+            println!();
         }
+        SnapshotFormat::Yaml => serde_yaml::to_writer(stdout().lock(), &snapshot)?,
     }
+    Ok(())
+}
+
+/// Run the web server and the sampling of the collector in the same process,
+/// until the server is stopped.
+///
+/// # Arguments
+///
+/// - `collector` : [`Collector`] running its components every interval.
+/// - `web` : [`WebConfig`] parameters of the server.
+///
+/// # Returns
+///
+/// An error if the server can't be started.
+pub fn run_serve(collector: Collector, web: WebConfig) -> Result<(), Box<dyn Error>> {
+    let interval = collector.config().interval;
+    let scheme = if web.tls.is_some() { "https" } else { "http" };
+    println!("[{HEADER}] Serving on {scheme}://{}:{}", web.bind, web.port);
+    // The errors of each sampling are logged by the collector, the next ones still run.
+    serve(web, interval, move || {
+        let _ = collector.collect(&collector.config().components);
+    })?;
+    Ok(())
+}
+
+/// Run the `measure` command, printing the energy profile of the command when it exits,
+/// and exiting with the code of the command if it failed.
+///
+/// # Arguments
+///
+/// - `arg` : [`MeasureArg`] given in command line.
+///
+/// # Returns
+///
+/// An error if the command can't be started or the summary can't be written.
+pub fn run_measure(arg: MeasureArg) -> Result<(), Box<dyn Error>> {
+    let res = measure(&arg.command, Duration::from_millis(arg.interval.max(1)))?;
+    let mut out: Box<dyn Write> = match &arg.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(stderr().lock()),
+    };
 
-    /// Run a probe to retrieve information about a component.
-    /// If component's data can't be retrieved, we log the error returned.
-    ///
-    /// # Arguments
-    ///
-    /// - `probe` : Concerning component with [`Probe`].
-    pub fn run_probe(probe: Probe) {
-        if let Err(e) = (probe.func)() {
-            error!("[{}] {e}", probe.label);
+    match arg.format {
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut out, &res)?;
+            writeln!(out)?;
+        }
+        ReportFormat::Table => {
+            let joules = |energy: Option<f64>| {
+                energy.map_or("not available".to_string(), |e| format!("{e:.2} J"))
+            };
+            writeln!(out, "\nEnergy profile of '{}':\n", res.command.join(" "))?;
+            writeln!(out, "{:>16}  {:.3} s", "wall time", res.wall_time)?;
+            writeln!(out, "{:>16}  {}", "cpu", joules(res.cpu_energy))?;
+            writeln!(out, "{:>16}  {}", "gpu", joules(res.gpu_energy))?;
+            writeln!(out, "{:>16}  {}", "dram", joules(res.dram_energy))?;
+            writeln!(out, "{:>16}  {:.2} J", "total", res.total_energy)?;
+            writeln!(out, "{:>16}  {:.2} W", "average power", res.average_power)?;
+            writeln!(out, "{:>16}  {:.2} W", "peak power", res.peak_power)?;
+            match res.attributable_share {
+                Some(share) => writeln!(
+                    out,
+                    "{:>16}  {:.2} J ({:.1} %)",
+                    "attributable",
+                    res.attributable_energy,
+                    share * 100.0
+                )?,
+                None => writeln!(out, "{:>16}  not available", "attributable")?,
+            }
+            writeln!(out, "{:>16}  {}", "samples", res.samples)?;
         }
     }
+    out.flush()?;
+    drop(out);
+
+    match res.exit_code {
+        Some(0) => Ok(()),
+        Some(code) => exit(code),
+        None => Err(format!("Process '{} killed by a signal'", arg.command[0]).into()),
+    }
 }
 
-/// Initialization and formatting information logger to store messages concerning microservices behavior.
+/// Run the `calibrate` command, storing the idle and peak powers measured in the database
+/// and printing them on standard output.
+///
+/// # Arguments
+///
+/// - `arg` : [`CalibrateArg`] given in command line.
+/// - `cpu_power` : CPU power model configured.
 ///
 /// # Returns
 ///
-/// Writing the error in the log file.
-/// Print IO error message if log writing failed.
-pub fn init_logger() -> Result<(), Box<dyn Error>> {
-    if let Some(parent) = Path::new(LOGGER).parent() {
-        create_dir_all(parent)?;
+/// An error if the calibration failed or the baselines can't be stored.
+pub fn run_calibrate(arg: CalibrateArg, cpu_power: CpuPowerModel) -> Result<(), Box<dyn Error>> {
+    let duration = Duration::from_secs(arg.duration.max(1));
+    eprintln!(
+        "[{HEADER}] Calibrating at rest for {} s{}, keep the machine idle",
+        duration.as_secs(),
+        if arg.load { ", then under load" } else { "" }
+    );
+    let baselines = calibrate(&CalibrateOptions {
+        duration,
+        load: arg.load.then_some(duration),
+        interval: Duration::from_millis(arg.interval.max(1)),
+        cpu_power,
+    })?;
+    store_baselines(&baselines)?;
+
+    match arg.format {
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(stdout().lock(), &baselines)?;
+            println!();
+        }
+        ReportFormat::Table => {
+            let mut out = stdout().lock();
+            writeln!(out, "{:<10} {:>14} PEAK (W)", "COMPONENT", "IDLE (W)")?;
+            for baseline in baselines {
+                writeln!(
+                    out,
+                    "{:<10} {:>14.2} {}",
+                    baseline.component.label(),
+                    baseline.idle_power,
+                    baseline
+                        .peak_power
+                        .map_or("-".to_string(), |p| format!("{p:.2}"))
+                )?;
+            }
+        }
     }
+    Ok(())
+}
 
-    let logfile = FileAppender::builder()
-        .encoder(Box::new(PatternEncoder::new("{d} {m} {n}")))
-        .build(LOGGER)?;
-
-    let config = Config::builder()
-        .appender(
-            Appender::builder()
-                .filter(Box::new(ThresholdFilter::new(LevelFilter::Error)))
-                .build("logfile", Box::new(logfile)),
-        )
-        .build(
-            Root::builder()
-                .appender("logfile")
-                .build(LevelFilter::Error),
-        )?;
-
-    let _ = write(LOGGER, "");
-    init_config(config)?;
+/// Run the `hash` command, printing the hash of the secret read on standard input.
+///
+/// # Returns
+///
+/// An error if the secret is empty or can't be read.
+pub fn run_hash() -> Result<(), Box<dyn Error>> {
+    let mut secret = String::new();
+    stdin().read_line(&mut secret)?;
+    let secret = secret.trim_end_matches(['\r', '\n']);
+    if secret.is_empty() {
+        return Err("Arguments 'No secret given on standard input'".into());
+    }
+    println!("{}", hash_secret(secret)?);
+    Ok(())
+}
 
+/// Run the `export` command, streaming the selected tables to the output.
+///
+/// # Arguments
+///
+/// - `arg` : [`ExportArg`] given in command line.
+///
+/// # Returns
+///
+/// An error if a database can't be read or the output can't be written.
+pub fn run_export(arg: ExportArg) -> Result<(), Box<dyn Error>> {
+    let options = ExportOptions {
+        databases: arg.db,
+        tables: arg.tables,
+        format: match arg.format {
+            ExportFormat::Csv => Format::Csv,
+            ExportFormat::Ndjson => Format::Ndjson,
+        },
+        filter: Filter {
+            from: arg.from,
+            to: arg.to,
+            entity: arg.entity,
+        },
+        host: arg.host,
+        output: arg.output,
+    };
+    export(&options)?;
+    Ok(())
+}
+
+/// Run the `report cost` command, printing the daily and monthly costs stored in the database.
+///
+/// # Arguments
+///
+/// - `arg` : [`CostArg`] given in command line.
+/// - `tariff` : Tariff configured, giving the local time of the days.
+///
+/// # Returns
+///
+/// An error if the database can't be read or the report can't be serialized.
+pub fn run_report_cost(arg: CostArg, tariff: Option<Tariff>) -> Result<(), Box<dyn Error>> {
+    if !arg.db.exists() {
+        return Err(format!("Arguments 'Database not found' : {}", arg.db.display()).into());
+    }
+    let conn = Connection::open_with_flags(&arg.db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let filter = Filter {
+        from: arg.from,
+        to: arg.to,
+        entity: None,
+    };
+    let offset = tariff
+        .and_then(|tariff| tariff.utc_offset)
+        .unwrap_or_else(|| Local::now().offset().fix());
+    let report = cost_report(&conn, &filter, offset)?;
+
+    match arg.format {
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(stdout().lock(), &report)?;
+            println!();
+        }
+        ReportFormat::Table => {
+            let mut out = stdout().lock();
+            writeln!(
+                out,
+                "{:<12} {:>14} {:>14} CURRENCY",
+                "PERIOD", "ENERGY (kWh)", "COST"
+            )?;
+            for rollup in report
+                .daily
+                .iter()
+                .chain(&report.monthly)
+                .chain(&report.total)
+            {
+                writeln!(
+                    out,
+                    "{:<12} {:>14.3} {:>14.2} {}",
+                    rollup.period, rollup.billed, rollup.cost, rollup.currency
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run the `report carbon` command, printing the operational and embodied emissions.
+///
+/// # Arguments
+///
+/// - `arg` : [`CarbonArg`] given in command line.
+///
+/// # Returns
+///
+/// An error if the database can't be read or the report can't be serialized.
+pub fn run_report_carbon(arg: CarbonArg) -> Result<(), Box<dyn Error>> {
+    if !arg.db.exists() {
+        return Err(format!("Arguments 'Database not found' : {}", arg.db.display()).into());
+    }
+    let conn = Connection::open_with_flags(&arg.db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let filter = Filter {
+        from: arg.from,
+        to: arg.to,
+        entity: None,
+    };
+    let report = carbon_report(&conn, &filter)?;
+
+    match arg.format {
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(stdout().lock(), &report)?;
+            println!();
+        }
+        ReportFormat::Table => {
+            let mut out = stdout().lock();
+            writeln!(
+                out,
+                "{:<12} {:>18} SHARE (%)",
+                "SOURCE", "EMISSIONS (gCO2e)"
+            )?;
+            for (name, emissions) in [
+                ("operational", report.operational),
+                ("embodied", report.embodied),
+                ("total", report.total),
+            ] {
+                let share = if report.total > 0.0 {
+                    format!("{:.1}", emissions / report.total * 100.0)
+                } else {
+                    "-".to_string()
+                };
+                writeln!(out, "{name:<12} {emissions:>18.4} {share}")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run the `report processes` command, printing the processes which consumed the most energy.
+///
+/// # Arguments
+///
+/// - `arg` : [`ProcessesArg`] given in command line.
+///
+/// # Returns
+///
+/// An error if the database can't be read or the report can't be serialized.
+pub fn run_report_processes(arg: ProcessesArg) -> Result<(), Box<dyn Error>> {
+    if !arg.db.exists() {
+        return Err(format!("Arguments 'Database not found' : {}", arg.db.display()).into());
+    }
+    let conn = Connection::open_with_flags(&arg.db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let filter = Filter {
+        from: arg.from,
+        to: arg.to,
+        entity: None,
+    };
+    let consumers = top_consumers(&conn, &filter, arg.limit)?;
+
+    match arg.format {
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(stdout().lock(), &consumers)?;
+            println!();
+        }
+        ReportFormat::Table => {
+            let mut out = stdout().lock();
+            writeln!(
+                out,
+                "{:>8} {:<24} {:>14} {:>12} SAMPLES",
+                "PID", "NAME", "ENERGY (J)", "POWER (W)"
+            )?;
+            for consumer in consumers {
+                writeln!(
+                    out,
+                    "{:>8} {:<24} {:>14.1} {:>12.2} {}",
+                    consumer.pid,
+                    consumer.name.as_deref().unwrap_or("-"),
+                    consumer.energy,
+                    consumer.power,
+                    consumer.samples
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run the `report energy` command, printing the static and dynamic energy of each component.
+///
+/// # Arguments
+///
+/// - `arg` : [`EnergyArg`] given in command line.
+///
+/// # Returns
+///
+/// An error if the database can't be read or the report can't be serialized.
+pub fn run_report_energy(arg: EnergyArg) -> Result<(), Box<dyn Error>> {
+    if !arg.db.exists() {
+        return Err(format!("Arguments 'Database not found' : {}", arg.db.display()).into());
+    }
+    let conn = Connection::open_with_flags(&arg.db, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let filter = Filter {
+        from: arg.from,
+        to: arg.to,
+        entity: arg.component.map(|c| c.label().to_string()),
+    };
+    let report = split_report(&conn, &filter)?;
+
+    match arg.format {
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(stdout().lock(), &report)?;
+            println!();
+        }
+        ReportFormat::Table => {
+            let mut out = stdout().lock();
+            writeln!(
+                out,
+                "{:<10} {:>14} {:>14} {:>14} DYNAMIC (%)",
+                "COMPONENT", "ENERGY (J)", "STATIC (J)", "DYNAMIC (J)"
+            )?;
+            for rollup in report.components.iter().chain(&report.total) {
+                let share = if rollup.energy > 0.0 {
+                    format!("{:.1}", rollup.dynamic_energy / rollup.energy * 100.0)
+                } else {
+                    "-".to_string()
+                };
+                writeln!(
+                    out,
+                    "{:<10} {:>14.1} {:>14.1} {:>14.1} {share}",
+                    rollup.component, rollup.energy, rollup.static_energy, rollup.dynamic_energy
+                )?;
+            }
+        }
+    }
     Ok(())
 }